@@ -0,0 +1,105 @@
+// `cargo bench --bench formatting`: timing loops for the formatting helpers and the
+// stats math. criterion isn't a dependency, so this is a plain binary (harness =
+// false) that prints the mean time per call; compare runs before and after a change.
+use std::fmt::Write as _;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use network_traffic_monitoring::tool::cli::RateUnits;
+use network_traffic_monitoring::{
+    format_bytes, format_rate, format_rate_bits, write_bytes, write_rate_as, NetworkStats,
+    RunSummary, UnitStyle,
+};
+
+// Runs `f` in batches for about `BUDGET` after a short warm-up
+const BUDGET: Duration = Duration::from_millis(300);
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    for _ in 0..1_000 {
+        f();
+    }
+    let start = Instant::now();
+    let mut calls = 0u64;
+    while start.elapsed() < BUDGET {
+        for _ in 0..1_000 {
+            f();
+        }
+        calls += 1_000;
+    }
+    let per_call = start.elapsed().as_nanos() as f64 / calls as f64;
+    println!("{:<36} {:>12.1} ns/call", name, per_call);
+}
+
+fn main() {
+    let sizes = [512, 10 * 1024, 5 * 1024 * 1024, 3 * 1024 * 1024 * 1024];
+    let rates = [800.0, 12_345.6, 98_765_432.1, 4.2e9];
+
+    bench("format_bytes", || {
+        for &size in &sizes {
+            black_box(format_bytes(black_box(size)));
+        }
+    });
+    bench("format_rate", || {
+        for &rate in &rates {
+            black_box(format_rate(black_box(rate)));
+        }
+    });
+    bench("format_rate_bits", || {
+        for &rate in &rates {
+            black_box(format_rate_bits(black_box(rate)));
+        }
+    });
+    // The same through one reused buffer
+    let mut out = String::new();
+    bench("write_bytes (reused buffer)", || {
+        for &size in &sizes {
+            out.clear();
+            let _ = write_bytes(&mut out, black_box(size), UnitStyle::Binary);
+            black_box(&out);
+        }
+    });
+    bench("write_rate_as (reused buffer)", || {
+        for &rate in &rates {
+            out.clear();
+            let _ = write_rate_as(&mut out, black_box(rate), RateUnits::Bytes);
+            black_box(&out);
+        }
+    });
+
+    // A status line like the progress display's, both ways
+    bench("status line via format_*", || {
+        out.clear();
+        let _ = write!(out, "{} sent, {} upload", format_bytes(sizes[2]), format_rate(rates[2]));
+        black_box(&out);
+    });
+    bench("status line via write_*", || {
+        out.clear();
+        let _ = write_bytes(&mut out, sizes[2], UnitStyle::Binary);
+        out.push_str(" sent, ");
+        let _ = write_rate_as(&mut out, rates[2], RateUnits::Bytes);
+        out.push_str(" upload");
+        black_box(&out);
+    });
+
+    let latencies: Vec<Duration> =
+        (0..10_000u64).map(|i| Duration::from_micros((i * 7919) % 10_007)).collect();
+    let stats = NetworkStats {
+        latencies,
+        ..NetworkStats::default()
+    };
+    bench("p50+p95+p99 over 10k latencies", || {
+        black_box((stats.p50_latency(), stats.p95_latency(), stats.p99_latency()));
+    });
+
+    let sweep: Vec<NetworkStats> = (1..=100)
+        .map(|i| NetworkStats {
+            bytes_sent: i * 1024,
+            bytes_received: i * 512,
+            elapsed_time: Duration::from_millis(i as u64),
+            ..NetworkStats::default()
+        })
+        .collect();
+    bench("RunSummary over 100 sizes", || {
+        black_box(RunSummary::from_stats(black_box(&sweep)));
+    });
+}
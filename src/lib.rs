@@ -0,0 +1,68 @@
+pub mod tool;
+
+pub use tool::address::validate_address;
+pub use tool::chart::render_rate_chart;
+pub use tool::cli::{Command, Config, OutputFormat, ProxyConfig, RateUnits, RunMode};
+pub use tool::clock::{Clock, ScriptedClock, SystemClock};
+pub use tool::compare::{run_compare, StatsDiff};
+pub use tool::config_file::{apply_config_text, load_config_file};
+pub use tool::dedup::{measure_dedup, run_dedup, DedupClass, DedupOptions, DedupReport};
+pub use tool::env::apply_env;
+pub use tool::error::TransferError;
+pub use tool::estimate::{
+    estimate_traffic, print_dry_run, render_estimate, run_estimate, TrafficEstimate,
+};
+pub use tool::goodput::{
+    estimate_goodput, parse_overhead_model, GoodputEstimate, ProtocolOverheadModel,
+};
+pub use tool::happy_eyeballs::{connect_first, interleave_families};
+pub use tool::http::{measure_http, HttpOptions};
+pub use tool::http2::measure_http2;
+pub use tool::iteration_stream::{measure_transfer_stream, IterationStream};
+pub use tool::json::{JsonObject, JsonValue};
+pub use tool::load::{measure_latency_under_load, LoadOptions, LoadedLatency};
+pub use tool::metrics::{serve_metrics, Metrics, DEFAULT_METRICS_BIND};
+pub use tool::mix::{parse_mix, SizeMix};
+pub use tool::mock_server::{
+    run_mock_server, MockServerConfig, MockServerStats, DEFAULT_BACKLOG, DEFAULT_MAX_RESPONSE_LEN,
+};
+#[cfg(unix)]
+pub use tool::mock_server::run_mock_server_uds;
+pub use tool::monitor::run_watch;
+pub use tool::mpc::{
+    estimate_mpc, estimate_mpc_measured, monitor_mpc_measured, monitor_mpc_simulation,
+    MpcEstimate, MpcOverheadModel,
+};
+pub use tool::multi::{measure_many, render_host_table, HostResult};
+pub use tool::payload::{PayloadSource, GENERATED_PAYLOAD_MIN};
+pub use tool::process::{sample_delta, sample_process, Connection, ProcessSample};
+pub use tool::proxy::{
+    run_proxy, run_proxy_with_checks, run_proxy_with_faults, run_proxy_with_limits, ProxyFaults,
+    ProxyLimits,
+};
+pub use tool::ramp::{measure_ramp, run_ramp, RampLevel, RampOptions, RampReport, RAMP_KNEE_GAIN};
+pub use tool::repeat::{run_repeat, RepeatedSize};
+pub use tool::replay::{load_schedule, parse_schedule, Schedule};
+pub use tool::sampler::BandwidthSampler;
+pub use tool::selftest::{self_test, SelfTestCheck, SelfTestReport};
+pub use tool::soak::{measure_soak, run_soak, SoakOptions, SoakReport};
+pub use tool::socks::{SocksAuth, SocksConfig};
+pub use tool::summary::{
+    link_utilization, phase_line, render_summary_table, utilization_line, RunSummary,
+};
+pub use tool::thresholds::{Thresholds, ThresholdsViolated};
+pub use tool::tool::{
+    format_bytes, format_bytes_with, format_measured_rate, format_per_iteration_rate, format_rate,
+    format_rate_as, format_rate_bits, measure_keepalive, measure_mix, measure_parallel,
+    measure_payload, measure_payload_with_progress, measure_stream, measure_transfer,
+    measure_transfer_udp, measure_transfer_udp_with_options, measure_transfer_with_progress, replay,
+    replay_with_options, run_benchmarks, run_benchmarks_until, run_benchmarks_with_metrics,
+    run_main, stats_schema_version, write_bytes, write_rate_as, AutoIterations, Direction,
+    IterationFairness, IterationRecord, IterationResult, KeepaliveEnd, KeepaliveOptions,
+    KeepaliveReport, MixBucket, MixOptions, MixStats, NetworkStats, ParallelOptions, ParallelStats,
+    PhaseBreakdown, RetryPolicy, StreamRate, StreamSamples, TransferOptions, UnitStyle,
+    MIN_MEASURABLE_TIME, STATS_SCHEMA_VERSION,
+};
+#[cfg(unix)]
+pub use tool::tool::{measure_transfer_uds, measure_transfer_uds_with_options};
+pub use tool::watch_display::{WatchDisplay, WatchRow};
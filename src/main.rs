@@ -1,12 +1,70 @@
-mod tool;
+use network_traffic_monitoring::tool;
+use network_traffic_monitoring::tool::cli::{self, Command};
 
 // async fn main() {
 #[tokio::main]
 async fn main(){
-    println!("Hello, world!");
+    tool::log::init_from_env();
+    let config = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(Command::Run(config)) => config,
+        Ok(Command::Proxy(proxy)) => {
+            let relay = tool::proxy::run_proxy_with_checks(
+                &proxy.listen,
+                &proxy.upstream,
+                proxy.limits,
+                proxy.faults,
+                proxy.check_sequence,
+            );
+            if let Err(e) = relay.await {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Ok(Command::Compare { baseline, candidate }) => {
+            if let Err(e) = tool::compare::run_compare(&baseline, &candidate) {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Ok(Command::Estimate { results, size }) => {
+            if let Err(e) = tool::estimate::run_estimate(&results, size) {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Ok(Command::Attach(attach)) => {
+            if let Err(e) = tool::process::run_attach(&attach).await {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Ok(Command::SelfTest) => {
+            if let Err(e) = tool::selftest::run_self_test().await {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
 
     // tool::run_main();
-    tool::tool::run_main().await.unwrap();
+    // Failures, including broken --min-download-rate style thresholds, exit with 1
+    if let Err(e) = tool::tool::run_main(&config).await {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
 
     
 }
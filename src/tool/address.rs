@@ -0,0 +1,42 @@
+use std::net::{IpAddr, SocketAddr};
+
+use super::error::TransferError;
+
+// Checks that `address` is a usable host:port and resolves it, so a typo fails before
+// any transfer starts instead of on the first connect. Returns the first address the
+// host resolved to. IPv6 literals need brackets, e.g. [::1]:8080.
+pub async fn validate_address(address: &str) -> Result<SocketAddr, TransferError> {
+    let invalid = |reason: String| TransferError::InvalidAddress {
+        address: address.to_string(),
+        reason,
+    };
+
+    if let Ok(addr) = address.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    if address.trim().is_empty() {
+        return Err(invalid("address is empty".to_string()));
+    }
+    // A bare IP, bracketed or not, is the most common way to forget the port
+    if address.trim_matches(['[', ']']).parse::<IpAddr>().is_ok() {
+        return Err(invalid("missing port (expected host:port)".to_string()));
+    }
+
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (host, port),
+        None => return Err(invalid("missing port (expected host:port)".to_string())),
+    };
+    if host.is_empty() {
+        return Err(invalid("missing host (expected host:port)".to_string()));
+    }
+    if port.parse::<u16>().is_err() {
+        return Err(invalid(format!("invalid port '{}'", port)));
+    }
+
+    let mut resolved = tokio::net::lookup_host(address)
+        .await
+        .map_err(|e| invalid(format!("could not resolve host '{}': {}", host, e)))?;
+    resolved
+        .next()
+        .ok_or_else(|| invalid(format!("host '{}' did not resolve to any address", host)))
+}
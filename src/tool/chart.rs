@@ -0,0 +1,37 @@
+use super::cli::RateUnits;
+use super::tool::{format_bytes, format_rate_as};
+
+// Longest bar, in characters, drawn for the fastest size
+const BAR_WIDTH: usize = 40;
+
+// Download rate per payload size as horizontal bars scaled to the fastest one. With
+// `fancy` false (e.g. stdout is not a terminal) only the labels and numbers are kept,
+// one per line, so the output stays readable in logs and pipes.
+pub fn render_rate_chart(results: &[(usize, f64)], units: RateUnits, fancy: bool) -> String {
+    let labels: Vec<String> = results.iter().map(|&(size, _)| format_bytes(size)).collect();
+    let label_width = labels.iter().map(String::len).max().unwrap_or(0);
+    let max_rate = results.iter().map(|&(_, rate)| rate).fold(0.0, f64::max);
+
+    let mut out = String::new();
+    for (label, &(_, rate)) in labels.iter().zip(results) {
+        let rate_text = format_rate_as(rate, units);
+        if fancy {
+            let bar = if max_rate > 0.0 {
+                ((rate / max_rate) * BAR_WIDTH as f64).round() as usize
+            } else {
+                0
+            };
+            out.push_str(&format!(
+                "  {:>lw$} │{:<bw$}│ {}\n",
+                label,
+                "█".repeat(bar),
+                rate_text,
+                lw = label_width,
+                bw = BAR_WIDTH
+            ));
+        } else {
+            out.push_str(&format!("  {:>lw$}  {}\n", label, rate_text, lw = label_width));
+        }
+    }
+    out
+}
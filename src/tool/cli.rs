@@ -0,0 +1,1750 @@
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::color;
+use super::config_file::{load_config_file, DEFAULT_CONFIG_FILE};
+use super::env::apply_env;
+use super::goodput::{parse_overhead_model, ProtocolOverheadModel};
+use super::happy_eyeballs::DEFAULT_HEAD_START;
+use super::mix::{parse_mix, SizeMix};
+use super::mock_server::{DEFAULT_BACKLOG, DEFAULT_MAX_RESPONSE_LEN};
+use super::payload::DEFAULT_SEED;
+use super::proxy::{ProxyFaults, ProxyLimits};
+use super::socks::{SocksAuth, SocksConfig};
+use super::thresholds::Thresholds;
+use super::tool::{AutoIterations, Direction};
+
+pub const USAGE: &str = "\
+Usage: traffic-monitor [OPTIONS]
+       traffic-monitor proxy --listen <HOST:PORT> --upstream <HOST:PORT>
+                             [--up-rate <B/S>] [--down-rate <B/S>]
+                             [--latency <DURATION>] [--drop-rate <X>] [--seed <N>]
+                             [--check-sequence]
+       traffic-monitor compare <BASELINE.json> <NEW.json>
+       traffic-monitor estimate --size <BYTES> <RESULTS.json>
+       traffic-monitor attach --pid <PID> [--interval <DURATION>] [--count <N>]
+                              [--units <UNITS>]
+       traffic-monitor selftest
+
+Commands:
+  proxy                   Relay connections to an upstream and report each session's traffic;
+                          --up-rate and --down-rate cap client→upstream and
+                          upstream→client bytes per second; --latency delays every
+                          chunk and --drop-rate discards that fraction of chunks (0-1),
+                          chosen reproducibly from --seed; --check-sequence counts
+                          client requests relayed out of order, for clients run with
+                          --sequence
+  compare                 Show the change between two --format json result files
+  estimate                Project how long moving --size bytes would take at the rates of
+                          each size in a --format json result file; --size takes decimal
+                          (KB, MB, GB, TB) or binary (KiB, MiB, GiB, TiB) units
+  attach                  Linux only: report a running process's traffic every --interval
+                          [default: 1s], from its network namespace's counters, plus its
+                          TCP connections; stops after --count intervals or on Ctrl-C
+  selftest                Measure against the built-in mock server on a free port and
+                          check the byte counts and half-size responses, printing PASS
+                          or FAIL; exits with 1 on a failure
+
+Options:
+  --config <PATH>         Read settings from a TOML file first; flags override it
+                          [default: config.toml, if present]
+  --address <HOST:PORT>   Endpoint to measure [default: the mock server, or 127.0.0.1:8080
+                          with --no-mock-server]
+  --hosts <LIST>          Comma-separated HOST:PORT endpoints to measure and compare in one
+                          run, instead of --address; no mock server is started
+  --parallel-hosts        Measure all --hosts at the same time instead of one by one
+  --sizes <LIST>          Comma-separated payload sizes in bytes [default: 1024,10240,102400]
+  --iterations <N>        Transfers per payload size, at least 1 [default: 5]; `auto`
+                          keeps doubling them from the first 5 until the download rate's
+                          95% confidence interval is within --target-precision
+  --target-precision <PCT>
+                          Relative margin of error for --iterations auto, e.g. 5% or 0.05
+                          [default: 5%]
+  --max-iterations <N>    Cap for --iterations auto [default: 1000]
+  --total-bytes <BYTES>   Instead of --iterations, repeat each size until exactly this many
+                          bytes are sent; the last iteration sends what remains
+  --delay-ms <MS>         Pause between iterations in milliseconds, 0 for none [default: 100]
+  --warmup <N>            Leading iterations left out of the stats; must be less
+                          than --iterations [default: 0]
+  --reuse-connection      Send all iterations over one connection instead of reconnecting
+  --half-close            Shut down the sending side after each request, for servers that
+                          wait for EOF before replying; not with --reuse-connection
+  --direction <DIR>       both, upload (the server discards the payload and answers
+                          nothing) or download (the server sends a payload-sized
+                          response to a tiny request); one-way needs the mock server or
+                          one that reads its request flags [default: both]
+  --under-load <BYTES>    Instead of a sweep, run --iterations bulk transfers of this size,
+                          --concurrency at a time, and report the latency of small pings
+                          sent alongside them, next to an idle baseline
+  --ping-interval <DURATION>
+                          Pause between --under-load pings [default: 50ms]
+  --keepalive <DURATION>  Instead of a sweep, keep one connection open with a small ping
+                          this often and report how long it survives
+  --keepalive-limit <DURATION>
+                          End --keepalive with the connection still up after this long
+                          [default: until it drops or Ctrl-C]
+  --size-mix <LIST>       Instead of --sizes, draw each of --iterations payload sizes from
+                          weighted SIZE:WEIGHT pairs, e.g. 1024:0.7,65536:0.25,1048576:0.05,
+                          with --seed, and report each size and the mix as a whole
+  --parallel <N>          Instead of a sweep, open N connections that each push data
+                          nonstop for --parallel-duration, and report their total rate
+                          and how evenly they shared it
+  --parallel-duration <DURATION>
+                          How long each --parallel stream pushes [default: 10s]
+  --repeat <N>            Run the whole sweep N times and report each size's mean rates,
+                          their standard deviation, and the best and worst run
+  --soak <DURATION>       Instead of a sweep, repeat the largest size nonstop for this long
+                          and report whether its throughput declines over time
+  --soak-window <DURATION>
+                          Width of the --soak throughput windows [default: a tenth of it]
+  --soak-max-drop <PERCENT>
+                          Warn when --soak throughput declines by more than this [default: 10]
+  --ramp <LEVELS>         Instead of a sweep, measure the largest size at each concurrency in
+                          a list like 1,2,4,8,16, or doubling from 1 up to a single N, and
+                          report where throughput stops scaling
+  --dedup-check           Instead of a sweep, alternate the largest size between the same
+                          random bytes every time and fresh ones, and warn when the
+                          repeated bytes go faster, as through a caching middlebox
+  --dedup-threshold <PERCENT>
+                          How much faster repeated bytes have to go for --dedup-check to
+                          suspect deduplication [default: 25]
+  --link-bandwidth <BITS>  Link speed in bits per second, e.g. 1G or 100Mbps, to report the
+                          rates as a share of
+  --goodput <MODEL>       Also estimate goodput, the rates less modelled protocol overhead:
+                          http, tls, https, or REQUEST_BYTES:RESPONSE_BYTES per request
+  --sequence              Number each request in its first 8 bytes, for proxy
+                          --check-sequence; needs sizes of at least 8 bytes
+  --response-size <BYTES>  Ask the server for responses of exactly this many bytes,
+                          whatever the request size [default: the server decides]
+  --expect-response <BYTES>
+                          Fail iterations whose response doesn't announce exactly this
+                          many payload bytes; needs a single size [default: any]
+  --requests-per-connection <N>
+                          Requests each connection sends in turn, reading every response,
+                          before it closes; an iteration covers them all [default: 1]
+  --think-time <DURATION> Pause between the requests of one connection, left out of the
+                          latencies and rates [default: none]
+  --tcp-nodelay           Disable Nagle's algorithm: usually lower latency for small
+                          payloads, less efficient bulk transfers
+  --send-buffer <BYTES>   Socket send buffer (SO_SNDBUF) for each connection [default: OS]
+  --recv-buffer <BYTES>   Socket receive buffer (SO_RCVBUF) for each connection [default: OS]
+  --bind <ADDR>           Local address to send from, e.g. 192.168.1.20 to go out the NIC
+                          that has it; IP:PORT pins the port too [default: OS choice]
+  --concurrency <N>       Iterations in flight at once; 1 runs them in sequence [default: 1]
+  --burst                 Start all --iterations of each size at the same instant, each on
+                          its own connection, to see how the server copes with the herd
+  --verify                Send a known byte pattern and check the echoed response; the mock
+                          server echoes to match
+  --random-payload        Send random bytes instead of zeros, so compression can't help
+  --seed <N>              Seed for --random-payload; the same seed sends the same bytes
+                          [default: 24301]
+  --payload-file <PATH>   Send this file's contents every iteration; replaces --sizes
+  --target-rate <B/S>     Cap the send rate at this many bytes per second
+  --write-chunk-size <BYTES>
+                          Write each request in pieces of this size, one write apiece, and
+                          measure each size again in single writes to compare
+  --flush-chunks          Flush after every --write-chunk-size piece
+  --sample-ms <MS>        Stream each payload once over a single connection and report
+                          the throughput of every MS-millisecond window
+  --replay <CSV>          Send the requests of a delay_ms,size_bytes schedule over one
+                          connection, with its gaps, instead of the size sweep
+  --url <URL>             Measure HTTP requests to an http:// URL instead of raw TCP;
+                          each size is sent as the request body
+  --method <METHOD>       HTTP method for --url [default: POST]
+  --follow-redirects      Follow HTTP 3xx redirects
+  --http2 <STREAMS>       Send --url requests over one cleartext HTTP/2 connection (h2c
+                          with prior knowledge), up to STREAMS of them at once
+  --http-close            Open a new connection for every HTTP request instead of reusing
+                          keep-alive connections
+  --http-idle-timeout <DURATION>
+                          Redial pooled HTTP connections idle this long [default: 90s]
+  --timeout-ms <MS>       Limit on each connect, write and read [default: 10000]
+  --max-duration <DURATION>
+                          Stop starting iterations this long into the run and report the
+                          ones finished, e.g. 30s [default: none]
+  --retries <N>           Extra connect attempts before an iteration fails [default: 0]
+  --eof-retries <N>       Repeat an iteration, on a new connection, when the server closes
+                          before its response is complete, up to N times [default: 0]
+  --fail-fast             Stop at the first failed iteration and exit with its error
+                          instead of reporting failures alongside the results
+  --backoff-ms <MS>       Wait before the first retry, doubling after each [default: 100]
+  --connect-head-start <DURATION>
+                          When the address resolves to several IPs, how long to wait on
+                          one before also trying the next, alternating IPv6 and IPv4
+                          [default: 250ms]
+  --socks5 <HOST:PORT>    Connect to the target through this SOCKS5 proxy, which resolves
+                          its host name
+  --socks5-user <NAME>    Username for the SOCKS5 proxy; needs --socks5-password
+  --socks5-password <PASSWORD>
+                          Password for the SOCKS5 proxy
+  --no-mock-server        Don't start the local mock server
+  --mock-address <ADDR>   Where the mock server listens; port 0 picks a free one
+                          [default: 127.0.0.1:0]
+  --ipv6                  Shorthand for --mock-address [::1]:0
+  --response-ratio <X>    Mock server response size as a multiple of the request [default: 0.5]
+  --mock-max-response <BYTES>
+                          Largest response a client may ask the mock server for
+                          [default: 268435456]
+  --mock-idle-timeout <DURATION>
+                          Have the mock server close connections idle this long
+                          [default: never]
+  --mock-backlog <N>      Connections the mock server's listen queue holds, for bursts
+                          from --concurrency or --parallel; capped by the OS
+                          [default: 1024]
+  --mock-accept-pause <DURATION>
+                          Keep the mock server accepting after a failed accept, e.g. out
+                          of file descriptors, pausing this long first [default: stop]
+  --mock-sink             Have the mock server discard requests without replying, for
+                          pure upload; implies --direction upload
+  --progress              Show iteration progress, upload rate and ETA while measuring
+                          (text output on a terminal only)
+  --dry-run               Print the traffic the run would send and receive, without
+                          connecting anywhere
+  --min-download-rate <B/S>
+                          Exit with status 1 if any size downloads slower than this
+  --max-ratio <X>         Exit with status 1 if any size's received/sent ratio is above this
+  --max-p99-latency <DURATION>
+                          Exit with status 1 if any size's p99 latency is above this, e.g. 250ms
+  --metrics-port <PORT>   Serve Prometheus metrics on http://127.0.0.1:PORT/metrics while running
+  --metrics-bind <ADDR>   Listen for --metrics-port on this address instead, e.g. 0.0.0.0 to
+                          expose the unauthenticated endpoint on every interface
+  --otlp-endpoint <URL>   Push each size's metrics to an OpenTelemetry collector over OTLP/HTTP,
+                          e.g. http://localhost:4318 (builds with --features otlp only)
+  --db <PATH>             Add each size's results to the runs table of this SQLite database,
+                          through the sqlite3 shell (builds with --features sqlite only)
+  --watch                 Repeat the sweep every --interval until Ctrl-C, then print a summary
+  --interval <DURATION>   Time between --watch cycles, e.g. 500ms, 5s or 1m [default: 5s]
+  --format <FORMAT>       Output format: text, json, csv, or jsonl with --watch
+                          [default: text]
+  --output <PATH>         Write json/csv results to a file instead of stdout
+  --label <TEXT>          Name saved with every JSON result, shown by compare
+  --export-records <PATH> Also write every iteration (size, bytes, connect and round-trip
+                          time, error) to this CSV file
+  --append                Add to the --output and --export-records files instead of
+                          replacing them; a CSV file's header has to match
+  --units <UNITS>         Rate units in text output: bytes (KiB/s, 1024-based) or
+                          bits (Kbps, 1000-based) [default: bytes]
+  --no-color              Plain text output even on a terminal; NO_COLOR does the same
+  -q, --quiet             Print only the totals over all sizes: one line, or one JSON object
+                          with --format json
+  -h, --help              Print this help
+
+Environment:
+  TRAFFIC_ADDRESS, TRAFFIC_SIZES, TRAFFIC_ITERATIONS
+                          Settings for --address, --sizes and --iterations, applied over
+                          the settings file; flags still override them";
+
+// Everything run_main needs to know about a run
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub address: Option<String>, // defaults to wherever the mock server bound
+    pub hosts: Vec<String>,      // compared side by side instead of `address` when not empty
+    pub parallel_hosts: bool,
+    pub sizes: Vec<usize>,
+    pub iterations: usize,
+    // `--iterations auto`: `iterations` is then only the first batch
+    pub auto_iterations: Option<AutoIterations>,
+    pub total_bytes: Option<usize>, // bytes each size sends, replacing `iterations`
+    pub delay: Duration,
+    pub timeout: Duration,
+    pub max_duration: Option<Duration>, // for the whole run, over every size
+    pub retries: usize,
+    pub eof_retries: usize,
+    pub fail_fast: bool,
+    pub backoff: Duration,
+    pub connect_head_start: Duration,
+    pub socks5: Option<SocksConfig>, // proxy every TCP connection goes through when set
+    pub concurrency: usize,
+    pub burst: bool, // every iteration at once instead of `concurrency` at a time
+    pub warmup: usize,
+    pub reuse_connection: bool,
+    pub half_close: bool,
+    pub expected_response_len: Option<usize>, // bytes every response must announce
+    pub direction: Direction,
+    pub response_size: Option<usize>, // asked of the server by every request when set
+    pub sequence: bool,
+    // --under-load: bulk transfers of this size, with pings alongside, instead of a sweep
+    pub under_load: Option<usize>,
+    pub ping_interval: Duration,
+    // --keepalive: an idle connection pinged this often, instead of a sweep
+    pub keepalive: Option<Duration>,
+    pub keepalive_limit: Option<Duration>,
+    pub size_mix: Option<SizeMix>, // --size-mix: sizes drawn per iteration instead of a sweep
+    pub goodput: Option<ProtocolOverheadModel>, // --goodput: an estimate next to each size
+    pub link_bandwidth: Option<f64>, // bits per second the rates are compared against
+    // --parallel: this many connections pushing at once for parallel_duration, instead of
+    // a sweep
+    pub parallel: Option<usize>,
+    pub parallel_duration: Duration,
+    pub repeat: Option<usize>, // --repeat: the whole sweep this many times, compared across runs
+    // --soak: the largest size repeated for this long, its throughput trend reported
+    pub soak: Option<Duration>,
+    pub soak_window: Option<Duration>,
+    pub soak_max_drop: Option<f64>, // percent
+    pub ramp: Option<Vec<usize>>, // --ramp: the largest size at each of these concurrencies
+    // --dedup-check: repeated against unique payloads, to catch caching on the path
+    pub dedup_check: bool,
+    pub dedup_threshold: Option<f64>, // percent
+    pub tcp_nodelay: bool,
+    pub socket_send_buffer: Option<u32>,
+    pub socket_recv_buffer: Option<u32>,
+    pub bind_addr: Option<SocketAddr>, // local address every TCP connection is bound to
+    pub requests_per_connection: usize,
+    pub think_time: Duration,
+    pub sample_interval: Option<Duration>, // streaming mode when set
+    pub target_rate: Option<f64>,          // bytes per second; unthrottled when unset
+    pub write_chunk_size: Option<usize>,   // also measured in single writes, per size
+    pub flush_chunks: bool,
+    pub verify: bool,
+    pub random_payload: bool,
+    pub seed: u64, // for random_payload
+    pub payload_file: Option<PathBuf>, // its length replaces `sizes`
+    pub replay: Option<PathBuf>, // schedule replayed instead of the size sweep
+    pub url: Option<String>, // HTTP mode when set
+    pub http2: Option<usize>, // --http2: --url requests as this many concurrent HTTP/2 streams
+    pub method: String,
+    pub follow_redirects: bool,
+    pub http_close: bool, // a connection per request instead of the keep-alive pool
+    pub http_idle_timeout: Duration,
+    pub mock_server: bool,
+    pub mock_address: String,
+    pub response_ratio: f64,
+    pub mock_max_response: usize, // largest response a client may ask the mock server for
+    pub mock_idle_timeout: Option<Duration>,
+    pub mock_backlog: u32,
+    pub mock_accept_pause: Option<Duration>,
+    pub mock_sink: bool, // the mock server never replies; implies --direction upload
+    pub progress: bool,
+    pub dry_run: bool,
+    pub thresholds: Thresholds, // checked after each size of a plain sweep
+    pub metrics_port: Option<u16>,
+    pub metrics_bind: Option<IpAddr>, // DEFAULT_METRICS_BIND when unset
+    pub otlp_endpoint: Option<String>, // only acted on with the `otlp` feature
+    pub db: Option<String>,            // only acted on with the `sqlite` feature
+    pub watch: bool,
+    pub watch_interval: Duration,
+    pub format: OutputFormat,
+    pub output: Option<String>, // json/csv destination; stdout when unset
+    pub label: Option<String>,  // saved with every result, for telling runs apart
+    pub export_records: Option<PathBuf>, // per-iteration CSV
+    pub append: bool, // to `output` and `export_records` instead of truncating them
+    pub units: RateUnits,
+    pub no_color: bool,
+    // Only the aggregate over all sizes: one text line or one JSON object
+    pub quiet: bool,
+}
+
+impl Config {
+    // Endpoint to measure; run_main fills `address` in from the mock server when unset
+    pub fn target_address(&self) -> &str {
+        self.address.as_deref().unwrap_or("127.0.0.1:8080")
+    }
+
+    // The settings that shape the numbers, on one line, e.g. "tcp 127.0.0.1:8080, 5
+    // iterations, concurrency 1, delay 100ms"; saved with each result as config_summary
+    pub fn summary(&self) -> String {
+        let mut parts = vec![match &self.url {
+            Some(url) => format!("{} {}", self.method, url),
+            None => format!("tcp {}", self.target_address()),
+        }];
+        parts.push(match (self.auto_iterations, self.total_bytes) {
+            (Some(auto), _) => format!("iterations auto up to {}", auto.max_iterations),
+            (None, Some(total)) => format!("{} bytes in total", total),
+            (None, None) => format!("{} iterations", self.iterations),
+        });
+        parts.push(match self.http2 {
+            Some(streams) => format!("http2 with {} streams", streams),
+            None if self.burst => "burst".to_string(),
+            None => format!("concurrency {}", self.concurrency),
+        });
+        parts.push(format!("delay {:?}", self.delay));
+        match self.direction {
+            Direction::Both => {}
+            Direction::UploadOnly => parts.push("upload only".to_string()),
+            Direction::DownloadOnly => parts.push("download only".to_string()),
+        }
+        if self.reuse_connection {
+            parts.push("reused connection".to_string());
+        }
+        if self.requests_per_connection > 1 {
+            parts.push(format!("{} requests per connection", self.requests_per_connection));
+        }
+        if self.http_close {
+            parts.push("a connection per request".to_string());
+        }
+        if self.tcp_nodelay {
+            parts.push("nodelay".to_string());
+        }
+        if let Some(rate) = self.target_rate {
+            parts.push(format!("target rate {} B/s", rate));
+        }
+        if self.random_payload {
+            parts.push(format!("random payload, seed {}", self.seed));
+        }
+        if let Some(proxy) = &self.socks5 {
+            parts.push(format!("via socks5 {}", proxy.proxy));
+        }
+        parts.join(", ")
+    }
+
+    // Whether text output to a stream gets ANSI colors; json and csv never do
+    pub fn color_for(&self, terminal: bool) -> bool {
+        self.format == OutputFormat::Text && !self.no_color && color::wanted(terminal)
+    }
+
+    // Every mode the flags ask for, in the order run_main would pick them; parse_args
+    // allows at most one
+    pub fn modes(&self) -> Vec<RunMode<'_>> {
+        let mut modes = Vec::new();
+        if !self.hosts.is_empty() {
+            modes.push(RunMode::Hosts);
+        }
+        if self.watch {
+            modes.push(RunMode::Watch);
+        }
+        if let Some(path) = &self.replay {
+            modes.push(RunMode::Replay(path));
+        }
+        if let Some(bulk_size) = self.under_load {
+            modes.push(RunMode::UnderLoad(bulk_size));
+        }
+        if let Some(interval) = self.keepalive {
+            modes.push(RunMode::Keepalive(interval));
+        }
+        if let Some(mix) = &self.size_mix {
+            modes.push(RunMode::SizeMix(mix));
+        }
+        if let Some(repeats) = self.repeat {
+            modes.push(RunMode::Repeat(repeats));
+        }
+        if let Some(streams) = self.parallel {
+            modes.push(RunMode::Parallel(streams));
+        }
+        if let Some(duration) = self.soak {
+            modes.push(RunMode::Soak(duration));
+        }
+        if let Some(levels) = &self.ramp {
+            modes.push(RunMode::Ramp(levels));
+        }
+        if self.dedup_check {
+            modes.push(RunMode::DedupCheck);
+        }
+        modes
+    }
+
+    pub fn mode(&self) -> RunMode<'_> {
+        self.modes().first().copied().unwrap_or(RunMode::Sweep)
+    }
+}
+
+// How a run measures. Each mode flag picks one in place of the plain sweep over --sizes,
+// so they exclude each other.
+#[derive(Debug, Clone, Copy)]
+pub enum RunMode<'a> {
+    Sweep,
+    Hosts,
+    Watch,
+    Replay(&'a Path),
+    UnderLoad(usize), // the bulk transfers' size
+    Keepalive(Duration),
+    SizeMix(&'a SizeMix),
+    Repeat(usize),
+    Parallel(usize), // streams
+    Soak(Duration),
+    Ramp(&'a [usize]),
+    DedupCheck,
+}
+
+impl RunMode<'_> {
+    // The flag that picks it, for error messages; a sweep is what's left without one
+    pub fn flag(&self) -> &'static str {
+        match self {
+            RunMode::Sweep => "",
+            RunMode::Hosts => "--hosts",
+            RunMode::Watch => "--watch",
+            RunMode::Replay(_) => "--replay",
+            RunMode::UnderLoad(_) => "--under-load",
+            RunMode::Keepalive(_) => "--keepalive",
+            RunMode::SizeMix(_) => "--size-mix",
+            RunMode::Repeat(_) => "--repeat",
+            RunMode::Parallel(_) => "--parallel",
+            RunMode::Soak(_) => "--soak",
+            RunMode::Ramp(_) => "--ramp",
+            RunMode::DedupCheck => "--dedup-check",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json, // one JSON object per measured size, no decoration
+    Csv,  // header row, then one row of raw numbers per measured size
+    // --watch only: one compact, timestamped object per size and cycle, each line
+    // flushed as it's written, for tail -f and time-series ingestion
+    JsonLines,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            address: None,
+            hosts: Vec::new(),
+            parallel_hosts: false,
+            sizes: vec![1024, 10 * 1024, 100 * 1024],
+            iterations: 5,
+            auto_iterations: None,
+            total_bytes: None,
+            delay: Duration::from_millis(100),
+            timeout: Duration::from_secs(10),
+            max_duration: None,
+            retries: 0,
+            eof_retries: 0,
+            fail_fast: false,
+            backoff: Duration::from_millis(100),
+            connect_head_start: DEFAULT_HEAD_START,
+            socks5: None,
+            concurrency: 1,
+            burst: false,
+            warmup: 0,
+            reuse_connection: false,
+            half_close: false,
+            expected_response_len: None,
+            direction: Direction::Both,
+            response_size: None,
+            sequence: false,
+            under_load: None,
+            ping_interval: Duration::from_millis(50),
+            keepalive: None,
+            keepalive_limit: None,
+            size_mix: None,
+            goodput: None,
+            link_bandwidth: None,
+            parallel: None,
+            repeat: None,
+            soak: None,
+            soak_window: None,
+            soak_max_drop: None,
+            ramp: None,
+            dedup_check: false,
+            dedup_threshold: None,
+            parallel_duration: Duration::from_secs(10),
+            tcp_nodelay: false,
+            socket_send_buffer: None,
+            socket_recv_buffer: None,
+            bind_addr: None,
+            requests_per_connection: 1,
+            think_time: Duration::ZERO,
+            sample_interval: None,
+            target_rate: None,
+            write_chunk_size: None,
+            flush_chunks: false,
+            verify: false,
+            random_payload: false,
+            seed: DEFAULT_SEED,
+            payload_file: None,
+            replay: None,
+            url: None,
+            http2: None,
+            method: "POST".to_string(),
+            follow_redirects: false,
+            http_close: false,
+            http_idle_timeout: Duration::from_secs(90),
+            mock_server: true,
+            mock_address: "127.0.0.1:0".to_string(),
+            response_ratio: 0.5,
+            mock_max_response: DEFAULT_MAX_RESPONSE_LEN,
+            mock_idle_timeout: None,
+            mock_backlog: DEFAULT_BACKLOG,
+            mock_accept_pause: None,
+            mock_sink: false,
+            progress: false,
+            dry_run: false,
+            thresholds: Thresholds::default(),
+            metrics_port: None,
+            metrics_bind: None,
+            otlp_endpoint: None,
+            db: None,
+            watch: false,
+            watch_interval: Duration::from_secs(5),
+            format: OutputFormat::Text,
+            output: None,
+            label: None,
+            export_records: None,
+            append: false,
+            units: RateUnits::Bytes,
+            no_color: false,
+            quiet: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateUnits {
+    Bytes,
+    Bits,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    pub listen: String,
+    pub upstream: String,
+    pub limits: ProxyLimits,
+    pub faults: ProxyFaults,
+    pub check_sequence: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct AttachConfig {
+    pub pid: u32,
+    pub interval: Duration,
+    pub count: Option<usize>, // None runs until Ctrl-C
+    pub units: RateUnits,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Run(Box<Config>),
+    Proxy(ProxyConfig),
+    Compare { baseline: String, candidate: String },
+    Estimate { results: String, size: usize },
+    Attach(AttachConfig),
+    SelfTest,
+    Help,
+}
+
+// Parses the arguments after the program name. Errors are human-readable messages.
+pub fn parse_args<I>(args: I) -> Result<Command, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter().peekable();
+    if args.peek().map(String::as_str) == Some("proxy") {
+        args.next();
+        return parse_proxy_args(args);
+    }
+    if args.peek().map(String::as_str) == Some("compare") {
+        args.next();
+        return parse_compare_args(args);
+    }
+    if args.peek().map(String::as_str) == Some("estimate") {
+        args.next();
+        return parse_estimate_args(args);
+    }
+    if args.peek().map(String::as_str) == Some("attach") {
+        args.next();
+        return parse_attach_args(args);
+    }
+    if args.peek().map(String::as_str) == Some("selftest") {
+        args.next();
+        return parse_selftest_args(args);
+    }
+
+    // The settings file is the base layer, so it has to be found before the flags
+    let args: Vec<String> = args.collect();
+    let mut config = Config::default();
+    match args.iter().position(|arg| arg == "--config") {
+        Some(i) => {
+            let path = args.get(i + 1).ok_or("--config requires a value")?;
+            load_config_file(Path::new(path), &mut config)?;
+        }
+        None if Path::new(DEFAULT_CONFIG_FILE).is_file() => {
+            load_config_file(Path::new(DEFAULT_CONFIG_FILE), &mut config)?;
+        }
+        None => {}
+    }
+    apply_env(|name| std::env::var_os(name), &mut config)?;
+
+    let mut args = args.into_iter();
+    // Only take effect with --iterations auto, which may come after them
+    let (mut target_precision, mut max_iterations) = (None, None);
+    // Joined into the --socks5 config once every flag is read
+    let (mut socks5_user, mut socks5_password) = (None, None);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--config" => {
+                args.next(); // already loaded
+            }
+            "--address" => config.address = Some(value(&arg, args.next())?),
+            "--hosts" => {
+                let raw = value(&arg, args.next())?;
+                config.hosts = raw.split(',').map(|h| h.trim().to_string()).collect();
+                if config.hosts.iter().any(String::is_empty) {
+                    return Err(format!("--hosts expects HOST:PORT,HOST:PORT,..., got '{}'", raw));
+                }
+            }
+            "--parallel-hosts" => config.parallel_hosts = true,
+            "--sizes" => config.sizes = parse_sizes(&value(&arg, args.next())?)?,
+            "--iterations" => match value(&arg, args.next())?.as_str() {
+                "auto" => config.auto_iterations = Some(AutoIterations::default()),
+                raw => {
+                    config.iterations = parse_number(&arg, raw)?;
+                    config.auto_iterations = None;
+                    if config.iterations == 0 {
+                        return Err("--iterations must be at least 1".to_string());
+                    }
+                }
+            },
+            "--target-precision" => {
+                target_precision = Some(parse_precision(&arg, &value(&arg, args.next())?)?);
+            }
+            "--total-bytes" => {
+                config.total_bytes = Some(parse_number(&arg, &value(&arg, args.next())?)?);
+            }
+            "--max-iterations" => {
+                max_iterations = Some(parse_number(&arg, &value(&arg, args.next())?)?);
+            }
+            "--delay-ms" => {
+                let ms = parse_number(&arg, &value(&arg, args.next())?)?;
+                config.delay = Duration::from_millis(ms as u64);
+            }
+            "--warmup" => config.warmup = parse_number(&arg, &value(&arg, args.next())?)?,
+            "--reuse-connection" => config.reuse_connection = true,
+            "--half-close" => config.half_close = true,
+            "--direction" => config.direction = parse_direction(&value(&arg, args.next())?)?,
+            "--sequence" => config.sequence = true,
+            "--under-load" => {
+                config.under_load = Some(parse_number(&arg, &value(&arg, args.next())?)?);
+            }
+            "--ping-interval" => {
+                config.ping_interval = parse_positive_duration(&arg, &value(&arg, args.next())?)?;
+            }
+            "--keepalive" => {
+                config.keepalive = Some(parse_positive_duration(&arg, &value(&arg, args.next())?)?);
+            }
+            "--keepalive-limit" => {
+                let limit = parse_positive_duration(&arg, &value(&arg, args.next())?)?;
+                config.keepalive_limit = Some(limit);
+            }
+            "--size-mix" => {
+                let mix = parse_mix(&value(&arg, args.next())?);
+                config.size_mix = Some(mix.map_err(|e| format!("--size-mix: {}", e))?);
+            }
+            "--parallel" => {
+                let streams = parse_number(&arg, &value(&arg, args.next())?)?;
+                if streams == 0 {
+                    return Err("--parallel needs at least one stream".to_string());
+                }
+                config.parallel = Some(streams);
+            }
+            "--repeat" => {
+                let repeats = parse_number(&arg, &value(&arg, args.next())?)?;
+                if repeats == 0 {
+                    return Err("--repeat needs at least one run".to_string());
+                }
+                config.repeat = Some(repeats);
+            }
+            "--ramp" => config.ramp = Some(parse_ramp(&arg, &value(&arg, args.next())?)?),
+            "--dedup-check" => config.dedup_check = true,
+            "--dedup-threshold" => {
+                config.dedup_threshold = Some(parse_positive(&arg, &value(&arg, args.next())?)?);
+            }
+            "--soak" => {
+                let duration = parse_positive_duration(&arg, &value(&arg, args.next())?)?;
+                config.soak = Some(duration);
+            }
+            "--soak-window" => {
+                let window = parse_positive_duration(&arg, &value(&arg, args.next())?)?;
+                config.soak_window = Some(window);
+            }
+            "--soak-max-drop" => {
+                config.soak_max_drop = Some(parse_positive(&arg, &value(&arg, args.next())?)?);
+            }
+            "--parallel-duration" => {
+                let duration = parse_positive_duration(&arg, &value(&arg, args.next())?)?;
+                config.parallel_duration = duration;
+            }
+            "--link-bandwidth" => {
+                config.link_bandwidth = Some(parse_bandwidth(&arg, &value(&arg, args.next())?)?);
+            }
+            "--goodput" => {
+                let model = parse_overhead_model(&value(&arg, args.next())?);
+                config.goodput = Some(model.map_err(|e| format!("--goodput: {}", e))?);
+            }
+            "--response-size" => {
+                config.response_size = Some(parse_number(&arg, &value(&arg, args.next())?)?);
+            }
+            "--mock-max-response" => {
+                config.mock_max_response = parse_number(&arg, &value(&arg, args.next())?)?;
+            }
+            "--mock-idle-timeout" => {
+                let timeout = parse_positive_duration(&arg, &value(&arg, args.next())?)?;
+                config.mock_idle_timeout = Some(timeout);
+            }
+            "--mock-backlog" => {
+                let backlog = parse_number(&arg, &value(&arg, args.next())?)?;
+                config.mock_backlog = u32::try_from(backlog)
+                    .ok()
+                    .filter(|&backlog| backlog > 0)
+                    .ok_or_else(|| format!("--mock-backlog expects 1 to {}", u32::MAX))?;
+            }
+            "--mock-accept-pause" => {
+                let pause = parse_positive_duration(&arg, &value(&arg, args.next())?)?;
+                config.mock_accept_pause = Some(pause);
+            }
+            "--mock-sink" => config.mock_sink = true,
+            "--expect-response" => {
+                let len = parse_number(&arg, &value(&arg, args.next())?)?;
+                config.expected_response_len = Some(len);
+            }
+            "--tcp-nodelay" => config.tcp_nodelay = true,
+            "--requests-per-connection" => {
+                config.requests_per_connection = parse_number(&arg, &value(&arg, args.next())?)?;
+                if config.requests_per_connection == 0 {
+                    return Err("--requests-per-connection must be at least 1".to_string());
+                }
+            }
+            "--think-time" => {
+                config.think_time = parse_duration(&arg, &value(&arg, args.next())?)?;
+            }
+            "--send-buffer" => {
+                let size = parse_buffer_size(&arg, &value(&arg, args.next())?)?;
+                config.socket_send_buffer = Some(size);
+            }
+            "--recv-buffer" => {
+                let size = parse_buffer_size(&arg, &value(&arg, args.next())?)?;
+                config.socket_recv_buffer = Some(size);
+            }
+            "--bind" => config.bind_addr = Some(parse_bind(&arg, &value(&arg, args.next())?)?),
+            "--concurrency" => config.concurrency = parse_number(&arg, &value(&arg, args.next())?)?,
+            "--burst" => config.burst = true,
+            "--verify" => config.verify = true,
+            "--random-payload" => config.random_payload = true,
+            "--seed" => config.seed = parse_number(&arg, &value(&arg, args.next())?)? as u64,
+            "--payload-file" => config.payload_file = Some(value(&arg, args.next())?.into()),
+            "--replay" => config.replay = Some(value(&arg, args.next())?.into()),
+            "--write-chunk-size" => {
+                let raw = value(&arg, args.next())?;
+                config.write_chunk_size = match parse_number(&arg, &raw)? {
+                    0 => return Err("--write-chunk-size must be at least 1 byte".to_string()),
+                    chunk => Some(chunk),
+                };
+            }
+            "--flush-chunks" => config.flush_chunks = true,
+            "--target-rate" => {
+                let raw = value(&arg, args.next())?;
+                config.target_rate = match raw.parse::<f64>() {
+                    Ok(rate) if rate > 0.0 && rate.is_finite() => Some(rate),
+                    _ => {
+                        return Err(format!(
+                            "--target-rate expects a positive number of bytes per second, got '{}'",
+                            raw
+                        ))
+                    }
+                };
+            }
+            "--sample-ms" => {
+                let ms = parse_number(&arg, &value(&arg, args.next())?)?;
+                if ms == 0 {
+                    return Err("--sample-ms must be at least 1".to_string());
+                }
+                config.sample_interval = Some(Duration::from_millis(ms as u64));
+            }
+            "--url" => config.url = Some(value(&arg, args.next())?),
+            "--method" => config.method = value(&arg, args.next())?,
+            "--follow-redirects" => config.follow_redirects = true,
+            "--http-close" => config.http_close = true,
+            "--http2" => {
+                let streams = parse_number(&arg, &value(&arg, args.next())?)?;
+                if streams == 0 {
+                    return Err("--http2 needs at least one stream".to_string());
+                }
+                config.http2 = Some(streams);
+            }
+            "--http-idle-timeout" => {
+                let timeout = parse_positive_duration(&arg, &value(&arg, args.next())?)?;
+                config.http_idle_timeout = timeout;
+            }
+            "--max-duration" => {
+                let limit = parse_positive_duration(&arg, &value(&arg, args.next())?)?;
+                config.max_duration = Some(limit);
+            }
+            "--timeout-ms" => {
+                let ms = parse_number(&arg, &value(&arg, args.next())?)?;
+                config.timeout = Duration::from_millis(ms as u64);
+            }
+            "--retries" => config.retries = parse_number(&arg, &value(&arg, args.next())?)?,
+            "--eof-retries" => {
+                config.eof_retries = parse_number(&arg, &value(&arg, args.next())?)?
+            }
+            "--fail-fast" => config.fail_fast = true,
+            "--backoff-ms" => {
+                let ms = parse_number(&arg, &value(&arg, args.next())?)?;
+                config.backoff = Duration::from_millis(ms as u64);
+            }
+            "--connect-head-start" => {
+                config.connect_head_start = parse_duration(&arg, &value(&arg, args.next())?)?;
+            }
+            "--socks5" => {
+                let proxy = value(&arg, args.next())?;
+                match proxy.rsplit_once(':') {
+                    Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {}
+                    _ => return Err(format!("--socks5 expects HOST:PORT, got '{}'", proxy)),
+                }
+                config.socks5 = Some(SocksConfig::new(&proxy));
+            }
+            "--socks5-user" => socks5_user = Some(parse_credential(&arg, args.next())?),
+            "--socks5-password" => socks5_password = Some(parse_credential(&arg, args.next())?),
+            "--no-mock-server" => config.mock_server = false,
+            "--mock-address" => config.mock_address = value(&arg, args.next())?,
+            "--ipv6" => config.mock_address = "[::1]:0".to_string(),
+            "--response-ratio" => {
+                let raw = value(&arg, args.next())?;
+                config.response_ratio = match raw.parse::<f64>() {
+                    Ok(ratio) if ratio >= 0.0 && ratio.is_finite() => ratio,
+                    _ => {
+                        return Err(format!(
+                            "--response-ratio expects a non-negative number, got '{}'",
+                            raw
+                        ))
+                    }
+                };
+            }
+            "--progress" => config.progress = true,
+            "--dry-run" => config.dry_run = true,
+            "--min-download-rate" => {
+                let rate = parse_positive(&arg, &value(&arg, args.next())?)?;
+                config.thresholds.min_download_rate = Some(rate);
+            }
+            "--max-ratio" => {
+                let ratio = parse_positive(&arg, &value(&arg, args.next())?)?;
+                config.thresholds.max_ratio = Some(ratio);
+            }
+            "--max-p99-latency" => {
+                let limit = parse_positive_duration(&arg, &value(&arg, args.next())?)?;
+                config.thresholds.max_p99_latency = Some(limit);
+            }
+            "--metrics-port" => {
+                let raw = value(&arg, args.next())?;
+                let port = raw
+                    .parse::<u16>()
+                    .map_err(|_| format!("--metrics-port expects a port number, got '{}'", raw))?;
+                config.metrics_port = Some(port);
+            }
+            "--metrics-bind" => {
+                let raw = value(&arg, args.next())?;
+                let address = raw.parse::<IpAddr>().map_err(|_| {
+                    format!("--metrics-bind expects an IP address like 0.0.0.0, got '{}'", raw)
+                })?;
+                config.metrics_bind = Some(address);
+            }
+            "--otlp-endpoint" => {
+                let endpoint = value(&arg, args.next())?;
+                if !cfg!(feature = "otlp") {
+                    return Err("--otlp-endpoint needs a build with `--features otlp`".to_string());
+                }
+                config.otlp_endpoint = Some(endpoint);
+            }
+            "--db" => {
+                let path = value(&arg, args.next())?;
+                if !cfg!(feature = "sqlite") {
+                    return Err("--db needs a build with `--features sqlite`".to_string());
+                }
+                config.db = Some(path);
+            }
+            "--watch" => config.watch = true,
+            "--interval" => {
+                config.watch_interval = parse_positive_duration(&arg, &value(&arg, args.next())?)?;
+            }
+            "--format" => config.format = parse_format(&value(&arg, args.next())?)?,
+            "--output" => config.output = Some(value(&arg, args.next())?),
+            "--label" => {
+                let label = value(&arg, args.next())?;
+                if label.trim().is_empty() {
+                    return Err("--label needs some text".to_string());
+                }
+                config.label = Some(label);
+            }
+            "--export-records" => config.export_records = Some(value(&arg, args.next())?.into()),
+            "--append" => config.append = true,
+            "--units" => config.units = parse_units(&value(&arg, args.next())?)?,
+            "--no-color" => config.no_color = true,
+            "-q" | "--quiet" => config.quiet = true,
+            other => return Err(format!("unknown argument '{}'", other)),
+        }
+    }
+
+    if let [first, second, ..] = config.modes()[..] {
+        return Err(format!(
+            "{} and {} are separate run modes, so they can't be combined",
+            first.flag(),
+            second.flag()
+        ));
+    }
+    if config.watch && config.format == OutputFormat::Csv {
+        return Err("--watch supports text, json and jsonl output, not csv".to_string());
+    }
+    if !config.watch && config.format == OutputFormat::JsonLines {
+        return Err("--format jsonl streams --watch cycles; use --format json for a single \
+                    sweep"
+            .to_string());
+    }
+    if !config.thresholds.is_empty() && (config.watch || !config.hosts.is_empty()) {
+        return Err("thresholds are checked after a single sweep, not with --watch or --hosts"
+            .to_string());
+    }
+    match (socks5_user, socks5_password) {
+        (Some(username), Some(password)) => match &mut config.socks5 {
+            Some(socks5) => socks5.auth = Some(SocksAuth { username, password }),
+            None => return Err("--socks5-user and --socks5-password need --socks5".to_string()),
+        },
+        (None, None) => {}
+        _ => return Err("--socks5-user and --socks5-password go together".to_string()),
+    }
+    if config.socks5.is_some() && config.url.is_some() {
+        return Err("--socks5 proxies raw TCP connections, not --url".to_string());
+    }
+    match &mut config.auto_iterations {
+        Some(auto) => {
+            auto.target_precision = target_precision.unwrap_or(auto.target_precision);
+            auto.max_iterations = max_iterations.unwrap_or(auto.max_iterations);
+            if auto.max_iterations + config.warmup < config.iterations {
+                return Err("--max-iterations is below the first batch of --iterations auto"
+                    .to_string());
+            }
+            if config.url.is_some() || config.sample_interval.is_some() || config.replay.is_some()
+            {
+                return Err("--iterations auto applies to raw TCP sweeps, not --url, \
+                            --sample-ms or --replay"
+                    .to_string());
+            }
+        }
+        None if target_precision.is_some() || max_iterations.is_some() => {
+            return Err("--target-precision and --max-iterations need --iterations auto"
+                .to_string());
+        }
+        None => {}
+    }
+    if config.half_close && (config.reuse_connection || config.requests_per_connection > 1) {
+        return Err("--half-close ends each connection after one request, so it can't be used \
+                    with --reuse-connection or --requests-per-connection"
+            .to_string());
+    }
+    if config.expected_response_len.is_some() {
+        if config.url.is_some() {
+            return Err("--expect-response checks raw TCP responses, not --url".to_string());
+        }
+        if config.sizes.len() > 1 && config.payload_file.is_none() {
+            return Err("--expect-response pins one response length, so it needs a single \
+                        --sizes value"
+                .to_string());
+        }
+    }
+    if config.sequence {
+        if config.url.is_some() || config.sample_interval.is_some() {
+            return Err("--sequence numbers raw TCP requests, not --url or --sample-ms".to_string());
+        }
+        if config.verify || config.direction == Direction::DownloadOnly {
+            return Err("--sequence writes into the request body, so it can't be combined with \
+                        --verify or --direction download"
+                .to_string());
+        }
+        if config.payload_file.is_none() && config.sizes.iter().any(|&size| size < 8) {
+            return Err("--sequence needs every size to be at least 8 bytes".to_string());
+        }
+    }
+    if config.mock_sink {
+        let mode = config.mode();
+        let answered = matches!(
+            mode,
+            RunMode::Hosts | RunMode::Replay(_) | RunMode::UnderLoad(_) | RunMode::Keepalive(_)
+        );
+        reject_conflicts(
+            "--mock-sink",
+            "has the mock server discard every request unanswered",
+            &[
+                (!config.mock_server, "--no-mock-server"),
+                (config.address.is_some(), "--address"),
+                (config.url.is_some(), "--url"),
+                (config.sample_interval.is_some(), "--sample-ms"),
+                (answered, mode.flag()),
+                (config.verify, "--verify"),
+                (config.expected_response_len.is_some(), "--expect-response"),
+                (config.response_size.is_some(), "--response-size"),
+                (config.direction == Direction::DownloadOnly, "--direction download"),
+            ],
+        )?;
+        // Nothing comes back, so the client mustn't wait for it
+        config.direction = Direction::UploadOnly;
+    }
+    if let Some(size) = config.response_size {
+        if config.url.is_some() || config.sample_interval.is_some() {
+            return Err("--response-size applies to raw TCP iterations, not --url or --sample-ms"
+                .to_string());
+        }
+        if config.direction == Direction::UploadOnly {
+            return Err("--direction upload gets no response to size".to_string());
+        }
+        if config.mock_server && config.address.is_none() && size > config.mock_max_response {
+            return Err(format!(
+                "--response-size {} is over the mock server's limit of {} bytes; raise it \
+                 with --mock-max-response",
+                size, config.mock_max_response
+            ));
+        }
+    }
+    if config.direction != Direction::Both {
+        if config.url.is_some() || config.sample_interval.is_some() {
+            return Err("--direction applies to raw TCP iterations, not --url or --sample-ms"
+                .to_string());
+        }
+        if config.direction == Direction::UploadOnly
+            && (config.verify || config.expected_response_len.is_some())
+        {
+            return Err("--direction upload gets no response, so it can't take --verify or \
+                        --expect-response"
+                .to_string());
+        }
+    }
+    if config.requests_per_connection > 1
+        && (config.url.is_some() || config.sample_interval.is_some())
+    {
+        return Err("--requests-per-connection applies to raw TCP iterations, not --url or \
+                    --sample-ms"
+            .to_string());
+    }
+    if config.metrics_bind.is_some() && config.metrics_port.is_none() {
+        return Err("--metrics-bind needs --metrics-port".to_string());
+    }
+    if config.otlp_endpoint.is_some() && (config.watch || !config.hosts.is_empty()) {
+        return Err("--otlp-endpoint exports a single sweep, not --watch or --hosts".to_string());
+    }
+    if config.db.is_some() {
+        let mode = config.mode();
+        let swept = matches!(mode, RunMode::Sweep);
+        reject_conflicts(
+            "--db",
+            "records each size of a sweep",
+            &[(!swept, mode.flag()), (config.dry_run, "--dry-run")],
+        )?;
+    }
+    if !config.hosts.is_empty() {
+        if config.format == OutputFormat::Csv {
+            return Err("--hosts supports one pass with text or json output".to_string());
+        }
+        if config.url.is_some() || config.sample_interval.is_some() {
+            return Err("--hosts measures raw TCP, not --url or --sample-ms".to_string());
+        }
+    }
+    let custom_payload = config.random_payload || config.payload_file.is_some();
+    if config.random_payload && config.payload_file.is_some() {
+        return Err("--random-payload and --payload-file can't be combined".to_string());
+    }
+    if custom_payload && config.verify {
+        return Err("--verify sends its own pattern, so it can't take a custom payload".to_string());
+    }
+    if custom_payload && config.sample_interval.is_some() && config.url.is_none() {
+        return Err("--sample-ms streams zeros and can't take a custom payload".to_string());
+    }
+    if config.append
+        && config.export_records.is_none()
+        && (config.output.is_none() || config.format == OutputFormat::Text)
+    {
+        return Err("--append adds to an --output file of json or csv results, or to \
+                    --export-records, so it needs one of them"
+            .to_string());
+    }
+    if config.max_duration.is_some() {
+        let mode = config.mode();
+        let untimed = matches!(
+            mode,
+            RunMode::Hosts
+                | RunMode::Watch
+                | RunMode::UnderLoad(_)
+                | RunMode::Keepalive(_)
+                | RunMode::Parallel(_)
+                | RunMode::Soak(_)
+        );
+        reject_conflicts(
+            "--max-duration",
+            "limits a sweep's iterations",
+            &[
+                (untimed, mode.flag()),
+                (config.sample_interval.is_some(), "--sample-ms"),
+                (config.dry_run, "--dry-run"),
+            ],
+        )?;
+    }
+    if let Some(total) = config.total_bytes {
+        if total == 0 {
+            return Err("--total-bytes needs at least one byte".to_string());
+        }
+        let mode = config.mode();
+        let counted = matches!(
+            mode,
+            RunMode::Sweep | RunMode::Hosts | RunMode::Watch | RunMode::Repeat(_)
+        );
+        reject_conflicts(
+            "--total-bytes",
+            "sets each size's iterations from its payload",
+            &[
+                (config.url.is_some(), "--url"),
+                (config.sample_interval.is_some(), "--sample-ms"),
+                (!counted, mode.flag()),
+                (config.auto_iterations.is_some(), "--iterations auto"),
+                (config.direction == Direction::DownloadOnly, "--direction download"),
+                (config.requests_per_connection > 1, "--requests-per-connection"),
+                (config.dry_run, "--dry-run"),
+            ],
+        )?;
+        if config.payload_file.is_none() && config.sizes.contains(&0) {
+            return Err("--total-bytes can't be sent in 0-byte payloads".to_string());
+        }
+    }
+    if config.burst {
+        let mode = config.mode();
+        let batched = matches!(
+            mode,
+            RunMode::Sweep | RunMode::Hosts | RunMode::Watch | RunMode::Repeat(_) | RunMode::Soak(_)
+        );
+        reject_conflicts(
+            "--burst",
+            "starts every iteration at once on its own connection",
+            &[
+                (config.url.is_some(), "--url"),
+                (config.sample_interval.is_some(), "--sample-ms"),
+                (!batched, mode.flag()),
+                (config.concurrency > 1, "--concurrency"),
+                (config.reuse_connection, "--reuse-connection"),
+                (config.auto_iterations.is_some(), "--iterations auto"),
+                (config.total_bytes.is_some(), "--total-bytes"),
+            ],
+        )?;
+    }
+    if config.write_chunk_size.is_some() {
+        if config.url.is_some() || config.sample_interval.is_some() {
+            return Err("--write-chunk-size splits raw TCP requests, not --url or --sample-ms"
+                .to_string());
+        }
+    } else if config.flush_chunks {
+        return Err("--flush-chunks needs --write-chunk-size".to_string());
+    }
+    if config.http_close && config.url.is_none() {
+        return Err("--http-close applies to --url measurements".to_string());
+    }
+    if config.bind_addr.is_some() && config.url.is_some() {
+        return Err("--bind applies to raw TCP connections, not --url".to_string());
+    }
+    if config.http2.is_some() {
+        if config.url.is_none() {
+            return Err("--http2 applies to --url measurements".to_string());
+        }
+        reject_conflicts(
+            "--http2",
+            "multiplexes every request over one connection without following redirects",
+            &[(config.http_close, "--http-close"), (config.follow_redirects, "--follow-redirects")],
+        )?;
+    }
+    if config.export_records.is_some() {
+        let mode = config.mode();
+        let recorded = matches!(mode, RunMode::Sweep | RunMode::Replay(_));
+        reject_conflicts(
+            "--export-records",
+            "keeps the raw TCP iterations of a sweep or --replay",
+            &[
+                (config.url.is_some(), "--url"),
+                (config.sample_interval.is_some(), "--sample-ms"),
+                (!recorded, mode.flag()),
+            ],
+        )?;
+    }
+    if config.dry_run {
+        let mode = config.mode();
+        let projected = matches!(mode, RunMode::Sweep | RunMode::Hosts | RunMode::Watch);
+        reject_conflicts(
+            "--dry-run",
+            "projects a sweep's traffic without measuring it",
+            &[(!projected, mode.flag())],
+        )?;
+    }
+    if config.format == OutputFormat::Csv {
+        let mode = config.mode();
+        reject_conflicts(
+            "csv output",
+            "has a row per size of a plain sweep",
+            &[(!matches!(mode, RunMode::Sweep), mode.flag())],
+        )?;
+    }
+    if config.under_load.is_some() {
+        reject_conflicts(
+            "--under-load",
+            "runs its own bulk and ping workloads",
+            &[
+                (config.url.is_some(), "--url"),
+                (config.sample_interval.is_some(), "--sample-ms"),
+                (config.auto_iterations.is_some(), "--iterations auto"),
+            ],
+        )?;
+    }
+    if config.keepalive.is_some() {
+        reject_conflicts(
+            "--keepalive",
+            "watches one idle connection",
+            &[
+                (config.url.is_some(), "--url"),
+                (config.sample_interval.is_some(), "--sample-ms"),
+                (config.direction != Direction::Both, "--direction"),
+            ],
+        )?;
+    } else if config.keepalive_limit.is_some() {
+        return Err("--keepalive-limit needs --keepalive".to_string());
+    }
+    if config.size_mix.is_some() {
+        reject_conflicts(
+            "--size-mix",
+            "draws one size per iteration, one iteration at a time",
+            &[
+                (config.url.is_some(), "--url"),
+                (config.sample_interval.is_some(), "--sample-ms"),
+                (config.payload_file.is_some(), "--payload-file"),
+                (config.concurrency > 1, "--concurrency"),
+                (config.warmup > 0, "--warmup"),
+                (config.auto_iterations.is_some(), "--iterations auto"),
+            ],
+        )?;
+    }
+    if config.parallel.is_some() {
+        reject_conflicts(
+            "--parallel",
+            "pushes raw TCP uploads for a fixed time",
+            &[
+                (config.url.is_some(), "--url"),
+                (config.sample_interval.is_some(), "--sample-ms"),
+                (config.direction != Direction::Both, "--direction"),
+            ],
+        )?;
+    }
+    if config.repeat.is_some() {
+        reject_conflicts(
+            "--repeat",
+            "runs the whole sweep N times and reports each size across the runs",
+            &[
+                (config.sample_interval.is_some(), "--sample-ms"),
+                (!config.thresholds.is_empty(), "thresholds"),
+            ],
+        )?;
+    }
+    if config.soak.is_none() && (config.soak_window.is_some() || config.soak_max_drop.is_some()) {
+        return Err("--soak-window and --soak-max-drop need --soak".to_string());
+    }
+    if let Some(duration) = config.soak {
+        let window = config.soak_window.unwrap_or(duration / 10);
+        if window.is_zero() || duration < window * 3 {
+            return Err("--soak needs to last at least three --soak-window widths".to_string());
+        }
+        reject_conflicts(
+            "--soak",
+            "repeats raw TCP transfers for a fixed time and reports their trend",
+            &[
+                (config.url.is_some(), "--url"),
+                (config.sample_interval.is_some(), "--sample-ms"),
+                (config.auto_iterations.is_some(), "--iterations auto"),
+                (!config.thresholds.is_empty(), "thresholds"),
+            ],
+        )?;
+    }
+    if config.ramp.is_some() {
+        reject_conflicts(
+            "--ramp",
+            "measures raw TCP transfers at each of its concurrency levels",
+            &[
+                (config.url.is_some(), "--url"),
+                (config.sample_interval.is_some(), "--sample-ms"),
+                (config.concurrency > 1, "--concurrency"),
+                (config.reuse_connection, "--reuse-connection"),
+                (config.auto_iterations.is_some(), "--iterations auto"),
+                (!config.thresholds.is_empty(), "thresholds"),
+            ],
+        )?;
+    }
+    if config.dedup_threshold.is_some() && !config.dedup_check {
+        return Err("--dedup-threshold needs --dedup-check".to_string());
+    }
+    if config.dedup_check {
+        reject_conflicts(
+            "--dedup-check",
+            "sends its own random payloads over raw TCP, one at a time",
+            &[
+                (config.url.is_some(), "--url"),
+                (config.sample_interval.is_some(), "--sample-ms"),
+                (config.payload_file.is_some(), "--payload-file"),
+                (config.verify, "--verify"),
+                (config.concurrency > 1, "--concurrency"),
+                (config.warmup > 0, "--warmup"),
+                (config.auto_iterations.is_some(), "--iterations auto"),
+                (!config.thresholds.is_empty(), "thresholds"),
+            ],
+        )?;
+    }
+    if config.goodput.is_some() {
+        let mode = config.mode();
+        reject_conflicts(
+            "--goodput",
+            "models the protocol around a plain sweep's raw TCP transfers",
+            &[
+                (config.url.is_some(), "--url"),
+                (!matches!(mode, RunMode::Sweep), mode.flag()),
+                (config.quiet, "--quiet"),
+                (config.dry_run, "--dry-run"),
+                (config.format == OutputFormat::Csv, "csv output"),
+            ],
+        )?;
+    }
+    if config.link_bandwidth.is_some() {
+        let mode = config.mode();
+        reject_conflicts(
+            "--link-bandwidth",
+            "compares a plain sweep's rates",
+            &[
+                (!matches!(mode, RunMode::Sweep), mode.flag()),
+                (config.quiet, "--quiet"),
+                (config.dry_run, "--dry-run"),
+                (config.format == OutputFormat::Csv, "csv output"),
+            ],
+        )?;
+    }
+    if config.replay.is_some() {
+        reject_conflicts(
+            "--replay",
+            "sends its schedule's requests over one raw TCP connection",
+            &[
+                (config.url.is_some(), "--url"),
+                (config.sample_interval.is_some(), "--sample-ms"),
+                (custom_payload, "a custom payload"),
+                (config.concurrency > 1, "--concurrency"),
+                (config.requests_per_connection > 1, "--requests-per-connection"),
+                (config.half_close, "--half-close"),
+            ],
+        )?;
+    }
+    if config.quiet {
+        let mode = config.mode();
+        reject_conflicts(
+            "--quiet",
+            "reports a plain sweep's totals",
+            &[
+                (!matches!(mode, RunMode::Sweep), mode.flag()),
+                (config.progress, "--progress"),
+                (config.dry_run, "--dry-run"),
+                (config.format == OutputFormat::Csv, "csv output"),
+            ],
+        )?;
+    }
+    Ok(Command::Run(Box::new(config)))
+}
+
+fn parse_proxy_args(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut listen = None;
+    let mut upstream = None;
+    let mut limits = ProxyLimits::default();
+    let mut faults = ProxyFaults::default();
+    let mut check_sequence = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--listen" => listen = Some(value(&arg, args.next())?),
+            "--upstream" => upstream = Some(value(&arg, args.next())?),
+            "--up-rate" => {
+                limits.client_to_upstream = Some(parse_positive(&arg, &value(&arg, args.next())?)?)
+            }
+            "--down-rate" => {
+                limits.upstream_to_client = Some(parse_positive(&arg, &value(&arg, args.next())?)?)
+            }
+            "--latency" => {
+                faults.latency = parse_duration(&arg, &value(&arg, args.next())?)?;
+            }
+            "--drop-rate" => {
+                let raw = value(&arg, args.next())?;
+                faults.drop_rate = match raw.parse::<f64>() {
+                    Ok(rate) if (0.0..=1.0).contains(&rate) => rate,
+                    _ => {
+                        return Err(format!(
+                            "--drop-rate expects a fraction from 0 to 1, got '{}'",
+                            raw
+                        ))
+                    }
+                };
+            }
+            "--seed" => faults.seed = parse_number(&arg, &value(&arg, args.next())?)? as u64,
+            "--check-sequence" => check_sequence = true,
+            other => return Err(format!("unknown proxy argument '{}'", other)),
+        }
+    }
+    if check_sequence && faults.drop_rate > 0.0 {
+        return Err("--check-sequence follows frame boundaries, which dropped chunks break, so \
+                    it can't be combined with --drop-rate"
+            .to_string());
+    }
+
+    Ok(Command::Proxy(ProxyConfig {
+        listen: listen.ok_or("proxy requires --listen")?,
+        upstream: upstream.ok_or("proxy requires --upstream")?,
+        limits,
+        faults,
+        check_sequence,
+    }))
+}
+
+fn parse_compare_args(args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let files: Vec<String> = args.collect();
+    if files.iter().any(|arg| arg == "-h" || arg == "--help") {
+        return Ok(Command::Help);
+    }
+    match <[String; 2]>::try_from(files) {
+        Ok([baseline, candidate]) => Ok(Command::Compare { baseline, candidate }),
+        Err(_) => Err("compare expects exactly two files: <BASELINE.json> <NEW.json>".to_string()),
+    }
+}
+
+fn parse_estimate_args(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut size = None;
+    let mut results = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--size" => size = Some(parse_byte_size(&arg, &value(&arg, args.next())?)?),
+            _ if results.is_none() && !arg.starts_with("--") => results = Some(arg),
+            other => return Err(format!("unknown estimate argument '{}'", other)),
+        }
+    }
+    match (results, size) {
+        (Some(results), Some(size)) => Ok(Command::Estimate { results, size }),
+        _ => Err("estimate expects --size <BYTES> and one results file".to_string()),
+    }
+}
+
+fn parse_selftest_args(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    match args.next().as_deref() {
+        None => Ok(Command::SelfTest),
+        Some("-h" | "--help") => Ok(Command::Help),
+        Some(other) => Err(format!("selftest takes no arguments, got '{}'", other)),
+    }
+}
+
+fn parse_attach_args(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut pid = None;
+    let mut interval = Duration::from_secs(1);
+    let mut count = None;
+    let mut units = RateUnits::Bytes;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--pid" => {
+                let raw = value(&arg, args.next())?;
+                let invalid = || format!("--pid expects a process id, got '{}'", raw);
+                pid = Some(raw.parse().map_err(|_| invalid())?)
+            }
+            "--interval" => interval = parse_positive_duration(&arg, &value(&arg, args.next())?)?,
+            "--count" => count = Some(parse_number(&arg, &value(&arg, args.next())?)?),
+            "--units" => units = parse_units(&value(&arg, args.next())?)?,
+            other => return Err(format!("unknown attach argument '{}'", other)),
+        }
+    }
+
+    Ok(Command::Attach(AttachConfig {
+        pid: pid.ok_or("attach requires --pid")?,
+        interval,
+        count,
+        units,
+    }))
+}
+
+// The first of `conflicts` that's set, as "MODE WHY, so it can't be combined with FLAG"
+fn reject_conflicts(mode: &str, why: &str, conflicts: &[(bool, &str)]) -> Result<(), String> {
+    match conflicts.iter().find(|(conflict, _)| *conflict) {
+        Some((_, flag)) => Err(format!("{} {}, so it can't be combined with {}", mode, why, flag)),
+        None => Ok(()),
+    }
+}
+
+fn value(flag: &str, next: Option<String>) -> Result<String, String> {
+    next.ok_or_else(|| format!("{} requires a value", flag))
+}
+
+fn parse_number(flag: &str, raw: &str) -> Result<usize, String> {
+    raw.parse()
+        .map_err(|_| format!("{} expects a non-negative integer, got '{}'", flag, raw))
+}
+
+// "5%" or "0.05"
+fn parse_precision(flag: &str, raw: &str) -> Result<f64, String> {
+    let parsed = match raw.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>().map(|p| p / 100.0),
+        None => raw.parse::<f64>(),
+    };
+    match parsed {
+        Ok(precision) if precision > 0.0 && precision < 1.0 => Ok(precision),
+        _ => Err(format!("{} expects a margin like 5% or 0.05, got '{}'", flag, raw)),
+    }
+}
+
+// RFC 1929 limits each of the username and password to 255 bytes
+fn parse_credential(flag: &str, next: Option<String>) -> Result<String, String> {
+    let raw = value(flag, next)?;
+    if raw.is_empty() || raw.len() > 255 {
+        return Err(format!("{} expects 1 to 255 bytes", flag));
+    }
+    Ok(raw)
+}
+
+fn parse_buffer_size(flag: &str, raw: &str) -> Result<u32, String> {
+    match raw.parse::<u32>() {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err(format!("{} expects a buffer size in bytes, got '{}'", flag, raw)),
+    }
+}
+
+// Bits per second, with an optional decimal multiple and "bps": "1G", "100Mbps", "2.5e9"
+fn parse_bandwidth(flag: &str, raw: &str) -> Result<f64, String> {
+    let trimmed = raw.trim();
+    let number = trimmed.strip_suffix("bps").unwrap_or(trimmed);
+    let (number, multiple) = match number.char_indices().last() {
+        Some((at, 'k' | 'K')) => (&number[..at], 1e3),
+        Some((at, 'M')) => (&number[..at], 1e6),
+        Some((at, 'G')) => (&number[..at], 1e9),
+        Some((at, 'T')) => (&number[..at], 1e12),
+        _ => (number, 1.0),
+    };
+    match number.trim().parse::<f64>() {
+        Ok(n) if n > 0.0 && (n * multiple).is_finite() => Ok(n * multiple),
+        _ => Err(format!(
+            "{} expects a bandwidth in bits per second, e.g. 1G or 100Mbps, got '{}'",
+            flag, raw
+        )),
+    }
+}
+
+// Bytes, with an optional decimal or binary unit: "5GB", "1.5 MiB", "100000"
+fn parse_byte_size(flag: &str, raw: &str) -> Result<usize, String> {
+    let trimmed = raw.trim();
+    let units = [
+        ("KiB", 1u64 << 10),
+        ("MiB", 1 << 20),
+        ("GiB", 1 << 30),
+        ("TiB", 1 << 40),
+        ("KB", 1_000),
+        ("MB", 1_000_000),
+        ("GB", 1_000_000_000),
+        ("TB", 1_000_000_000_000),
+        ("B", 1),
+    ];
+    let (number, multiple) = units
+        .iter()
+        .find_map(|&(unit, multiple)| Some((trimmed.strip_suffix(unit)?, multiple)))
+        .unwrap_or((trimmed, 1));
+    match number.trim().parse::<f64>() {
+        Ok(n) if n > 0.0 && (n * multiple as f64) < usize::MAX as f64 => {
+            Ok((n * multiple as f64).round() as usize)
+        }
+        _ => Err(format!("{} expects a size in bytes, e.g. 5GB or 512MiB, got '{}'", flag, raw)),
+    }
+}
+
+// An IP, bound with a port of the OS's choosing, or IP:PORT ([::1]:PORT for IPv6)
+fn parse_bind(flag: &str, raw: &str) -> Result<SocketAddr, String> {
+    if let Ok(address) = raw.parse::<SocketAddr>() {
+        return Ok(address);
+    }
+    match raw.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) => Ok(SocketAddr::new(ip, 0)),
+        Err(_) => Err(format!("{} expects a local IP address or IP:PORT, got '{}'", flag, raw)),
+    }
+}
+
+// "1,2,4,8" as given, or "16" for the doublings 1, 2, 4, 8, 16; the levels must rise
+fn parse_ramp(flag: &str, raw: &str) -> Result<Vec<usize>, String> {
+    let invalid = || {
+        format!("{} expects concurrencies like 1,2,4,8 or a top level like 16, got '{}'", flag, raw)
+    };
+    let mut levels = raw
+        .split(',')
+        .map(|part| part.trim().parse::<usize>().ok().filter(|&n| n > 0))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(invalid)?;
+    if let [top] = levels[..] {
+        levels = std::iter::successors(Some(1usize), |n| n.checked_mul(2))
+            .take_while(|&n| n < top)
+            .chain(std::iter::once(top))
+            .collect();
+    }
+    if levels.len() < 2 {
+        return Err(format!("{} needs at least two levels to compare, got '{}'", flag, raw));
+    }
+    if levels.windows(2).any(|pair| pair[1] <= pair[0]) {
+        return Err(format!("{} levels have to rise, got '{}'", flag, raw));
+    }
+    Ok(levels)
+}
+
+fn parse_positive(flag: &str, raw: &str) -> Result<f64, String> {
+    match raw.parse::<f64>() {
+        Ok(n) if n > 0.0 && n.is_finite() => Ok(n),
+        _ => Err(format!("{} expects a positive number, got '{}'", flag, raw)),
+    }
+}
+
+// "500ms", "5s", "2m", or a bare number of seconds
+fn parse_duration(flag: &str, raw: &str) -> Result<Duration, String> {
+    let invalid = || format!("{} expects a duration like 500ms, 5s or 1m, got '{}'", flag, raw);
+    let (number, unit_ms) = if let Some(n) = raw.strip_suffix("ms") {
+        (n, 1)
+    } else if let Some(n) = raw.strip_suffix('s') {
+        (n, 1000)
+    } else if let Some(n) = raw.strip_suffix('m') {
+        (n, 60_000)
+    } else {
+        (raw, 1000)
+    };
+    let count: u64 = number.trim().parse().map_err(|_| invalid())?;
+    Ok(Duration::from_millis(count.checked_mul(unit_ms).ok_or_else(invalid)?))
+}
+
+// For intervals and limits, where zero makes no sense
+fn parse_positive_duration(flag: &str, raw: &str) -> Result<Duration, String> {
+    match parse_duration(flag, raw)? {
+        duration if duration.is_zero() => Err(format!("{} must be greater than zero", flag)),
+        duration => Ok(duration),
+    }
+}
+
+fn parse_sizes(raw: &str) -> Result<Vec<usize>, String> {
+    let sizes = raw
+        .split(',')
+        .map(|part| parse_number("--sizes", part.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if sizes.is_empty() {
+        return Err("--sizes needs at least one size".to_string());
+    }
+    Ok(sizes)
+}
+
+fn parse_format(raw: &str) -> Result<OutputFormat, String> {
+    match raw {
+        "text" => Ok(OutputFormat::Text),
+        "json" => Ok(OutputFormat::Json),
+        "csv" => Ok(OutputFormat::Csv),
+        "jsonl" => Ok(OutputFormat::JsonLines),
+        other => Err(format!("--format expects text, json, jsonl or csv, got '{}'", other)),
+    }
+}
+
+fn parse_direction(raw: &str) -> Result<Direction, String> {
+    match raw {
+        "both" => Ok(Direction::Both),
+        "upload" => Ok(Direction::UploadOnly),
+        "download" => Ok(Direction::DownloadOnly),
+        other => Err(format!("--direction expects both, upload or download, got '{}'", other)),
+    }
+}
+
+fn parse_units(raw: &str) -> Result<RateUnits, String> {
+    match raw {
+        "bytes" => Ok(RateUnits::Bytes),
+        "bits" => Ok(RateUnits::Bits),
+        other => Err(format!("--units expects bits or bytes, got '{}'", other)),
+    }
+}
@@ -0,0 +1,60 @@
+// Where measure_transfer gets its instants. Iteration round trips and connect times are
+// read through TransferOptions::clock, so a ScriptedClock can stand in for the system
+// clock and make the rates exact in tests. Pacing, timeouts and DNS lookup times
+// always use real time.
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+
+    // Time from `start` to now, zero if the clock reads earlier than `start`
+    fn elapsed_since(&self, start: Instant) -> Duration {
+        self.now().saturating_duration_since(start)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// Answers each call to now() with the next scripted offset from when it was created;
+// once the script runs out, time stands still at the last one. Readings are taken in
+// program order, so only sequential runs see them in a predictable order.
+#[derive(Debug)]
+pub struct ScriptedClock {
+    origin: Instant,
+    script: Mutex<(VecDeque<Duration>, Duration)>, // offsets left, last offset handed out
+}
+
+impl ScriptedClock {
+    pub fn new(offsets: impl IntoIterator<Item = Duration>) -> Self {
+        ScriptedClock {
+            origin: Instant::now(),
+            script: Mutex::new((offsets.into_iter().collect(), Duration::ZERO)),
+        }
+    }
+
+    // Offsets not handed out yet
+    pub fn remaining(&self) -> usize {
+        self.script.lock().unwrap_or_else(|e| e.into_inner()).0.len()
+    }
+}
+
+impl Clock for ScriptedClock {
+    fn now(&self) -> Instant {
+        let mut script = self.script.lock().unwrap_or_else(|e| e.into_inner());
+        let (offsets, last) = &mut *script;
+        if let Some(next) = offsets.pop_front() {
+            *last = next;
+        }
+        self.origin + *last
+    }
+}
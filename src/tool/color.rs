@@ -0,0 +1,21 @@
+// ANSI colors for the text output, written by hand rather than through owo-colors or
+// termcolor, which aren't dependencies of this crate
+pub const GREEN: &str = "\x1b[32m";
+pub const YELLOW: &str = "\x1b[33m";
+pub const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+// Colors only go to a terminal, and a non-empty NO_COLOR (https://no-color.org) turns
+// them off everywhere
+pub fn wanted(terminal: bool) -> bool {
+    terminal && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+// `text` wrapped in `color` and a reset, or unchanged when `on` is false
+pub fn paint(text: &str, color: &str, on: bool) -> String {
+    if on {
+        format!("{}{}{}", color, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
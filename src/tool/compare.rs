@@ -0,0 +1,163 @@
+use std::error::Error;
+use std::fmt;
+use std::io::IsTerminal;
+
+use super::color::{self, GREEN, RED};
+use super::json::{self, JsonValue};
+use super::monitor::utc_timestamp;
+use super::tool::NetworkStats;
+
+// Relative change of each headline number between a baseline and a new run, in
+// percent. None when the baseline value is zero and no percentage exists.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsDiff {
+    pub upload_rate: Option<f64>,
+    pub download_rate: Option<f64>,
+    pub ratio: Option<f64>,
+    pub elapsed_time: Option<f64>,
+}
+
+impl StatsDiff {
+    pub fn between(baseline: &NetworkStats, candidate: &NetworkStats) -> StatsDiff {
+        StatsDiff {
+            upload_rate: percent_change(baseline.upload_rate, candidate.upload_rate),
+            download_rate: percent_change(baseline.download_rate, candidate.download_rate),
+            ratio: percent_change(baseline.ratio, candidate.ratio),
+            elapsed_time: percent_change(
+                baseline.elapsed_time.as_secs_f64(),
+                candidate.elapsed_time.as_secs_f64(),
+            ),
+        }
+    }
+
+    // Same lines as Display with improvements in green and regressions in red.
+    // Faster rates and shorter times count as improvements; the ratio is left uncolored.
+    pub fn render(&self, color: bool) -> String {
+        let rows = [
+            (self.upload_rate, "upload", Some(true)),
+            (self.download_rate, "download", Some(true)),
+            (self.ratio, "ratio", None),
+            (self.elapsed_time, "elapsed", Some(false)),
+        ];
+        let mut out = String::new();
+        for (change, label, higher_is_better) in rows {
+            let text = match change {
+                Some(change) => format!("{:+.1}% {}", change, label),
+                None => format!("n/a {} (baseline was zero)", label),
+            };
+            let paint = match (change, higher_is_better) {
+                (Some(change), Some(higher)) if change != 0.0 => {
+                    Some(if (change > 0.0) == higher { GREEN } else { RED })
+                }
+                _ => None,
+            };
+            let text = match paint {
+                Some(paint) => color::paint(&text, paint, color),
+                None => text,
+            };
+            out.push_str(&format!("{}\n", text));
+        }
+        out
+    }
+}
+
+impl fmt::Display for StatsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(false))
+    }
+}
+
+fn percent_change(baseline: f64, candidate: f64) -> Option<f64> {
+    if baseline == 0.0 || !baseline.is_finite() || !candidate.is_finite() {
+        None
+    } else {
+        Some((candidate - baseline) / baseline * 100.0)
+    }
+}
+
+// One line of a results file: the payload size, when recorded, and its stats
+pub type SavedRun = (Option<usize>, NetworkStats);
+
+// Every line of a --format json results file
+pub fn load_runs(path: &str) -> Result<Vec<SavedRun>, Box<dyn Error>> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let mut runs = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value = json::parse(line).map_err(|e| format!("{}:{}: {}", path, index + 1, e))?;
+        let stats =
+            NetworkStats::from_json(&value).map_err(|e| format!("{}:{}: {}", path, index + 1, e))?;
+        let size = value.get("size").and_then(JsonValue::as_f64).map(|s| s as usize);
+        runs.push((size, stats));
+    }
+    if runs.is_empty() {
+        return Err(format!("{} contains no results", path).into());
+    }
+    Ok(runs)
+}
+
+// "Baseline: nightly (2024-05-01T12:00:00Z; tcp 10.0.0.5:9000, 5 iterations, ...)" from
+// a file's first result, with the path standing in for a missing label; None for files
+// saved before results carried a label, timestamp or summary
+pub fn describe_file(role: &str, path: &str, runs: &[SavedRun]) -> Option<String> {
+    let (_, first) = runs.first()?;
+    if first.label.is_none() && first.timestamp.is_none() && first.config_summary.is_empty() {
+        return None;
+    }
+    let details: Vec<String> = first
+        .timestamp
+        .map(utc_timestamp)
+        .into_iter()
+        .chain((!first.config_summary.is_empty()).then(|| first.config_summary.clone()))
+        .collect();
+    let name = first.label.as_deref().unwrap_or(path);
+    Some(match details.is_empty() {
+        true => format!("{}: {}", role, name),
+        false => format!("{}: {} ({})", role, name, details.join("; ")),
+    })
+}
+
+// Prints the diff for every payload size present in both files. Lines without a
+// size are paired up by position instead.
+pub fn run_compare(baseline_path: &str, candidate_path: &str) -> Result<(), Box<dyn Error>> {
+    let baseline = load_runs(baseline_path)?;
+    let candidate = load_runs(candidate_path)?;
+    let color = color::wanted(std::io::stdout().is_terminal());
+    let files = [("Baseline", baseline_path, &baseline), ("New", candidate_path, &candidate)];
+    let described: Vec<String> =
+        files.iter().filter_map(|(role, path, runs)| describe_file(role, path, runs)).collect();
+    if !described.is_empty() {
+        println!("{}\n", described.join("\n"));
+    }
+
+    let mut matched = 0;
+    for (position, (size, base)) in baseline.iter().enumerate() {
+        let other = match size {
+            Some(size) => candidate.iter().find(|(s, _)| *s == Some(*size)),
+            None => candidate.get(position).filter(|(s, _)| s.is_none()),
+        };
+        let Some((_, new)) = other else {
+            continue;
+        };
+        match size {
+            Some(size) => println!("Payload {} bytes", size),
+            None => println!("Run {}", position + 1),
+        }
+        for line in base.compare(new).render(color).lines() {
+            println!("  {}", line);
+        }
+        println!();
+        matched += 1;
+    }
+
+    if matched == 0 {
+        return Err(format!(
+            "{} and {} have no payload sizes in common",
+            baseline_path, candidate_path
+        )
+        .into());
+    }
+    Ok(())
+}
@@ -0,0 +1,107 @@
+// Spread of per-iteration samples, for telling whether two runs really differ. The
+// 95% interval uses Student's t distribution since runs rarely have more than a few
+// dozen iterations.
+
+pub fn mean(samples: &[f64]) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+    Some(samples.iter().sum::<f64>() / samples.len() as f64)
+}
+
+// Sample standard deviation (n - 1 denominator); None with fewer than two samples
+pub fn sample_std_dev(samples: &[f64]) -> Option<f64> {
+    if samples.len() < 2 {
+        return None;
+    }
+    let mean = mean(samples)?;
+    let squares: f64 = samples.iter().map(|sample| (sample - mean).powi(2)).sum();
+    Some((squares / (samples.len() - 1) as f64).sqrt())
+}
+
+pub fn standard_error(samples: &[f64]) -> Option<f64> {
+    Some(sample_std_dev(samples)? / (samples.len() as f64).sqrt())
+}
+
+// (low, high) bounds of the 95% confidence interval for the mean
+pub fn confidence_interval_95(samples: &[f64]) -> Option<(f64, f64)> {
+    let mean = mean(samples)?;
+    let margin = standard_error(samples)? * t_critical_95(samples.len() - 1);
+    Some((mean - margin, mean + margin))
+}
+
+// Half the 95% interval's width over the mean, e.g. 0.05 for ±5%; None when there's
+// no interval or the mean is zero
+pub fn relative_margin_95(samples: &[f64]) -> Option<f64> {
+    let (low, high) = confidence_interval_95(samples)?;
+    let mean = mean(samples)?;
+    (mean != 0.0).then(|| (high - low) / 2.0 / mean.abs())
+}
+
+// Jain's fairness index: 1 when every sample is the same, down to 1/n when one sample
+// has it all; None when there are no samples or they're all zero
+pub fn jain_index(samples: &[f64]) -> Option<f64> {
+    let squares: f64 = samples.iter().map(|sample| sample * sample).sum();
+    if squares == 0.0 {
+        return None;
+    }
+    Some(samples.iter().sum::<f64>().powi(2) / (samples.len() as f64 * squares))
+}
+
+// Least-squares straight line through a series, e.g. rates against time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinearTrend {
+    pub slope: f64,
+    pub intercept: f64,
+    // Half the width of the slope's 95% interval; None with only two points
+    pub slope_margin_95: Option<f64>,
+}
+
+impl LinearTrend {
+    pub fn at(&self, x: f64) -> f64 {
+        self.intercept + self.slope * x
+    }
+}
+
+// None with fewer than two points or when every x is the same
+pub fn linear_trend(points: &[(f64, f64)]) -> Option<LinearTrend> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|&(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|&(_, y)| y).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|&(x, _)| (x - mean_x).powi(2)).sum();
+    if sxx == 0.0 {
+        return None;
+    }
+    let sxy: f64 = points.iter().map(|&(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let slope = sxy / sxx;
+    let intercept = mean_y - slope * mean_x;
+    let slope_margin_95 = (points.len() > 2).then(|| {
+        let residuals: f64 =
+            points.iter().map(|&(x, y)| (y - intercept - slope * x).powi(2)).sum();
+        let df = points.len() - 2;
+        (residuals / df as f64 / sxx).sqrt() * t_critical_95(df)
+    });
+    Some(LinearTrend { slope, intercept, slope_margin_95 })
+}
+
+// Two-sided 95% critical value of Student's t for `df` degrees of freedom, from the
+// usual table; between rows the next smaller df is used, which errs wide
+fn t_critical_95(df: usize) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179,
+        2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064,
+        2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+    ];
+    match df {
+        0 => f64::INFINITY,
+        1..=30 => TABLE[df - 1],
+        31..=39 => 2.042,
+        40..=59 => 2.021,
+        60..=119 => 2.000,
+        120..=999 => 1.980,
+        _ => 1.960,
+    }
+}
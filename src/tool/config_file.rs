@@ -0,0 +1,156 @@
+// Settings file read before the command line, so repeated runs don't need long flag
+// lists. Only the small subset of TOML these settings need is understood: top-level
+// `key = value` lines with strings, integers, booleans and arrays of integers.
+use std::path::Path;
+use std::time::Duration;
+
+use super::cli::Config;
+
+// Looked for in the working directory when --config isn't given
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+// Applies the settings in `path` on top of `config`
+pub fn load_config_file(path: &Path, config: &mut Config) -> Result<(), String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    apply_config_text(&text, config).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn apply_config_text(text: &str, config: &mut Config) -> Result<(), String> {
+    let mut lines = text.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let line_no = index + 1;
+        let mut line = strip_comment(line).trim().to_string();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err(format!("line {}: tables are not supported", line_no));
+        }
+
+        // Arrays may span several lines; keep reading until the brackets close
+        while line.matches('[').count() > line.matches(']').count() {
+            match lines.next() {
+                Some((_, more)) => {
+                    line.push(' ');
+                    line.push_str(strip_comment(more).trim());
+                }
+                None => return Err(format!("line {}: unterminated array", line_no)),
+            }
+        }
+
+        let (key, raw) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected key = value", line_no))?;
+        let value = parse_value(raw.trim()).map_err(|e| format!("line {}: {}", line_no, e))?;
+        apply(key.trim(), value, config).map_err(|e| format!("line {}: {}", line_no, e))?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+fn apply(key: &str, value: Value, config: &mut Config) -> Result<(), String> {
+    match (key, value) {
+        ("address", Value::String(address)) => config.address = Some(address),
+        ("sizes", Value::Array(items)) => {
+            let sizes = items
+                .into_iter()
+                .map(|item| match item {
+                    Value::Integer(n) if n >= 0 => Ok(n as usize),
+                    other => Err(format!("sizes must be non-negative integers, got {:?}", other)),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if sizes.is_empty() {
+                return Err("sizes needs at least one size".to_string());
+            }
+            config.sizes = sizes;
+        }
+        ("iterations", Value::Integer(0)) => {
+            return Err("iterations must be at least 1".to_string());
+        }
+        ("iterations", Value::Integer(n)) if n > 0 => config.iterations = n as usize,
+        ("delay_ms", Value::Integer(n)) if n >= 0 => config.delay = Duration::from_millis(n as u64),
+        ("mock_server", Value::Boolean(enabled)) => config.mock_server = enabled,
+        ("label", Value::String(label)) => config.label = Some(label),
+        ("address" | "sizes" | "iterations" | "delay_ms" | "mock_server" | "label", value) => {
+            return Err(format!("unexpected value {:?} for {}", value, key))
+        }
+        (other, _) => return Err(format!("unknown setting '{}'", other)),
+    }
+    Ok(())
+}
+
+fn parse_value(raw: &str) -> Result<Value, String> {
+    if let Some(rest) = raw.strip_prefix('"') {
+        let body = rest
+            .strip_suffix('"')
+            .ok_or_else(|| format!("unterminated string {}", raw))?;
+        return unescape(body).map(Value::String);
+    }
+    if let Some(rest) = raw.strip_prefix('[') {
+        let body = rest
+            .strip_suffix(']')
+            .ok_or_else(|| format!("unterminated array {}", raw))?;
+        return body
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty()) // allows a trailing comma
+            .map(parse_value)
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array);
+    }
+    match raw {
+        "true" => return Ok(Value::Boolean(true)),
+        "false" => return Ok(Value::Boolean(false)),
+        _ => {}
+    }
+    raw.replace('_', "")
+        .parse::<i64>()
+        .map(Value::Integer)
+        .map_err(|_| format!("unsupported value '{}'", raw))
+}
+
+fn unescape(body: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(body.len());
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            other => return Err(format!("unsupported escape \\{}", other.unwrap_or(' '))),
+        }
+    }
+    Ok(out)
+}
+
+// Drops a trailing `# comment`, leaving `#` inside strings alone
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
@@ -0,0 +1,43 @@
+// CPU time of this process, to tell when a measurement is limited by the tool rather
+// than the network: on loopback, building and copying payloads can use a whole core
+// before the link is anywhere near full. Read from /proc/self/stat, since getrusage
+// would need the libc crate; other platforms report no CPU time.
+use std::time::Duration;
+
+// Average cores busy over a transfer from which a run counts as CPU-bound: about one
+// core's worth, as a single client task pegging its thread would show
+pub const CPU_BOUND_UTILIZATION: f64 = 0.9;
+
+// Shorter transfers are too coarse to judge against the 10ms clock ticks
+const MIN_SAMPLE: Duration = Duration::from_millis(100);
+
+// The kernel's USER_HZ, in which /proc reports CPU time; 100 on every mainstream Linux
+const TICKS_PER_SEC: u64 = 100;
+
+// User plus system time used by this process so far, or None off Linux
+pub fn process_cpu_time() -> Option<Duration> {
+    #[cfg(target_os = "linux")]
+    return parse_proc_stat(&std::fs::read_to_string("/proc/self/stat").ok()?);
+    #[cfg(not(target_os = "linux"))]
+    return None;
+}
+
+// utime + stime from the contents of /proc/<pid>/stat. The command name in field 2 can
+// hold spaces and parentheses, so the fields are counted from the last ')'.
+pub fn parse_proc_stat(text: &str) -> Option<Duration> {
+    let (_, rest) = text.rsplit_once(')')?;
+    let mut fields = rest.split_whitespace().skip(11); // state is field 3, utime field 14
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    Some(Duration::from_millis((utime + stime) * 1000 / TICKS_PER_SEC))
+}
+
+// Cores busy on average since `start` (a process_cpu_time reading) over `elapsed`, or
+// None without a reading or for a transfer too short to tell
+pub(crate) fn utilization_since(start: Option<Duration>, elapsed: Duration) -> Option<f64> {
+    if elapsed < MIN_SAMPLE {
+        return None;
+    }
+    let used = process_cpu_time()?.saturating_sub(start?);
+    Some(used.as_secs_f64() / elapsed.as_secs_f64())
+}
@@ -0,0 +1,198 @@
+// Run history in SQLite (--db, `sqlite` feature). Each measured size becomes a row of
+// a `runs` table, its headline figures in columns for querying and its full stats as
+// JSON for NetworkStats::from_json. The database is driven through the sqlite3
+// command-line shell instead of the rusqlite crate, so the feature adds no
+// dependencies, but the shell has to be installed.
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::SystemTime;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use super::json::{self, JsonValue};
+use super::monitor::utc_timestamp;
+use super::tool::NetworkStats;
+
+// The shell run when no other is given
+pub const SQLITE3: &str = "sqlite3";
+
+const CREATE_RUNS: &str = "CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    recorded_at TEXT NOT NULL,
+    label TEXT,
+    config_summary TEXT NOT NULL,
+    size INTEGER,
+    upload_rate REAL,
+    download_rate REAL,
+    mean_latency_secs REAL,
+    p99_latency_secs REAL,
+    successful_iterations INTEGER NOT NULL,
+    failed_iterations INTEGER NOT NULL,
+    bytes_sent INTEGER NOT NULL,
+    bytes_received INTEGER NOT NULL,
+    stats TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS runs_by_label ON runs (label, id);";
+
+// A row read back from the runs table
+#[derive(Debug)]
+pub struct StoredRun {
+    pub id: u64,
+    pub size: Option<usize>,
+    pub stats: NetworkStats,
+}
+
+#[derive(Debug, Clone)]
+pub struct RunDatabase {
+    path: PathBuf,
+    program: PathBuf,
+}
+
+impl RunDatabase {
+    // Opens `path` through the sqlite3 on PATH, creating the file and the runs table if
+    // they don't exist yet
+    pub async fn open(path: impl AsRef<Path>) -> Result<RunDatabase, String> {
+        RunDatabase::open_with(path, SQLITE3).await
+    }
+
+    // open with a sqlite3 shell that isn't on PATH
+    pub async fn open_with(
+        path: impl AsRef<Path>,
+        program: impl AsRef<Path>,
+    ) -> Result<RunDatabase, String> {
+        let db = RunDatabase {
+            path: path.as_ref().to_path_buf(),
+            program: program.as_ref().to_path_buf(),
+        };
+        db.execute(CREATE_RUNS, false).await?;
+        Ok(db)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Adds one measured size. recorded_at is the stats' timestamp, or now for stats
+    // without one.
+    pub async fn insert(&self, size: Option<usize>, stats: &NetworkStats) -> Result<(), String> {
+        let recorded_at = utc_timestamp(stats.timestamp.unwrap_or_else(SystemTime::now));
+        let values = [
+            sql_text(&recorded_at),
+            stats.label.as_deref().map_or("NULL".to_string(), sql_text),
+            sql_text(&stats.config_summary),
+            size.map_or("NULL".to_string(), |size| size.to_string()),
+            sql_real(stats.upload_rate),
+            sql_real(stats.download_rate),
+            sql_real(stats.mean_latency.as_secs_f64()),
+            sql_real(stats.p99_latency().as_secs_f64()),
+            stats.successful_iterations.to_string(),
+            stats.failed_iterations.to_string(),
+            stats.bytes_sent.to_string(),
+            stats.bytes_received.to_string(),
+            sql_text(&stats.to_json()),
+        ];
+        let sql = format!(
+            "INSERT INTO runs (recorded_at, label, config_summary, size, upload_rate, \
+             download_rate, mean_latency_secs, p99_latency_secs, successful_iterations, \
+             failed_iterations, bytes_sent, bytes_received, stats) VALUES ({});",
+            values.join(", ")
+        );
+        self.execute(&sql, false).await.map(|_| ())
+    }
+
+    // The last `count` rows recorded under `label`, or without a label for None, oldest
+    // first so they read as a trend
+    pub async fn last_runs(
+        &self,
+        label: Option<&str>,
+        count: usize,
+    ) -> Result<Vec<StoredRun>, String> {
+        let filter = match label {
+            Some(label) => format!("label = {}", sql_text(label)),
+            None => "label IS NULL".to_string(),
+        };
+        let sql = format!(
+            "SELECT id, size, stats FROM runs WHERE {} ORDER BY id DESC LIMIT {};",
+            filter, count
+        );
+        let output = self.execute(&sql, true).await?;
+        // The shell prints nothing at all for no rows
+        if output.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let rows = json::parse(&output).map_err(|e| format!("unreadable sqlite3 output: {}", e))?;
+        let mut runs = rows
+            .as_array()
+            .ok_or("unreadable sqlite3 output: expected an array of rows")?
+            .iter()
+            .map(stored_run)
+            .collect::<Result<Vec<_>, _>>()?;
+        runs.reverse();
+        Ok(runs)
+    }
+
+    // Feeds `sql` to the shell on stdin, returning what it printed; `json` switches the
+    // output of queries to a JSON array of rows
+    async fn execute(&self, sql: &str, json: bool) -> Result<String, String> {
+        let mut command = Command::new(&self.program);
+        command.arg("-bail").arg("-batch");
+        if json {
+            command.arg("-json");
+        }
+        let mut child = command
+            .arg(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => format!(
+                    "--db needs the sqlite3 command-line shell, and {} wasn't found",
+                    self.program.display()
+                ),
+                _ => format!("cannot run {}: {}", self.program.display(), e),
+            })?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let written = stdin.write_all(sql.as_bytes()).await;
+        drop(stdin);
+        let output = child
+            .wait_with_output()
+            .await
+            .map_err(|e| format!("cannot run {}: {}", self.program.display(), e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("{}: {}", self.path.display(), stderr.trim()));
+        }
+        written.map_err(|e| format!("cannot write to {}: {}", self.program.display(), e))?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+fn stored_run(row: &JsonValue) -> Result<StoredRun, String> {
+    let id = row.get("id").and_then(JsonValue::as_f64).ok_or("a row without an id")?;
+    let id = id as u64;
+    let stats = row
+        .get("stats")
+        .and_then(JsonValue::as_str)
+        .ok_or_else(|| format!("run {} has no stats", id))?;
+    let stats = json::parse(stats)
+        .and_then(|stats| NetworkStats::from_json(&stats))
+        .map_err(|e| format!("run {}: {}", id, e))?;
+    let size = row.get("size").and_then(JsonValue::as_f64).map(|size| size as usize);
+    Ok(StoredRun { id, size, stats })
+}
+
+// A string literal, with single quotes doubled
+fn sql_text(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// SQL has no literal for infinities or NaN
+fn sql_real(value: f64) -> String {
+    if value.is_finite() {
+        format!("{:?}", value)
+    } else {
+        "NULL".to_string()
+    }
+}
@@ -0,0 +1,239 @@
+// --dedup-check: the same payload sent over and over, alternated with a fresh one each
+// time, to catch middleboxes that cache or deduplicate repeated bytes. Both are random
+// so compression helps neither; only having seen the bytes before can.
+use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use tokio::sync::watch;
+use tokio::time::sleep;
+
+use super::cli::{Config, OutputFormat, RateUnits};
+use super::color;
+use super::confidence;
+use super::error::TransferError;
+use super::json::JsonObject;
+use super::output::open_output;
+use super::payload::PayloadSource;
+use super::tool::{format_bytes, format_rate_as, measure_payload, transfer_options, TransferOptions};
+
+#[derive(Debug, Clone)]
+pub struct DedupOptions {
+    pub iterations: usize, // of each payload class
+    // The identical payload's seed; the unique ones count up from the next seed
+    pub seed: u64,
+    // How much faster, in percent, repeated bytes have to go to suspect deduplication
+    pub threshold_percent: f64,
+    pub transfer: TransferOptions,
+}
+
+impl Default for DedupOptions {
+    fn default() -> Self {
+        DedupOptions {
+            iterations: 10,
+            seed: super::payload::DEFAULT_SEED,
+            threshold_percent: 25.0,
+            transfer: TransferOptions::default(),
+        }
+    }
+}
+
+// One payload class: each successful iteration's upload rate, in bytes per second
+#[derive(Debug, Clone, Default)]
+pub struct DedupClass {
+    pub rates: Vec<f64>,
+    pub failed_iterations: usize,
+}
+
+impl DedupClass {
+    // Mean of the per-iteration rates; None when every iteration failed
+    pub fn rate(&self) -> Option<f64> {
+        confidence::mean(&self.rates)
+    }
+
+    pub fn interval_95(&self) -> Option<(f64, f64)> {
+        confidence::confidence_interval_95(&self.rates)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DedupReport {
+    pub size: usize,
+    pub identical: DedupClass, // the same bytes every time
+    pub unique: DedupClass,    // bytes never sent before
+    pub interrupted: bool,     // stopped early through TransferOptions::cancel (Ctrl-C)
+}
+
+impl DedupReport {
+    // How much faster the identical payload went, in percent of the unique one's rate;
+    // negative when it was slower
+    pub fn gain_percent(&self) -> Option<f64> {
+        let (identical, unique) = (self.identical.rate()?, self.unique.rate()?);
+        (unique > 0.0).then(|| (identical / unique - 1.0) * 100.0)
+    }
+
+    // A gain of more than `threshold_percent` that isn't noise: the identical payload's
+    // 95% interval lies wholly above the unique one's. Under two iterations per class
+    // there are no intervals, so the gain alone decides.
+    pub fn suspected(&self, threshold_percent: f64) -> bool {
+        let gained = self.gain_percent().is_some_and(|gain| gain > threshold_percent);
+        let separate = match (self.identical.interval_95(), self.unique.interval_95()) {
+            (Some((identical_low, _)), Some((_, unique_high))) => identical_low > unique_high,
+            _ => true,
+        };
+        gained && separate
+    }
+}
+
+// Sends `size`-byte payloads, `options.iterations` of each class, alternating between
+// the identical and a unique payload in ABBA order so neither gets all the quieter
+// moments. The identical payload is sent once beforehand, uncounted, so a cache on the
+// path has seen it. An iteration that fails is counted against its class; other
+// errors, such as a bad address, end the check, and so does a class without a single
+// success.
+pub async fn measure_dedup(
+    address: &str,
+    size: usize,
+    options: &DedupOptions,
+) -> Result<DedupReport, TransferError> {
+    let mut report = DedupReport { size, ..DedupReport::default() };
+    let transfer = TransferOptions { warmup: 0, ..options.transfer.clone() };
+    let identical = PayloadSource::Random { size, seed: options.seed };
+    let cancelled = || transfer.cancel.as_ref().is_some_and(|cancel| *cancel.borrow());
+    let mut last_error = None;
+
+    let _ = measure_payload(address, &identical, 1, &transfer).await;
+    let mut unique_seed = options.seed;
+    'rounds: for round in 0..options.iterations {
+        let order = if round % 2 == 0 { [true, false] } else { [false, true] };
+        for repeated in order {
+            if cancelled() {
+                report.interrupted = true;
+                break 'rounds;
+            }
+            sleep(transfer.delay).await;
+            let (payload, class) = if repeated {
+                (identical.clone(), &mut report.identical)
+            } else {
+                unique_seed = unique_seed.wrapping_add(1);
+                (PayloadSource::Random { size, seed: unique_seed }, &mut report.unique)
+            };
+            match measure_payload(address, &payload, 1, &transfer).await {
+                Ok(stats) if stats.successful_iterations > 0 => class.rates.push(stats.upload_rate),
+                Ok(stats) => report.interrupted |= stats.interrupted,
+                Err(e @ TransferError::AllFailed { .. }) => {
+                    class.failed_iterations += 1;
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    let measured = !report.identical.rates.is_empty() && !report.unique.rates.is_empty();
+    if !report.interrupted && !measured {
+        if let Some(e) = last_error {
+            return Err(e);
+        }
+    }
+    Ok(report)
+}
+
+// Runs the --dedup-check with the largest of the configured sizes
+pub async fn run_dedup(
+    config: &Config,
+    cancel: Option<watch::Receiver<bool>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut options = DedupOptions {
+        iterations: config.iterations,
+        seed: config.seed,
+        transfer: TransferOptions { cancel, ..transfer_options(config) },
+        ..DedupOptions::default()
+    };
+    if let Some(threshold) = config.dedup_threshold {
+        options.threshold_percent = threshold;
+    }
+    let size = config.sizes.iter().copied().max().unwrap_or_default();
+    let text = config.format == OutputFormat::Text;
+    if text {
+        println!(
+            "Dedup check: {} payload, {} iterations each of identical and unique bytes",
+            format_bytes(size),
+            options.iterations
+        );
+    }
+    let report = measure_dedup(config.target_address(), size, &options).await?;
+    if text {
+        let color = config.color_for(std::io::stdout().is_terminal());
+        print_dedup(&report, options.threshold_percent, config.units, color);
+        return Ok(());
+    }
+    let mut out: Box<dyn Write> = match &config.output {
+        Some(path) => Box::new(open_output(Path::new(path), config.append, None)?),
+        None => Box::new(std::io::stdout()),
+    };
+    writeln!(out, "{}", dedup_json(&report, options.threshold_percent))?;
+    Ok(())
+}
+
+pub fn print_dedup(report: &DedupReport, threshold_percent: f64, units: RateUnits, color: bool) {
+    if report.interrupted {
+        println!("  Interrupted");
+    }
+    for (name, class) in [("Identical", &report.identical), ("Unique", &report.unique)] {
+        let rate = class.rate().map_or("n/a".to_string(), |rate| format_rate_as(rate, units));
+        let interval = class.interval_95().map_or(String::new(), |(low, high)| {
+            format!(" (95% CI {} to {})", format_rate_as(low, units), format_rate_as(high, units))
+        });
+        let failed = match class.failed_iterations {
+            0 => String::new(),
+            n => format!(", {} failed", n),
+        };
+        let iterations = class.rates.len();
+        println!(
+            "  {}: {} upload over {} iterations{}{}",
+            name, rate, iterations, interval, failed
+        );
+    }
+    let Some(gain) = report.gain_percent() else {
+        return;
+    };
+    let comparison = format!(
+        "{:.1}% {} for repeated bytes",
+        gain.abs(),
+        if gain >= 0.0 { "faster" } else { "slower" }
+    );
+    if report.suspected(threshold_percent) {
+        let warning = format!(
+            "Dedup suspected: {}, more than the {}% allowed; something on the path caches or \
+             deduplicates payloads, so rates for repeated data overstate the link",
+            comparison, threshold_percent
+        );
+        println!("  {}", color::paint(&warning, color::RED, color));
+    } else {
+        let clean = format!("No dedup detected: {}", comparison);
+        println!("  {}", color::paint(&clean, color::GREEN, color));
+    }
+}
+
+// One object: each class's mean rate, interval and counts, then the gain and verdict
+pub fn dedup_json(report: &DedupReport, threshold_percent: f64) -> String {
+    let optional = |value: Option<f64>| value.map_or("null".to_string(), |v| v.to_string());
+    let class = |class: &DedupClass| {
+        let interval = class.interval_95();
+        let mut obj = JsonObject::new();
+        obj.raw("upload_rate", &optional(class.rate()))
+            .raw("ci95_low", &optional(interval.map(|(low, _)| low)))
+            .raw("ci95_high", &optional(interval.map(|(_, high)| high)))
+            .uint("iterations", class.rates.len() as u64)
+            .uint("failed_iterations", class.failed_iterations as u64);
+        obj.finish()
+    };
+    let mut obj = JsonObject::new();
+    obj.uint("size", report.size as u64)
+        .raw("identical", &class(&report.identical))
+        .raw("unique", &class(&report.unique))
+        .raw("gain_percent", &optional(report.gain_percent()))
+        .float("threshold_percent", threshold_percent)
+        .raw("dedup_suspected", &report.suspected(threshold_percent).to_string())
+        .raw("interrupted", &report.interrupted.to_string());
+    obj.finish()
+}
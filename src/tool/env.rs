@@ -0,0 +1,51 @@
+// TRAFFIC_* environment variables, for deployments where setting the environment is
+// easier than passing flags. They apply on top of the settings file and under the
+// command line: defaults, then config.toml, then the environment, then flags.
+use std::ffi::OsString;
+
+use super::cli::Config;
+
+pub const ADDRESS_VAR: &str = "TRAFFIC_ADDRESS"; // as --address
+pub const SIZES_VAR: &str = "TRAFFIC_SIZES"; // as --sizes, e.g. "1024,65536"
+pub const ITERATIONS_VAR: &str = "TRAFFIC_ITERATIONS"; // as --iterations, a number
+
+// Applies the variables `lookup` finds (std::env::var_os, in the binary) on top of
+// `config`. An unset or empty variable leaves the setting alone; one that doesn't
+// parse is an error naming it, rather than being skipped.
+pub fn apply_env(
+    lookup: impl Fn(&str) -> Option<OsString>,
+    config: &mut Config,
+) -> Result<(), String> {
+    let get = |name: &str| -> Result<Option<String>, String> {
+        match lookup(name) {
+            None => Ok(None),
+            Some(raw) => match raw.into_string() {
+                Ok(value) if value.trim().is_empty() => Ok(None),
+                Ok(value) => Ok(Some(value.trim().to_string())),
+                Err(_) => Err(format!("{} is not valid UTF-8", name)),
+            },
+        }
+    };
+    if let Some(address) = get(ADDRESS_VAR)? {
+        config.address = Some(address);
+    }
+    if let Some(raw) = get(SIZES_VAR)? {
+        let invalid = || {
+            format!("{} expects sizes in bytes separated by commas, got '{}'", SIZES_VAR, raw)
+        };
+        config.sizes = raw
+            .split(',')
+            .map(|part| part.trim().parse().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()?;
+    }
+    if let Some(raw) = get(ITERATIONS_VAR)? {
+        config.iterations = match raw.parse() {
+            Ok(0) | Err(_) => {
+                return Err(format!("{} expects a positive integer, got '{}'", ITERATIONS_VAR, raw))
+            }
+            Ok(iterations) => iterations,
+        };
+        config.auto_iterations = None;
+    }
+    Ok(())
+}
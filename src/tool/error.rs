@@ -0,0 +1,214 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use super::socks;
+
+// Why a transfer, or a single iteration of one, failed
+#[derive(Debug)]
+pub enum TransferError {
+    Resolve(io::Error), // looking up the host failed
+    Connect(io::Error),
+    Write(io::Error),
+    Read(io::Error),
+    // The peer reset the connection while the request was sent or the response read:
+    // ECONNRESET, or EPIPE on a write. `phase` is "write" or "read".
+    ConnectionReset { phase: &'static str, source: io::Error },
+    // The process (EMFILE) or the whole system (ENFILE) had no file descriptor left for
+    // a socket, usually from too many connections at once. `phase` is where it struck.
+    OutOfFileDescriptors { phase: &'static str, source: io::Error },
+    // The named phase ("resolve", "connect", "write", "read", or "proxy connect" and
+    // "proxy handshake" through a SOCKS5 proxy) didn't finish within the limit
+    Timeout { phase: &'static str, after: Duration },
+    InvalidAddress { address: String, reason: String },
+    InvalidOptions(String), // rejected before any traffic was sent
+    Corrupted { offset: usize }, // the response broke the --verify pattern at this byte
+    // Sending stopped after `written` of `total` payload bytes; `cause` is the Write
+    // or Timeout error that stopped it
+    PartialWrite {
+        written: usize,
+        total: usize,
+        cause: Box<TransferError>,
+    },
+    // Every measured iteration failed; `last` is the final iteration's message and
+    // `cause` its typed error when one was recorded
+    AllFailed {
+        iterations: usize,
+        retries: usize,
+        last: String,
+        cause: Option<Box<TransferError>>,
+    },
+    // Reaching the SOCKS5 proxy failed, or it hung up or answered with something other
+    // than SOCKS5 during the handshake (see socks.rs)
+    ProxyConnect(io::Error),
+    ProxyAuth(String), // the proxy turned down the credentials, or their absence
+    // The proxy was reached but couldn't connect to the target; `code` is its reply
+    ProxyRefused { code: u8 },
+    Io(io::Error), // I/O outside the connect/write/read phases
+}
+
+impl TransferError {
+    // Attributes an I/O error to the phase it happened in, telling resets apart from
+    // other write and read failures, and running out of file descriptors from any
+    pub fn during(phase: &str, e: io::Error) -> Self {
+        if is_out_of_files(&e) {
+            let phase = match phase {
+                "resolve" => "resolve",
+                "connect" => "connect",
+                "write" => "write",
+                "read" => "read",
+                _ => "io",
+            };
+            return TransferError::OutOfFileDescriptors { phase, source: e };
+        }
+        let reset = matches!(
+            e.kind(),
+            io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+        );
+        match phase {
+            "resolve" => TransferError::Resolve(e),
+            "connect" => TransferError::Connect(e),
+            "write" if reset => TransferError::ConnectionReset { phase: "write", source: e },
+            "read" if reset => TransferError::ConnectionReset { phase: "read", source: e },
+            "write" => TransferError::Write(e),
+            "read" => TransferError::Read(e),
+            _ => TransferError::Io(e),
+        }
+    }
+
+    // Short label for grouping failures in reports; AllFailed and PartialWrite report
+    // their cause's
+    pub fn category(&self) -> &'static str {
+        match self {
+            TransferError::Resolve(_) => "dns",
+            TransferError::Connect(_) => "connect",
+            TransferError::Write(_) => "write",
+            TransferError::Read(_) => "read",
+            TransferError::ConnectionReset { .. } => "reset",
+            TransferError::OutOfFileDescriptors { .. } => "file descriptors",
+            TransferError::Timeout { .. } => "timeout",
+            TransferError::InvalidAddress { .. } => "invalid address",
+            TransferError::InvalidOptions(_) => "invalid options",
+            TransferError::Corrupted { .. } => "corrupted",
+            TransferError::PartialWrite { cause, .. } => cause.category(),
+            TransferError::AllFailed { cause: Some(cause), .. } => cause.category(),
+            TransferError::AllFailed { cause: None, .. } => "failed",
+            TransferError::ProxyConnect(_) => "proxy connect",
+            TransferError::ProxyAuth(_) => "proxy auth",
+            TransferError::ProxyRefused { .. } => "proxy refused",
+            TransferError::Io(_) => "io",
+        }
+    }
+
+    // The connection closed before the response was complete: the server hung up
+    // instead of answering, or part-way through its answer
+    pub fn is_premature_eof(&self) -> bool {
+        matches!(self, TransferError::Read(e) if e.kind() == io::ErrorKind::UnexpectedEof)
+    }
+
+    // The peer reset the connection mid-transfer, also when that cut a write short
+    pub fn is_connection_reset(&self) -> bool {
+        match self {
+            TransferError::ConnectionReset { .. } => true,
+            TransferError::PartialWrite { cause, .. } => cause.is_connection_reset(),
+            _ => false,
+        }
+    }
+
+    // No file descriptor was left for a socket, also when that cut a write short
+    pub fn is_out_of_file_descriptors(&self) -> bool {
+        match self {
+            TransferError::OutOfFileDescriptors { .. } => true,
+            TransferError::PartialWrite { cause, .. } => cause.is_out_of_file_descriptors(),
+            _ => false,
+        }
+    }
+}
+
+// EMFILE or ENFILE, which std has no ErrorKind for; the numbers are the same on Linux,
+// the BSDs and macOS
+#[cfg(unix)]
+fn is_out_of_files(e: &io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(23 | 24))
+}
+
+#[cfg(not(unix))]
+fn is_out_of_files(_: &io::Error) -> bool {
+    false
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransferError::Resolve(e) => write!(f, "resolve failed: {}", e),
+            TransferError::Connect(e) => write!(f, "connect failed: {}", e),
+            TransferError::Write(e) => write!(f, "write failed: {}", e),
+            TransferError::Read(e) => write!(f, "read failed: {}", e),
+            TransferError::ConnectionReset { phase, source } => {
+                write!(f, "connection reset by peer during {}: {}", phase, source)
+            }
+            TransferError::OutOfFileDescriptors { phase, source } => write!(
+                f,
+                "out of file descriptors during {}: {}; lower the concurrency or raise the \
+                 open file limit (ulimit -n)",
+                phase, source
+            ),
+            TransferError::Timeout { phase, after } => {
+                write!(f, "{} timed out after {:?}", phase, after)
+            }
+            TransferError::InvalidAddress { address, reason } => {
+                write!(f, "invalid address '{}': {}", address, reason)
+            }
+            TransferError::InvalidOptions(message) => write!(f, "{}", message),
+            TransferError::Corrupted { offset } => {
+                write!(f, "response corrupted at byte {}", offset)
+            }
+            TransferError::PartialWrite { written, total, cause } => {
+                write!(f, "{} after {} of {} bytes were sent", cause, written, total)
+            }
+            TransferError::AllFailed { iterations, retries, last, .. } => {
+                write!(f, "all {} iterations failed", iterations)?;
+                if *retries > 0 {
+                    write!(f, " after {} connect retries", retries)?;
+                }
+                write!(f, ", last error: {}", last)
+            }
+            TransferError::ProxyConnect(e) => write!(f, "proxy connect failed: {}", e),
+            TransferError::ProxyAuth(message) => write!(f, "proxy auth failed: {}", message),
+            TransferError::ProxyRefused { code } => write!(
+                f,
+                "proxy couldn't reach the target: {} (SOCKS5 reply {})",
+                socks::reply_message(*code),
+                code
+            ),
+            TransferError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for TransferError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TransferError::Resolve(e)
+            | TransferError::Connect(e)
+            | TransferError::Write(e)
+            | TransferError::Read(e)
+            | TransferError::ConnectionReset { source: e, .. }
+            | TransferError::OutOfFileDescriptors { source: e, .. }
+            | TransferError::ProxyConnect(e)
+            | TransferError::Io(e) => Some(e),
+            TransferError::AllFailed { cause: Some(cause), .. }
+            | TransferError::PartialWrite { cause, .. } => Some(cause.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TransferError {
+    fn from(e: io::Error) -> Self {
+        TransferError::Io(e)
+    }
+}
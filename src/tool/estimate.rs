@@ -0,0 +1,133 @@
+use std::error::Error;
+use std::time::Duration;
+
+use super::cli::Config;
+use super::compare::{load_runs, SavedRun};
+use super::tool::{format_bytes, format_rate};
+
+// Projected traffic of one payload size, without sending anything
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrafficEstimate {
+    pub size: usize,
+    pub iterations: usize,
+    pub total_sent: usize,
+    pub total_received: usize,
+}
+
+// Every iteration, warmup included, sends `data_size` bytes and gets back
+// `data_size * response_ratio`, the mock server's response model. Frame headers
+// are not counted, matching the measured totals.
+pub fn estimate_traffic(
+    data_size: usize,
+    iterations: usize,
+    response_ratio: f64,
+) -> TrafficEstimate {
+    let response_size = (data_size as f64 * response_ratio) as usize;
+    TrafficEstimate {
+        size: data_size,
+        iterations,
+        total_sent: data_size * iterations,
+        total_received: response_size * iterations,
+    }
+}
+
+// --dry-run: prints what the configured sweep would transfer, then returns
+pub fn print_dry_run(config: &Config) -> Vec<TrafficEstimate> {
+    println!("Dry run: no connections are opened");
+    if config.mock_server {
+        println!("Responses follow the mock server's {}x ratio", config.response_ratio);
+    } else {
+        println!(
+            "Responses are assumed to be {}x the request, the mock server's ratio",
+            config.response_ratio
+        );
+    }
+    if config.watch {
+        println!("Figures are per --watch cycle");
+    }
+    // An adaptive run stops somewhere up to its cap, so project the worst case
+    let iterations = match config.auto_iterations {
+        Some(auto) => {
+            let cap = auto.max_iterations;
+            println!("With --iterations auto, figures assume all {} iterations run", cap);
+            auto.max_iterations + config.warmup
+        }
+        None => config.iterations,
+    };
+    if config.requests_per_connection > 1 {
+        println!(
+            "Counts are requests: {} per connection, {} connections per size",
+            config.requests_per_connection, iterations
+        );
+    }
+    println!();
+
+    let estimates: Vec<TrafficEstimate> = config
+        .sizes
+        .iter()
+        .map(|&size| {
+            let requests = iterations * config.requests_per_connection;
+            estimate_traffic(size, requests, config.response_ratio)
+        })
+        .collect();
+    for estimate in &estimates {
+        println!(
+            "{} payload x {}: {} sent, {} received",
+            format_bytes(estimate.size),
+            estimate.iterations,
+            format_bytes(estimate.total_sent),
+            format_bytes(estimate.total_received)
+        );
+    }
+    let sent: usize = estimates.iter().map(|e| e.total_sent).sum();
+    let received: usize = estimates.iter().map(|e| e.total_received).sum();
+    println!("Total: {} sent, {} received", format_bytes(sent), format_bytes(received));
+    estimates
+}
+
+// `estimate`: how long moving `bytes` would take at the rates of every run in a
+// --format json results file
+pub fn run_estimate(path: &str, bytes: usize) -> Result<(), Box<dyn Error>> {
+    print!("{}", render_estimate(&load_runs(path)?, bytes));
+    Ok(())
+}
+
+// The `estimate` report: a block per run, named by payload size like `compare` does
+pub fn render_estimate(runs: &[SavedRun], bytes: usize) -> String {
+    let mut out = format!("Moving {} ({} bytes)\n", format_bytes(bytes), bytes);
+    for (position, (size, stats)) in runs.iter().enumerate() {
+        match size {
+            Some(size) => out.push_str(&format!("At the rates of the {}-byte payloads\n", size)),
+            None => out.push_str(&format!("At the rates of run {}\n", position + 1)),
+        }
+        let directions = [
+            ("download", stats.estimate_transfer_time(bytes), stats.download_rate),
+            ("upload", stats.estimate_upload_time(bytes), stats.upload_rate),
+        ];
+        for (direction, time, rate) in directions {
+            let line = if time == Duration::MAX {
+                format!("  no measured {} rate to go by\n", direction)
+            } else {
+                format!(
+                    "  \u{2248} {} at measured {} rate ({})\n",
+                    approximate(time),
+                    direction,
+                    format_rate(rate)
+                )
+            };
+            out.push_str(&line);
+        }
+    }
+    out
+}
+
+// Rounded to what's worth planning by: "850ms", "42.3s", "3m 05s", "2h 14m"
+fn approximate(time: Duration) -> String {
+    let secs = time.as_secs();
+    match secs {
+        0 => format!("{}ms", time.as_millis()),
+        1..=59 => format!("{:.1}s", time.as_secs_f64()),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
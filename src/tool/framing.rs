@@ -0,0 +1,300 @@
+// Length-prefixed framing: a 4-byte big-endian payload length, then the payload.
+// Every TCP request and response uses it, so each side knows exactly how many bytes
+// to read and a connection can carry more than one exchange. A request's two top
+// header bits are flags saying how it wants to be answered, which leaves 30 bits, just
+// under 1 GiB, for the length of any frame.
+use std::collections::HashSet;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+pub const HEADER_LEN: usize = 4;
+// The server reads the payload and answers nothing
+pub const NO_REPLY: u32 = 1 << 31;
+// The payload starts with a 4-byte big-endian requested_response_bytes field, followed
+// by the request body; the server answers with exactly that many bytes
+pub const SIZED_REPLY: u32 = 1 << 30;
+const FLAGS: u32 = NO_REPLY | SIZED_REPLY;
+pub const MAX_FRAME_LEN: usize = !FLAGS as usize;
+// A sequenced request's body opens with this many bytes of big-endian sequence number
+pub const SEQUENCE_LEN: usize = 8;
+
+// Header and payload go out in a single write: two small writes would let Nagle's
+// algorithm and delayed ACKs stall each request by tens of milliseconds
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, payload: &[u8]) -> io::Result<()> {
+    writer.write_all(&encode_frame(payload)?).await?;
+    writer.flush().await
+}
+
+// Header followed by payload in one buffer, for callers that write it out themselves
+pub fn encode_frame(payload: &[u8]) -> io::Result<Vec<u8>> {
+    encode_flagged_frame(payload, 0)
+}
+
+// A request frame with NO_REPLY and/or SIZED_REPLY set in its header
+pub fn encode_flagged_frame(payload: &[u8], flags: u32) -> io::Result<Vec<u8>> {
+    let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+    let header = u32::from_be_bytes(frame_header(payload.len())?) | (flags & FLAGS);
+    frame.extend_from_slice(&header.to_be_bytes());
+    frame.extend_from_slice(payload);
+    Ok(frame)
+}
+
+// A SIZED_REPLY request carrying `body` and asking for a `response_len`-byte response,
+// which has to fit in a frame too
+pub fn encode_sized_request(body: &[u8], response_len: usize) -> io::Result<Vec<u8>> {
+    let mut payload = Vec::with_capacity(HEADER_LEN + body.len());
+    payload.extend_from_slice(&frame_header(response_len)?);
+    payload.extend_from_slice(body);
+    encode_flagged_frame(&payload, SIZED_REPLY)
+}
+
+// The start of a request frame whose `body_len`-byte body the caller writes after it:
+// the header with `flags`, then the requested_response_bytes field when `response_len`
+// is set, as encode_sized_request would write them
+pub fn encode_request_head(
+    body_len: usize,
+    flags: u32,
+    response_len: Option<usize>,
+) -> io::Result<Vec<u8>> {
+    let (flags, len) = match response_len {
+        Some(_) => (flags | SIZED_REPLY, body_len.saturating_add(HEADER_LEN)),
+        None => (flags, body_len),
+    };
+    let mut head = Vec::with_capacity(2 * HEADER_LEN);
+    let header = u32::from_be_bytes(frame_header(len)?) | (flags & FLAGS);
+    head.extend_from_slice(&header.to_be_bytes());
+    if let Some(response_len) = response_len {
+        head.extend_from_slice(&frame_header(response_len)?);
+    }
+    Ok(head)
+}
+
+// Writes a frame of `len` filler bytes without building the whole payload in memory
+pub async fn write_filler_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    len: usize,
+    fill: u8,
+) -> io::Result<()> {
+    let mut chunk = vec![fill; HEADER_LEN + len.min(8192)];
+    chunk[..HEADER_LEN].copy_from_slice(&frame_header(len)?);
+    writer.write_all(&chunk).await?;
+
+    let mut remaining = len - (chunk.len() - HEADER_LEN);
+    while remaining > 0 {
+        let n = remaining.min(chunk.len() - HEADER_LEN);
+        writer.write_all(&chunk[HEADER_LEN..HEADER_LEN + n]).await?;
+        remaining -= n;
+    }
+    writer.flush().await
+}
+
+pub fn frame_header(len: usize) -> io::Result<[u8; HEADER_LEN]> {
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "payload too large for a frame"));
+    }
+    Ok((len as u32).to_be_bytes())
+}
+
+// Reads the next frame header. Ok(None) means the peer closed cleanly between frames.
+pub async fn read_frame_len<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Option<usize>> {
+    let mut header = [0u8; HEADER_LEN];
+    let mut filled = 0;
+    while filled < HEADER_LEN {
+        let n = reader.read(&mut header[filled..]).await?;
+        if n == 0 {
+            return if filled == 0 {
+                Ok(None)
+            } else {
+                Err(io::ErrorKind::UnexpectedEof.into())
+            };
+        }
+        filled += n;
+    }
+    Ok(Some(u32::from_be_bytes(header) as usize))
+}
+
+// Reads the next request header as (flags, payload length)
+pub async fn read_request_header<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> io::Result<Option<(u32, usize)>> {
+    let header = read_frame_len(reader).await?;
+    Ok(header.map(|word| (word as u32 & FLAGS, (word as u32 & !FLAGS) as usize)))
+}
+
+// Reads the requested_response_bytes field opening the payload of a SIZED_REPLY
+// request of `len` bytes, returning it with the length of the body that follows
+pub async fn read_requested_len<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    len: usize,
+) -> io::Result<(usize, usize)> {
+    if len < HEADER_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("a sized-reply request needs at least {} bytes, got {}", HEADER_LEN, len),
+        ));
+    }
+    let mut requested = [0u8; HEADER_LEN];
+    reader.read_exact(&mut requested).await?;
+    Ok((u32::from_be_bytes(requested) as usize, len - HEADER_LEN))
+}
+
+// Reads and discards one frame's payload of `len` bytes through `buffer`
+pub async fn skip_payload<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    len: usize,
+    buffer: &mut [u8],
+) -> io::Result<()> {
+    read_payload_chunks(reader, len, buffer, |_, _| {}).await
+}
+
+// Reads one frame's payload of `len` bytes through `buffer`, handing each chunk and
+// its offset within the payload to `on_chunk`
+pub async fn read_payload_chunks<R, F>(
+    reader: &mut R,
+    len: usize,
+    buffer: &mut [u8],
+    mut on_chunk: F,
+) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    F: FnMut(&[u8], usize),
+{
+    let mut offset = 0;
+    while offset < len {
+        let want = (len - offset).min(buffer.len());
+        let n = reader.read(&mut buffer[..want]).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        on_chunk(&buffer[..n], offset);
+        offset += n;
+    }
+    Ok(())
+}
+
+// Writes `sequence` over the start of a request frame's body, after the
+// requested_response_bytes field of a SIZED_REPLY request
+pub fn stamp_sequence(frame: &mut [u8], sequence: u64) -> io::Result<()> {
+    let header = frame.get(..HEADER_LEN).map(|h| u32::from_be_bytes(h.try_into().unwrap()));
+    let start = match header {
+        Some(header) if header & SIZED_REPLY != 0 => 2 * HEADER_LEN,
+        _ => HEADER_LEN,
+    };
+    match frame.get_mut(start..start + SEQUENCE_LEN) {
+        Some(slot) => {
+            slot.copy_from_slice(&sequence.to_be_bytes());
+            Ok(())
+        }
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("a sequenced request needs a body of at least {} bytes", SEQUENCE_LEN),
+        )),
+    }
+}
+
+// Follows a byte stream of sequenced request frames, as relayed from a client, and
+// counts those that arrived out of order. Chunks may split frames anywhere. Dropped
+// bytes break the frame boundaries, so the counts mean nothing after a drop.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SequenceCheck {
+    pub frames: u64,
+    pub unsequenced: u64, // bodies too short to carry a number
+    pub reordered: u64,   // numbers lower than one seen before, seen for the first time
+    pub duplicated: u64,  // numbers seen before
+    seen: HashSet<u64>,
+    lowest: Option<u64>,
+    highest: Option<u64>,
+    state: Parse,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Parse {
+    Header { filled: usize, bytes: [u8; HEADER_LEN] },
+    // `skip` bytes before the number (a requested_response_bytes field), then the
+    // number, then `rest` bytes up to the end of the payload
+    Body { skip: usize, filled: usize, bytes: [u8; SEQUENCE_LEN], rest: usize },
+    Skip(usize),
+}
+
+impl Default for Parse {
+    fn default() -> Self {
+        Parse::Header { filled: 0, bytes: [0; HEADER_LEN] }
+    }
+}
+
+impl SequenceCheck {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Numbers between the lowest and highest seen that never arrived
+    pub fn missing(&self) -> u64 {
+        match (self.lowest, self.highest) {
+            (Some(low), Some(high)) => (high - low + 1).saturating_sub(self.seen.len() as u64),
+            _ => 0,
+        }
+    }
+
+    pub fn feed(&mut self, mut chunk: &[u8]) {
+        while !chunk.is_empty() {
+            match &mut self.state {
+                Parse::Header { filled, bytes } => {
+                    let n = (HEADER_LEN - *filled).min(chunk.len());
+                    bytes[*filled..*filled + n].copy_from_slice(&chunk[..n]);
+                    *filled += n;
+                    chunk = &chunk[n..];
+                    if *filled == HEADER_LEN {
+                        let header = u32::from_be_bytes(*bytes);
+                        let len = (header & !FLAGS) as usize;
+                        let skip = if header & SIZED_REPLY != 0 { HEADER_LEN } else { 0 };
+                        self.frames += 1;
+                        self.state = if len < skip + SEQUENCE_LEN {
+                            self.unsequenced += 1;
+                            Parse::Skip(len)
+                        } else {
+                            let rest = len - skip - SEQUENCE_LEN;
+                            Parse::Body { skip, filled: 0, bytes: [0; SEQUENCE_LEN], rest }
+                        };
+                    }
+                }
+                Parse::Body { skip, .. } if *skip > 0 => {
+                    let n = (*skip).min(chunk.len());
+                    *skip -= n;
+                    chunk = &chunk[n..];
+                }
+                Parse::Body { filled, bytes, rest, .. } => {
+                    let n = (SEQUENCE_LEN - *filled).min(chunk.len());
+                    bytes[*filled..*filled + n].copy_from_slice(&chunk[..n]);
+                    *filled += n;
+                    chunk = &chunk[n..];
+                    if *filled == SEQUENCE_LEN {
+                        let (sequence, rest) = (u64::from_be_bytes(*bytes), *rest);
+                        self.record(sequence);
+                        self.state = Parse::Skip(rest);
+                    }
+                }
+                Parse::Skip(remaining) => {
+                    let n = (*remaining).min(chunk.len());
+                    *remaining -= n;
+                    chunk = &chunk[n..];
+                }
+            }
+            // Also ends frames with an empty body right after their header
+            if self.state == Parse::Skip(0) {
+                self.state = Parse::default();
+            }
+        }
+    }
+
+    fn record(&mut self, sequence: u64) {
+        if !self.seen.insert(sequence) {
+            self.duplicated += 1;
+            return;
+        }
+        if self.highest.is_some_and(|highest| sequence < highest) {
+            self.reordered += 1;
+        }
+        self.lowest = Some(self.lowest.map_or(sequence, |low| low.min(sequence)));
+        self.highest = Some(self.highest.map_or(sequence, |high| high.max(sequence)));
+    }
+}
@@ -0,0 +1,185 @@
+// --goodput: the useful share of the measured bytes. Throughput counts every byte,
+// but over HTTP or TLS part of them are headers, handshakes and record framing. A
+// ProtocolOverheadModel says how many, and estimate_goodput takes them off the measured
+// totals, as estimate_mpc models MPC-TLS costs on top of them.
+use super::cli::RateUnits;
+use super::color;
+use super::json::JsonObject;
+use super::tool::{format_bytes, format_rate_as, NetworkStats};
+
+// TLS 1.3 with an AEAD cipher: 16 KiB of plaintext per record, which adds a 5-byte
+// header, the inner content type byte and a 16-byte tag
+const TLS_RECORD_SIZE: usize = 16 * 1024;
+const TLS_RECORD_OVERHEAD: usize = 22;
+
+// Protocol bytes carried alongside the payload. The presets are typical figures, not
+// measurements; a custom model sets the per-request and per-response bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtocolOverheadModel {
+    pub name: String,
+    pub request_bytes: usize,  // per request sent, e.g. an HTTP request line and headers
+    pub response_bytes: usize, // per response received
+    // Per connection opened, e.g. the two sides of a TLS handshake
+    pub connection_sent: usize,
+    pub connection_received: usize,
+    // When the stream is cut into records (TLS), the payload bytes per record and the
+    // bytes each record adds; a record_size of 0 means no records
+    pub record_size: usize,
+    pub record_overhead: usize,
+}
+
+impl ProtocolOverheadModel {
+    // HTTP/1.1 with a short request header block and a typical API response's headers
+    pub fn http() -> Self {
+        ProtocolOverheadModel {
+            name: "http".to_string(),
+            request_bytes: 200,
+            response_bytes: 150,
+            connection_sent: 0,
+            connection_received: 0,
+            record_size: 0,
+            record_overhead: 0,
+        }
+    }
+
+    // TLS 1.3 records, plus a full handshake per connection: the certificate chain
+    // makes the server's side the larger one
+    pub fn tls() -> Self {
+        ProtocolOverheadModel {
+            name: "tls".to_string(),
+            request_bytes: 0,
+            response_bytes: 0,
+            connection_sent: 600,
+            connection_received: 4000,
+            record_size: TLS_RECORD_SIZE,
+            record_overhead: TLS_RECORD_OVERHEAD,
+        }
+    }
+
+    // HTTP/1.1 over TLS 1.3
+    pub fn https() -> Self {
+        let tls = ProtocolOverheadModel::tls();
+        ProtocolOverheadModel {
+            name: "https".to_string(),
+            connection_sent: tls.connection_sent,
+            connection_received: tls.connection_received,
+            record_size: tls.record_size,
+            record_overhead: tls.record_overhead,
+            ..ProtocolOverheadModel::http()
+        }
+    }
+}
+
+// "http", "tls", "https", or REQUEST_BYTES:RESPONSE_BYTES for a custom model
+pub fn parse_overhead_model(raw: &str) -> Result<ProtocolOverheadModel, String> {
+    match raw {
+        "http" => return Ok(ProtocolOverheadModel::http()),
+        "tls" => return Ok(ProtocolOverheadModel::tls()),
+        "https" => return Ok(ProtocolOverheadModel::https()),
+        _ => {}
+    }
+    let invalid = || {
+        format!("expected http, tls, https or REQUEST_BYTES:RESPONSE_BYTES, got '{}'", raw)
+    };
+    let (request, response) = raw.split_once(':').ok_or_else(invalid)?;
+    Ok(ProtocolOverheadModel {
+        name: raw.to_string(),
+        request_bytes: request.trim().parse().map_err(|_| invalid())?,
+        response_bytes: response.trim().parse().map_err(|_| invalid())?,
+        ..ProtocolOverheadModel::http()
+    })
+}
+
+// The measured bytes split into modelled overhead and goodput, with the rates of both
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoodputEstimate {
+    pub overhead_sent: usize,
+    pub overhead_received: usize,
+    pub goodput_sent: usize,
+    pub goodput_received: usize,
+    pub upload_goodput: f64,   // bytes per second, over the same time as upload_rate
+    pub download_goodput: f64, // bytes per second, over the same time as download_rate
+}
+
+impl GoodputEstimate {
+    // Share of the received bytes that were payload; None when nothing was received
+    pub fn download_efficiency(&self) -> Option<f64> {
+        let received = self.goodput_received + self.overhead_received;
+        (received > 0).then(|| self.goodput_received as f64 / received as f64)
+    }
+
+    pub fn write_json_fields(&self, obj: &mut JsonObject) {
+        obj.uint("overhead_sent", self.overhead_sent as u64)
+            .uint("overhead_received", self.overhead_received as u64)
+            .uint("goodput_sent", self.goodput_sent as u64)
+            .uint("goodput_received", self.goodput_received as u64)
+            .float("upload_goodput", self.upload_goodput)
+            .float("download_goodput", self.download_goodput);
+    }
+}
+
+// Treats the measured totals as what the wire carried and takes the model's overhead
+// off them. Every successful request is assumed to have had a response, unless nothing
+// was received at all (upload-only runs). The overhead never exceeds the bytes
+// measured, so goodput bottoms out at zero.
+pub fn estimate_goodput(stats: &NetworkStats, model: &ProtocolOverheadModel) -> GoodputEstimate {
+    let requests = stats.successful_iterations * stats.requests_per_connection.max(1);
+    let responses = if stats.bytes_received == 0 { 0 } else { requests };
+    let overhead = |total: usize, messages: usize, per_message: usize, per_connection: usize| {
+        let records = match (model.record_size, messages) {
+            (0, _) | (_, 0) => 0,
+            (size, n) => n * (total / n).div_ceil(size + model.record_overhead),
+        };
+        let modelled = messages * per_message
+            + stats.connections * per_connection
+            + records * model.record_overhead;
+        modelled.min(total)
+    };
+    let overhead_sent =
+        overhead(stats.bytes_sent, requests, model.request_bytes, model.connection_sent);
+    let overhead_received =
+        overhead(stats.bytes_received, responses, model.response_bytes, model.connection_received);
+    let goodput_sent = stats.bytes_sent - overhead_sent;
+    let goodput_received = stats.bytes_received - overhead_received;
+    let secs = stats.elapsed_time.as_secs_f64();
+    let rate = |bytes: usize| if secs > 0.0 { bytes as f64 / secs } else { 0.0 };
+    GoodputEstimate {
+        overhead_sent,
+        overhead_received,
+        goodput_sent,
+        goodput_received,
+        upload_goodput: rate(goodput_sent),
+        download_goodput: rate(goodput_received),
+    }
+}
+
+// The "goodput" object of a size's JSON line
+pub(crate) fn goodput_json(stats: &NetworkStats, model: &ProtocolOverheadModel) -> String {
+    let mut obj = JsonObject::new();
+    obj.string("model", &model.name);
+    estimate_goodput(stats, model).write_json_fields(&mut obj);
+    obj.finish()
+}
+
+pub fn print_goodput(
+    estimate: &GoodputEstimate,
+    model: &ProtocolOverheadModel,
+    units: RateUnits,
+    color: bool,
+) {
+    println!(
+        "  Goodput ({} model): {} up, {} down",
+        model.name,
+        color::paint(&format_rate_as(estimate.upload_goodput, units), color::GREEN, color),
+        color::paint(&format_rate_as(estimate.download_goodput, units), color::GREEN, color)
+    );
+    if let Some(efficiency) = estimate.download_efficiency() {
+        println!(
+            "  Modelled overhead: {} sent, {} received ({:.1}% of the download is payload)",
+            format_bytes(estimate.overhead_sent),
+            format_bytes(estimate.overhead_received),
+            efficiency * 100.0
+        );
+    }
+    println!();
+}
@@ -0,0 +1,72 @@
+// Connecting to a name with both IPv6 and IPv4 addresses, after RFC 8305 ("happy
+// eyeballs") without its resolution delay: a broken path in one family costs a short
+// head start instead of a whole connect timeout before the other is tried.
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+
+// The default TransferOptions::connect_head_start, RFC 8305's recommended
+// connection attempt delay
+pub const DEFAULT_HEAD_START: Duration = Duration::from_millis(250);
+
+// The order to try `resolved` in: alternating families, starting with the family of
+// the resolver's first choice and keeping its order within each family
+pub fn interleave_families(resolved: &[SocketAddr]) -> Vec<SocketAddr> {
+    let first_is_v6 = resolved.first().is_some_and(|address| address.is_ipv6());
+    let (preferred, other): (Vec<SocketAddr>, Vec<SocketAddr>) =
+        resolved.iter().partition(|address| address.is_ipv6() == first_is_v6);
+    let mut order = Vec::with_capacity(resolved.len());
+    for index in 0..preferred.len().max(other.len()) {
+        order.extend(preferred.get(index));
+        order.extend(other.get(index));
+    }
+    order
+}
+
+// Races `connect` over `resolved` in interleave_families order. Each attempt gets
+// `head_start` to succeed or fail on its own before the next one starts alongside it,
+// and a failure starts the next one at once. The first connection made is returned
+// and the attempts still running are dropped; if all of them fail, the last error is.
+// A single address is simply connected to.
+pub async fn connect_first<T, F, Fut>(
+    resolved: &[SocketAddr],
+    head_start: Duration,
+    connect: F,
+) -> io::Result<T>
+where
+    F: Fn(SocketAddr) -> Fut,
+    Fut: Future<Output = io::Result<T>> + Send + 'static,
+    T: Send + 'static,
+{
+    if let [address] = resolved {
+        return connect(*address).await;
+    }
+    let mut pending = interleave_families(resolved).into_iter();
+    let mut attempts = JoinSet::new();
+    let mut last_error = None;
+    loop {
+        if let Some(address) = pending.next() {
+            attempts.spawn(connect(address));
+        }
+        let finished = if pending.len() == 0 {
+            attempts.join_next().await
+        } else {
+            match timeout(head_start, attempts.join_next()).await {
+                Ok(finished) => finished,
+                Err(_) => continue, // still going; start the next one alongside
+            }
+        };
+        match finished {
+            Some(Ok(Ok(stream))) => return Ok(stream),
+            Some(Ok(Err(e))) => last_error = Some(e),
+            Some(Err(e)) => last_error = Some(io::Error::other(e)),
+            None => {
+                return Err(last_error.unwrap_or_else(|| io::ErrorKind::NotFound.into()));
+            }
+        }
+    }
+}
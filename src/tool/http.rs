@@ -0,0 +1,489 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::watch;
+use tokio::time::timeout;
+
+use super::error::TransferError;
+use super::framing;
+use super::http2::measure_http2;
+use super::tool::{check_iterations, deadline_after, NetworkStats, Tally};
+
+#[derive(Debug, Clone)]
+pub struct HttpOptions {
+    pub follow_redirects: bool,
+    pub max_redirects: usize,
+    pub timeout: Duration, // limit on one whole request/response exchange
+    // Reuse keep-alive connections across iterations instead of sending
+    // `Connection: close` and dialling for every request
+    pub keep_alive: bool,
+    // Pooled connections idle for longer are closed and redialled, as servers tend to
+    // drop them around then anyway
+    pub pool_idle_timeout: Duration,
+    pub fail_fast: bool, // as in TransferOptions; a 4xx/5xx status counts as a failure
+    pub cancel: Option<watch::Receiver<bool>>, // as in TransferOptions
+    pub max_duration: Option<Duration>,        // as in TransferOptions
+    // Send the requests as HTTP/2 streams over one connection, up to this many at once,
+    // instead of over HTTP/1.1 (see http2.rs); keep_alive, follow_redirects and
+    // pool_idle_timeout don't apply then
+    pub http2_streams: Option<usize>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        HttpOptions {
+            follow_redirects: false,
+            max_redirects: 10,
+            timeout: Duration::from_secs(10),
+            keep_alive: true,
+            pool_idle_timeout: Duration::from_secs(90),
+            fail_fast: false,
+            cancel: None,
+            max_duration: None,
+            http2_streams: None,
+        }
+    }
+}
+
+// Sends `iterations` HTTP/1.1 requests to `url`. Request body bytes count as sent,
+// response body bytes as received; headers are not counted. A 4xx/5xx status
+// fails the iteration. Only plain `http://` URLs are supported. With keep_alive the
+// requests share pooled connections, and connecting stays part of each iteration's
+// time either way, so comparing the two shows what pooling is worth. With
+// http2_streams the requests go to measure_http2 instead.
+pub async fn measure_http(
+    url: &str,
+    method: &str,
+    body: &[u8],
+    iterations: usize,
+    options: &HttpOptions,
+) -> Result<NetworkStats, Box<dyn Error>> {
+    check_iterations(iterations)?;
+    if let Some(streams) = options.http2_streams {
+        return measure_http2(url, method, body, iterations, streams, options).await;
+    }
+    let mut target = Url::parse(url)?;
+    let method = method.to_ascii_uppercase();
+    let mut tally = Tally::default();
+    let mut active_time = Duration::ZERO;
+    let mut pool = Pool::new(options);
+
+    let deadline = deadline_after(options.max_duration);
+    for index in 0..iterations {
+        if index > 0
+            && (tally.stop_requested(options.cancel.as_ref()) || tally.deadline_passed(deadline))
+        {
+            break;
+        }
+        let iteration_start = Instant::now();
+        let request = request_following(&mut pool, &mut target, &method, body, options);
+        let exchange = timeout(options.timeout, request);
+        match exchange.await {
+            Ok(Ok((sent, response))) if response.status < 400 => {
+                tally.record_success(sent, response.body_len, iteration_start.elapsed())
+            }
+            Ok(Ok((_, response))) => {
+                let status = format!("HTTP {} {}", response.status, response.reason);
+                if options.fail_fast {
+                    return Err(status.into());
+                }
+                tally.record_failure(status)
+            }
+            Ok(Err(e)) if options.fail_fast => return Err(e.into()),
+            Ok(Err(e)) => tally.record_error(e),
+            Err(_) => {
+                let e = TransferError::Timeout { phase: "http request", after: options.timeout };
+                if options.fail_fast {
+                    return Err(e.into());
+                }
+                tally.record_error(e)
+            }
+        }
+        active_time += iteration_start.elapsed();
+    }
+
+    tally.record_connections(pool.opened);
+    Ok(tally.into_stats(active_time)?)
+}
+
+// Issues one request, chasing redirects when enabled. Returns the request body
+// bytes sent across all hops and the final response. A permanent redirect
+// updates `target` so later iterations go straight to the new location.
+async fn request_following(
+    pool: &mut Pool,
+    target: &mut Url,
+    method: &str,
+    body: &[u8],
+    options: &HttpOptions,
+) -> Result<(usize, Response), TransferError> {
+    let mut url = target.clone();
+    let mut method = method.to_string();
+    let mut body = body;
+    let mut sent = 0;
+
+    for _ in 0..=options.max_redirects {
+        let response = pool.request(&url, &method, body).await?;
+        sent += body.len();
+
+        let location = match (&response.location, response.status) {
+            (Some(location), 301 | 302 | 303 | 307 | 308) if options.follow_redirects => location,
+            _ => return Ok((sent, response)),
+        };
+        url = url.join(location).map_err(invalid_input)?;
+        if response.status == 301 || response.status == 308 {
+            *target = url.clone();
+        }
+        // 303, and in practice 301/302, turn the follow-up into a bodiless GET
+        if matches!(response.status, 301..=303) && method != "HEAD" {
+            method = "GET".to_string();
+            body = &[];
+        }
+    }
+
+    Err(invalid_input(format!(
+        "more than {} redirects",
+        options.max_redirects
+    )))
+}
+
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    reason: String,
+    location: Option<String>,
+    body_len: usize,
+}
+
+type Connection = BufReader<TcpStream>;
+
+// Idle keep-alive connections by host and port. measure_http sends one request at a
+// time, so one idle connection per host is all it can use.
+struct Pool {
+    keep_alive: bool,
+    idle_timeout: Duration,
+    idle: HashMap<(String, u16), (Connection, Instant)>,
+    opened: usize, // connections dialled, successfully or not
+}
+
+impl Pool {
+    fn new(options: &HttpOptions) -> Self {
+        Pool {
+            keep_alive: options.keep_alive,
+            idle_timeout: options.pool_idle_timeout,
+            idle: HashMap::new(),
+            opened: 0,
+        }
+    }
+
+    async fn request(
+        &mut self,
+        url: &Url,
+        method: &str,
+        body: &[u8],
+    ) -> Result<Response, TransferError> {
+        let key = (url.host.clone(), url.port);
+        let pooled =
+            self.idle.remove(&key).filter(|(_, since)| since.elapsed() < self.idle_timeout);
+        if let Some((connection, _)) = pooled {
+            match send_request(connection, url, method, None, body, false).await {
+                Ok((response, connection)) => {
+                    self.keep(key, connection);
+                    return Ok(response);
+                }
+                // The server closed the idle connection before it saw the request; that
+                // says nothing about the request itself, so it goes out on a new one
+                Err(TransferError::Io(e)) if is_stale(&e) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.opened += 1;
+        let connection = BufReader::new(dial(url).await?);
+        let (response, connection) =
+            send_request(connection, url, method, None, body, !self.keep_alive).await?;
+        self.keep(key, connection);
+        Ok(response)
+    }
+
+    fn keep(&mut self, key: (String, u16), connection: Option<Connection>) {
+        if let Some(connection) = connection {
+            self.idle.insert(key, (connection, Instant::now()));
+        }
+    }
+}
+
+fn is_stale(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::UnexpectedEof
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe
+    )
+}
+
+// POSTs `body` to `url` once, without redirects; a status other than 2xx is an error
+#[cfg(feature = "otlp")]
+pub(crate) async fn post(url: &str, content_type: &str, body: &[u8]) -> Result<(), TransferError> {
+    let response = request_once(&Url::parse(url)?, "POST", Some(content_type), body).await?;
+    match response.status {
+        200..=299 => Ok(()),
+        status => Err(invalid_data(format!("HTTP {} {}", status, response.reason))),
+    }
+}
+
+#[cfg(feature = "otlp")]
+async fn request_once(
+    url: &Url,
+    method: &str,
+    content_type: Option<&str>,
+    body: &[u8],
+) -> Result<Response, TransferError> {
+    let connection = BufReader::new(dial(url).await?);
+    let (response, _) = send_request(connection, url, method, content_type, body, true).await?;
+    Ok(response)
+}
+
+pub(crate) async fn dial(url: &Url) -> Result<TcpStream, TransferError> {
+    TcpStream::connect((url.host.as_str(), url.port))
+        .await
+        .map_err(TransferError::Connect)
+}
+
+// Writes one request on `reader`'s stream and reads the response. The connection is
+// handed back when it can carry another request: `close` wasn't asked for, the
+// server didn't close it, and the body had a known length.
+async fn send_request(
+    mut reader: Connection,
+    url: &Url,
+    method: &str,
+    content_type: Option<&str>,
+    body: &[u8],
+    close: bool,
+) -> Result<(Response, Option<Connection>), TransferError> {
+    let content_type =
+        content_type.map_or(String::new(), |t| format!("Content-Type: {}\r\n", t));
+    let connection = if close { "close" } else { "keep-alive" };
+    let head = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: traffic-monitor\r\n{}Content-Length: {}\r\nConnection: {}\r\n\r\n",
+        method,
+        url.path,
+        url.host_header(),
+        content_type,
+        body.len(),
+        connection
+    );
+    let stream = reader.get_mut();
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Err(TransferError::Io(ErrorKind::UnexpectedEof.into()));
+    }
+    let (status, reason) = parse_status_line(&line)?;
+    // HTTP/1.0 closes after each response unless it says otherwise
+    let mut reusable = !close && line.starts_with("HTTP/1.1");
+
+    let mut content_length = None;
+    let mut chunked = false;
+    let mut location = None;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(invalid_data("connection closed inside response headers"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => {
+                    content_length = Some(value.parse().map_err(|_| invalid_data("bad Content-Length"))?)
+                }
+                "transfer-encoding" => chunked = value.to_ascii_lowercase().contains("chunked"),
+                "location" => location = Some(value.to_string()),
+                "connection" => reusable &= !value.eq_ignore_ascii_case("close"),
+                _ => {}
+            }
+        }
+    }
+
+    // HEAD responses and 1xx/204/304 never carry a body
+    let body_len = if method == "HEAD" || status < 200 || status == 204 || status == 304 {
+        0
+    } else if chunked {
+        read_chunked(&mut reader).await?
+    } else if let Some(len) = content_length {
+        let mut limited = (&mut reader).take(len as u64);
+        let n = tokio::io::copy(&mut limited, &mut tokio::io::sink()).await? as usize;
+        if n < len {
+            return Err(invalid_data("connection closed before the full body arrived"));
+        }
+        n
+    } else {
+        // Only the end of the connection marks the end of this body
+        reusable = false;
+        tokio::io::copy(&mut reader, &mut tokio::io::sink()).await? as usize
+    };
+
+    let response = Response {
+        status,
+        reason,
+        location,
+        body_len,
+    };
+    Ok((response, reusable.then_some(reader)))
+}
+
+// Decodes a chunked body, returning the payload length (chunk framing excluded)
+async fn read_chunked<R: AsyncBufReadExt + Unpin>(reader: &mut R) -> Result<usize, TransferError> {
+    let mut total = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).await?;
+        let size_field = line.trim().split(';').next().unwrap_or("");
+        let size = usize::from_str_radix(size_field, 16)
+            .map_err(|_| invalid_data("bad chunk size"))?;
+        if size == 0 {
+            // Skip trailers up to the terminating blank line
+            loop {
+                line.clear();
+                if reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+                    return Ok(total);
+                }
+            }
+        }
+        // Capped at a frame's length, as the socket modes are, so a hostile size line
+        // can't overflow the counts below
+        if size > framing::MAX_FRAME_LEN {
+            return Err(invalid_data(format!(
+                "a {}-byte chunk is over the {}-byte limit",
+                size,
+                framing::MAX_FRAME_LEN
+            )));
+        }
+        let framed = size.checked_add(2).ok_or_else(|| invalid_data("bad chunk size"))?;
+        let mut chunk = reader.take(framed as u64); // chunk data plus CRLF
+        let n = tokio::io::copy(&mut chunk, &mut tokio::io::sink()).await? as usize;
+        if n < framed {
+            return Err(invalid_data("connection closed inside a chunk"));
+        }
+        total = total.checked_add(size).ok_or_else(|| invalid_data("chunked body too long"))?;
+    }
+}
+
+fn parse_status_line(line: &str) -> Result<(u16, String), TransferError> {
+    let mut parts = line.trim_end().splitn(3, ' ');
+    let version = parts.next().unwrap_or("");
+    if !version.starts_with("HTTP/") {
+        return Err(invalid_data(format!("not an HTTP response: {:?}", line.trim_end())));
+    }
+    let status = parts
+        .next()
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| invalid_data("missing status code"))?;
+    Ok((status, parts.next().unwrap_or("").to_string()))
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Url {
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    pub(crate) path: String, // path plus query, always starting with '/'
+}
+
+impl Url {
+    pub(crate) fn parse(url: &str) -> Result<Url, TransferError> {
+        let rest = match url.split_once("://") {
+            Some(("http", rest)) => rest,
+            Some((scheme, _)) => {
+                return Err(invalid_input(format!(
+                    "unsupported scheme '{}', only http:// is supported",
+                    scheme
+                )))
+            }
+            None => return Err(invalid_input(format!("'{}' is not an http:// URL", url))),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = split_host_port(authority)?;
+        if host.is_empty() {
+            return Err(invalid_input(format!("'{}' has no host", url)));
+        }
+        Ok(Url {
+            host,
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    // Resolves a Location header against this URL
+    fn join(&self, location: &str) -> Result<Url, String> {
+        if location.contains("://") {
+            return Url::parse(location).map_err(|e| e.to_string());
+        }
+        let path = if location.starts_with('/') {
+            location.to_string()
+        } else {
+            let dir = &self.path[..self.path.rfind('/').map_or(0, |i| i + 1)];
+            format!("{}{}", dir, location)
+        };
+        Ok(Url {
+            path,
+            ..self.clone()
+        })
+    }
+
+    pub(crate) fn host_header(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        if self.port == 80 {
+            host
+        } else {
+            format!("{}:{}", host, self.port)
+        }
+    }
+}
+
+fn split_host_port(authority: &str) -> Result<(String, u16), TransferError> {
+    // Bracketed IPv6 literal, e.g. [::1]:8080
+    if let Some(rest) = authority.strip_prefix('[') {
+        let (host, after) = rest
+            .split_once(']')
+            .ok_or_else(|| invalid_input("unterminated IPv6 literal"))?;
+        let port = match after.strip_prefix(':') {
+            Some(port) => parse_port(port)?,
+            None => 80,
+        };
+        return Ok((host.to_string(), port));
+    }
+    match authority.rsplit_once(':') {
+        Some((host, port)) => Ok((host.to_string(), parse_port(port)?)),
+        None => Ok((authority.to_string(), 80)),
+    }
+}
+
+fn parse_port(raw: &str) -> Result<u16, TransferError> {
+    raw.parse()
+        .map_err(|_| invalid_input(format!("invalid port '{}'", raw)))
+}
+
+fn invalid_input(message: impl Into<String>) -> TransferError {
+    TransferError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, message.into()))
+}
+
+pub(crate) fn invalid_data(message: impl Into<String>) -> TransferError {
+    TransferError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, message.into()))
+}
@@ -0,0 +1,754 @@
+// HTTP/2 over cleartext TCP with prior knowledge (RFC 9113 section 3.3, "h2c"), written
+// by hand like the HTTP/1.1 client in http.rs because neither `h2` nor `hyper` is a
+// dependency. It does what a measurement needs and no more: many requests as
+// concurrent streams over one connection, flow control both ways, and just enough
+// HPACK to send a request and read back its `:status`. The HPACK dynamic table is
+// turned off (SETTINGS_HEADER_TABLE_SIZE 0), so decoding keeps no state.
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::sync::mpsc;
+use tokio::time::timeout_at;
+
+use super::error::TransferError;
+use super::http::{dial, invalid_data, HttpOptions, Url};
+use super::tool::{check_iterations, deadline_after, NetworkStats, Tally};
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+// Frame types
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const PUSH_PROMISE: u8 = 0x5;
+const PING: u8 = 0x6;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+const CONTINUATION: u8 = 0x9;
+
+// Frame flags
+const END_STREAM: u8 = 0x1;
+const ACK: u8 = 0x1; // on SETTINGS and PING
+const END_HEADERS: u8 = 0x4;
+const PADDED: u8 = 0x8;
+const PRIORITY: u8 = 0x20;
+
+// Settings
+const HEADER_TABLE_SIZE: u16 = 0x1;
+const ENABLE_PUSH: u16 = 0x2;
+const MAX_CONCURRENT_STREAMS: u16 = 0x3;
+const INITIAL_WINDOW_SIZE: u16 = 0x4;
+const MAX_FRAME_SIZE: u16 = 0x5;
+
+const DEFAULT_WINDOW: i64 = 65_535;
+const MAX_WINDOW: i64 = (1 << 31) - 1;
+const DEFAULT_MAX_FRAME: usize = 16_384;
+const CANCEL: u32 = 0x8; // RST_STREAM error code
+
+// Sends `iterations` requests to `url` as HTTP/2 streams over one connection, up to
+// `streams` at once, or fewer when the server's SETTINGS_MAX_CONCURRENT_STREAMS says
+// so. Bytes are counted as in measure_http: request and response bodies, not
+// headers. Each stream's latency runs from its HEADERS frame going out to the end of
+// its response; the rates cover the whole run, connecting included, so comparing
+// them with HTTP/1.1's (`keep_alive` off) shows what multiplexing saves. Redirects
+// are not followed, and a connection that fails or is sent GOAWAY fails the
+// requests still to come.
+pub async fn measure_http2(
+    url: &str,
+    method: &str,
+    body: &[u8],
+    iterations: usize,
+    streams: usize,
+    options: &HttpOptions,
+) -> Result<NetworkStats, Box<dyn Error>> {
+    check_iterations(iterations)?;
+    let target = Url::parse(url)?;
+    let method = method.to_ascii_uppercase();
+    let mut tally = Tally::default();
+    let start = Instant::now();
+
+    tally.record_connections(1);
+    let mut run = Run {
+        streams: BTreeMap::new(),
+        limit: streams.max(1),
+        started: 0,
+        peak: 0,
+        stopped: false,
+        failure: None,
+    };
+    let result = match connect(&target, options.timeout).await {
+        Ok((mut connection, mut frames)) => {
+            let request = Request { url: &target, method: &method, body, iterations };
+            let driven = run.drive(&mut connection, &mut frames, &mut tally, &request, options);
+            let result = driven.await;
+            connection.reader.abort();
+            result
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        if options.fail_fast {
+            return Err(e.into());
+        }
+        // The streams in flight and the requests never sent all go down with it
+        let lost = run.streams.len() + iterations - run.started;
+        if lost > 0 {
+            for _ in 1..lost {
+                tally.record_failure(e.to_string());
+            }
+            tally.record_error(e);
+        }
+    }
+    if let Some(e) = run.failure {
+        return Err(e);
+    }
+
+    tally.record_http2_streams(run.peak);
+    Ok(tally.into_stats(start.elapsed())?)
+}
+
+// What every stream sends
+struct Request<'a> {
+    url: &'a Url,
+    method: &'a str,
+    body: &'a [u8],
+    iterations: usize,
+}
+
+type Frames = mpsc::UnboundedReceiver<Result<Frame, TransferError>>;
+
+struct Frame {
+    kind: u8,
+    flags: u8,
+    stream: u32,
+    payload: Vec<u8>,
+}
+
+// The write side of the connection and what the server's SETTINGS allow. Frames are
+// read by a task of their own, so a large upload never stops the client from seeing
+// the WINDOW_UPDATEs it waits for.
+struct Connection {
+    writer: BufWriter<OwnedWriteHalf>,
+    reader: tokio::task::JoinHandle<()>,
+    next_stream: u32,
+    send_window: i64, // connection-level, as granted by the server
+    initial_window: i64,
+    max_frame: usize,
+    max_streams: usize,
+    unacknowledged: i64, // DATA bytes read but not yet handed back with a WINDOW_UPDATE
+    continuing: Option<(u32, u8, Vec<u8>)>, // a header block still waiting for CONTINUATION
+    goaway: Option<(u32, u32)>,              // last stream id and error code
+}
+
+struct Stream {
+    started: Instant,
+    deadline: Instant,
+    sent: usize,
+    window: i64,
+    unacknowledged: i64,
+    status: Option<u16>,
+    received: usize,
+}
+
+// One measurement's streams, kept apart from the connection so a failure midway still
+// knows what was lost
+struct Run {
+    streams: BTreeMap<u32, Stream>,
+    limit: usize,
+    started: usize,
+    peak: usize,  // most streams open at once
+    stopped: bool, // cancelled, out of time or sent GOAWAY: no new streams
+    failure: Option<Box<dyn Error>>, // the first failed request, with fail_fast
+}
+
+// Dials, sends the preface and our SETTINGS, and waits for the server's, which must be
+// its first frame
+async fn connect(url: &Url, limit: Duration) -> Result<(Connection, Frames), TransferError> {
+    let dialled = tokio::time::timeout(limit, dial(url)).await;
+    let stream = dialled.map_err(|_| TransferError::Timeout { phase: "connect", after: limit })?;
+    let stream = stream?;
+    // Frame headers are only 9 bytes, and Nagle's algorithm would hold them back
+    stream.set_nodelay(true).map_err(TransferError::Connect)?;
+    let (read_half, write_half) = stream.into_split();
+    let (sender, mut frames) = mpsc::unbounded_channel();
+    let reader = tokio::spawn(async move {
+        let mut read_half = BufReader::new(read_half);
+        loop {
+            let frame = read_frame(&mut read_half).await;
+            let failed = frame.is_err();
+            if sender.send(frame).is_err() || failed {
+                return;
+            }
+        }
+    });
+    let mut connection = Connection {
+        writer: BufWriter::with_capacity(4 * DEFAULT_MAX_FRAME, write_half),
+        reader,
+        next_stream: 1,
+        send_window: DEFAULT_WINDOW,
+        initial_window: DEFAULT_WINDOW,
+        max_frame: DEFAULT_MAX_FRAME,
+        max_streams: usize::MAX,
+        unacknowledged: 0,
+        continuing: None,
+        goaway: None,
+    };
+
+    let mut settings = Vec::new();
+    let ours = [(HEADER_TABLE_SIZE, 0), (ENABLE_PUSH, 0), (INITIAL_WINDOW_SIZE, MAX_WINDOW)];
+    for (id, value) in ours {
+        settings.extend_from_slice(&id.to_be_bytes());
+        settings.extend_from_slice(&(value as u32).to_be_bytes());
+    }
+    connection.write(PREFACE).await?;
+    connection.frame(SETTINGS, 0, 0, &settings).await?;
+    // The connection's own window can only be opened up this way
+    connection.window_update(0, MAX_WINDOW - DEFAULT_WINDOW).await?;
+    connection.flush().await?;
+
+    let deadline = tokio::time::Instant::now() + limit;
+    let first = match timeout_at(deadline, frames.recv()).await {
+        Ok(Some(frame)) => frame?,
+        Ok(None) => return Err(TransferError::Read(ErrorKind::UnexpectedEof.into())),
+        Err(_) => return Err(TransferError::Timeout { phase: "http2 settings", after: limit }),
+    };
+    if first.kind != SETTINGS || first.flags & ACK != 0 {
+        return Err(invalid_data("the server didn't open with HTTP/2 SETTINGS; is it h2c?"));
+    }
+    connection.apply_settings(&first.payload, &mut BTreeMap::new()).await?;
+    Ok((connection, frames))
+}
+
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Frame, TransferError> {
+    let mut head = [0u8; 9];
+    reader.read_exact(&mut head).await.map_err(|e| TransferError::during("read", e))?;
+    let len = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload).await.map_err(|e| TransferError::during("read", e))?;
+    Ok(Frame {
+        kind: head[3],
+        flags: head[4],
+        stream: u32::from_be_bytes([head[5], head[6], head[7], head[8]]) & 0x7fff_ffff,
+        payload,
+    })
+}
+
+impl Run {
+    async fn drive(
+        &mut self,
+        connection: &mut Connection,
+        frames: &mut Frames,
+        tally: &mut Tally,
+        request: &Request<'_>,
+        options: &HttpOptions,
+    ) -> Result<(), TransferError> {
+        let Request { url, method, body, iterations } = *request;
+        let deadline = deadline_after(options.max_duration);
+        loop {
+            if self.failure.is_some() {
+                return Ok(());
+            }
+            let room = self.limit.min(connection.max_streams);
+            while !self.stopped && self.started < iterations && self.streams.len() < room {
+                if self.started > 0
+                    && (tally.stop_requested(options.cancel.as_ref())
+                        || tally.deadline_passed(deadline))
+                {
+                    self.stopped = true;
+                    break;
+                }
+                let id = connection.open(method, url, body.len()).await?;
+                let started = Instant::now();
+                let stream = Stream {
+                    started,
+                    deadline: started + options.timeout,
+                    sent: 0,
+                    window: connection.initial_window,
+                    unacknowledged: 0,
+                    status: None,
+                    received: 0,
+                };
+                self.streams.insert(id, stream);
+                self.started += 1;
+                self.peak = self.peak.max(self.streams.len());
+            }
+            connection.send_bodies(&mut self.streams, body).await?;
+            connection.flush().await?;
+            // The streams the server said it would process got to finish; the rest fail
+            if let Some((_, code)) = connection.goaway {
+                if self.streams.is_empty() && self.started < iterations {
+                    let message = format!("the server sent GOAWAY (error code {})", code);
+                    return Err(invalid_data(message));
+                }
+            }
+            let Some(earliest) = self.streams.values().map(|stream| stream.deadline).min() else {
+                return Ok(());
+            };
+
+            match timeout_at(earliest.into(), frames.recv()).await {
+                Ok(Some(frame)) => self.handle(connection, frame?, tally, options).await?,
+                Ok(None) => return Err(TransferError::Read(ErrorKind::UnexpectedEof.into())),
+                Err(_) => {
+                    let now = Instant::now();
+                    let expired: Vec<u32> = self
+                        .streams
+                        .iter()
+                        .filter(|(_, stream)| stream.deadline <= now)
+                        .map(|(&id, _)| id)
+                        .collect();
+                    for id in expired {
+                        self.streams.remove(&id);
+                        connection.frame(RST_STREAM, 0, id, &CANCEL.to_be_bytes()).await?;
+                        let after = options.timeout;
+                        let timed_out = TransferError::Timeout { phase: "http2 stream", after };
+                        self.fail(timed_out, tally, options);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn handle(
+        &mut self,
+        connection: &mut Connection,
+        frame: Frame,
+        tally: &mut Tally,
+        options: &HttpOptions,
+    ) -> Result<(), TransferError> {
+        if let Some((id, _, _)) = &connection.continuing {
+            if frame.kind != CONTINUATION || frame.stream != *id {
+                return Err(invalid_data("HTTP/2 header block interrupted by another frame"));
+            }
+        }
+        match frame.kind {
+            SETTINGS if frame.flags & ACK == 0 => {
+                connection.apply_settings(&frame.payload, &mut self.streams).await?
+            }
+            PING if frame.flags & ACK == 0 => {
+                connection.frame(PING, ACK, 0, &frame.payload).await?
+            }
+            WINDOW_UPDATE => {
+                let increment = i64::from(read_u32(&frame.payload, 0)? & 0x7fff_ffff);
+                match frame.stream {
+                    0 => connection.send_window += increment,
+                    id => {
+                        if let Some(stream) = self.streams.get_mut(&id) {
+                            stream.window += increment;
+                        }
+                    }
+                }
+            }
+            GOAWAY => {
+                let last = read_u32(&frame.payload, 0)? & 0x7fff_ffff;
+                let code = read_u32(&frame.payload, 4)?;
+                connection.goaway = Some((last, code));
+                self.stopped = true;
+                let refused: Vec<u32> =
+                    self.streams.range(last + 1..).map(|(&id, _)| id).collect();
+                for id in refused {
+                    self.streams.remove(&id);
+                    let message = format!("the server sent GOAWAY (error code {})", code);
+                    self.fail(invalid_data(message), tally, options);
+                }
+            }
+            RST_STREAM if self.streams.remove(&frame.stream).is_some() => {
+                let code = read_u32(&frame.payload, 0)?;
+                let message = format!("the server reset the HTTP/2 stream (error code {})", code);
+                self.fail(invalid_data(message), tally, options);
+            }
+            PUSH_PROMISE => {
+                let pushed = "the server pushed a stream although push was turned off";
+                return Err(invalid_data(pushed));
+            }
+            HEADERS => {
+                let fragment = strip_padding(&frame.payload, frame.flags, true)?;
+                if frame.flags & END_HEADERS == 0 {
+                    connection.continuing = Some((frame.stream, frame.flags, fragment.to_vec()));
+                    return Ok(());
+                }
+                self.headers(frame.stream, fragment, frame.flags, tally, options)?;
+            }
+            CONTINUATION => {
+                let Some((id, flags, mut block)) = connection.continuing.take() else {
+                    return Err(invalid_data("HTTP/2 CONTINUATION without a header block"));
+                };
+                block.extend_from_slice(&frame.payload);
+                if frame.flags & END_HEADERS == 0 {
+                    connection.continuing = Some((id, flags, block));
+                    return Ok(());
+                }
+                self.headers(id, &block, flags, tally, options)?;
+            }
+            DATA => {
+                let data = strip_padding(&frame.payload, frame.flags, false)?.len();
+                // Padding counts against the windows too, so all of it is handed back
+                let consumed = frame.payload.len() as i64;
+                connection.unacknowledged += consumed;
+                if connection.unacknowledged >= MAX_WINDOW / 2 {
+                    connection.window_update(0, connection.unacknowledged).await?;
+                    connection.unacknowledged = 0;
+                }
+                if let Some(stream) = self.streams.get_mut(&frame.stream) {
+                    stream.received += data;
+                    stream.unacknowledged += consumed;
+                    if frame.flags & END_STREAM != 0 {
+                        self.finish(frame.stream, tally, options);
+                    } else if stream.unacknowledged >= MAX_WINDOW / 2 {
+                        let increment = stream.unacknowledged;
+                        stream.unacknowledged = 0;
+                        connection.window_update(frame.stream, increment).await?;
+                    }
+                }
+            }
+            // PRIORITY, acknowledgements and frame types this client doesn't know
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn headers(
+        &mut self,
+        id: u32,
+        block: &[u8],
+        flags: u8,
+        tally: &mut Tally,
+        options: &HttpOptions,
+    ) -> Result<(), TransferError> {
+        let status = response_status(block)?;
+        let Some(stream) = self.streams.get_mut(&id) else {
+            // A stream that already timed out
+            return Ok(());
+        };
+        match status {
+            // 1xx responses come before the real one
+            Some(100..=199) => {}
+            Some(status) if stream.status.is_none() => stream.status = Some(status),
+            // Trailers, after the response's own headers
+            _ if stream.status.is_some() => {}
+            _ => return Err(invalid_data("HTTP/2 response without a :status")),
+        }
+        if flags & END_STREAM != 0 {
+            self.finish(id, tally, options);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, id: u32, tally: &mut Tally, options: &HttpOptions) {
+        let Some(stream) = self.streams.remove(&id) else {
+            return;
+        };
+        match stream.status {
+            Some(status) if status < 400 => {
+                tally.record_success(stream.sent, stream.received, stream.started.elapsed())
+            }
+            Some(status) => {
+                let message = format!("HTTP {}", status);
+                if options.fail_fast && self.failure.is_none() {
+                    self.failure = Some(message.clone().into());
+                }
+                tally.record_failure(message)
+            }
+            None => {
+                let error = invalid_data("HTTP/2 stream ended without a response");
+                self.fail(error, tally, options)
+            }
+        }
+    }
+
+    fn fail(&mut self, error: TransferError, tally: &mut Tally, options: &HttpOptions) {
+        if options.fail_fast && self.failure.is_none() {
+            self.failure = Some(error.into());
+            return;
+        }
+        tally.record_error(error);
+    }
+}
+
+impl Connection {
+    async fn write(&mut self, bytes: &[u8]) -> Result<(), TransferError> {
+        self.writer.write_all(bytes).await.map_err(|e| TransferError::during("write", e))
+    }
+
+    async fn flush(&mut self) -> Result<(), TransferError> {
+        self.writer.flush().await.map_err(|e| TransferError::during("write", e))
+    }
+
+    async fn frame(
+        &mut self,
+        kind: u8,
+        flags: u8,
+        stream: u32,
+        payload: &[u8],
+    ) -> Result<(), TransferError> {
+        let len = (payload.len() as u32).to_be_bytes();
+        let mut head = [len[1], len[2], len[3], kind, flags, 0, 0, 0, 0];
+        head[5..].copy_from_slice(&stream.to_be_bytes());
+        self.write(&head).await?;
+        self.write(payload).await
+    }
+
+    async fn window_update(&mut self, stream: u32, increment: i64) -> Result<(), TransferError> {
+        self.frame(WINDOW_UPDATE, 0, stream, &(increment as u32).to_be_bytes()).await
+    }
+
+    // Sends a request's HEADERS on a new stream, ending it there when there is no body
+    async fn open(
+        &mut self,
+        method: &str,
+        url: &Url,
+        body_len: usize,
+    ) -> Result<u32, TransferError> {
+        let id = self.next_stream;
+        self.next_stream += 2;
+        let block = request_headers(method, url, body_len);
+        let flags = if body_len == 0 { END_HEADERS | END_STREAM } else { END_HEADERS };
+        self.frame(HEADERS, flags, id, &block).await?;
+        Ok(id)
+    }
+
+    // Writes as much of every stream's body as the windows allow, a frame per stream
+    // at a time so they share the connection evenly
+    async fn send_bodies(
+        &mut self,
+        streams: &mut BTreeMap<u32, Stream>,
+        body: &[u8],
+    ) -> Result<(), TransferError> {
+        let mut progress = true;
+        while progress && self.send_window > 0 {
+            progress = false;
+            for (&id, stream) in streams.iter_mut() {
+                let left = body.len() - stream.sent;
+                if left == 0 || stream.window <= 0 || self.send_window <= 0 {
+                    continue;
+                }
+                let window = stream.window.min(self.send_window) as usize;
+                let chunk = left.min(window).min(self.max_frame);
+                let flags = if chunk == left { END_STREAM } else { 0 };
+                self.frame(DATA, flags, id, &body[stream.sent..stream.sent + chunk]).await?;
+                stream.sent += chunk;
+                stream.window -= chunk as i64;
+                self.send_window -= chunk as i64;
+                progress = true;
+            }
+        }
+        Ok(())
+    }
+
+    async fn apply_settings(
+        &mut self,
+        payload: &[u8],
+        streams: &mut BTreeMap<u32, Stream>,
+    ) -> Result<(), TransferError> {
+        if !payload.len().is_multiple_of(6) {
+            return Err(invalid_data("malformed HTTP/2 SETTINGS frame"));
+        }
+        for setting in payload.chunks(6) {
+            let value = read_u32(setting, 2)?;
+            match u16::from_be_bytes([setting[0], setting[1]]) {
+                MAX_CONCURRENT_STREAMS => self.max_streams = value as usize,
+                INITIAL_WINDOW_SIZE => {
+                    // Changes every open stream's window by the difference
+                    let delta = i64::from(value) - self.initial_window;
+                    self.initial_window = i64::from(value);
+                    for stream in streams.values_mut() {
+                        stream.window += delta;
+                    }
+                }
+                MAX_FRAME_SIZE => self.max_frame = value as usize,
+                _ => {}
+            }
+        }
+        self.frame(SETTINGS, ACK, 0, &[]).await
+    }
+}
+
+fn read_u32(payload: &[u8], at: usize) -> Result<u32, TransferError> {
+    match payload.get(at..at + 4) {
+        Some(bytes) => Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        None => Err(invalid_data("truncated HTTP/2 frame")),
+    }
+}
+
+// A HEADERS or DATA frame's payload without its padding and, for HEADERS, priority
+fn strip_padding(payload: &[u8], flags: u8, headers: bool) -> Result<&[u8], TransferError> {
+    let truncated = || invalid_data("truncated HTTP/2 frame");
+    let (pad, mut rest) = if flags & PADDED != 0 {
+        let (&pad, rest) = payload.split_first().ok_or_else(truncated)?;
+        (pad as usize, rest)
+    } else {
+        (0, payload)
+    };
+    if headers && flags & PRIORITY != 0 {
+        rest = rest.get(5..).ok_or_else(truncated)?;
+    }
+    rest.len().checked_sub(pad).map(|len| &rest[..len]).ok_or_else(truncated)
+}
+
+// The request's header block: static table entries where there are some, literals
+// without Huffman coding or indexing otherwise (RFC 7541)
+fn request_headers(method: &str, url: &Url, body_len: usize) -> Vec<u8> {
+    let mut block = Vec::new();
+    match method {
+        "GET" => block.push(0x82),
+        "POST" => block.push(0x83),
+        other => literal(&mut block, 2, other),
+    }
+    block.push(0x86); // :scheme http
+    match url.path.as_str() {
+        "/" => block.push(0x84),
+        path => literal(&mut block, 4, path),
+    }
+    literal(&mut block, 1, &url.host_header()); // :authority
+    literal(&mut block, 58, "traffic-monitor"); // user-agent
+    if body_len > 0 {
+        literal(&mut block, 28, &body_len.to_string()); // content-length
+    }
+    block
+}
+
+// A literal header field without indexing, its name from the static table
+fn literal(block: &mut Vec<u8>, name_index: usize, value: &str) {
+    encode_integer(block, name_index, 4, 0x00);
+    encode_integer(block, value.len(), 7, 0x00);
+    block.extend_from_slice(value.as_bytes());
+}
+
+fn encode_integer(block: &mut Vec<u8>, value: usize, prefix_bits: u32, flags: u8) {
+    let max = (1 << prefix_bits) - 1;
+    if value < max {
+        block.push(flags | value as u8);
+        return;
+    }
+    block.push(flags | max as u8);
+    let mut rest = value - max;
+    while rest >= 0x80 {
+        block.push((rest % 0x80) as u8 | 0x80);
+        rest /= 0x80;
+    }
+    block.push(rest as u8);
+}
+
+fn decode_integer(
+    block: &[u8],
+    pos: &mut usize,
+    prefix_bits: u32,
+) -> Result<usize, TransferError> {
+    let malformed = || invalid_data("malformed HPACK integer");
+    let max = (1usize << prefix_bits) - 1;
+    let mut value = *block.get(*pos).ok_or_else(malformed)? as usize & max;
+    *pos += 1;
+    if value < max {
+        return Ok(value);
+    }
+    let mut shift = 0;
+    loop {
+        let byte = *block.get(*pos).ok_or_else(malformed)?;
+        *pos += 1;
+        value += ((byte & 0x7f) as usize).checked_shl(shift).ok_or_else(malformed)?;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+        if shift > 28 {
+            return Err(malformed());
+        }
+    }
+}
+
+// A string literal: whether it is Huffman coded, and its bytes as sent
+fn decode_string<'a>(
+    block: &'a [u8],
+    pos: &mut usize,
+) -> Result<(bool, &'a [u8]), TransferError> {
+    let huffman = block.get(*pos).is_some_and(|byte| byte & 0x80 != 0);
+    let len = decode_integer(block, pos, 7)?;
+    let truncated = || invalid_data("truncated HPACK string");
+    let bytes = block.get(*pos..*pos + len).ok_or_else(truncated)?;
+    *pos += len;
+    Ok((huffman, bytes))
+}
+
+// The `:status` in a response header block, if it has one; every other field is
+// skipped. Static table entries 8 to 14 are the common statuses.
+fn response_status(block: &[u8]) -> Result<Option<u16>, TransferError> {
+    const STATUSES: [u16; 7] = [200, 204, 206, 304, 400, 404, 500];
+    let is_status = |index: usize| (8..=14).contains(&index);
+    let check = |index: usize| match index {
+        0 => Err(invalid_data("HPACK index 0")),
+        1..=61 => Ok(()),
+        _ => Err(invalid_data(format!(
+            "HPACK dynamic table entry {} used although the table was turned off",
+            index
+        ))),
+    };
+
+    let mut status = None;
+    let mut pos = 0;
+    while pos < block.len() {
+        let first = block[pos];
+        if first & 0x80 != 0 {
+            let index = decode_integer(block, &mut pos, 7)?;
+            check(index)?;
+            if is_status(index) {
+                status = Some(STATUSES[index - 8]);
+            }
+            continue;
+        }
+        if first & 0xe0 == 0x20 {
+            if decode_integer(block, &mut pos, 5)? > 0 {
+                return Err(invalid_data("HPACK table size update above the 0 we allow"));
+            }
+            continue;
+        }
+        // A literal, with incremental indexing (which a table of size 0 drops at once)
+        // or without
+        let prefix_bits = if first & 0x40 != 0 { 6 } else { 4 };
+        let name_index = decode_integer(block, &mut pos, prefix_bits)?;
+        let named_status = match name_index {
+            0 => matches!(decode_string(block, &mut pos)?, (false, b":status")),
+            index => check(index).map(|_| is_status(index))?,
+        };
+        let (huffman, value) = decode_string(block, &mut pos)?;
+        if named_status {
+            let digits = if huffman {
+                huffman_digits(value)
+            } else {
+                String::from_utf8(value.to_vec()).ok()
+            };
+            let parsed = digits.and_then(|digits| digits.parse().ok());
+            status = Some(parsed.ok_or_else(|| invalid_data("malformed :status"))?);
+        }
+    }
+    Ok(status)
+}
+
+// Decodes a Huffman-coded string of the digits 0-9, all a `:status` can hold. In the
+// RFC 7541 code, 0-2 are the 5-bit codes 0 to 2 and 3-9 the 6-bit codes 0x19 to 0x1f,
+// and the padding at the end is up to 7 one bits.
+fn huffman_digits(bytes: &[u8]) -> Option<String> {
+    let total = bytes.len() * 8;
+    let bit = |i: usize| (bytes[i / 8] >> (7 - i % 8)) & 1;
+    let bits = |from: usize, n: usize| (from..from + n).fold(0u8, |code, i| code << 1 | bit(i));
+    let mut digits = String::new();
+    let mut pos = 0;
+    while pos < total {
+        let left = total - pos;
+        if left < 8 && (pos..total).all(|i| bit(i) == 1) {
+            break;
+        }
+        if left >= 5 && bits(pos, 5) <= 2 {
+            digits.push((b'0' + bits(pos, 5)) as char);
+            pos += 5;
+        } else if left >= 6 && (0x19..=0x1f).contains(&bits(pos, 6)) {
+            digits.push((b'3' + bits(pos, 6) - 0x19) as char);
+            pos += 6;
+        } else {
+            return None;
+        }
+    }
+    Some(digits)
+}
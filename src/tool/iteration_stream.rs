@@ -0,0 +1,74 @@
+// measure_transfer as a stream of iterations, for callers that compose with other
+// tokio pipelines or react to each result live. The measurement runs in a task of its
+// own through measure_transfer_with_progress, whose callback feeds a channel, so the
+// iterations go through exactly the same path as a blocking measurement.
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use super::error::TransferError;
+use super::tool::{measure_transfer_with_progress, IterationResult, TransferOptions};
+
+// Each measured iteration as it finishes. A failed iteration is an Ok item with its
+// `error` set, as the progress callback sees it; an Err is a failure of the measurement
+// as a whole (invalid options, an unreachable address, a fail_fast stop) and is the
+// last item. Dropping the stream stops the measurement.
+//
+// futures-core isn't a dependency, so this doesn't implement its Stream trait, but
+// poll_next has that trait's signature and `next` that of StreamExt::next.
+#[derive(Debug)]
+pub struct IterationStream {
+    results: mpsc::UnboundedReceiver<Result<IterationResult, TransferError>>,
+    task: JoinHandle<()>,
+}
+
+impl IterationStream {
+    pub fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<IterationResult, TransferError>>> {
+        self.results.poll_recv(cx)
+    }
+
+    // The next iteration, or None once the measurement is over
+    pub async fn next(&mut self) -> Option<Result<IterationResult, TransferError>> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl Drop for IterationStream {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// Starts measure_transfer's iterations and returns them as a stream; the measurement
+// begins right away rather than on the first poll. Warmup iterations are not yielded.
+pub fn measure_transfer_stream(
+    address: &str,
+    data_size: usize,
+    iterations: usize,
+    options: &TransferOptions,
+) -> IterationStream {
+    let (sender, results) = mpsc::unbounded_channel();
+    let (address, options) = (address.to_string(), options.clone());
+    let task = tokio::spawn(async move {
+        let on_progress = |result: &IterationResult| {
+            // Nobody to tell once the stream is gone
+            let _ = sender.send(Ok(result.clone()));
+        };
+        let outcome =
+            measure_transfer_with_progress(&address, data_size, iterations, &options, on_progress)
+                .await;
+        match outcome {
+            // Every failed iteration has been yielded already
+            Ok(_) | Err(TransferError::AllFailed { .. }) => {}
+            Err(e) => {
+                let _ = sender.send(Err(e));
+            }
+        }
+    });
+    IterationStream { results, task }
+}
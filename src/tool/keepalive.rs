@@ -0,0 +1,113 @@
+// --keepalive: how long the target lets a quiet connection live. One connection gets a
+// small ping every interval (measure_keepalive) until a ping fails, --keepalive-limit
+// passes or Ctrl-C, and the report says how long it survived.
+use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use super::cli::{Config, OutputFormat};
+use super::color;
+use super::json::{quote, JsonObject};
+use super::output::open_output;
+use super::tool::{
+    format_bytes, measure_keepalive, transfer_options, KeepaliveEnd, KeepaliveOptions,
+    KeepaliveReport, TransferOptions,
+};
+
+pub async fn run_keepalive(
+    config: &Config,
+    interval: Duration,
+    cancel: Option<watch::Receiver<bool>>,
+) -> Result<(), Box<dyn Error>> {
+    let options = KeepaliveOptions {
+        interval,
+        limit: config.keepalive_limit,
+        transfer: TransferOptions { cancel, ..transfer_options(config) },
+        ..KeepaliveOptions::default()
+    };
+    let text = config.format == OutputFormat::Text;
+    if text {
+        println!(
+            "Keepalive: a {} ping every {:?} on one connection to {}",
+            format_bytes(options.ping_size),
+            interval,
+            config.target_address()
+        );
+    }
+    let report = measure_keepalive(config.target_address(), &options).await?;
+    if text {
+        print_keepalive(&report, config.color_for(std::io::stdout().is_terminal()));
+        return Ok(());
+    }
+
+    let mut out: Box<dyn Write> = match &config.output {
+        Some(path) => Box::new(open_output(Path::new(path), config.append, None)?),
+        None => Box::new(std::io::stdout()),
+    };
+    let (end, error, noticed) = match &report.end {
+        KeepaliveEnd::Dropped { error, noticed_after } => (
+            "dropped",
+            quote(&error.to_string()),
+            noticed_after.as_secs_f64().to_string(),
+        ),
+        KeepaliveEnd::Limit => ("limit", "null".to_string(), "null".to_string()),
+        KeepaliveEnd::Cancelled => ("cancelled", "null".to_string(), "null".to_string()),
+    };
+    let pings = report.pings.as_ref().map_or("null".to_string(), |stats| {
+        let mut obj = JsonObject::new();
+        stats.write_json_fields(&mut obj);
+        obj.finish()
+    });
+    let mut obj = JsonObject::new();
+    obj.float("interval_secs", interval.as_secs_f64())
+        .float("alive_secs", report.alive_for.as_secs_f64())
+        .raw("noticed_after_secs", &noticed)
+        .string("end", end)
+        .raw("error", &error)
+        .raw("pings", &pings);
+    writeln!(out, "{}", obj.finish())?;
+    Ok(())
+}
+
+pub fn print_keepalive(report: &KeepaliveReport, color: bool) {
+    let headline = match &report.end {
+        KeepaliveEnd::Dropped { error, noticed_after } if report.pings.is_none() => {
+            color::paint(
+                &format!(
+                    "Connection dropped before the first ping (noticed at {:.2?}): {}",
+                    noticed_after, error
+                ),
+                color::RED,
+                color,
+            )
+        }
+        KeepaliveEnd::Dropped { error, noticed_after } => color::paint(
+            &format!(
+                "Connection dropped after {:.2?} (noticed at {:.2?}): {}",
+                report.alive_for, noticed_after, error
+            ),
+            color::RED,
+            color,
+        ),
+        KeepaliveEnd::Limit => color::paint(
+            &format!("Connection still up after {:.2?}, the limit", report.alive_for),
+            color::GREEN,
+            color,
+        ),
+        KeepaliveEnd::Cancelled => format!(
+            "Stopped with the connection still up after {:.2?}",
+            report.alive_for
+        ),
+    };
+    println!("\n{}", headline);
+    match &report.pings {
+        Some(stats) => println!(
+            "  Pings answered: {}, latency min {:.2?}, mean {:.2?}, max {:.2?}",
+            stats.successful_iterations, stats.min_latency, stats.mean_latency, stats.max_latency
+        ),
+        None => println!("  No ping was answered"),
+    }
+    println!();
+}
@@ -0,0 +1,201 @@
+// --under-load: bufferbloat-style latency. Bulk transfers saturate the path while small
+// pings go out on a connection of their own, so the ping latencies show how much
+// queueing the load adds. Both are ordinary measure_transfer runs in separate tasks;
+// the bulk task's end is the stop signal for the pings.
+use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use super::cli::{Config, OutputFormat, RateUnits};
+use super::color;
+use super::error::TransferError;
+use super::json::JsonObject;
+use super::output::open_output;
+use super::tool::{
+    format_bytes, format_rate_as, measure_transfer, print_stats, transfer_options,
+    NetworkStats, TransferOptions,
+};
+
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    pub bulk_size: usize,       // payload of each bulk transfer
+    pub bulk_iterations: usize, // bulk transfers, `transfer.concurrency` at a time
+    pub ping_size: usize,
+    pub ping_interval: Duration, // pause between pings, which share one connection
+    pub idle_pings: usize,       // pings before the load starts, as a baseline; 0 skips them
+    // For both workloads. The bulk transfers ignore `delay` so the path stays busy, and
+    // the pings always reuse their connection, so only the first one pays to connect.
+    pub transfer: TransferOptions,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            bulk_size: 10 * 1024 * 1024,
+            bulk_iterations: 20,
+            ping_size: 64,
+            ping_interval: Duration::from_millis(50),
+            idle_pings: 10,
+            transfer: TransferOptions { concurrency: 4, ..TransferOptions::default() },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LoadedLatency {
+    pub bulk: NetworkStats,
+    pub idle: Option<NetworkStats>, // the baseline pings, unless idle_pings was 0
+    pub loaded: NetworkStats,       // the pings sent while the bulk transfers ran
+}
+
+impl LoadedLatency {
+    // How much the load raised the median ping; None without a baseline
+    pub fn added_latency(&self) -> Option<Duration> {
+        let idle = self.idle.as_ref()?;
+        Some(self.loaded.p50_latency().saturating_sub(idle.p50_latency()))
+    }
+}
+
+pub async fn measure_latency_under_load(
+    address: &str,
+    options: &LoadOptions,
+) -> Result<LoadedLatency, TransferError> {
+    let pings = TransferOptions {
+        delay: options.ping_interval,
+        concurrency: 1,
+        warmup: 0,
+        reuse_connection: true,
+        progress: false,
+        keep_records: false,
+        auto_iterations: None,
+        ..options.transfer.clone()
+    };
+    let idle = match options.idle_pings {
+        0 => None,
+        count => Some(measure_transfer(address, options.ping_size, count, &pings).await?),
+    };
+
+    let (done, load_done) = watch::channel(false);
+    let bulk = TransferOptions {
+        delay: Duration::ZERO,
+        progress: false,
+        ..options.transfer.clone()
+    };
+    let bulk_task = {
+        let (address, size, iterations) =
+            (address.to_string(), options.bulk_size, options.bulk_iterations);
+        tokio::spawn(async move {
+            let stats = measure_transfer(&address, size, iterations, &bulk).await;
+            let _ = done.send(true);
+            stats
+        })
+    };
+    // Pings until the bulk transfers are over; the cap is never reached in practice
+    let ping_task = {
+        let pings = TransferOptions { cancel: Some(load_done), ..pings };
+        let (address, size) = (address.to_string(), options.ping_size);
+        tokio::spawn(async move { measure_transfer(&address, size, usize::MAX, &pings).await })
+    };
+
+    let bulk = join(bulk_task.await)?;
+    let mut loaded = join(ping_task.await)?;
+    // Stopping the pings is how the run ends, not an interruption
+    loaded.interrupted = bulk.interrupted;
+    Ok(LoadedLatency { bulk, idle, loaded })
+}
+
+fn join(
+    result: Result<Result<NetworkStats, TransferError>, tokio::task::JoinError>,
+) -> Result<NetworkStats, TransferError> {
+    result.map_err(|e| TransferError::Io(std::io::Error::other(e)))?
+}
+
+// Runs the --under-load measurement against the configured address and reports it
+pub async fn run_under_load(
+    config: &Config,
+    bulk_size: usize,
+    cancel: Option<watch::Receiver<bool>>,
+) -> Result<(), Box<dyn Error>> {
+    let options = LoadOptions {
+        bulk_size,
+        bulk_iterations: config.iterations,
+        ping_interval: config.ping_interval,
+        transfer: TransferOptions { cancel, ..transfer_options(config) },
+        ..LoadOptions::default()
+    };
+    let text = config.format == OutputFormat::Text;
+    if text {
+        println!(
+            "Latency under load: {} bulk transfers of {}, {} at a time, {} pings every {:?}",
+            config.iterations,
+            format_bytes(bulk_size),
+            options.transfer.concurrency,
+            format_bytes(options.ping_size),
+            options.ping_interval
+        );
+    }
+    let result = measure_latency_under_load(config.target_address(), &options).await?;
+    if text {
+        let color = config.color_for(std::io::stdout().is_terminal());
+        print_latency_under_load(&result, config.units, color);
+        return Ok(());
+    }
+
+    let mut out: Box<dyn Write> = match &config.output {
+        Some(path) => Box::new(open_output(Path::new(path), config.append, None)?),
+        None => Box::new(std::io::stdout()),
+    };
+    let section = |stats: &NetworkStats| {
+        let mut obj = JsonObject::new();
+        stats.write_json_fields(&mut obj);
+        obj.finish()
+    };
+    let mut obj = JsonObject::new();
+    obj.uint("bulk_size", bulk_size as u64)
+        .raw("bulk", &section(&result.bulk))
+        .raw("idle_pings", &result.idle.as_ref().map_or("null".to_string(), &section))
+        .raw("loaded_pings", &section(&result.loaded));
+    if let Some(added) = result.added_latency() {
+        obj.float("added_latency_secs", added.as_secs_f64());
+    }
+    writeln!(out, "{}", obj.finish())?;
+    Ok(())
+}
+
+pub fn print_latency_under_load(result: &LoadedLatency, units: RateUnits, color: bool) {
+    println!("\nBulk transfers");
+    print_stats(&result.bulk, units, color);
+    println!("Ping latency");
+    if let Some(idle) = &result.idle {
+        print_pings("Idle", idle);
+    }
+    print_pings("Under load", &result.loaded);
+    if let (Some(added), Some(idle)) = (result.added_latency(), &result.idle) {
+        let idle_p50 = idle.p50_latency().as_secs_f64();
+        let factor = if idle_p50 > 0.0 {
+            format!(" ({:.1}x idle)", result.loaded.p50_latency().as_secs_f64() / idle_p50)
+        } else {
+            String::new()
+        };
+        println!("  Added by the load: {:.2?} at p50{}", added, factor);
+    }
+    println!(
+        "  Saturated throughput: {} up, {} down",
+        color::paint(&format_rate_as(result.bulk.upload_rate, units), color::GREEN, color),
+        color::paint(&format_rate_as(result.bulk.download_rate, units), color::GREEN, color)
+    );
+}
+
+fn print_pings(label: &str, stats: &NetworkStats) {
+    println!(
+        "  {}: p50 {:.2?}, p95 {:.2?}, p99 {:.2?}, max {:.2?} over {} pings",
+        label,
+        stats.p50_latency(),
+        stats.p95_latency(),
+        stats.p99_latency(),
+        stats.max_latency,
+        stats.successful_iterations
+    );
+}
@@ -0,0 +1,173 @@
+// Leveled, structured diagnostics on stderr, filtered by RUST_LOG the way
+// tracing_subscriber's EnvFilter reads it. Results stay on stdout; this is only
+// for watching what the tool is doing.
+use std::fmt::Display;
+use std::io::Write;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use super::monitor::utc_timestamp;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    fn label(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+
+    fn parse(raw: &str) -> Option<Level> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            "trace" => Some(Level::Trace),
+            _ => None,
+        }
+    }
+}
+
+// Parsed RUST_LOG: the level for targets no directive names, and the `target=level`
+// directives. None turns a target off ("off" in RUST_LOG).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    default: Option<Level>,
+    targets: Vec<(String, Option<Level>)>,
+}
+
+impl Filter {
+    // The level for `target` (a module path): that of the longest directive target it
+    // starts with, the later directive winning a tie, or the bare level if none match
+    pub fn level_for(&self, target: &str) -> Option<Level> {
+        let mut best: Option<(usize, Option<Level>)> = None;
+        for (prefix, level) in &self.targets {
+            let longer = best.is_none_or(|(len, _)| prefix.len() >= len);
+            if longer && target.starts_with(prefix.as_str()) {
+                best = Some((prefix.len(), *level));
+            }
+        }
+        best.map_or(self.default, |(_, level)| level)
+    }
+
+    // The most verbose level any target gets
+    fn max_level(&self) -> Option<Level> {
+        self.targets.iter().map(|(_, level)| *level).fold(self.default, Option::max)
+    }
+}
+
+// Warnings and errors only, everywhere
+static FILTER: RwLock<Filter> =
+    RwLock::new(Filter { default: Some(Level::Warn), targets: Vec::new() });
+// FILTER's max_level, so most disabled events are skipped without taking the lock;
+// 0 turns everything off
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Warn as u8);
+
+pub fn set_filter(filter: Filter) {
+    MAX_LEVEL.store(filter.max_level().map_or(0, |l| l as u8), Ordering::Relaxed);
+    *FILTER.write().unwrap_or_else(|e| e.into_inner()) = filter;
+}
+
+pub fn enabled(level: Level, target: &str) -> bool {
+    if level as u8 > MAX_LEVEL.load(Ordering::Relaxed) {
+        return false;
+    }
+    let filter = FILTER.read().unwrap_or_else(|e| e.into_inner());
+    filter.level_for(target).is_some_and(|max| level <= max)
+}
+
+// Applies RUST_LOG when it's set; warnings and errors only otherwise
+pub fn init_from_env() {
+    if let Ok(filter) = std::env::var("RUST_LOG") {
+        set_filter(parse_filter(&filter));
+    }
+}
+
+// Comma-separated directives, each a bare level or `target=level`, as EnvFilter reads
+// them: the most specific target matching a module decides its level. Directives with
+// an unknown level are ignored, and without a bare level unnamed targets are off.
+pub fn parse_filter(filter: &str) -> Filter {
+    let mut parsed = Filter { default: None, targets: Vec::new() };
+    for directive in filter.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        let (target, raw) = match directive.split_once('=') {
+            Some((target, raw)) => (Some(target.trim()), raw),
+            None => (None, directive),
+        };
+        let level = if raw.trim().eq_ignore_ascii_case("off") {
+            None
+        } else {
+            match Level::parse(raw) {
+                Some(level) => Some(level),
+                None => continue,
+            }
+        };
+        match target {
+            Some(target) => parsed.targets.push((target.to_string(), level)),
+            None => parsed.default = level,
+        }
+    }
+    parsed
+}
+
+// One line: timestamp, level, module, message, then key=value fields
+pub fn emit(level: Level, target: &str, message: &str, fields: &[(&str, &dyn Display)]) {
+    let mut line = format!(
+        "{} {:<5} {}: {}",
+        utc_timestamp(SystemTime::now()),
+        level.label(),
+        target,
+        message
+    );
+    for (key, value) in fields {
+        line.push_str(&format!(" {}={}", key, value));
+    }
+    line.push('\n');
+    // A single write keeps lines from concurrent tasks whole
+    let _ = std::io::stderr().lock().write_all(line.as_bytes());
+}
+
+// event!(Level::Debug, "message", key = value, ...); fields need Display, and are
+// only evaluated when the level is enabled
+macro_rules! event {
+    ($level:expr, $message:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        if $crate::tool::log::enabled($level, module_path!()) {
+            $crate::tool::log::emit(
+                $level,
+                module_path!(),
+                $message,
+                &[$((stringify!($key), &$value as &dyn std::fmt::Display)),*],
+            );
+        }
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::tool::log::event!($crate::tool::log::Level::Debug, $($arg)+) };
+}
+
+macro_rules! info {
+    ($($arg:tt)+) => { $crate::tool::log::event!($crate::tool::log::Level::Info, $($arg)+) };
+}
+
+macro_rules! warning {
+    ($($arg:tt)+) => { $crate::tool::log::event!($crate::tool::log::Level::Warn, $($arg)+) };
+}
+
+macro_rules! error {
+    ($($arg:tt)+) => { $crate::tool::log::event!($crate::tool::log::Level::Error, $($arg)+) };
+}
+
+pub(crate) use {debug, error, event, info, warning};
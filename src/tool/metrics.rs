@@ -0,0 +1,160 @@
+// Prometheus text exposition of the latest measurement, served on /metrics by a
+// small HTTP/1.1 listener so the tool can be scraped while it runs
+use std::error::Error;
+use std::fmt::Write as _;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::{JoinHandle, JoinSet};
+
+use super::log::{info, warning};
+use super::mock_server::ServerResult;
+use super::tool::NetworkStats;
+
+// Where run_main listens without --metrics-bind. The endpoint has no authentication,
+// so other hosts only reach it when asked for.
+pub const DEFAULT_METRICS_BIND: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+// Request heads larger than this are rejected; scrapers send a few hundred bytes
+const MAX_REQUEST_HEAD: usize = 8192;
+
+// Shared between the measurement loop, which updates it after each size, and the
+// endpoint, which renders it on every scrape
+#[derive(Debug, Default)]
+pub struct Metrics {
+    state: Mutex<MetricsState>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct MetricsState {
+    // Gauges from the most recent successful measurement
+    payload_size: usize,
+    upload_rate: f64,
+    download_rate: f64,
+    ratio: f64,
+    // Counters over the whole run
+    bytes_sent: u64,
+    bytes_received: u64,
+    failed_iterations: u64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    pub fn record(&self, size: usize, stats: &NetworkStats) {
+        let mut state = self.state.lock().unwrap();
+        state.payload_size = size;
+        state.upload_rate = stats.upload_rate;
+        state.download_rate = stats.download_rate;
+        state.ratio = stats.ratio;
+        state.bytes_sent += stats.bytes_sent as u64;
+        state.bytes_received += stats.bytes_received as u64;
+        state.failed_iterations += stats.failed_iterations as u64;
+    }
+
+    // For measurements that produced no stats at all
+    pub fn record_failures(&self, iterations: usize) {
+        self.state.lock().unwrap().failed_iterations += iterations as u64;
+    }
+
+    pub fn render(&self) -> String {
+        let s = self.state.lock().unwrap().clone();
+        let rows = [
+            ("payload_size_bytes", "gauge", "Latest payload size.", s.payload_size as f64),
+            ("upload_rate_bytes_per_second", "gauge", "Latest upload rate.", s.upload_rate),
+            ("download_rate_bytes_per_second", "gauge", "Latest download rate.", s.download_rate),
+            ("ratio", "gauge", "Latest received/sent ratio.", s.ratio),
+            ("bytes_sent_total", "counter", "Total bytes sent.", s.bytes_sent as f64),
+            ("bytes_received_total", "counter", "Total bytes received.", s.bytes_received as f64),
+            ("failed_iterations_total", "counter", "Failed iterations.", s.failed_iterations as f64),
+        ];
+
+        let mut out = String::new();
+        for (name, kind, help, value) in rows {
+            let _ = writeln!(out, "# HELP traffic_monitor_{} {}", name, help);
+            let _ = writeln!(out, "# TYPE traffic_monitor_{} {}", name, kind);
+            let _ = writeln!(out, "traffic_monitor_{} {}", name, value);
+        }
+        out
+    }
+}
+
+// Serves `metrics` on /metrics at `address` until `shutdown` is set, like
+// run_mock_server: returns the bound address and the accept loop's handle
+pub async fn serve_metrics(
+    address: &str,
+    metrics: Arc<Metrics>,
+    shutdown: watch::Receiver<bool>,
+) -> std::io::Result<(SocketAddr, JoinHandle<ServerResult>)> {
+    let listener = TcpListener::bind(address).await?;
+    let bound = listener.local_addr()?;
+    info!("metrics endpoint listening", url = format!("http://{}/metrics", bound));
+
+    let handle = tokio::spawn(accept_loop(listener, metrics, shutdown));
+    Ok((bound, handle))
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    metrics: Arc<Metrics>,
+    mut shutdown: watch::Receiver<bool>,
+) -> ServerResult {
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (socket, _) = accepted?;
+                connections.spawn(handle_scrape(socket, Arc::clone(&metrics)));
+                while connections.try_join_next().is_some() {}
+            }
+            _ = shutdown.wait_for(|stop| *stop) => return Ok(()),
+        }
+    }
+}
+
+async fn handle_scrape(mut socket: TcpStream, metrics: Arc<Metrics>) {
+    if let Err(e) = respond(&mut socket, &metrics).await {
+        warning!("metrics request failed", error = e);
+    }
+}
+
+async fn respond(socket: &mut TcpStream, metrics: &Metrics) -> Result<(), Box<dyn Error>> {
+    // Only the request line matters, but the whole head is read before replying
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = socket.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(()); // client went away
+        }
+        head.extend_from_slice(&buf[..n]);
+        if head.len() > MAX_REQUEST_HEAD {
+            return Err("request head too large".into());
+        }
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line.next().unwrap_or_default();
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => ("200 OK", "text/plain; version=0.0.4", metrics.render()),
+        ("GET", _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "method not allowed\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    socket.write_all(response.as_bytes()).await?;
+    socket.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,177 @@
+// --size-mix: traffic as a mix of payload sizes rather than one. Each iteration's size
+// is drawn from a weighted distribution with a seeded generator, so a mix and a seed
+// always send the same sequence; measure_mix reports every size and the whole run.
+use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use tokio::sync::watch;
+
+use super::cli::{Config, OutputFormat, RateUnits};
+use super::json::JsonObject;
+use super::output::open_output;
+use super::payload::SplitMix64;
+use super::thresholds::ThresholdsViolated;
+use super::tool::{
+    format_bytes, format_rate_as, measure_mix, print_stats, transfer_options, MixOptions,
+    MixStats, NetworkStats, TransferOptions,
+};
+
+// Payload sizes with their share of the iterations, in the order given
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizeMix {
+    buckets: Vec<(usize, f64)>, // weights scaled to add up to 1
+}
+
+impl SizeMix {
+    // Weights are relative, so [(1024, 7.0), (65536, 3.0)] is the same mix as
+    // [(1024, 0.7), (65536, 0.3)]
+    pub fn new(buckets: &[(usize, f64)]) -> Result<Self, String> {
+        if buckets.is_empty() {
+            return Err("a size mix needs at least one size".to_string());
+        }
+        for (index, &(size, weight)) in buckets.iter().enumerate() {
+            if !(weight > 0.0 && weight.is_finite()) {
+                return Err(format!("size {} has an invalid weight {}", size, weight));
+            }
+            if buckets[..index].iter().any(|&(earlier, _)| earlier == size) {
+                return Err(format!("size {} appears twice in the mix", size));
+            }
+        }
+        // Scaled by the largest first, so weights near f64::MAX can't add up to inf
+        let largest = buckets.iter().map(|&(_, weight)| weight).fold(0.0, f64::max);
+        let total: f64 = buckets.iter().map(|&(_, weight)| weight / largest).sum();
+        let buckets =
+            buckets.iter().map(|&(size, weight)| (size, weight / largest / total)).collect();
+        Ok(SizeMix { buckets })
+    }
+
+    pub fn buckets(&self) -> &[(usize, f64)] {
+        &self.buckets
+    }
+
+    // The sizes `count` iterations send with this seed
+    pub fn draw(&self, count: usize, seed: u64) -> Vec<usize> {
+        let mut rng = SplitMix64::new(seed);
+        (0..count).map(|_| self.buckets[self.pick(&mut rng)].0).collect()
+    }
+
+    // Index of the bucket the next iteration uses
+    pub(crate) fn pick(&self, rng: &mut SplitMix64) -> usize {
+        let mut point = rng.next_f64();
+        for (index, &(_, weight)) in self.buckets.iter().enumerate() {
+            if point < weight {
+                return index;
+            }
+            point -= weight;
+        }
+        // Rounding can leave the point just past the last weight
+        self.buckets.len() - 1
+    }
+}
+
+// "1024:0.7,65536:0.25,1048576:0.05", SIZE:WEIGHT pairs
+pub fn parse_mix(raw: &str) -> Result<SizeMix, String> {
+    let buckets = raw
+        .split(',')
+        .map(|part| {
+            let part = part.trim();
+            let invalid = || format!("expected SIZE:WEIGHT, got '{}'", part);
+            let (size, weight) = part.split_once(':').ok_or_else(invalid)?;
+            let size = size.trim().parse().map_err(|_| invalid())?;
+            let weight = weight.trim().parse().map_err(|_| invalid())?;
+            Ok((size, weight))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    SizeMix::new(&buckets)
+}
+
+// Runs the --size-mix measurement against the configured address and reports it
+pub async fn run_mix(
+    config: &Config,
+    mix: &SizeMix,
+    cancel: Option<watch::Receiver<bool>>,
+) -> Result<(), Box<dyn Error>> {
+    let options = MixOptions {
+        seed: config.seed,
+        random_payload: config.random_payload,
+        transfer: TransferOptions { cancel, ..transfer_options(config) },
+    };
+    let text = config.format == OutputFormat::Text;
+    if text {
+        let shares: Vec<String> = mix
+            .buckets()
+            .iter()
+            .map(|&(size, weight)| format!("{} {:.0}%", format_bytes(size), weight * 100.0))
+            .collect();
+        println!(
+            "Size mix: {} iterations drawn from {} (seed {})",
+            config.iterations,
+            shares.join(", "),
+            config.seed
+        );
+    }
+    let result = measure_mix(config.target_address(), mix, config.iterations, &options).await?;
+    if text {
+        print_mix(&result, config.units, config.color_for(std::io::stdout().is_terminal()));
+    } else {
+        let mut out: Box<dyn Write> = match &config.output {
+            Some(path) => Box::new(open_output(Path::new(path), config.append, None)?),
+            None => Box::new(std::io::stdout()),
+        };
+        let section = |stats: &NetworkStats| {
+            let mut obj = JsonObject::new();
+            stats.write_json_fields(&mut obj);
+            obj.finish()
+        };
+        let buckets: Vec<String> = result
+            .buckets
+            .iter()
+            .map(|bucket| {
+                let mut obj = JsonObject::new();
+                obj.uint("size", bucket.size as u64)
+                    .float("weight", bucket.weight)
+                    .uint("drawn", bucket.drawn as u64)
+                    .raw("stats", &bucket.stats.as_ref().map_or("null".to_string(), section));
+                obj.finish()
+            })
+            .collect();
+        let mut obj = JsonObject::new();
+        obj.uint("seed", config.seed)
+            .raw("size_mix", &format!("[{}]", buckets.join(",")))
+            .raw("overall", &section(&result.overall));
+        writeln!(out, "{}", obj.finish())?;
+    }
+
+    let violations: Vec<String> = config
+        .thresholds
+        .check(&result.overall)
+        .into_iter()
+        .map(|violation| format!("size mix: {}", violation))
+        .collect();
+    if !violations.is_empty() {
+        return Err(ThresholdsViolated { violations }.into());
+    }
+    Ok(())
+}
+
+pub fn print_mix(result: &MixStats, units: RateUnits, color: bool) {
+    println!("\nBy size");
+    for bucket in &result.buckets {
+        let label = format!("{} ({:.0}%)", format_bytes(bucket.size), bucket.weight * 100.0);
+        match &bucket.stats {
+            Some(stats) => println!(
+                "  {}: {} iterations, {} up, {} down, p50 {:.2?}, {} failed",
+                label,
+                bucket.drawn,
+                format_rate_as(stats.upload_rate, units),
+                format_rate_as(stats.download_rate, units),
+                stats.p50_latency(),
+                stats.failed_iterations
+            ),
+            None if bucket.drawn == 0 => println!("  {}: never drawn", label),
+            None => println!("  {}: {} iterations, all failed", label, bucket.drawn),
+        }
+    }
+    println!("Overall");
+    print_stats(&result.overall, units, color);
+}
@@ -0,0 +1,406 @@
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpSocket};
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::sync::watch;
+use tokio::task::{JoinHandle, JoinSet};
+
+use super::framing;
+use super::log::{debug, error, info, warning};
+use super::verify;
+
+pub type ServerResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+#[derive(Debug, Clone)]
+pub struct MockServerConfig {
+    // "[::1]:0" listens on IPv6 loopback; "[::]:0" is dual-stack where the OS allows it
+    pub address: String,
+    // Response length as a multiple of the bytes read, e.g. 0.5 for a download-light
+    // service or 10.0 for a download-heavy one
+    pub response_ratio: f64,
+    // Answer with the bytes read, continued with the verify.rs pattern when the reply
+    // is longer, instead of filler, so clients can check integrity (--verify)
+    pub echo: bool,
+    // Read and discard every request, whatever its flags, and never reply: a sink for
+    // upload-only clients (Direction::UploadOnly) measuring pure upload
+    pub sink: bool,
+    // Largest response a SIZED_REPLY request may ask for; a bigger request fails its
+    // connection instead of allocating whatever the client named
+    pub max_response_len: usize,
+    // Close connections that send nothing for this long, as servers and load balancers
+    // do with idle sockets; None keeps them open until the client closes
+    pub idle_timeout: Option<Duration>,
+    // Connections the kernel queues for accept; a burst beyond it is refused or left
+    // to retry its handshake. Linux caps it at net.core.somaxconn.
+    pub backlog: u32,
+    // After a failed accept, e.g. with no file descriptors left, wait this long and
+    // carry on accepting, counting the failure in `stats`. None stops the server.
+    pub accept_error_pause: Option<Duration>,
+    // Totals over every connection; clones of the config share them, so keep a clone of
+    // the Arc to read what a running server has done
+    pub stats: Arc<MockServerStats>,
+}
+
+pub const DEFAULT_MAX_RESPONSE_LEN: usize = 256 * 1024 * 1024;
+pub const DEFAULT_BACKLOG: u32 = 1024; // what TcpListener::bind uses
+
+impl Default for MockServerConfig {
+    fn default() -> Self {
+        MockServerConfig {
+            address: "127.0.0.1:0".to_string(), // any free port
+            response_ratio: 0.5,
+            echo: false,
+            sink: false,
+            max_response_len: DEFAULT_MAX_RESPONSE_LEN,
+            idle_timeout: None,
+            backlog: DEFAULT_BACKLOG,
+            accept_error_pause: None,
+            stats: Arc::default(),
+        }
+    }
+}
+
+// What the server has read and written so far. Bytes are payload bytes, as the client
+// counts them: no frame headers, and no requested_response_bytes field.
+#[derive(Debug, Default)]
+pub struct MockServerStats {
+    connections: AtomicU64,
+    requests: AtomicU64, // answered or, for NO_REPLY and sink, read in full
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    errors: AtomicU64, // connections that ended on a failed read or write
+    active: AtomicU64,
+    peak_connections: AtomicU64, // most connections open at once
+    accept_errors: AtomicU64,    // failed accepts carried on from (accept_error_pause)
+}
+
+impl MockServerStats {
+    pub fn connections(&self) -> u64 {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    pub fn active_connections(&self) -> u64 {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn peak_connections(&self) -> u64 {
+        self.peak_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn accept_errors(&self) -> u64 {
+        self.accept_errors.load(Ordering::Relaxed)
+    }
+}
+
+// Counts a connection as open for as long as it lives, aborted on shutdown included
+struct ActiveConnection<'a>(&'a MockServerStats);
+
+impl<'a> ActiveConnection<'a> {
+    fn open(stats: &'a MockServerStats) -> Self {
+        let now = stats.active.fetch_add(1, Ordering::Relaxed) + 1;
+        stats.peak_connections.fetch_max(now, Ordering::Relaxed);
+        ActiveConnection(stats)
+    }
+}
+
+impl Drop for ActiveConnection<'_> {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// Mock server for testing
+// Binds `config.address` (port 0 picks a free one) and returns the address actually
+// bound together with the handle of the background accept loop, so callers can
+// connect without racing the bind. A failed bind is returned here, naming the
+// address, rather than surfacing later as refused connections. The loop returns
+// Ok(()) once `shutdown` is set to true or its sender is dropped; connections still
+// open at that point are aborted.
+pub async fn run_mock_server(
+    config: MockServerConfig,
+    shutdown: watch::Receiver<bool>,
+) -> std::io::Result<(SocketAddr, JoinHandle<ServerResult>)> {
+    let listener = bind(&config.address, config.backlog).await.map_err(|e| {
+        std::io::Error::new(
+            e.kind(),
+            format!("mock server can't listen on {}: {}", config.address, e),
+        )
+    })?;
+    let bound = listener.local_addr()?;
+    info!("mock server listening", address = bound, backlog = config.backlog);
+
+    let handle = tokio::spawn(accept_loop(listener, config, shutdown));
+    Ok((bound, handle))
+}
+
+// TcpListener::bind with a chosen backlog: the first of `address`'s resolved addresses
+// that can be listened on
+async fn bind(address: &str, backlog: u32) -> std::io::Result<TcpListener> {
+    let mut last_error = None;
+    for address in tokio::net::lookup_host(address).await? {
+        let socket = if address.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        // As TcpListener::bind does, so a restarted server can reuse its port at once
+        #[cfg(unix)]
+        socket.set_reuseaddr(true)?;
+        match socket.bind(address).and_then(|()| socket.listen(backlog)) {
+            Ok(listener) => return Ok(listener),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| std::io::ErrorKind::AddrNotAvailable.into()))
+}
+
+// Whether the accept loop can carry on after `e`: with accept_error_pause, after the
+// pause; otherwise the error stops the server
+async fn accept_failed(e: std::io::Error, config: &MockServerConfig) -> ServerResult {
+    match config.accept_error_pause {
+        Some(pause) => {
+            config.stats.accept_errors.fetch_add(1, Ordering::Relaxed);
+            warning!("mock server failed to accept a connection", error = e);
+            tokio::time::sleep(pause).await;
+            Ok(())
+        }
+        None => {
+            // Measurements would only see refused connections from here on, so say why
+            error!("mock server stopped accepting connections", error = e);
+            Err(e.into())
+        }
+    }
+}
+
+fn log_stopped(stats: &MockServerStats) {
+    info!(
+        "mock server stopped",
+        connections = stats.connections(),
+        peak_connections = stats.peak_connections(),
+        accept_errors = stats.accept_errors(),
+    );
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    config: MockServerConfig,
+    mut shutdown: watch::Receiver<bool>,
+) -> ServerResult {
+    // Owning the connection tasks means dropping the set on shutdown aborts them
+    let mut connections = JoinSet::new();
+
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = shutdown.wait_for(|stop| *stop) => {
+                log_stopped(&config.stats);
+                return Ok(());
+            }
+        };
+        match accepted {
+            Ok((socket, _)) => {
+                connections.spawn(handle_connection(socket, config.clone()));
+            }
+            Err(e) => accept_failed(e, &config).await?,
+        }
+        // Reap finished connections so the set doesn't grow without bound
+        while connections.try_join_next().is_some() {}
+    }
+}
+
+// run_mock_server on a Unix domain socket at `path` instead of `config.address`. The
+// socket file must not exist yet; it is removed again when the loop shuts down.
+#[cfg(unix)]
+pub async fn run_mock_server_uds(
+    path: impl AsRef<Path>,
+    config: MockServerConfig,
+    shutdown: watch::Receiver<bool>,
+) -> std::io::Result<JoinHandle<ServerResult>> {
+    let path = path.as_ref().to_path_buf();
+    let listener = UnixListener::bind(&path).map_err(|e| {
+        std::io::Error::new(
+            e.kind(),
+            format!("mock server can't listen on {}: {}", path.display(), e),
+        )
+    })?;
+    info!("mock server listening", path = path.display());
+
+    Ok(tokio::spawn(accept_loop_uds(listener, path, config, shutdown)))
+}
+
+#[cfg(unix)]
+async fn accept_loop_uds(
+    listener: UnixListener,
+    path: PathBuf,
+    config: MockServerConfig,
+    mut shutdown: watch::Receiver<bool>,
+) -> ServerResult {
+    let mut connections = JoinSet::new();
+
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = shutdown.wait_for(|stop| *stop) => {
+                log_stopped(&config.stats);
+                let _ = std::fs::remove_file(&path);
+                return Ok(());
+            }
+        };
+        match accepted {
+            Ok((socket, _)) => {
+                connections.spawn(handle_connection(socket, config.clone()));
+            }
+            Err(e) => accept_failed(e, &config).await?,
+        }
+        while connections.try_join_next().is_some() {}
+    }
+}
+
+// Answers each request frame (see framing.rs) with a frame scaled by
+// `response_ratio`, until the client closes the connection. NO_REPLY requests, and
+// with `sink` every request, are read and discarded; SIZED_REPLY ones get the length
+// they ask for, regardless of the ratio, up to `max_response_len`. Each connection's
+// totals are logged when it ends, and added to `config.stats` as it goes.
+async fn handle_connection<S: AsyncRead + AsyncWrite + Unpin>(
+    mut socket: S,
+    config: MockServerConfig,
+) {
+    let MockServerConfig {
+        response_ratio, echo, sink, max_response_len, idle_timeout, stats, ..
+    } = config;
+    stats.connections.fetch_add(1, Ordering::Relaxed);
+    let _active = ActiveConnection::open(&stats);
+    let mut buf = vec![0u8; 8192];
+    let mut received = Vec::new(); // request prefix kept for echo replies
+    let (mut requests, mut read, mut written) = (0u64, 0u64, 0u64);
+    loop {
+        // Which side of the exchange an error came from, for the log
+        let mut phase = "read";
+        let result = async {
+            let header = framing::read_request_header(&mut socket);
+            let header = match idle_timeout {
+                Some(limit) => match tokio::time::timeout(limit, header).await {
+                    Ok(header) => header?,
+                    Err(_) => {
+                        let idle_secs = limit.as_secs_f64();
+                        debug!("mock server closing idle connection", idle_secs = idle_secs);
+                        return Ok(false);
+                    }
+                },
+                None => header.await?,
+            };
+            let (flags, len) = match header {
+                Some(header) => header,
+                None => return Ok(false), // client is done
+            };
+            received.clear();
+            if flags & framing::NO_REPLY != 0 || sink {
+                // The length a SIZED_REPLY request asks for isn't payload
+                let len = if flags & framing::SIZED_REPLY != 0 {
+                    framing::read_requested_len(&mut socket, len).await?.1
+                } else {
+                    len
+                };
+                framing::skip_payload(&mut socket, len, &mut buf).await?;
+                requests += 1;
+                read += len as u64;
+                stats.requests.fetch_add(1, Ordering::Relaxed);
+                stats.bytes_read.fetch_add(len as u64, Ordering::Relaxed);
+                return Ok(true);
+            }
+            let (response_len, len) = if flags & framing::SIZED_REPLY != 0 {
+                let (requested, body) = framing::read_requested_len(&mut socket, len).await?;
+                if requested > max_response_len {
+                    // Closing with the body unread would reset the connection rather
+                    // than end it, racing the client's read of the close
+                    framing::skip_payload(&mut socket, body, &mut buf).await?;
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(
+                            "requested response of {} bytes is over the {}-byte limit",
+                            requested, max_response_len
+                        ),
+                    ));
+                }
+                (requested, body)
+            } else {
+                ((len as f64 * response_ratio) as usize, len)
+            };
+            if echo {
+                framing::read_payload_chunks(&mut socket, len, &mut buf, |chunk, offset| {
+                    let keep = response_len.saturating_sub(offset).min(chunk.len());
+                    received.extend_from_slice(&chunk[..keep]);
+                })
+                .await?;
+            } else {
+                framing::skip_payload(&mut socket, len, &mut buf).await?;
+            }
+            read += len as u64;
+            stats.bytes_read.fetch_add(len as u64, Ordering::Relaxed);
+            phase = "write";
+            reply(&mut socket, &received, response_len, echo).await?;
+            requests += 1;
+            written += response_len as u64;
+            stats.requests.fetch_add(1, Ordering::Relaxed);
+            stats.bytes_written.fetch_add(response_len as u64, Ordering::Relaxed);
+            Ok::<_, std::io::Error>(true)
+        }
+        .await;
+
+        match result {
+            Ok(true) => continue,
+            Ok(false) => break,
+            Err(e) => {
+                stats.errors.fetch_add(1, Ordering::Relaxed);
+                warning!(
+                    "mock server connection failed",
+                    phase = phase,
+                    error = e,
+                    requests = requests,
+                    read = read,
+                    written = written,
+                );
+                return;
+            }
+        }
+    }
+    debug!("mock server connection closed", requests = requests, read = read, written = written);
+}
+
+// An echo reply starts with `received` and continues the verify.rs pattern; otherwise
+// the reply is filler
+async fn reply<S: AsyncWrite + Unpin>(
+    socket: &mut S,
+    received: &[u8],
+    len: usize,
+    echo: bool,
+) -> std::io::Result<()> {
+    if echo {
+        framing::write_frame(socket, &verify::echo_response(received, len)).await
+    } else {
+        framing::write_filler_frame(socket, len, 1).await
+    }
+}
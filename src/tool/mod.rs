@@ -1 +1,52 @@
-pub mod tool;
\ No newline at end of file
+pub mod address;
+pub mod chart;
+pub mod cli;
+pub mod clock;
+pub mod color;
+pub mod compare;
+pub mod confidence;
+pub mod config_file;
+pub mod cpu;
+#[cfg(feature = "sqlite")]
+pub mod db;
+pub mod dedup;
+pub mod env;
+pub mod error;
+pub mod estimate;
+pub mod framing;
+pub mod goodput;
+pub mod happy_eyeballs;
+pub mod http;
+pub mod http2;
+pub mod iteration_stream;
+pub mod json;
+pub mod keepalive;
+pub mod load;
+pub mod log;
+pub mod metrics;
+pub mod mix;
+pub mod mock_server;
+pub mod monitor;
+pub mod mpc;
+pub mod multi;
+#[cfg(feature = "otlp")]
+pub mod otlp;
+pub mod output;
+pub mod parallel;
+pub mod payload;
+pub mod process;
+pub mod progress;
+pub mod proxy;
+pub mod ramp;
+pub mod repeat;
+pub mod replay;
+pub mod sampler;
+pub mod selftest;
+pub mod soak;
+pub mod socks;
+pub mod summary;
+pub mod thresholds;
+#[allow(clippy::module_inception)]
+pub mod tool;
+pub mod verify;
+pub mod watch_display;
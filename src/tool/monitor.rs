@@ -0,0 +1,277 @@
+// --watch: repeat the size sweep on a fixed interval until Ctrl-C, printing one
+// timestamped line per cycle, or on a terminal redrawing one block in place
+// (watch_display.rs), and a summary at the end
+use std::collections::VecDeque;
+use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::time::sleep;
+
+use super::cli::{Config, OutputFormat};
+use super::color;
+use super::json::JsonObject;
+use super::metrics::Metrics;
+use super::output::open_output;
+use super::tool::{
+    format_bytes, format_rate_as, http_options, measure_size, report_error, transfer_options,
+    NetworkStats,
+};
+use super::watch_display::{WatchDisplay, WatchRow};
+
+// Number of most recent cycles the rolling averages cover
+const ROLLING_WINDOW: usize = 10;
+
+// Everything seen for one payload size across the cycles so far
+#[derive(Debug)]
+struct SizeHistory {
+    size: usize,
+    successes: usize,
+    failures: usize,
+    bytes_sent: usize,
+    bytes_received: usize,
+    upload_rate_sum: f64,
+    download_rate_sum: f64,
+    recent_upload: VecDeque<f64>, // last ROLLING_WINDOW upload rates
+    recent_download: VecDeque<f64>,
+    latest: Option<Result<(f64, f64), String>>, // the last cycle's rates, or its error
+}
+
+impl SizeHistory {
+    fn new(size: usize) -> Self {
+        SizeHistory {
+            size,
+            successes: 0,
+            failures: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            upload_rate_sum: 0.0,
+            download_rate_sum: 0.0,
+            recent_upload: VecDeque::with_capacity(ROLLING_WINDOW),
+            recent_download: VecDeque::with_capacity(ROLLING_WINDOW),
+            latest: None,
+        }
+    }
+
+    fn record(&mut self, stats: &NetworkStats) {
+        self.successes += 1;
+        self.bytes_sent += stats.bytes_sent;
+        self.bytes_received += stats.bytes_received;
+        self.upload_rate_sum += stats.upload_rate;
+        self.download_rate_sum += stats.download_rate;
+        for (recent, rate) in [
+            (&mut self.recent_upload, stats.upload_rate),
+            (&mut self.recent_download, stats.download_rate),
+        ] {
+            if recent.len() == ROLLING_WINDOW {
+                recent.pop_front();
+            }
+            recent.push_back(rate);
+        }
+        self.latest = Some(Ok((stats.upload_rate, stats.download_rate)));
+    }
+
+    fn rolling_download(&self) -> f64 {
+        rolling(&self.recent_download)
+    }
+
+    fn row(&self) -> WatchRow {
+        WatchRow {
+            size: self.size,
+            latest: self.latest.clone().unwrap_or_else(|| Err("not measured yet".to_string())),
+            rolling_upload: rolling(&self.recent_upload),
+            rolling_download: self.rolling_download(),
+            bytes_sent: self.bytes_sent,
+            bytes_received: self.bytes_received,
+        }
+    }
+}
+
+fn rolling(recent: &VecDeque<f64>) -> f64 {
+    if recent.is_empty() {
+        return 0.0;
+    }
+    recent.iter().sum::<f64>() / recent.len() as f64
+}
+
+pub async fn run_watch(config: &Config, metrics: Option<&Metrics>) -> Result<(), Box<dyn Error>> {
+    let options = transfer_options(config);
+    let http_options = http_options(config);
+    let lines = config.format == OutputFormat::JsonLines;
+    let json = lines || config.format == OutputFormat::Json;
+    let color = config.color_for(std::io::stdout().is_terminal());
+    let error_color = config.color_for(std::io::stderr().is_terminal());
+    let mut out: Box<dyn Write> = match &config.output {
+        Some(path) if json => Box::new(open_output(Path::new(path), config.append, None)?),
+        _ => Box::new(std::io::stdout()),
+    };
+
+    let mut history: Vec<SizeHistory> = config.sizes.iter().map(|&s| SizeHistory::new(s)).collect();
+    // Errors show in the block rather than on stderr, which would scroll it
+    let mut live = (!json && std::io::stdout().is_terminal()).then(WatchDisplay::new);
+    let started = Instant::now();
+    let mut cycles = 0;
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    eprintln!("Watching every {:?}; press Ctrl-C to stop", config.watch_interval);
+    'watch: loop {
+        let cycle_start = Instant::now();
+        let timestamp = utc_timestamp(SystemTime::now());
+        let mut line = format!("[{}] cycle {}:", timestamp, cycles + 1);
+
+        for entry in history.iter_mut() {
+            let measurement = measure_size(config, entry.size, &options, &http_options, metrics);
+            let measured = tokio::select! {
+                result = measurement => result,
+                _ = &mut ctrl_c => break 'watch,
+            };
+            match measured {
+                Ok(stats) => {
+                    entry.record(&stats);
+                    if json {
+                        let mut obj = JsonObject::new();
+                        obj.string("timestamp", &timestamp)
+                            .uint("cycle", cycles as u64 + 1)
+                            .uint("size", entry.size as u64);
+                        stats.write_json_fields(&mut obj);
+                        writeln!(out, "{}", obj.finish())?;
+                        // Readers following the stream see each size as soon as it's in
+                        if lines {
+                            out.flush()?;
+                        }
+                    }
+                    line.push_str(&format!(
+                        " {} down {} (avg {});",
+                        format_bytes(entry.size),
+                        format_rate_as(stats.download_rate, config.units),
+                        format_rate_as(entry.rolling_download(), config.units)
+                    ));
+                }
+                Err(e) => {
+                    entry.failures += 1;
+                    entry.latest = Some(Err(e.to_string()));
+                    if live.is_none() {
+                        report_error(e.as_ref(), error_color);
+                    }
+                    let failed = format!("{} failed", format_bytes(entry.size));
+                    line.push_str(&format!(" {};", color::paint(&failed, color::RED, color)));
+                }
+            }
+        }
+        cycles += 1;
+        if let Some(display) = &mut live {
+            let every = config.watch_interval;
+            let header = format!("[{}] cycle {}, every {:?}", timestamp, cycles, every);
+            let rows: Vec<WatchRow> = history.iter().map(SizeHistory::row).collect();
+            print!("{}", display.frame(&header, &rows, config.units, color));
+            std::io::stdout().flush()?;
+        } else if !json {
+            println!("{}", line.trim_end_matches(';'));
+        }
+
+        let pause = config.watch_interval.saturating_sub(cycle_start.elapsed());
+        tokio::select! {
+            _ = sleep(pause) => {}
+            _ = &mut ctrl_c => break,
+        }
+    }
+
+    // The summary goes to stderr in JSON mode so stdout stays machine-readable
+    let summary = render_summary(&history, cycles, started.elapsed(), config);
+    if json {
+        eprint!("{}", summary);
+    } else {
+        print!("{}", summary);
+    }
+    Ok(())
+}
+
+fn render_summary(
+    history: &[SizeHistory],
+    cycles: usize,
+    elapsed: Duration,
+    config: &Config,
+) -> String {
+    let mut out = format!("\nWatch summary: {} complete cycles in {:.1?}\n", cycles, elapsed);
+    for entry in history {
+        let mean = |sum: f64| {
+            if entry.successes == 0 {
+                0.0
+            } else {
+                sum / entry.successes as f64
+            }
+        };
+        out.push_str(&format!(
+            "  {}: {} ok, {} failed, mean upload {}, mean download {}, sent {}, received {}\n",
+            format_bytes(entry.size),
+            entry.successes,
+            entry.failures,
+            format_rate_as(mean(entry.upload_rate_sum), config.units),
+            format_rate_as(mean(entry.download_rate_sum), config.units),
+            format_bytes(entry.bytes_sent),
+            format_bytes(entry.bytes_received)
+        ));
+    }
+    out
+}
+
+// RFC 3339 in UTC to the second, e.g. 2024-05-01T12:00:00Z
+pub(crate) fn utc_timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+// The inverse of utc_timestamp; None for anything else, offsets and fractions included
+pub(crate) fn parse_utc_timestamp(text: &str) -> Option<SystemTime> {
+    let (date, time) = text.strip_suffix('Z')?.split_once('T')?;
+    let number = |field: Option<&str>| field?.parse::<u64>().ok();
+    let mut date = date.splitn(3, '-');
+    let (year, month, day) = (number(date.next())?, number(date.next())?, number(date.next())?);
+    let mut time = time.splitn(3, ':');
+    let (hour, minute, second) = (number(time.next())?, number(time.next())?, number(time.next())?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+    // A saved file can carry any year, so a far-off one is treated like a malformed one
+    let year = i64::try_from(year).ok()?;
+    let days = u64::try_from(days_from_civil(year, month as u32, day as u32)?).ok()?;
+    let secs = days.checked_mul(86_400)?.checked_add(hour * 3600 + minute * 60 + second.min(60))?;
+    UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
+
+// Days since 1970-01-01 to a proleptic Gregorian (year, month, day)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+// A proleptic Gregorian date to days since 1970-01-01, the inverse of civil_from_days, or
+// None for a year too far off to count the days of
+fn days_from_civil(year: i64, month: u32, day: u32) -> Option<i64> {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era.checked_mul(146_097)?.checked_add(doe - 719_468)
+}
@@ -0,0 +1,163 @@
+use std::error::Error;
+use std::time::Duration;
+
+use super::tool::{format_bytes, NetworkStats};
+
+// Traffic model for an MPC-TLS session, defaulting to TLSNotary's documented costs
+#[derive(Debug, Clone, PartialEq)]
+pub struct MpcOverheadModel {
+    pub base_overhead: usize,          // fixed upload cost per session, in bytes
+    pub upload_overhead_factor: f64,   // upload bytes per byte of outgoing request data
+    pub download_overhead_factor: f64, // download bytes per byte of incoming response data
+    pub response_multiplier: f64,      // assumed response size as a multiple of the request
+}
+
+impl Default for MpcOverheadModel {
+    fn default() -> Self {
+        // Simulate MPC overhead based on TLSNotary's documentation
+        MpcOverheadModel {
+            base_overhead: 25 * 1024 * 1024, // 25MB fixed cost
+            upload_overhead_factor: 10.0,
+            download_overhead_factor: 0.04,
+            response_multiplier: 10.0, // Assume response is 10x the request size
+        }
+    }
+}
+
+// Estimated MPC traffic for one payload size
+#[derive(Debug, Clone, PartialEq)]
+pub struct MpcEstimate {
+    pub size: usize,
+    pub request_size: usize,
+    pub response_size: usize,
+    pub upload_overhead: usize,
+    pub download_overhead: usize,
+    pub upload_ratio: f64,   // upload overhead per request byte
+    pub download_ratio: f64, // download overhead per response byte
+}
+
+// Computes the estimates without printing anything, one entry per input size
+pub fn estimate_mpc(data_sizes: &[usize], model: &MpcOverheadModel) -> Vec<MpcEstimate> {
+    data_sizes
+        .iter()
+        .map(|&size| {
+            let response_size = (size as f64 * model.response_multiplier) as usize;
+            estimate(size, size, response_size, model)
+        })
+        .collect()
+}
+
+// estimate_mpc grounded in a measurement of payload `size`: a successful iteration's
+// mean bytes sent and received stand in for the nominal request and the
+// response_multiplier. None when no iteration succeeded.
+pub fn estimate_mpc_measured(
+    size: usize,
+    stats: &NetworkStats,
+    model: &MpcOverheadModel,
+) -> Option<MpcEstimate> {
+    let iterations = stats.successful_iterations;
+    (iterations > 0).then(|| {
+        let request_size = stats.bytes_sent / iterations;
+        estimate(size, request_size, stats.bytes_received / iterations, model)
+    })
+}
+
+// The ratios are zero rather than NaN for an empty request or response
+fn estimate(
+    size: usize,
+    request_size: usize,
+    response_size: usize,
+    model: &MpcOverheadModel,
+) -> MpcEstimate {
+    let upload_total =
+        model.base_overhead + (request_size as f64 * model.upload_overhead_factor) as usize;
+    let download_total = (response_size as f64 * model.download_overhead_factor) as usize;
+    let per_byte = |total: usize, bytes: usize| {
+        if bytes == 0 {
+            0.0
+        } else {
+            total as f64 / bytes as f64
+        }
+    };
+    MpcEstimate {
+        size,
+        request_size,
+        response_size,
+        upload_overhead: upload_total,
+        download_overhead: download_total,
+        upload_ratio: per_byte(upload_total, request_size),
+        download_ratio: per_byte(download_total, response_size),
+    }
+}
+
+// A more comprehensive monitoring example that could track a TLSNotary-like application
+pub async fn monitor_mpc_simulation(
+    data_sizes: &[usize],
+    model: &MpcOverheadModel,
+) -> Result<Vec<MpcEstimate>, Box<dyn Error>> {
+    println!("MPC Communication Simulation");
+    println!("============================\n");
+    
+    let estimates = estimate_mpc(data_sizes, model);
+    for estimate in &estimates {
+        println!("Testing with {} payload", format_bytes(estimate.size));
+        println!("  Simulated request size: {}", format_bytes(estimate.request_size));
+        println!("  Simulated response size: {}", format_bytes(estimate.response_size));
+        println!("  Estimated MPC upload overhead: {}", format_bytes(estimate.upload_overhead));
+        println!("  Estimated MPC download overhead: {}", format_bytes(estimate.download_overhead));
+        println!("  Overhead ratio: {:.2}x upload, {:.2}x download", 
+                estimate.upload_ratio,
+                estimate.download_ratio);
+        println!();
+        
+        // Could add actual network transfer test here with mock MPC
+    }
+    
+    Ok(estimates)
+}
+
+// The model applied to what run_benchmarks measured, each size's measured bytes next to
+// their MPC-TLS equivalent and how long that would take at the measured rates. Sizes
+// with no successful iteration are left out.
+pub fn monitor_mpc_measured(
+    measured: &[(usize, NetworkStats)],
+    model: &MpcOverheadModel,
+) -> Vec<MpcEstimate> {
+    println!("MPC Communication Estimate (from the measured transfers)");
+    println!("========================================================\n");
+
+    let mut estimates = Vec::new();
+    for (size, stats) in measured {
+        let Some(estimate) = estimate_mpc_measured(*size, stats, model) else {
+            continue;
+        };
+        println!("Measured with {} payload, per iteration", format_bytes(*size));
+        println!(
+            "  Sent: {} measured, {} with MPC ({:.2}x)",
+            format_bytes(estimate.request_size),
+            format_bytes(estimate.upload_overhead),
+            estimate.upload_ratio
+        );
+        println!(
+            "  Received: {} measured, {} with MPC ({:.2}x)",
+            format_bytes(estimate.response_size),
+            format_bytes(estimate.download_overhead),
+            estimate.download_ratio
+        );
+        let upload = stats.estimate_upload_time(estimate.upload_overhead);
+        let download = stats.estimate_transfer_time(estimate.download_overhead);
+        println!("  At the measured rates: upload {}, download {}", time(upload), time(download));
+        println!();
+        estimates.push(estimate);
+    }
+    estimates
+}
+
+// Duration::MAX is estimate_upload_time's "never", for a rate of zero
+fn time(duration: Duration) -> String {
+    if duration == Duration::MAX {
+        "N/A".to_string()
+    } else {
+        format!("{:.2?}", duration)
+    }
+}
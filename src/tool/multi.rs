@@ -0,0 +1,155 @@
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+
+use tokio::task::JoinSet;
+
+use super::cli::{Config, OutputFormat, RateUnits};
+use super::error::TransferError;
+use super::json::JsonObject;
+use super::output::open_output;
+use super::tool::{
+    format_bytes, format_rate_as, measure_transfer, transfer_options, NetworkStats,
+    TransferOptions,
+};
+
+// One address's outcome, named so results can be told apart
+pub type HostResult = (String, Result<NetworkStats, TransferError>);
+
+// Runs measure_transfer against every address, one after another or all at once
+// when `parallel` is set. Results come back in the order of `addresses`; a failed
+// host doesn't stop the others.
+pub async fn measure_many(
+    addresses: &[&str],
+    data_size: usize,
+    iterations: usize,
+    options: &TransferOptions,
+    parallel: bool,
+) -> Vec<HostResult> {
+    if !parallel {
+        let mut results = Vec::with_capacity(addresses.len());
+        for &address in addresses {
+            let stats = measure_transfer(address, data_size, iterations, options).await;
+            results.push((address.to_string(), stats));
+        }
+        return results;
+    }
+
+    let mut tasks = JoinSet::new();
+    for (position, &address) in addresses.iter().enumerate() {
+        let address = address.to_string();
+        let options = options.clone();
+        tasks.spawn(async move {
+            let stats = measure_transfer(&address, data_size, iterations, &options).await;
+            (position, (address, stats))
+        });
+    }
+    let mut slots: Vec<Option<HostResult>> = addresses.iter().map(|_| None).collect();
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok((position, result)) = joined {
+            slots[position] = Some(result);
+        }
+    }
+    // Measurement doesn't panic, but a lost task mustn't lose its row
+    slots
+        .into_iter()
+        .zip(addresses)
+        .map(|(slot, &address)| {
+            slot.unwrap_or_else(|| {
+                let error = TransferError::InvalidOptions("measurement task failed".to_string());
+                (address.to_string(), Err(error))
+            })
+        })
+        .collect()
+}
+
+// Hosts side by side, fastest download first; failed hosts are listed after the table
+pub fn render_host_table(results: &[HostResult], units: RateUnits) -> String {
+    let mut measured: Vec<(&str, &NetworkStats)> = results
+        .iter()
+        .filter_map(|(host, result)| result.as_ref().ok().map(|stats| (host.as_str(), stats)))
+        .collect();
+    measured.sort_by(|a, b| b.1.download_rate.total_cmp(&a.1.download_rate));
+
+    let rows: Vec<[String; 5]> = measured
+        .iter()
+        .map(|(host, stats)| {
+            [
+                host.to_string(),
+                format_rate_as(stats.upload_rate, units),
+                format_rate_as(stats.download_rate, units),
+                format!("{:.2}", stats.ratio),
+                format!("{:.2?}", stats.mean_latency),
+            ]
+        })
+        .collect();
+    let header = ["Host", "Upload", "Download", "Ratio", "Mean latency"].map(String::from);
+    let mut widths = header.clone().map(|h| h.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(column, (cell, width))| {
+                // Host names read best left-aligned, numbers right-aligned
+                if column == 0 {
+                    format!("{:<width$}", cell)
+                } else {
+                    format!("{:>width$}", cell)
+                }
+            })
+            .collect();
+        out.push_str(&format!("  {}\n", cells.join("  ").trim_end()));
+    }
+    for (host, result) in results {
+        if let Err(e) = result {
+            out.push_str(&format!("  {} failed ({}): {}\n", host, e.category(), e));
+        }
+    }
+    out
+}
+
+// --hosts: measures every host at each size and prints one comparison per size
+pub async fn run_hosts(config: &Config) -> Result<(), Box<dyn Error>> {
+    let options = transfer_options(config);
+    let hosts: Vec<&str> = config.hosts.iter().map(String::as_str).collect();
+    let mut out: Box<dyn Write> = match &config.output {
+        Some(path) if config.format == OutputFormat::Json => {
+            Box::new(open_output(Path::new(path), config.append, None)?)
+        }
+        _ => Box::new(std::io::stdout()),
+    };
+
+    for &size in &config.sizes {
+        let results = measure_many(&hosts, size, config.iterations, &options, config.parallel_hosts)
+            .await;
+        match config.format {
+            OutputFormat::Json => {
+                for (host, result) in &results {
+                    let mut obj = JsonObject::new();
+                    obj.string("host", host).uint("size", size as u64);
+                    match result {
+                        Ok(stats) => stats.write_json_fields(&mut obj),
+                        Err(e) => {
+                            obj.string("error", &e.to_string()).string("category", e.category());
+                        }
+                    }
+                    writeln!(out, "{}", obj.finish())?;
+                }
+            }
+            _ => {
+                println!("{} payload across {} hosts", format_bytes(size), hosts.len());
+                print!("{}", render_host_table(&results, config.units));
+                println!();
+            }
+        }
+    }
+    Ok(())
+}
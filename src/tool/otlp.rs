@@ -0,0 +1,239 @@
+// OpenTelemetry export (--otlp-endpoint, `otlp` feature): OTLP/HTTP with the JSON
+// encoding, written by hand like the rest of the JSON here instead of through the
+// opentelemetry crates. Each measured size is one push: its latencies as an
+// exponential histogram and its rates as gauges, tagged with the payload size, plus
+// the byte totals so far as cumulative counters.
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::http;
+use super::json::{quote, JsonObject};
+use super::tool::NetworkStats;
+
+// The OpenTelemetry SDKs' default bucket limit for exponential histograms
+pub const MAX_BUCKETS: usize = 160;
+const MAX_SCALE: i32 = 20;
+const MIN_SCALE: i32 = -10;
+
+// OTLP AggregationTemporality values
+const DELTA: u64 = 1;
+const CUMULATIVE: u64 = 2;
+
+// Positive samples counted into buckets whose boundaries are powers of
+// base = 2^(2^-scale); bucket i covers (base^i, base^(i+1)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExponentialHistogram {
+    pub scale: i32,
+    pub zero_count: u64,
+    pub offset: i32, // bucket index of counts[0]
+    pub counts: Vec<u64>,
+}
+
+impl ExponentialHistogram {
+    // The finest scale that fits every sample in `max_buckets` buckets. Negative and
+    // non-finite samples are ignored.
+    pub fn from_values(values: &[f64], max_buckets: usize) -> Self {
+        let positive: Vec<f64> =
+            values.iter().copied().filter(|v| v.is_finite() && *v > 0.0).collect();
+        let zero_count = values.iter().filter(|v| **v == 0.0).count() as u64;
+        let (Some(&low), Some(&high)) = (
+            positive.iter().min_by(|a, b| a.total_cmp(b)),
+            positive.iter().max_by(|a, b| a.total_cmp(b)),
+        ) else {
+            let counts = Vec::new();
+            return ExponentialHistogram { scale: 0, zero_count, offset: 0, counts };
+        };
+
+        let mut scale = MAX_SCALE;
+        while scale > MIN_SCALE
+            && (bucket_index(high, scale) - bucket_index(low, scale)) as usize >= max_buckets
+        {
+            scale -= 1;
+        }
+        let offset = bucket_index(low, scale);
+        let mut counts = vec![0; (bucket_index(high, scale) - offset + 1) as usize];
+        for value in positive {
+            counts[(bucket_index(value, scale) - offset) as usize] += 1;
+        }
+        ExponentialHistogram { scale, zero_count, offset, counts }
+    }
+}
+
+fn bucket_index(value: f64, scale: i32) -> i32 {
+    (value.log2() * 2f64.powi(scale)).ceil() as i32 - 1
+}
+
+// Pushes to one collector; keeps the run's byte totals for the cumulative counters
+#[derive(Debug)]
+pub struct OtlpExporter {
+    url: String,
+    start_nanos: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+impl OtlpExporter {
+    // A bare collector address like http://localhost:4318 gets the standard
+    // /v1/metrics path; a URL with a path of its own is used as given
+    pub fn new(endpoint: &str) -> Self {
+        let has_path = endpoint
+            .split_once("://")
+            .map_or(endpoint, |(_, rest)| rest)
+            .trim_end_matches('/')
+            .contains('/');
+        let url = if has_path {
+            endpoint.to_string()
+        } else {
+            format!("{}/v1/metrics", endpoint.trim_end_matches('/'))
+        };
+        OtlpExporter { url, start_nanos: unix_nanos(), bytes_sent: 0, bytes_received: 0 }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub async fn export(
+        &mut self,
+        size: usize,
+        stats: &NetworkStats,
+    ) -> Result<(), Box<dyn Error>> {
+        let body = self.encode(size, stats);
+        http::post(&self.url, "application/json", body.as_bytes()).await?;
+        Ok(())
+    }
+
+    // The ExportMetricsServiceRequest for one size, counting its bytes into the totals
+    pub fn encode(&mut self, size: usize, stats: &NetworkStats) -> String {
+        self.bytes_sent += stats.bytes_sent as u64;
+        self.bytes_received += stats.bytes_received as u64;
+        let now = unix_nanos();
+        let measured_from = now.saturating_sub(stats.elapsed_time.as_nanos() as u64);
+        let attributes = format!(
+            "[{{\"key\":\"payload_size\",\"value\":{{\"intValue\":\"{}\"}}}}]",
+            size
+        );
+
+        let latencies: Vec<f64> = stats.latencies.iter().map(|l| l.as_secs_f64()).collect();
+        let histogram = ExponentialHistogram::from_values(&latencies, MAX_BUCKETS);
+        let counts: Vec<String> = histogram.counts.iter().map(|c| quote(&c.to_string())).collect();
+        let mut positive = JsonObject::new();
+        positive
+            .raw("offset", &histogram.offset.to_string())
+            .raw("bucketCounts", &format!("[{}]", counts.join(",")));
+        let mut point = timed_point(&attributes, measured_from, now);
+        point
+            .string("count", &latencies.len().to_string())
+            .float("sum", latencies.iter().sum())
+            .float("min", stats.min_latency.as_secs_f64())
+            .float("max", stats.max_latency.as_secs_f64())
+            .raw("scale", &histogram.scale.to_string())
+            .string("zeroCount", &histogram.zero_count.to_string())
+            .raw("positive", &positive.finish());
+        let mut latency = JsonObject::new();
+        latency
+            .uint("aggregationTemporality", DELTA)
+            .raw("dataPoints", &format!("[{}]", point.finish()));
+
+        let since = self.start_nanos;
+        let tagged_gauge = |name, unit, description, value| {
+            gauge(name, unit, description, value, &attributes, now)
+        };
+        let metrics = [
+            metric(
+                "traffic_monitor.latency",
+                "s",
+                "Per-iteration latency.",
+                "exponentialHistogram",
+                latency.finish(),
+            ),
+            counter("traffic_monitor.bytes_sent", "Total bytes sent.", self.bytes_sent, since, now),
+            counter(
+                "traffic_monitor.bytes_received",
+                "Total bytes received.",
+                self.bytes_received,
+                since,
+                now,
+            ),
+            tagged_gauge("traffic_monitor.upload_rate", "By/s", "Upload rate.", stats.upload_rate),
+            tagged_gauge(
+                "traffic_monitor.download_rate",
+                "By/s",
+                "Download rate.",
+                stats.download_rate,
+            ),
+            tagged_gauge("traffic_monitor.ratio", "1", "Received/sent ratio.", stats.ratio),
+        ];
+
+        let mut scope = JsonObject::new();
+        scope.string("name", "traffic-monitor").string("version", env!("CARGO_PKG_VERSION"));
+        let mut scope_metrics = JsonObject::new();
+        scope_metrics
+            .raw("scope", &scope.finish())
+            .raw("metrics", &format!("[{}]", metrics.join(",")));
+        let mut resource = JsonObject::new();
+        resource.raw(
+            "attributes",
+            "[{\"key\":\"service.name\",\"value\":{\"stringValue\":\"traffic-monitor\"}}]",
+        );
+        let mut resource_metrics = JsonObject::new();
+        resource_metrics
+            .raw("resource", &resource.finish())
+            .raw("scopeMetrics", &format!("[{}]", scope_metrics.finish()));
+        let mut request = JsonObject::new();
+        request.raw("resourceMetrics", &format!("[{}]", resource_metrics.finish()));
+        request.finish()
+    }
+}
+
+// OTLP JSON writes 64-bit integers, timestamps included, as strings
+fn timed_point(attributes: &str, start_nanos: u64, time_nanos: u64) -> JsonObject {
+    let mut point = JsonObject::new();
+    point
+        .raw("attributes", attributes)
+        .string("startTimeUnixNano", &start_nanos.to_string())
+        .string("timeUnixNano", &time_nanos.to_string());
+    point
+}
+
+fn metric(name: &str, unit: &str, description: &str, kind: &str, data: String) -> String {
+    let mut metric = JsonObject::new();
+    metric
+        .string("name", name)
+        .string("unit", unit)
+        .string("description", description)
+        .raw(kind, &data);
+    metric.finish()
+}
+
+fn counter(name: &str, description: &str, total: u64, start_nanos: u64, now: u64) -> String {
+    let mut point = timed_point("[]", start_nanos, now);
+    point.string("asInt", &total.to_string());
+    let mut sum = JsonObject::new();
+    sum.uint("aggregationTemporality", CUMULATIVE)
+        .raw("isMonotonic", "true")
+        .raw("dataPoints", &format!("[{}]", point.finish()));
+    metric(name, "By", description, "sum", sum.finish())
+}
+
+fn gauge(
+    name: &str,
+    unit: &str,
+    description: &str,
+    value: f64,
+    attributes: &str,
+    now: u64,
+) -> String {
+    let mut point = JsonObject::new();
+    point
+        .raw("attributes", attributes)
+        .string("timeUnixNano", &now.to_string())
+        .float("asDouble", value);
+    let mut gauge = JsonObject::new();
+    gauge.raw("dataPoints", &format!("[{}]", point.finish()));
+    metric(name, unit, description, "gauge", gauge.finish())
+}
+
+fn unix_nanos() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64
+}
@@ -0,0 +1,59 @@
+// Opening the --output file (and --export-records), either afresh or, with --append,
+// at its end so one file collects the results of many runs. A CSV file is only
+// appended to when it starts with the header the new rows follow; JSON output is one
+// object per line, so new lines simply follow the old.
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+// Opens `path` for writing results. Without `append` the file is truncated and
+// `csv_header`, if any, starts it. With `append` the file is created if missing, the
+// header is written only when it is empty, and a file that doesn't hold the same kind
+// of records is refused rather than mixed into: CSV with another header, or JSON
+// lines where CSV is expected and the other way round.
+pub fn open_output(path: &Path, append: bool, csv_header: Option<&str>) -> io::Result<File> {
+    if !append {
+        let mut file = File::create(path)?;
+        if let Some(header) = csv_header {
+            writeln!(file, "{}", header)?;
+        }
+        return Ok(file);
+    }
+
+    let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+    let mut first_line = String::new();
+    BufReader::new(&mut file).read_line(&mut first_line)?;
+    if first_line.is_empty() {
+        if let Some(header) = csv_header {
+            writeln!(file, "{}", header)?;
+        }
+        return Ok(file);
+    }
+
+    let first_line = first_line.trim_end_matches(['\r', '\n']);
+    let mismatch = match csv_header {
+        Some(header) if first_line != header => Some(format!(
+            "it starts with a different CSV header ('{}', expected '{}')",
+            first_line, header
+        )),
+        None if !first_line.starts_with('{') => {
+            Some("it doesn't hold JSON lines".to_string())
+        }
+        _ => None,
+    };
+    if let Some(reason) = mismatch {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("can't append to {}: {}", path.display(), reason),
+        ));
+    }
+
+    // A last record cut off without its newline would run into the first new one
+    file.seek(SeekFrom::End(-1))?;
+    let mut last = [0u8; 1];
+    file.read_exact(&mut last)?;
+    if last[0] != b'\n' {
+        writeln!(file)?;
+    }
+    Ok(file)
+}
@@ -0,0 +1,111 @@
+// --parallel: how much bandwidth several TCP streams get between them, since a single
+// stream often can't fill a fat pipe. Unlike --concurrency's repeated short iterations,
+// each stream is one long-lived connection pushing data nonstop (measure_parallel).
+use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use tokio::sync::watch;
+
+use super::cli::{Config, OutputFormat, RateUnits};
+use super::color;
+use super::json::{quote, JsonObject};
+use super::output::open_output;
+use super::tool::{
+    format_bytes, format_rate_as, measure_parallel, transfer_options, ParallelOptions,
+    ParallelStats, TransferOptions,
+};
+
+// Runs the --parallel measurement against the configured address and reports it
+pub async fn run_parallel(
+    config: &Config,
+    streams: usize,
+    cancel: Option<watch::Receiver<bool>>,
+) -> Result<(), Box<dyn Error>> {
+    let options = ParallelOptions {
+        streams,
+        duration: config.parallel_duration,
+        transfer: TransferOptions { cancel, ..transfer_options(config) },
+        ..ParallelOptions::default()
+    };
+    let text = config.format == OutputFormat::Text;
+    if text {
+        println!(
+            "Parallel streams: {} connections pushing {} frames for {:?}",
+            streams,
+            format_bytes(options.frame_size),
+            options.duration
+        );
+    }
+    let result = measure_parallel(config.target_address(), &options).await?;
+    if text {
+        print_parallel(&result, config.units, config.color_for(std::io::stdout().is_terminal()));
+        return Ok(());
+    }
+
+    let mut out: Box<dyn Write> = match &config.output {
+        Some(path) => Box::new(open_output(Path::new(path), config.append, None)?),
+        None => Box::new(std::io::stdout()),
+    };
+    let streams: Vec<String> = result
+        .streams
+        .iter()
+        .map(|stream| {
+            let mut obj = JsonObject::new();
+            obj.uint("bytes_sent", stream.bytes_sent as u64)
+                .float("elapsed_secs", stream.elapsed.as_secs_f64())
+                .float("upload_rate", stream.upload_rate)
+                .raw("error", &stream.error.as_deref().map_or("null".to_string(), quote));
+            obj.finish()
+        })
+        .collect();
+    let mut obj = JsonObject::new();
+    obj.raw("streams", &format!("[{}]", streams.join(",")))
+        .uint("bytes_sent", result.bytes_sent as u64)
+        .float("elapsed_secs", result.elapsed.as_secs_f64())
+        .float("upload_rate", result.upload_rate)
+        .float("connect_secs", result.connect_time.as_secs_f64())
+        .float("min_stream_rate", result.min_stream_rate())
+        .float("max_stream_rate", result.max_stream_rate())
+        .float("stream_rate_std_dev", result.stream_rate_std_dev())
+        .float("fairness", result.fairness())
+        .raw("interrupted", if result.interrupted { "true" } else { "false" });
+    writeln!(out, "{}", obj.finish())?;
+    Ok(())
+}
+
+pub fn print_parallel(result: &ParallelStats, units: RateUnits, color: bool) {
+    if result.interrupted {
+        println!("  Interrupted: these cover the time before stopping");
+    }
+    for (index, stream) in result.streams.iter().enumerate() {
+        println!(
+            "  Stream {}: {} ({} in {:.2?})",
+            index + 1,
+            format_rate_as(stream.upload_rate, units),
+            format_bytes(stream.bytes_sent),
+            stream.elapsed
+        );
+        if let Some(error) = &stream.error {
+            let line = format!("ended early: {}", error);
+            println!("    - {}", color::paint(&line, color::RED, color));
+        }
+    }
+    println!(
+        "  Aggregate: {} ({} in {:.2?})",
+        color::paint(&format_rate_as(result.upload_rate, units), color::GREEN, color),
+        format_bytes(result.bytes_sent),
+        result.elapsed
+    );
+    println!(
+        "  Per stream: min {}, max {}, std dev {}",
+        format_rate_as(result.min_stream_rate(), units),
+        format_rate_as(result.max_stream_rate(), units),
+        format_rate_as(result.stream_rate_std_dev(), units)
+    );
+    let fairness = format!("{:.3}", result.fairness());
+    println!(
+        "  Fairness: {} (Jain's index; 1 is an even share, 1/{} one stream taking it all)",
+        color::paint(&fairness, color::YELLOW, color),
+        result.streams.len()
+    );
+}
@@ -0,0 +1,169 @@
+use std::io;
+use std::path::PathBuf;
+
+use super::verify;
+
+// Seed used for random payloads unless one is given, so runs compare like for like
+pub const DEFAULT_SEED: u64 = 0x5eed;
+// Zero and random payloads from this size up are generated a chunk at a time as they
+// are sent, instead of being held in memory, so a soak test's size isn't bounded by RAM
+pub const GENERATED_PAYLOAD_MIN: usize = 16 * 1024 * 1024;
+
+// What each iteration sends
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayloadSource {
+    Zeros(usize),
+    // Incompressible bytes, for paths through compressing proxies or links. The same
+    // seed always produces the same bytes.
+    Random { size: usize, seed: u64 },
+    // Replays a file's contents, e.g. captured traffic; read once per measurement
+    File(PathBuf),
+}
+
+impl PayloadSource {
+    pub fn load(&self) -> io::Result<Vec<u8>> {
+        match self {
+            PayloadSource::Zeros(len) => Ok(vec![0u8; *len]),
+            PayloadSource::Random { size, seed } => Ok(random_bytes(*size, *seed)),
+            PayloadSource::File(path) => std::fs::read(path).map_err(|e| {
+                io::Error::new(e.kind(), format!("can't read {}: {}", path.display(), e))
+            }),
+        }
+    }
+
+    // The seed that generated the bytes, for random payloads
+    pub fn seed(&self) -> Option<u64> {
+        match self {
+            PayloadSource::Random { seed, .. } => Some(*seed),
+            _ => None,
+        }
+    }
+
+    // How a payload of at least GENERATED_PAYLOAD_MIN bytes is generated instead of
+    // loaded; `verify` replaces zeros with the verify pattern. None for smaller payloads
+    // and files.
+    pub(crate) fn generated(&self, verify: bool) -> Option<Generated> {
+        let (len, fill) = match *self {
+            PayloadSource::Zeros(len) if verify => (len, Fill::Pattern),
+            PayloadSource::Zeros(len) => (len, Fill::Zeros),
+            PayloadSource::Random { size, seed } => (size, Fill::Random(seed)),
+            PayloadSource::File(_) => return None,
+        };
+        (len >= GENERATED_PAYLOAD_MIN).then_some(Generated { len, fill })
+    }
+}
+
+// A payload that is produced while it is sent: the bytes load() would return, or the
+// verify pattern, without ever holding more than a chunk of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Generated {
+    pub(crate) len: usize,
+    fill: Fill,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fill {
+    Zeros,
+    Random(u64),
+    Pattern,
+}
+
+impl Generated {
+    // The first `len` bytes of the payload, or all of it when that is shorter
+    pub(crate) fn prefix(self, len: usize) -> Generated {
+        Generated { len: len.min(self.len), ..self }
+    }
+
+    // A fresh pass over the payload, from its first byte
+    pub(crate) fn filler(&self) -> Filler {
+        let seed = match self.fill {
+            Fill::Random(seed) => seed,
+            Fill::Zeros | Fill::Pattern => 0,
+        };
+        let (spare, spare_used) = ([0; 8], 8);
+        Filler { payload: *self, offset: 0, rng: SplitMix64::new(seed), spare, spare_used }
+    }
+}
+
+// Hands out a Generated payload's bytes in order
+#[derive(Debug, Clone)]
+pub(crate) struct Filler {
+    payload: Generated,
+    offset: usize,
+    rng: SplitMix64,
+    // The random word being handed out, so chunks of any length continue it exactly
+    spare: [u8; 8],
+    spare_used: usize,
+}
+
+impl Filler {
+    pub(crate) fn remaining(&self) -> usize {
+        self.payload.len - self.offset
+    }
+
+    // Fills the start of `buf` with the next bytes, returning how many; 0 at the end
+    pub(crate) fn fill(&mut self, buf: &mut [u8]) -> usize {
+        let n = buf.len().min(self.remaining());
+        let out = &mut buf[..n];
+        match self.payload.fill {
+            Fill::Zeros => out.fill(0),
+            Fill::Pattern => {
+                for (i, byte) in out.iter_mut().enumerate() {
+                    *byte = verify::pattern_byte(self.offset + i);
+                }
+            }
+            Fill::Random(_) => {
+                let mut done = 0;
+                while done < n {
+                    if self.spare_used == self.spare.len() {
+                        self.spare = self.rng.next_u64().to_le_bytes();
+                        self.spare_used = 0;
+                    }
+                    let take = (self.spare.len() - self.spare_used).min(n - done);
+                    out[done..done + take]
+                        .copy_from_slice(&self.spare[self.spare_used..self.spare_used + take]);
+                    self.spare_used += take;
+                    done += take;
+                }
+            }
+        }
+        self.offset += n;
+        n
+    }
+}
+
+fn random_bytes(len: usize, seed: u64) -> Vec<u8> {
+    let mut rng = SplitMix64::new(seed);
+    let mut out = Vec::with_capacity(len + 8);
+    while out.len() < len {
+        out.extend_from_slice(&rng.next_u64().to_le_bytes());
+    }
+    out.truncate(len);
+    out
+}
+
+// splitmix64: fast, accepts any seed, and plenty random to defeat compression or
+// pick which chunks a proxy drops. Not for anything that needs real unpredictability.
+#[derive(Debug, Clone)]
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -0,0 +1,243 @@
+// `attach`: best-effort traffic of an already running process, read from procfs.
+// Linux has no per-process byte counters for sockets, so this takes the interface
+// counters of the process's network namespace (/proc/<pid>/net/dev) and lists the
+// TCP connections the process owns to show what the bytes are likely to belong to.
+// The numbers are exact when the process has a namespace of its own (a container,
+// `ip netns exec`); otherwise they include everything else in that namespace.
+use std::error::Error;
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+use super::cli::{AttachConfig, RateUnits};
+use super::tool::{format_bytes, format_rate_as, NetworkStats, Tally};
+
+// One TCP socket held open by the process
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connection {
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+    pub state: &'static str, // as netstat spells it, e.g. "ESTABLISHED"
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessSample {
+    pub received: u64, // namespace-wide interface counters, see the header comment
+    pub sent: u64,
+    pub connections: Vec<Connection>,
+    pub shares_namespace: bool, // same network namespace as this process
+}
+
+// Reads the counters and connections of `pid` once. Fails with ErrorKind::Unsupported
+// off Linux, and with PermissionDenied for another user's process unless run as root.
+pub fn sample_process(pid: u32) -> io::Result<ProcessSample> {
+    #[cfg(target_os = "linux")]
+    return procfs::sample(pid);
+    #[cfg(not(target_os = "linux"))]
+    return Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("attaching to process {} needs /proc, which only Linux provides", pid),
+    ));
+}
+
+// Traffic between two samples taken `elapsed` apart, as a single-iteration NetworkStats
+pub fn sample_delta(
+    before: &ProcessSample,
+    after: &ProcessSample,
+    elapsed: Duration,
+) -> NetworkStats {
+    let mut tally = Tally::default();
+    // Counters can go backwards when an interface disappears; treat that as no traffic
+    let sent = after.sent.saturating_sub(before.sent) as usize;
+    let received = after.received.saturating_sub(before.received) as usize;
+    tally.record_success(sent, received, elapsed);
+    tally
+        .into_stats(elapsed)
+        .expect("a recorded success always produces stats")
+}
+
+// Prints one summary per interval until `count` intervals have passed or Ctrl-C
+pub async fn run_attach(config: &AttachConfig) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut before = sample_process(config.pid).map_err(|e| attach_error(config.pid, e))?;
+    println!(
+        "Watching process {} every {:?} ({} TCP connections)",
+        config.pid,
+        config.interval,
+        before.connections.len()
+    );
+    if before.shares_namespace {
+        println!(
+            "  Note: the process shares this network namespace, so the byte counts include \
+             all traffic in it, not only that of process {}",
+            config.pid
+        );
+    }
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let mut taken = Instant::now();
+    let mut intervals = 0;
+    while config.count.is_none_or(|count| intervals < count) {
+        tokio::select! {
+            _ = sleep(config.interval) => {}
+            _ = &mut ctrl_c => break,
+        }
+        let after = sample_process(config.pid).map_err(|e| attach_error(config.pid, e))?;
+        let now = Instant::now();
+        print_interval(&sample_delta(&before, &after, now - taken), &after, config.units);
+        before = after;
+        taken = now;
+        intervals += 1;
+    }
+    Ok(())
+}
+
+fn attach_error(pid: u32, e: io::Error) -> Box<dyn Error + Send + Sync> {
+    match e.kind() {
+        io::ErrorKind::NotFound => format!("no process with pid {}", pid).into(),
+        io::ErrorKind::PermissionDenied => {
+            format!("cannot inspect process {}: {} (try running as its user or root)", pid, e)
+                .into()
+        }
+        _ => e.into(),
+    }
+}
+
+fn print_interval(stats: &NetworkStats, sample: &ProcessSample, units: RateUnits) {
+    println!(
+        "  Sent {} ({}), received {} ({}), ratio {:.2}, {} connections",
+        format_bytes(stats.bytes_sent),
+        format_rate_as(stats.upload_rate, units),
+        format_bytes(stats.bytes_received),
+        format_rate_as(stats.download_rate, units),
+        stats.ratio,
+        sample.connections.len()
+    );
+    for connection in &sample.connections {
+        println!(
+            "    {} -> {} {}",
+            connection.local, connection.remote, connection.state
+        );
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod procfs {
+    use std::collections::HashSet;
+    use std::fs;
+    use std::io;
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    use super::{Connection, ProcessSample};
+
+    pub(super) fn sample(pid: u32) -> io::Result<ProcessSample> {
+        let root = format!("/proc/{}", pid);
+        let (received, sent) = parse_net_dev(&fs::read_to_string(format!("{}/net/dev", root))?);
+        let inodes = socket_inodes(&root)?;
+
+        let mut connections = Vec::new();
+        for table in ["tcp", "tcp6"] {
+            // tcp6 is missing when IPv6 is disabled
+            if let Ok(text) = fs::read_to_string(format!("{}/net/{}", root, table)) {
+                connections.extend(
+                    parse_tcp_table(&text)
+                        .into_iter()
+                        .filter(|(inode, _)| inodes.contains(inode))
+                        .map(|(_, connection)| connection),
+                );
+            }
+        }
+
+        let namespace = |root: &str| fs::read_link(format!("{}/ns/net", root)).ok();
+        let shares_namespace =
+            namespace(&root).is_some_and(|ns| Some(ns) == namespace("/proc/self"));
+        Ok(ProcessSample { received, sent, connections, shares_namespace })
+    }
+
+    // Inodes of the sockets among the process's open file descriptors
+    fn socket_inodes(root: &str) -> io::Result<HashSet<u64>> {
+        let mut inodes = HashSet::new();
+        for entry in fs::read_dir(format!("{}/fd", root))? {
+            // Descriptors can close between listing and reading them
+            let Ok(target) = fs::read_link(entry?.path()) else { continue };
+            let target = target.to_string_lossy();
+            if let Some(inode) = target.strip_prefix("socket:[").and_then(|t| t.strip_suffix(']')) {
+                if let Ok(inode) = inode.parse() {
+                    inodes.insert(inode);
+                }
+            }
+        }
+        Ok(inodes)
+    }
+
+    // (received, sent) bytes summed over all interfaces, loopback included so local
+    // services can be watched too
+    fn parse_net_dev(text: &str) -> (u64, u64) {
+        let mut totals = (0, 0);
+        // Two header lines, then "iface: rx_bytes rx_packets ... tx_bytes ..."
+        for line in text.lines().skip(2) {
+            let Some((_, counters)) = line.split_once(':') else { continue };
+            let fields: Vec<u64> =
+                counters.split_whitespace().filter_map(|f| f.parse().ok()).collect();
+            if fields.len() >= 9 {
+                totals.0 += fields[0];
+                totals.1 += fields[8];
+            }
+        }
+        totals
+    }
+
+    // (inode, connection) for every row of /proc/net/tcp or tcp6
+    fn parse_tcp_table(text: &str) -> Vec<(u64, Connection)> {
+        text.lines()
+            .skip(1)
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let connection = Connection {
+                    local: parse_address(fields.get(1)?)?,
+                    remote: parse_address(fields.get(2)?)?,
+                    state: tcp_state(fields.get(3)?),
+                };
+                Some((fields.get(9)?.parse().ok()?, connection))
+            })
+            .collect()
+    }
+
+    // "0100007F:1F90" is 127.0.0.1:8080: the address is hex in host byte order, as
+    // one 32-bit word for IPv4 and four for IPv6, and the port is plain hex
+    fn parse_address(raw: &str) -> Option<SocketAddr> {
+        let (ip, port) = raw.split_once(':')?;
+        let port = u16::from_str_radix(port, 16).ok()?;
+        let word = |i: usize| u32::from_str_radix(ip.get(i * 8..i * 8 + 8)?, 16).ok();
+        let ip = match ip.len() {
+            8 => Ipv4Addr::from(word(0)?.to_ne_bytes()).into(),
+            32 => {
+                let mut bytes = [0u8; 16];
+                for i in 0..4 {
+                    bytes[i * 4..i * 4 + 4].copy_from_slice(&word(i)?.to_ne_bytes());
+                }
+                Ipv6Addr::from(bytes).into()
+            }
+            _ => return None,
+        };
+        Some(SocketAddr::new(ip, port))
+    }
+
+    fn tcp_state(raw: &str) -> &'static str {
+        match raw {
+            "01" => "ESTABLISHED",
+            "02" => "SYN_SENT",
+            "03" => "SYN_RECV",
+            "04" => "FIN_WAIT1",
+            "05" => "FIN_WAIT2",
+            "06" => "TIME_WAIT",
+            "07" => "CLOSE",
+            "08" => "CLOSE_WAIT",
+            "09" => "LAST_ACK",
+            "0A" => "LISTEN",
+            "0B" => "CLOSING",
+            _ => "UNKNOWN",
+        }
+    }
+}
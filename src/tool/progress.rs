@@ -0,0 +1,50 @@
+use std::fmt::Write as _;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use super::cli::RateUnits;
+use super::tool::write_rate_as;
+
+// One-line `\r` progress display for measure_transfer's iteration loop. Callers only
+// create it when stdout is a terminal showing text output.
+pub(crate) struct Progress {
+    start: Instant,
+    total: usize,
+    line: String, // reused by every update rather than formatted afresh
+}
+
+impl Progress {
+    pub(crate) fn new(total: usize) -> Self {
+        Progress {
+            start: Instant::now(),
+            total,
+            line: String::new(),
+        }
+    }
+
+    // `done` iterations have finished, successful or not, sending `bytes_sent` so far
+    pub(crate) fn update(&mut self, done: usize, bytes_sent: usize) {
+        let elapsed = self.start.elapsed();
+        let rate = bytes_sent as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+        let eta = if done == 0 {
+            Duration::ZERO
+        } else {
+            elapsed.mul_f64((self.total - done) as f64 / done as f64)
+        };
+        let percent = done as f64 / self.total.max(1) as f64 * 100.0;
+        self.line.clear();
+        let _ = write!(self.line, "\r  {}/{} iterations ({:.0}%), ", done, self.total, percent);
+        let _ = write_rate_as(&mut self.line, rate, RateUnits::Bytes);
+        // \x1b[K clears whatever a longer previous line left behind
+        let _ = write!(self.line, " upload, ETA {:.1?}\x1b[K", eta);
+        let mut stdout = std::io::stdout().lock();
+        let _ = stdout.write_all(self.line.as_bytes());
+        let _ = stdout.flush();
+    }
+
+    // Erases the progress line so the results print in its place
+    pub(crate) fn finish(&self) {
+        print!("\r\x1b[K");
+        let _ = std::io::stdout().flush();
+    }
+}
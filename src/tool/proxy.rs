@@ -0,0 +1,335 @@
+use std::error::Error;
+use std::io::IsTerminal;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::{sleep, sleep_until};
+
+use super::cli::RateUnits;
+use super::color;
+use super::framing::SequenceCheck;
+use super::log::warning;
+use super::payload::{SplitMix64, DEFAULT_SEED};
+use super::tool::{format_bytes, format_rate, print_stats, Tally};
+
+// Throughput caps in bytes per second for each direction of every session, to emulate
+// a slow or asymmetric link. None relays as fast as the endpoints allow.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProxyLimits {
+    pub client_to_upstream: Option<f64>,
+    pub upstream_to_client: Option<f64>,
+}
+
+// Degraded-network injection, applied to both directions of every session
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProxyFaults {
+    pub latency: Duration, // added to every forwarded chunk, without limiting throughput
+    // Fraction of read chunks discarded instead of forwarded, from 0 up to 1. The
+    // stream arrives with holes in it, as after unrecovered loss.
+    pub drop_rate: f64,
+    // Picks the dropped chunks; the same seed drops the same chunks of the same traffic.
+    // Each session and direction derives its own stream from it.
+    pub seed: u64,
+}
+
+impl Default for ProxyFaults {
+    fn default() -> Self {
+        ProxyFaults {
+            latency: Duration::ZERO,
+            drop_rate: 0.0,
+            seed: DEFAULT_SEED,
+        }
+    }
+}
+
+impl ProxyFaults {
+    fn is_none(&self) -> bool {
+        self.latency.is_zero() && self.drop_rate == 0.0
+    }
+}
+
+// Relays every accepted connection to `upstream_addr` and prints a summary when
+// each session closes. "Sent" is client→upstream and "received" upstream→client.
+pub async fn run_proxy(
+    listen_addr: &str,
+    upstream_addr: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    run_proxy_with_limits(listen_addr, upstream_addr, ProxyLimits::default()).await
+}
+
+// run_proxy with each direction throttled to `limits`
+pub async fn run_proxy_with_limits(
+    listen_addr: &str,
+    upstream_addr: &str,
+    limits: ProxyLimits,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    run_proxy_with_faults(listen_addr, upstream_addr, limits, ProxyFaults::default()).await
+}
+
+// run_proxy_with_limits that also delays and drops traffic per `faults`
+pub async fn run_proxy_with_faults(
+    listen_addr: &str,
+    upstream_addr: &str,
+    limits: ProxyLimits,
+    faults: ProxyFaults,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    run_proxy_with_checks(listen_addr, upstream_addr, limits, faults, false).await
+}
+
+// run_proxy_with_faults that, with `check_sequence`, follows the client's requests as
+// sequenced frames (see framing::SequenceCheck) and reports any that arrived out of
+// order or twice in the session summary. Responses aren't checked.
+pub async fn run_proxy_with_checks(
+    listen_addr: &str,
+    upstream_addr: &str,
+    limits: ProxyLimits,
+    faults: ProxyFaults,
+    check_sequence: bool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let listener = TcpListener::bind(listen_addr).await?;
+    eprintln!(
+        "Proxy listening on {}, forwarding to {}",
+        listener.local_addr()?,
+        upstream_addr
+    );
+
+    for session in 0u64.. {
+        let (client, peer) = listener.accept().await?;
+        let upstream_addr = upstream_addr.to_string();
+        let faults = ProxyFaults {
+            seed: faults.seed.wrapping_add(session),
+            ..faults
+        };
+
+        tokio::spawn(async move {
+            match proxy_session(client, &upstream_addr, limits, faults, check_sequence).await {
+                Ok(session) => session.print(peer),
+                Err(e) => warning!("proxy session failed", peer = peer, error = e),
+            }
+        });
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+struct Session {
+    client_to_upstream: Relayed,
+    upstream_to_client: Relayed,
+    duration: Duration,
+    limits: ProxyLimits,
+    faults: ProxyFaults,
+}
+
+// One direction's byte counts
+#[derive(Debug, Default, Clone)]
+struct Relayed {
+    forwarded: u64,
+    dropped: u64,
+    sequence: Option<SequenceCheck>, // of the forwarded bytes, when checked
+}
+
+impl Session {
+    fn print(&self, peer: SocketAddr) {
+        let mut tally = Tally::default();
+        tally.record_success(
+            self.client_to_upstream.forwarded as usize,
+            self.upstream_to_client.forwarded as usize,
+            self.duration,
+        );
+        println!("Proxy session {} closed", peer);
+        // What was allowed; the rates print_stats reports are what was achieved
+        if self.limits != ProxyLimits::default() {
+            let cap = |limit: Option<f64>| limit.map_or("unlimited".to_string(), format_rate);
+            println!(
+                "  Caps: {} client→upstream, {} upstream→client",
+                cap(self.limits.client_to_upstream),
+                cap(self.limits.upstream_to_client)
+            );
+        }
+        if !self.faults.is_none() {
+            println!(
+                "  Faults: {:?} added latency, {:.1}% of chunks dropped (seed {})",
+                self.faults.latency,
+                self.faults.drop_rate * 100.0,
+                self.faults.seed
+            );
+            println!(
+                "  Dropped: {} client→upstream, {} upstream→client",
+                format_bytes(self.client_to_upstream.dropped as usize),
+                format_bytes(self.upstream_to_client.dropped as usize)
+            );
+        }
+        if let Some(check) = &self.client_to_upstream.sequence {
+            println!(
+                "  Sequence: {} requests, {} reordered, {} duplicated, {} missing, {} unnumbered",
+                check.frames,
+                check.reordered,
+                check.duplicated,
+                check.missing(),
+                check.unsequenced
+            );
+        }
+        // Sent and Received below count forwarded bytes only
+        if let Ok(stats) = tally.into_stats(self.duration) {
+            let color = color::wanted(std::io::stdout().is_terminal());
+            print_stats(&stats, RateUnits::Bytes, color);
+        }
+    }
+}
+
+async fn proxy_session(
+    client: TcpStream,
+    upstream_addr: &str,
+    limits: ProxyLimits,
+    faults: ProxyFaults,
+    check_sequence: bool,
+) -> std::io::Result<Session> {
+    let start = Instant::now();
+    let upstream = TcpStream::connect(upstream_addr).await?;
+
+    let (client_read, client_write) = client.into_split();
+    let (upstream_read, upstream_write) = upstream.into_split();
+
+    // Distinct streams per direction, so one side's traffic doesn't shift the other's drops
+    let upstream_faults = ProxyFaults {
+        seed: faults.seed.rotate_left(32),
+        ..faults
+    };
+    let (client_to_upstream, upstream_to_client) = tokio::try_join!(
+        pipe(client_read, upstream_write, limits.client_to_upstream, faults, check_sequence),
+        pipe(upstream_read, client_write, limits.upstream_to_client, upstream_faults, false),
+    )?;
+
+    Ok(Session {
+        client_to_upstream,
+        upstream_to_client,
+        duration: start.elapsed(),
+        limits,
+        faults,
+    })
+}
+
+// Copies one direction until EOF, at most `limit` bytes per second when set and
+// degraded per `faults`, then half-closes the far side so the peer sees it too
+async fn pipe<R, W>(
+    mut from: R,
+    mut to: W,
+    limit: Option<f64>,
+    faults: ProxyFaults,
+    check_sequence: bool,
+) -> std::io::Result<Relayed>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let relayed = if limit.is_none() && faults.is_none() && !check_sequence {
+        Relayed {
+            forwarded: tokio::io::copy(&mut from, &mut to).await?,
+            ..Relayed::default()
+        }
+    } else {
+        shaped_copy(&mut from, &mut to, limit, faults, check_sequence).await?
+    };
+    to.shutdown().await?;
+    Ok(relayed)
+}
+
+// The reader stamps each chunk with when it may go out and the writer holds it until
+// then, so added latency delays the stream without slowing it down. The sequence
+// check sees the chunks as they go out.
+async fn shaped_copy<R, W>(
+    from: &mut R,
+    to: &mut W,
+    limit: Option<f64>,
+    faults: ProxyFaults,
+    check_sequence: bool,
+) -> std::io::Result<Relayed>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut bucket = limit.map(TokenBucket::new);
+    let chunk_len = bucket.as_ref().map_or(16 * 1024, |b| b.capacity);
+    let mut rng = SplitMix64::new(faults.seed);
+    let (queue, mut pending) = mpsc::channel::<(tokio::time::Instant, Vec<u8>)>(64);
+
+    let read = async move {
+        let mut dropped = 0;
+        let mut buffer = vec![0u8; chunk_len];
+        loop {
+            let n = from.read(&mut buffer).await?;
+            if n == 0 {
+                break;
+            }
+            if let Some(bucket) = &mut bucket {
+                bucket.take(n).await;
+            }
+            if faults.drop_rate > 0.0 && rng.next_f64() < faults.drop_rate {
+                dropped += n as u64;
+                continue;
+            }
+            let due = tokio::time::Instant::now() + faults.latency;
+            if queue.send((due, buffer[..n].to_vec())).await.is_err() {
+                break; // the writer failed and has its own error to report
+            }
+        }
+        Ok::<_, std::io::Error>(dropped)
+    };
+    let write = async {
+        let mut forwarded = 0;
+        let mut sequence = check_sequence.then(SequenceCheck::new);
+        while let Some((due, chunk)) = pending.recv().await {
+            sleep_until(due).await;
+            to.write_all(&chunk).await?;
+            forwarded += chunk.len() as u64;
+            if let Some(sequence) = &mut sequence {
+                sequence.feed(&chunk);
+            }
+        }
+        Ok::<_, std::io::Error>((forwarded, sequence))
+    };
+
+    let (dropped, (forwarded, sequence)) = tokio::try_join!(read, write)?;
+    Ok(Relayed { forwarded, dropped, sequence })
+}
+
+// Refills at `rate` tokens (bytes) per second up to `capacity`, a tenth of a second's
+// worth, so an idle connection can burst briefly but never sustain more than the rate
+struct TokenBucket {
+    rate: f64,
+    capacity: usize,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        let capacity = ((rate / 10.0) as usize).clamp(1, 64 * 1024);
+        TokenBucket {
+            rate,
+            capacity,
+            tokens: capacity as f64,
+            refilled: Instant::now(),
+        }
+    }
+
+    // Waits until `n` (at most `capacity`) tokens are available and spends them
+    async fn take(&mut self, n: usize) {
+        self.refill();
+        let missing = n as f64 - self.tokens;
+        if missing > 0.0 {
+            sleep(Duration::from_secs_f64(missing / self.rate)).await;
+            self.refill();
+        }
+        self.tokens -= n as f64;
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let earned = now.duration_since(self.refilled).as_secs_f64() * self.rate;
+        self.tokens = (self.tokens + earned).min(self.capacity as f64);
+        self.refilled = now;
+    }
+}
@@ -0,0 +1,271 @@
+// --ramp: the same payload measured at rising concurrency, e.g. 1, 2, 4, 8 and 16
+// connections in flight, to find the knee where adding load stops adding throughput
+// and only makes each request wait longer
+use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use tokio::sync::watch;
+
+use super::cli::{Config, OutputFormat, RateUnits};
+use super::color;
+use super::error::TransferError;
+use super::json::{quote, JsonObject};
+use super::output::open_output;
+use super::tool::{
+    describe_run, format_bytes, format_rate_as, measure_transfer, transfer_options,
+    NetworkStats, TransferOptions,
+};
+
+// A level that raises throughput by less than this, as a fraction of the level before
+// it, no longer scales
+pub const RAMP_KNEE_GAIN: f64 = 0.1;
+
+#[derive(Debug, Clone)]
+pub struct RampOptions {
+    pub levels: Vec<usize>, // concurrencies to measure, in order
+    // Per connection in flight, so every level gives each connection the same work
+    pub iterations: usize,
+    pub transfer: TransferOptions, // its concurrency is replaced by each level's
+}
+
+impl Default for RampOptions {
+    fn default() -> Self {
+        RampOptions {
+            levels: vec![1, 2, 4, 8, 16],
+            iterations: 5,
+            transfer: TransferOptions::default(),
+        }
+    }
+}
+
+// One concurrency level: its measurement, or the error every iteration failed with
+#[derive(Debug)]
+pub struct RampLevel {
+    pub concurrency: usize,
+    pub iterations: usize,
+    pub result: Result<NetworkStats, String>,
+}
+
+impl RampLevel {
+    // Bytes per second both ways, over the level's wall-clock time
+    pub fn throughput(&self) -> Option<f64> {
+        let stats = self.result.as_ref().ok()?;
+        Some(stats.upload_rate + stats.download_rate)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RampReport {
+    pub size: usize,
+    pub levels: Vec<RampLevel>,
+    pub interrupted: bool, // stopped early through TransferOptions::cancel (Ctrl-C)
+}
+
+impl RampReport {
+    // The last level before throughput stopped scaling: the first one whose next
+    // measured level gains less than RAMP_KNEE_GAIN on it. Failed levels are skipped;
+    // None when every step scaled or fewer than two levels measured anything.
+    pub fn knee(&self) -> Option<&RampLevel> {
+        let measured: Vec<(&RampLevel, f64)> =
+            self.levels.iter().filter_map(|level| Some((level, level.throughput()?))).collect();
+        measured
+            .windows(2)
+            .find(|pair| pair[1].1 < pair[0].1 * (1.0 + RAMP_KNEE_GAIN))
+            .map(|pair| pair[0].0)
+    }
+
+    // The measured level with the highest throughput
+    pub fn peak(&self) -> Option<&RampLevel> {
+        self.levels
+            .iter()
+            .filter_map(|level| Some((level, level.throughput()?)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(level, _)| level)
+    }
+}
+
+// Measures `size`-byte iterations at each of `options.levels` in turn, running
+// `options.iterations` per connection in flight. A level where every iteration failed,
+// as an overloaded server may make them, is recorded and the ramp goes on; other
+// errors, such as a bad address, end it. Fails with the last error when no level
+// measured anything.
+pub async fn measure_ramp(
+    address: &str,
+    size: usize,
+    options: &RampOptions,
+) -> Result<RampReport, TransferError> {
+    let mut report = RampReport { size, ..RampReport::default() };
+    let mut last_error = None;
+    let cancelled = || options.transfer.cancel.as_ref().is_some_and(|cancel| *cancel.borrow());
+    for &concurrency in &options.levels {
+        if cancelled() {
+            report.interrupted = true;
+            break;
+        }
+        let iterations = options.iterations * concurrency;
+        let transfer = TransferOptions { concurrency, ..options.transfer.clone() };
+        let result = match measure_transfer(address, size, iterations, &transfer).await {
+            Ok(stats) => {
+                report.interrupted |= stats.interrupted;
+                Ok(stats)
+            }
+            Err(e @ TransferError::AllFailed { .. }) => {
+                let message = e.to_string();
+                last_error = Some(e);
+                Err(message)
+            }
+            Err(e) => return Err(e),
+        };
+        report.levels.push(RampLevel { concurrency, iterations, result });
+        if report.interrupted {
+            break;
+        }
+    }
+    if report.levels.iter().all(|level| level.result.is_err()) {
+        if let Some(e) = last_error {
+            return Err(e);
+        }
+    }
+    Ok(report)
+}
+
+// Runs the --ramp measurement with the largest of the configured sizes and reports
+// each level. Their stats are described as if run with that level's --concurrency.
+pub async fn run_ramp(
+    config: &Config,
+    levels: &[usize],
+    cancel: Option<watch::Receiver<bool>>,
+) -> Result<(), Box<dyn Error>> {
+    let options = RampOptions {
+        levels: levels.to_vec(),
+        iterations: config.iterations,
+        transfer: TransferOptions { cancel, ..transfer_options(config) },
+    };
+    let size = config.sizes.iter().copied().max().unwrap_or_default();
+    let text = config.format == OutputFormat::Text;
+    if text {
+        println!(
+            "Ramp: {} payload at concurrency {}, {} iterations per connection",
+            format_bytes(size),
+            join_levels(levels),
+            options.iterations
+        );
+    }
+    let mut report = measure_ramp(config.target_address(), size, &options).await?;
+    for level in &mut report.levels {
+        if let Ok(stats) = &mut level.result {
+            let level_config = Config {
+                concurrency: level.concurrency,
+                iterations: level.iterations,
+                ..config.clone()
+            };
+            *stats = describe_run(std::mem::take(stats), &level_config);
+        }
+    }
+    if text {
+        let color = config.color_for(std::io::stdout().is_terminal());
+        print_ramp(&report, config.units, color);
+        return Ok(());
+    }
+    let mut out: Box<dyn Write> = match &config.output {
+        Some(path) => Box::new(open_output(Path::new(path), config.append, None)?),
+        None => Box::new(std::io::stdout()),
+    };
+    writeln!(out, "{}", ramp_json(&report))?;
+    Ok(())
+}
+
+fn join_levels(levels: &[usize]) -> String {
+    levels.iter().map(usize::to_string).collect::<Vec<_>>().join(", ")
+}
+
+pub fn print_ramp(report: &RampReport, units: RateUnits, color: bool) {
+    if report.interrupted {
+        println!("  Interrupted after {} levels", report.levels.len());
+    }
+    let row = |concurrency: &str, throughput: &str, p50: &str, p99: &str, failed: &str| {
+        println!(
+            "  {:>11}  {:>14}  {:>10}  {:>10}  {:>6}",
+            concurrency, throughput, p50, p99, failed
+        )
+    };
+    row("Concurrency", "Throughput", "p50", "p99", "Failed");
+    for level in &report.levels {
+        match &level.result {
+            Ok(stats) => row(
+                &level.concurrency.to_string(),
+                &format_rate_as(stats.upload_rate + stats.download_rate, units),
+                &format!("{:.2?}", stats.p50_latency()),
+                &format!("{:.2?}", stats.p99_latency()),
+                &stats.failed_iterations.to_string(),
+            ),
+            Err(e) => {
+                let failed = format!("every iteration failed: {}", e);
+                let failed = color::paint(&failed, color::RED, color);
+                println!("  {:>11}  {}", level.concurrency, failed);
+            }
+        }
+    }
+    let Some(peak) = report.peak() else {
+        return;
+    };
+    println!(
+        "  Peak: {} at concurrency {}",
+        format_rate_as(peak.throughput().unwrap_or_default(), units),
+        peak.concurrency
+    );
+    let Some(knee) = report.knee() else {
+        let scaled = format!(
+            "No knee: every level raised throughput by {:.0}% or more",
+            RAMP_KNEE_GAIN * 100.0
+        );
+        println!("  {}", color::paint(&scaled, color::GREEN, color));
+        return;
+    };
+    // How the wait grows past the knee, up to the last level that measured anything
+    let mut warning =
+        format!("Knee: throughput stops scaling after concurrency {}", knee.concurrency);
+    let last = report.levels.iter().rev().find(|level| level.result.is_ok());
+    if let (Ok(at_knee), Some(last)) = (&knee.result, last) {
+        if let (Ok(at_last), true) = (&last.result, last.concurrency > knee.concurrency) {
+            warning.push_str(&format!(
+                ", while p99 goes from {:.2?} to {:.2?} by concurrency {}",
+                at_knee.p99_latency(),
+                at_last.p99_latency(),
+                last.concurrency
+            ));
+        }
+    }
+    println!("  {}", color::paint(&warning, color::YELLOW, color));
+}
+
+// One object: every level in order, with its figures and full stats (null for a level
+// that failed), then the peak and the knee
+pub fn ramp_json(report: &RampReport) -> String {
+    let optional = |value: Option<f64>| value.map_or("null".to_string(), |v| v.to_string());
+    let concurrency =
+        |level: Option<&RampLevel>| level.map_or("null".to_string(), |l| l.concurrency.to_string());
+    let levels: Vec<String> = report
+        .levels
+        .iter()
+        .map(|level| {
+            let stats = level.result.as_ref().ok();
+            let mut obj = JsonObject::new();
+            obj.uint("concurrency", level.concurrency as u64)
+                .uint("iterations", level.iterations as u64)
+                .raw("throughput", &optional(level.throughput()))
+                .raw("p50_latency_secs", &optional(stats.map(|s| s.p50_latency().as_secs_f64())))
+                .raw("p99_latency_secs", &optional(stats.map(|s| s.p99_latency().as_secs_f64())))
+                .raw("error", &level.result.as_ref().err().map_or("null".to_string(), |e| quote(e)))
+                .raw("stats", &stats.map_or("null".to_string(), NetworkStats::to_json));
+            obj.finish()
+        })
+        .collect();
+    let mut obj = JsonObject::new();
+    obj.uint("size", report.size as u64)
+        .raw("levels", &format!("[{}]", levels.join(",")))
+        .raw("peak_concurrency", &concurrency(report.peak()))
+        .raw("knee_concurrency", &concurrency(report.knee()))
+        .raw("interrupted", &report.interrupted.to_string());
+    obj.finish()
+}
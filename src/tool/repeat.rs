@@ -0,0 +1,232 @@
+// --repeat: the whole size sweep run several times over, to tell a reliable result from
+// a lucky or unlucky one. Iteration counts and confidence intervals cover the noise
+// within a run; this covers what changes between runs, such as other traffic or a
+// busy host.
+use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use tokio::sync::watch;
+
+use super::cli::{Config, OutputFormat, RateUnits};
+use super::color;
+use super::confidence;
+use super::json::JsonObject;
+use super::metrics::Metrics;
+use super::output::open_output;
+use super::tool::{
+    format_bytes, format_rate_as, http_options, measure_size, report_error, transfer_options,
+    NetworkStats, TransferOptions,
+};
+
+// One payload size over every run: run n's measurement, or the error it failed with
+#[derive(Debug)]
+pub struct RepeatedSize {
+    pub size: usize,
+    pub runs: Vec<Result<NetworkStats, String>>,
+}
+
+impl RepeatedSize {
+    pub fn new(size: usize) -> Self {
+        RepeatedSize { size, runs: Vec::new() }
+    }
+
+    // The runs that measured something, with their 0-based run index
+    pub fn successful(&self) -> impl Iterator<Item = (usize, &NetworkStats)> {
+        self.runs.iter().enumerate().filter_map(|(run, result)| Some((run, result.as_ref().ok()?)))
+    }
+
+    pub fn failed_runs(&self) -> usize {
+        self.runs.iter().filter(|result| result.is_err()).count()
+    }
+
+    pub fn download_rates(&self) -> Vec<f64> {
+        self.successful().map(|(_, stats)| stats.download_rate).collect()
+    }
+
+    pub fn upload_rates(&self) -> Vec<f64> {
+        self.successful().map(|(_, stats)| stats.upload_rate).collect()
+    }
+
+    // Across the successful runs; zero when there were none
+    pub fn mean_download_rate(&self) -> f64 {
+        mean(&self.download_rates())
+    }
+
+    pub fn mean_upload_rate(&self) -> f64 {
+        mean(&self.upload_rates())
+    }
+
+    // Sample standard deviation across the successful runs; None with fewer than two
+    pub fn download_rate_std_dev(&self) -> Option<f64> {
+        confidence::sample_std_dev(&self.download_rates())
+    }
+
+    pub fn upload_rate_std_dev(&self) -> Option<f64> {
+        confidence::sample_std_dev(&self.upload_rates())
+    }
+
+    // The runs with the highest and the lowest download rate, or upload rate when
+    // nothing was downloaded (--direction upload); None when every run failed
+    pub fn best_run(&self) -> Option<(usize, &NetworkStats)> {
+        let rate = self.ranking();
+        self.successful().max_by(|(_, a), (_, b)| rate(a).total_cmp(&rate(b)))
+    }
+
+    pub fn worst_run(&self) -> Option<(usize, &NetworkStats)> {
+        let rate = self.ranking();
+        self.successful().min_by(|(_, a), (_, b)| rate(a).total_cmp(&rate(b)))
+    }
+
+    fn ranking(&self) -> fn(&NetworkStats) -> f64 {
+        if self.successful().any(|(_, stats)| stats.download_rate > 0.0) {
+            |stats| stats.download_rate
+        } else {
+            |stats| stats.upload_rate
+        }
+    }
+}
+
+fn mean(samples: &[f64]) -> f64 {
+    if samples.is_empty() {
+        0.0
+    } else {
+        samples.iter().sum::<f64>() / samples.len() as f64
+    }
+}
+
+// Runs the sweep `repeats` times against the configured address and reports each size
+// across the runs. Ctrl-C stops before the next size with what finished reported.
+pub async fn run_repeat(
+    config: &Config,
+    repeats: usize,
+    metrics: Option<&Metrics>,
+    cancel: Option<watch::Receiver<bool>>,
+) -> Result<(), Box<dyn Error>> {
+    let cancelled = || cancel.as_ref().is_some_and(|cancel| *cancel.borrow());
+    let options = TransferOptions { cancel: cancel.clone(), ..transfer_options(config) };
+    let http_options = http_options(config);
+    let text = config.format == OutputFormat::Text;
+    let error_color = config.color_for(std::io::stderr().is_terminal());
+
+    let mut sizes: Vec<RepeatedSize> = config.sizes.iter().map(|&s| RepeatedSize::new(s)).collect();
+    'runs: for run in 0..repeats {
+        if text {
+            println!("Run {} of {}", run + 1, repeats);
+        }
+        for entry in sizes.iter_mut() {
+            if cancelled() {
+                break 'runs;
+            }
+            let measured = measure_size(config, entry.size, &options, &http_options, metrics);
+            match measured.await {
+                Ok(stats) => {
+                    if text {
+                        println!(
+                            "  {}: download {}, upload {}",
+                            format_bytes(entry.size),
+                            format_rate_as(stats.download_rate, config.units),
+                            format_rate_as(stats.upload_rate, config.units)
+                        );
+                    }
+                    entry.runs.push(Ok(stats));
+                }
+                Err(e) => {
+                    report_error(e.as_ref(), error_color);
+                    entry.runs.push(Err(e.to_string()));
+                }
+            }
+        }
+    }
+
+    if text {
+        println!();
+        let color = config.color_for(std::io::stdout().is_terminal());
+        for entry in &sizes {
+            print_repeated(entry, config.units, color);
+        }
+        return Ok(());
+    }
+    let mut out: Box<dyn Write> = match &config.output {
+        Some(path) => Box::new(open_output(Path::new(path), config.append, None)?),
+        None => Box::new(std::io::stdout()),
+    };
+    for entry in &sizes {
+        writeln!(out, "{}", repeated_json(entry))?;
+    }
+    Ok(())
+}
+
+pub fn print_repeated(entry: &RepeatedSize, units: RateUnits, color: bool) {
+    let runs = entry.runs.len();
+    println!("{} payload over {} runs", format_bytes(entry.size), runs);
+    let (Some((best, best_stats)), Some((worst, worst_stats))) =
+        (entry.best_run(), entry.worst_run())
+    else {
+        println!("  {}", color::paint("every run failed", color::RED, color));
+        return;
+    };
+    let spread =
+        |std_dev: Option<f64>| std_dev.map_or("n/a".to_string(), |d| format_rate_as(d, units));
+    println!(
+        "  Download: mean {}, std dev {}",
+        color::paint(&format_rate_as(entry.mean_download_rate(), units), color::GREEN, color),
+        spread(entry.download_rate_std_dev())
+    );
+    println!(
+        "  Upload: mean {}, std dev {}",
+        color::paint(&format_rate_as(entry.mean_upload_rate(), units), color::GREEN, color),
+        spread(entry.upload_rate_std_dev())
+    );
+    println!(
+        "  Best run {}: download {}, upload {}",
+        best + 1,
+        format_rate_as(best_stats.download_rate, units),
+        format_rate_as(best_stats.upload_rate, units)
+    );
+    println!(
+        "  Worst run {}: download {}, upload {}",
+        worst + 1,
+        format_rate_as(worst_stats.download_rate, units),
+        format_rate_as(worst_stats.upload_rate, units)
+    );
+    if entry.failed_runs() > 0 {
+        let failed = format!("Failed runs: {} of {}", entry.failed_runs(), runs);
+        println!("  {}", color::paint(&failed, color::RED, color));
+    }
+}
+
+// One object per size: the cross-run figures, then every run's own stats in order, null
+// for a run that failed
+pub fn repeated_json(entry: &RepeatedSize) -> String {
+    let optional = |value: Option<f64>| value.map_or("null".to_string(), |v| v.to_string());
+    let run_number = |run: Option<(usize, &NetworkStats)>| {
+        run.map_or("null".to_string(), |(index, _)| (index + 1).to_string())
+    };
+    let runs: Vec<String> = entry
+        .runs
+        .iter()
+        .map(|result| match result {
+            Ok(stats) => {
+                let mut obj = JsonObject::new();
+                stats.write_json_fields(&mut obj);
+                obj.finish()
+            }
+            Err(_) => "null".to_string(),
+        })
+        .collect();
+    let errors: Vec<String> =
+        entry.runs.iter().filter_map(|result| result.as_ref().err().cloned()).collect();
+    let mut obj = JsonObject::new();
+    obj.uint("size", entry.size as u64)
+        .uint("repeats", entry.runs.len() as u64)
+        .uint("failed_runs", entry.failed_runs() as u64)
+        .float("mean_download_rate", entry.mean_download_rate())
+        .raw("download_rate_std_dev", &optional(entry.download_rate_std_dev()))
+        .float("mean_upload_rate", entry.mean_upload_rate())
+        .raw("upload_rate_std_dev", &optional(entry.upload_rate_std_dev()))
+        .raw("best_run", &run_number(entry.best_run()))
+        .raw("worst_run", &run_number(entry.worst_run()))
+        .strings("errors", &errors)
+        .raw("runs", &format!("[{}]", runs.join(",")));
+    obj.finish()
+}
@@ -0,0 +1,118 @@
+// --replay: reproduces a recorded traffic pattern from a CSV schedule of
+// `delay_ms,size_bytes` lines, one request each, sent with replay_with_options. A
+// capture can be turned into one with e.g. `tshark -r capture.pcap -Y tcp.len>0 -T
+// fields -e frame.time_delta_displayed -e tcp.len -E separator=,` and scaling the
+// first column from seconds to milliseconds.
+use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use super::cli::{Config, OutputFormat};
+use super::json::JsonObject;
+use super::output::open_output;
+use super::thresholds::ThresholdsViolated;
+use super::tool::{
+    describe_run, format_bytes, print_stats, records_file, replay_with_options, transfer_options,
+    TransferOptions,
+};
+
+// (wait before sending, payload size) per request
+pub type Schedule = Vec<(Duration, usize)>;
+
+pub fn load_schedule(path: &Path) -> Result<Schedule, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    parse_schedule(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Blank lines and `#` comments are skipped, as is a header row such as
+// `delay_ms,size_bytes`. Delays may be fractional milliseconds.
+pub fn parse_schedule(text: &str) -> Result<Schedule, String> {
+    let mut schedule = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line_no = index + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some((delay, size)) = line.split_once(',') else {
+            return Err(format!("line {}: expected delay_ms,size_bytes", line_no));
+        };
+        let (delay, size) = (delay.trim(), size.trim());
+        let is_header = schedule.is_empty() && delay.parse::<f64>().is_err();
+        if is_header {
+            continue;
+        }
+        let delay = match delay.parse::<f64>() {
+            Ok(ms) if ms >= 0.0 && ms.is_finite() => Duration::from_secs_f64(ms / 1000.0),
+            _ => return Err(format!("line {}: invalid delay '{}'", line_no, delay)),
+        };
+        let size = size
+            .parse()
+            .map_err(|_| format!("line {}: invalid size '{}'", line_no, size))?;
+        schedule.push((delay, size));
+    }
+    if schedule.is_empty() {
+        return Err("no requests in the schedule".to_string());
+    }
+    Ok(schedule)
+}
+
+// Replays `path` against the configured address and reports it like one measured size.
+// Once `cancel` holds true the rest of the schedule is skipped.
+pub async fn run_replay(
+    config: &Config,
+    path: &Path,
+    cancel: Option<watch::Receiver<bool>>,
+) -> Result<(), Box<dyn Error>> {
+    let schedule = load_schedule(path)?;
+    let total: usize = schedule.iter().map(|&(_, size)| size).sum();
+    let waiting: Duration = schedule.iter().map(|&(delay, _)| delay).sum();
+    let text = config.format == OutputFormat::Text;
+    if text {
+        println!(
+            "Replaying {} requests from {} ({} in total over {:.2?} of waits)",
+            schedule.len(),
+            path.display(),
+            format_bytes(total),
+            waiting
+        );
+    }
+
+    let options = TransferOptions { cancel, ..transfer_options(config) };
+    let stats = replay_with_options(config.target_address(), &schedule, &options).await?;
+    let stats = describe_run(stats, config);
+    if let Some(path) = &config.export_records {
+        let mut out = records_file(path, config.append)?;
+        for record in &stats.records {
+            writeln!(out, "{}", record.csv_row(schedule[record.index].1))?;
+        }
+        out.flush()?;
+    }
+    if text {
+        print_stats(&stats, config.units, config.color_for(std::io::stdout().is_terminal()));
+    } else {
+        let mut out: Box<dyn Write> = match &config.output {
+            Some(path) => Box::new(open_output(Path::new(path), config.append, None)?),
+            None => Box::new(std::io::stdout()),
+        };
+        let mut obj = JsonObject::new();
+        obj.string("replay", &path.display().to_string())
+            .uint("requests", schedule.len() as u64);
+        stats.write_json_fields(&mut obj);
+        writeln!(out, "{}", obj.finish())?;
+    }
+
+    let violations: Vec<String> = config
+        .thresholds
+        .check(&stats)
+        .into_iter()
+        .map(|violation| format!("replay: {}", violation))
+        .collect();
+    if !violations.is_empty() {
+        return Err(ThresholdsViolated { violations }.into());
+    }
+    Ok(())
+}
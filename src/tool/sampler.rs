@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+
+// Counts bytes into fixed-width time windows measured from a start instant, so a
+// long transfer can be shown as a series of rates rather than one average
+#[derive(Debug, Clone)]
+pub struct BandwidthSampler {
+    start: Instant,
+    interval: Duration,
+    buckets: Vec<u64>, // bytes seen in each window, index 0 starting at `start`
+}
+
+impl BandwidthSampler {
+    pub fn new(interval: Duration) -> Self {
+        Self::starting_at(Instant::now(), interval)
+    }
+
+    // Lets several samplers share one time origin so their windows line up
+    pub fn starting_at(start: Instant, interval: Duration) -> Self {
+        BandwidthSampler {
+            start,
+            interval: interval.max(Duration::from_millis(1)),
+            buckets: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, bytes: usize) {
+        self.record_at(Instant::now(), bytes);
+    }
+
+    pub fn record_at(&mut self, at: Instant, bytes: usize) {
+        let offset = at.saturating_duration_since(self.start);
+        let index = (offset.as_nanos() / self.interval.as_nanos()) as usize;
+        if self.buckets.len() <= index {
+            self.buckets.resize(index + 1, 0);
+        }
+        self.buckets[index] += bytes as u64;
+    }
+
+    // (window start offset, bytes per second) for every window up to the last one that
+    // saw traffic; empty windows in between are kept so stalls show up as zeros
+    pub fn rates(&self) -> Vec<(Duration, f64)> {
+        let seconds = self.interval.as_secs_f64();
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(i, &bytes)| (self.interval * i as u32, bytes as f64 / seconds))
+            .collect()
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+}
@@ -0,0 +1,168 @@
+// `selftest`: a known measurement against the built-in mock server, to check that the
+// binary works on this machine before pointing it at a real target. Unlike the cargo
+// tests this goes through an installed binary, its sockets and the loopback device.
+use std::error::Error;
+use std::io::IsTerminal;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+use super::color;
+use super::mock_server::{run_mock_server, MockServerConfig, MockServerStats};
+use super::tool::{measure_transfer, NetworkStats, TransferOptions};
+
+// The known measurement: small enough to finish at once, several iterations so the
+// per-connection counts mean something
+pub const SELF_TEST_SIZE: usize = 10_000;
+pub const SELF_TEST_ITERATIONS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfTestCheck {
+    pub passed: bool,
+    pub detail: String, // what was seen and, on failure, what was expected
+}
+
+#[derive(Debug)]
+pub struct SelfTestReport {
+    pub address: SocketAddr, // where the mock server listened
+    pub checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    pub fn failures(&self) -> usize {
+        self.checks.iter().filter(|check| !check.passed).count()
+    }
+}
+
+fn check(passed: bool, detail: String) -> SelfTestCheck {
+    SelfTestCheck { passed, detail }
+}
+
+// Starts the mock server on a free loopback port, measures against it and checks the
+// results against what its half-size responses imply. Only failing to start the server
+// is an error; a failed measurement is a failed check.
+pub async fn self_test() -> Result<SelfTestReport, Box<dyn Error>> {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig::default();
+    let server_stats = config.stats.clone();
+    let (address, server) = run_mock_server(config, stop_signal).await?;
+
+    let options = TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() };
+    let result =
+        measure_transfer(&address.to_string(), SELF_TEST_SIZE, SELF_TEST_ITERATIONS, &options)
+            .await;
+    let checks = match result {
+        Ok(stats) => {
+            wait_for_requests(&server_stats, SELF_TEST_ITERATIONS as u64).await;
+            check_stats(&stats, &server_stats)
+        }
+        Err(e) => vec![check(false, format!("measurement failed: {}", e))],
+    };
+
+    let _ = stop.send(true);
+    let _ = server.await;
+    Ok(SelfTestReport { address, checks })
+}
+
+// The server counts a request once its response is out, which the client can see
+// first
+async fn wait_for_requests(stats: &MockServerStats, requests: u64) {
+    for _ in 0..100 {
+        if stats.requests() >= requests {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+fn check_stats(stats: &NetworkStats, server: &MockServerStats) -> Vec<SelfTestCheck> {
+    let sent = SELF_TEST_SIZE * SELF_TEST_ITERATIONS;
+    let received = sent / 2;
+    let expect = |seen: usize, expected: usize| {
+        if seen == expected {
+            String::new()
+        } else {
+            format!(" (expected {})", expected)
+        }
+    };
+    vec![
+        check(
+            stats.successful_iterations == SELF_TEST_ITERATIONS && stats.failed_iterations == 0,
+            format!(
+                "{} of {} iterations succeeded{}",
+                stats.successful_iterations,
+                SELF_TEST_ITERATIONS,
+                stats.errors.first().map_or(String::new(), |error| format!(": {}", error))
+            ),
+        ),
+        check(
+            stats.bytes_sent == sent,
+            format!("sent {} bytes{}", stats.bytes_sent, expect(stats.bytes_sent, sent)),
+        ),
+        check(
+            stats.bytes_received == received,
+            format!(
+                "received {} bytes, half of those sent{}",
+                stats.bytes_received,
+                expect(stats.bytes_received, received)
+            ),
+        ),
+        check_ratio(stats.ratio),
+        check(
+            stats.upload_rate > 0.0 && stats.download_rate > 0.0,
+            format!(
+                "rates are positive ({:.0} B/s up, {:.0} B/s down)",
+                stats.upload_rate, stats.download_rate
+            ),
+        ),
+        check_server(server, sent, received),
+    ]
+}
+
+// The mock server answers with half of each request
+fn check_ratio(ratio: f64) -> SelfTestCheck {
+    let passed = (ratio - 0.5).abs() < 1e-9;
+    let expected = if passed { "" } else { " (expected 0.500)" };
+    check(passed, format!("download/upload ratio {:.3}{}", ratio, expected))
+}
+
+// What the server saw has to agree with what the client counted
+fn check_server(server: &MockServerStats, sent: usize, received: usize) -> SelfTestCheck {
+    let seen = (server.bytes_read(), server.bytes_written(), server.connections());
+    let expected = (sent as u64, received as u64, SELF_TEST_ITERATIONS as u64);
+    let mut detail =
+        format!("server read {} and wrote {} bytes over {} connections", seen.0, seen.1, seen.2);
+    if seen != expected {
+        detail += &format!(" (expected {}, {} and {})", expected.0, expected.1, expected.2);
+    }
+    check(seen == expected, detail)
+}
+
+// Runs the self-test and prints a PASS or FAIL line per check and overall; any failed
+// check makes it an error so the exit status shows it
+pub async fn run_self_test() -> Result<(), Box<dyn Error>> {
+    let report = self_test().await?;
+    let on = color::wanted(std::io::stdout().is_terminal());
+    let verdict = |passed: bool| {
+        if passed {
+            color::paint("PASS", color::GREEN, on)
+        } else {
+            color::paint("FAIL", color::RED, on)
+        }
+    };
+    println!("Self-test against the mock server on {}", report.address);
+    for check in &report.checks {
+        println!("  {}  {}", verdict(check.passed), check.detail);
+    }
+    println!("{}", verdict(report.passed()));
+    if !report.passed() {
+        let checks = report.checks.len();
+        return Err(format!("{} of {} self-test checks failed", report.failures(), checks).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,272 @@
+// --soak: the same transfer repeated nonstop for a fixed time, its throughput binned
+// into windows and fitted with a straight line, to catch the slow decline of a leak or
+// of thermal throttling that a short run never lasts long enough to show
+use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::time::sleep;
+
+use super::cli::{Config, OutputFormat, RateUnits};
+use super::color;
+use super::confidence::{self, LinearTrend};
+use super::error::TransferError;
+use super::json::JsonObject;
+use super::output::open_output;
+use super::sampler::BandwidthSampler;
+use super::tool::{
+    format_bytes, format_rate_as, measure_transfer_with_progress, transfer_options,
+    IterationResult, TransferOptions,
+};
+
+#[derive(Debug, Clone)]
+pub struct SoakOptions {
+    pub duration: Duration,
+    pub window: Duration,  // width of each throughput bin
+    pub iterations: usize, // per measure_transfer call, called again until the time is up
+    // A significant decline of more than this, in percent, is flagged as degradation
+    pub max_drop_percent: f64,
+    pub transfer: TransferOptions,
+}
+
+impl Default for SoakOptions {
+    fn default() -> Self {
+        SoakOptions {
+            duration: Duration::from_secs(600),
+            window: Duration::from_secs(60),
+            iterations: 5,
+            max_drop_percent: 10.0,
+            transfer: TransferOptions::default(),
+        }
+    }
+}
+
+// What measure_soak saw: the rate in each full window and the trend through them
+#[derive(Debug, Clone, Default)]
+pub struct SoakReport {
+    pub size: usize,
+    pub window: Duration,
+    // (window start, bytes per second) of every window that ran its full width; each
+    // iteration's bytes, both ways, count in the window it finished in
+    pub windows: Vec<(Duration, f64)>,
+    pub iterations: usize,
+    pub failed_iterations: usize,
+    pub elapsed: Duration,
+    pub interrupted: bool, // stopped early through TransferOptions::cancel (Ctrl-C)
+}
+
+impl SoakReport {
+    // Least-squares line through the window rates against each window's midpoint in
+    // seconds, so the slope is in bytes per second per second; None under two windows
+    pub fn trend(&self) -> Option<LinearTrend> {
+        let half = self.window.as_secs_f64() / 2.0;
+        let points: Vec<(f64, f64)> = self
+            .windows
+            .iter()
+            .map(|&(start, rate)| (start.as_secs_f64() + half, rate))
+            .collect();
+        confidence::linear_trend(&points)
+    }
+
+    // How far the fitted line falls from the first window to the last, in percent of
+    // where it started, so one noisy window doesn't decide it; negative when it rose
+    pub fn drop_percent(&self) -> Option<f64> {
+        let trend = self.trend()?;
+        let half = self.window.as_secs_f64() / 2.0;
+        let first = trend.at(self.windows.first()?.0.as_secs_f64() + half);
+        let last = trend.at(self.windows.last()?.0.as_secs_f64() + half);
+        (first > 0.0).then(|| (first - last) / first * 100.0)
+    }
+
+    // Whether the slope is negative with its whole 95% interval below zero, which
+    // takes at least three windows
+    pub fn declining(&self) -> bool {
+        self.trend().is_some_and(|trend| {
+            trend.slope_margin_95.is_some_and(|margin| trend.slope + margin < 0.0)
+        })
+    }
+
+    // A significant decline of more than `max_drop_percent`
+    pub fn degraded(&self, max_drop_percent: f64) -> bool {
+        self.declining() && self.drop_percent().is_some_and(|drop| drop > max_drop_percent)
+    }
+}
+
+// Measures `size`-byte iterations, `options.iterations` at a time, until
+// `options.duration` is up or `options.transfer.cancel` fires. A batch that fails
+// outright is counted and followed by another after a short pause, so a passing outage
+// doesn't end the run; other errors, such as a bad address, do. Fails with the last
+// error when no iteration succeeded at all.
+pub async fn measure_soak(
+    address: &str,
+    size: usize,
+    options: &SoakOptions,
+) -> Result<SoakReport, TransferError> {
+    let start = Instant::now();
+    let mut sampler = BandwidthSampler::starting_at(start, options.window);
+    let mut report = SoakReport { size, window: sampler.interval(), ..SoakReport::default() };
+    let mut last_error = None;
+    let cancelled = || options.transfer.cancel.as_ref().is_some_and(|cancel| *cancel.borrow());
+    while start.elapsed() < options.duration {
+        if cancelled() {
+            report.interrupted = true;
+            break;
+        }
+        let remaining = options.duration.saturating_sub(start.elapsed());
+        let transfer =
+            TransferOptions { max_duration: Some(remaining), ..options.transfer.clone() };
+        let record = |iteration: &IterationResult| {
+            if iteration.error.is_none() {
+                sampler.record(iteration.bytes_sent + iteration.bytes_received);
+            }
+        };
+        match measure_transfer_with_progress(address, size, options.iterations, &transfer, record)
+            .await
+        {
+            Ok(stats) => {
+                report.iterations += stats.successful_iterations;
+                report.failed_iterations += stats.failed_iterations;
+                report.interrupted |= stats.interrupted;
+            }
+            Err(e @ TransferError::AllFailed { .. }) => {
+                if let TransferError::AllFailed { iterations, .. } = &e {
+                    report.failed_iterations += iterations;
+                }
+                last_error = Some(e);
+                sleep(options.transfer.delay.max(Duration::from_millis(100))).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    report.elapsed = start.elapsed();
+    if report.iterations == 0 {
+        if let Some(e) = last_error {
+            return Err(e);
+        }
+    }
+
+    // Only full windows count, since the last one is cut short by the end of the run and
+    // would read as a drop; windows after the last traffic were stalls and stay zeros
+    let full = (report.elapsed.as_nanos() / report.window.as_nanos()) as usize;
+    let mut windows = sampler.rates();
+    windows.truncate(full);
+    while windows.len() < full {
+        windows.push((report.window * windows.len() as u32, 0.0));
+    }
+    report.windows = windows;
+    Ok(report)
+}
+
+// Runs the --soak measurement with the largest of the configured sizes, the one whose
+// throughput depends on the link the most, and reports the trend
+pub async fn run_soak(
+    config: &Config,
+    duration: Duration,
+    cancel: Option<watch::Receiver<bool>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut options = SoakOptions {
+        duration,
+        window: config.soak_window.unwrap_or(duration / 10),
+        iterations: config.iterations,
+        transfer: TransferOptions { cancel, ..transfer_options(config) },
+        ..SoakOptions::default()
+    };
+    if let Some(max_drop) = config.soak_max_drop {
+        options.max_drop_percent = max_drop;
+    }
+    let size = config.sizes.iter().copied().max().unwrap_or_default();
+    let text = config.format == OutputFormat::Text;
+    if text {
+        println!(
+            "Soak: {} payload for {:?}, in {:?} windows",
+            format_bytes(size),
+            options.duration,
+            options.window
+        );
+    }
+    let report = measure_soak(config.target_address(), size, &options).await?;
+    if text {
+        let color = config.color_for(std::io::stdout().is_terminal());
+        print_soak(&report, options.max_drop_percent, config.units, color);
+        return Ok(());
+    }
+    let mut out: Box<dyn Write> = match &config.output {
+        Some(path) => Box::new(open_output(Path::new(path), config.append, None)?),
+        None => Box::new(std::io::stdout()),
+    };
+    writeln!(out, "{}", soak_json(&report, options.max_drop_percent))?;
+    Ok(())
+}
+
+pub fn print_soak(report: &SoakReport, max_drop_percent: f64, units: RateUnits, color: bool) {
+    if report.interrupted {
+        println!("  Interrupted after {:.1?}", report.elapsed);
+    }
+    for &(start, rate) in &report.windows {
+        println!("  {:>7.1}s: {}", start.as_secs_f64(), format_rate_as(rate, units));
+    }
+    println!(
+        "  Iterations: {} successful, {} failed",
+        report.iterations, report.failed_iterations
+    );
+    let (Some(trend), Some(drop)) = (report.trend(), report.drop_percent()) else {
+        println!("  Trend: too few full windows to fit one");
+        return;
+    };
+    let per_hour = |slope: f64| {
+        let sign = if slope < 0.0 { "-" } else { "+" };
+        format!("{}{} per hour", sign, format_rate_as(slope.abs() * 3600.0, units))
+    };
+    let margin = trend.slope_margin_95.map_or(String::new(), |margin| {
+        format!(" (\u{b1}{} at 95%)", format_rate_as(margin * 3600.0, units))
+    });
+    println!(
+        "  Trend: {}{}, {:.1}% {} at the end than at the start",
+        per_hour(trend.slope),
+        margin,
+        drop.abs(),
+        if drop >= 0.0 { "lower" } else { "higher" }
+    );
+    if report.degraded(max_drop_percent) {
+        let warning = format!(
+            "Warning: throughput is declining, {:.1}% over the run, more than the {}% allowed",
+            drop, max_drop_percent
+        );
+        println!("  {}", color::paint(&warning, color::RED, color));
+    } else if report.declining() {
+        let declining = format!(
+            "Throughput is declining, but by no more than the {}% allowed",
+            max_drop_percent
+        );
+        println!("  {}", color::paint(&declining, color::YELLOW, color));
+    } else {
+        println!("  {}", color::paint("No significant decline", color::GREEN, color));
+    }
+}
+
+// One object: the windows as [start_secs, rate] pairs, then the fitted trend
+pub fn soak_json(report: &SoakReport, max_drop_percent: f64) -> String {
+    let optional = |value: Option<f64>| value.map_or("null".to_string(), |v| v.to_string());
+    let windows: Vec<String> = report
+        .windows
+        .iter()
+        .map(|&(start, rate)| format!("[{},{}]", start.as_secs_f64(), rate))
+        .collect();
+    let trend = report.trend();
+    let mut obj = JsonObject::new();
+    obj.uint("size", report.size as u64)
+        .float("elapsed_secs", report.elapsed.as_secs_f64())
+        .float("window_secs", report.window.as_secs_f64())
+        .raw("windows", &format!("[{}]", windows.join(",")))
+        .uint("iterations", report.iterations as u64)
+        .uint("failed_iterations", report.failed_iterations as u64)
+        .raw("slope", &optional(trend.map(|trend| trend.slope)))
+        .raw("slope_margin_95", &optional(trend.and_then(|trend| trend.slope_margin_95)))
+        .raw("drop_percent", &optional(report.drop_percent()))
+        .raw("declining", &report.declining().to_string())
+        .raw("degraded", &report.degraded(max_drop_percent).to_string())
+        .float("max_drop_percent", max_drop_percent)
+        .raw("interrupted", &report.interrupted.to_string());
+    obj.finish()
+}
@@ -0,0 +1,201 @@
+// Dialing through a SOCKS5 proxy (RFC 1928), optionally with username/password
+// authentication (RFC 1929). Only CONNECT is spoken: after the handshake the stream
+// carries the measurement as if it were connected to the target directly, so the
+// stats count the same payload bytes; the few handshake bytes aren't counted.
+use std::io;
+use std::net::IpAddr;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::error::TransferError;
+
+const VERSION: u8 = 5;
+const NO_AUTH: u8 = 0x00;
+const USER_PASSWORD: u8 = 0x02;
+const NO_ACCEPTABLE_METHOD: u8 = 0xff;
+const CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocksConfig {
+    pub proxy: String, // host:port of the SOCKS5 server
+    // Offered alongside no authentication when set; the proxy picks
+    pub auth: Option<SocksAuth>,
+}
+
+impl SocksConfig {
+    pub fn new(proxy: &str) -> Self {
+        SocksConfig { proxy: proxy.to_string(), auth: None }
+    }
+}
+
+// RFC 1929 credentials, each 1 to 255 bytes
+#[derive(Clone, PartialEq, Eq)]
+pub struct SocksAuth {
+    pub username: String,
+    pub password: String,
+}
+
+// Keeps the password out of logs and error messages
+impl std::fmt::Debug for SocksAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SocksAuth").field("username", &self.username).finish_non_exhaustive()
+    }
+}
+
+// What the proxy's reply code means, for error messages
+pub fn reply_message(code: u8) -> &'static str {
+    match code {
+        0x01 => "general SOCKS server failure",
+        0x02 => "connection not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "TTL expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown reply",
+    }
+}
+
+// Asks the proxy on the other end of `stream` to connect to `target` (host:port). A
+// host name is passed on for the proxy to resolve, so lookups happen on its side of
+// the tunnel. I/O errors and replies that aren't SOCKS5 are ProxyConnect errors, as
+// the proxy itself is at fault; a rejected login is ProxyAuth and a target the proxy
+// couldn't reach is ProxyRefused.
+pub async fn handshake<S>(
+    stream: &mut S,
+    target: &str,
+    auth: Option<&SocksAuth>,
+) -> Result<(), TransferError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let request = connect_request(target)?;
+    if let Some(auth) = auth {
+        let fits = |field: &str| (1..=255).contains(&field.len());
+        if !fits(&auth.username) || !fits(&auth.password) {
+            return Err(TransferError::InvalidOptions(
+                "SOCKS5 username and password must each be 1 to 255 bytes".to_string(),
+            ));
+        }
+    }
+    negotiate(stream, auth).await?;
+
+    stream.write_all(&request).await.map_err(TransferError::ProxyConnect)?;
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await.map_err(TransferError::ProxyConnect)?;
+    if reply[0] != VERSION {
+        return Err(not_socks5(reply[0]));
+    }
+    if reply[1] != 0 {
+        return Err(TransferError::ProxyRefused { code: reply[1] });
+    }
+    // The address the proxy connected from, which the measurement has no use for
+    let bound_len = match reply[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => stream.read_u8().await.map_err(TransferError::ProxyConnect)? as usize,
+        other => {
+            return Err(TransferError::ProxyConnect(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("proxy replied with unknown address type {}", other),
+            )))
+        }
+    };
+    let mut bound = vec![0u8; bound_len + 2];
+    stream.read_exact(&mut bound).await.map_err(TransferError::ProxyConnect)?;
+    Ok(())
+}
+
+// Method selection, then RFC 1929 authentication if the proxy chose it
+async fn negotiate<S>(stream: &mut S, auth: Option<&SocksAuth>) -> Result<(), TransferError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let greeting: &[u8] = match auth {
+        Some(_) => &[VERSION, 2, NO_AUTH, USER_PASSWORD],
+        None => &[VERSION, 1, NO_AUTH],
+    };
+    stream.write_all(greeting).await.map_err(TransferError::ProxyConnect)?;
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice).await.map_err(TransferError::ProxyConnect)?;
+    if choice[0] != VERSION {
+        return Err(not_socks5(choice[0]));
+    }
+    match (choice[1], auth) {
+        (NO_AUTH, _) => Ok(()),
+        (USER_PASSWORD, Some(auth)) => authenticate(stream, auth).await,
+        (NO_ACCEPTABLE_METHOD, None) => Err(TransferError::ProxyAuth(
+            "proxy requires authentication; pass a username and password".to_string(),
+        )),
+        (NO_ACCEPTABLE_METHOD, Some(_)) => Err(TransferError::ProxyAuth(
+            "proxy accepts neither username/password nor no authentication".to_string(),
+        )),
+        (other, _) => Err(TransferError::ProxyConnect(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("proxy chose authentication method {}, which wasn't offered", other),
+        ))),
+    }
+}
+
+async fn authenticate<S>(stream: &mut S, auth: &SocksAuth) -> Result<(), TransferError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut request = vec![1u8];
+    for field in [&auth.username, &auth.password] {
+        request.push(field.len() as u8);
+        request.extend_from_slice(field.as_bytes());
+    }
+    stream.write_all(&request).await.map_err(TransferError::ProxyConnect)?;
+    let mut status = [0u8; 2];
+    stream.read_exact(&mut status).await.map_err(TransferError::ProxyConnect)?;
+    if status[1] != 0 {
+        return Err(TransferError::ProxyAuth(format!(
+            "proxy rejected the credentials for '{}'",
+            auth.username
+        )));
+    }
+    Ok(())
+}
+
+// VER CMD RSV ATYP DST.ADDR DST.PORT
+fn connect_request(target: &str) -> Result<Vec<u8>, TransferError> {
+    let invalid = |reason: &str| TransferError::InvalidAddress {
+        address: target.to_string(),
+        reason: reason.to_string(),
+    };
+    let (host, port) = target.rsplit_once(':').ok_or_else(|| invalid("missing port"))?;
+    let port: u16 = port.parse().map_err(|_| invalid("invalid port"))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let mut request = vec![VERSION, CONNECT, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(ATYP_IPV4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(ATYP_IPV6);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) if host.is_empty() => return Err(invalid("missing host")),
+        Err(_) if host.len() > 255 => return Err(invalid("host name is over 255 bytes")),
+        Err(_) => {
+            request.extend_from_slice(&[ATYP_DOMAIN, host.len() as u8]);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    Ok(request)
+}
+
+fn not_socks5(version: u8) -> TransferError {
+    TransferError::ProxyConnect(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("proxy answered with version {}, not SOCKS5", version),
+    ))
+}
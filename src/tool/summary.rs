@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+use super::cli::RateUnits;
+use super::json::JsonObject;
+use super::tool::{
+    format_bytes, format_measured_rate, format_rate_as, format_rate_bits, NetworkStats,
+    PhaseBreakdown,
+};
+
+// Share of a link of `link_bandwidth` bits per second that `rate` bytes per second
+// uses, e.g. 0.72 for 90 MB/s on a 1 Gbps link
+pub fn link_utilization(rate: f64, link_bandwidth: f64) -> f64 {
+    rate * 8.0 / link_bandwidth
+}
+
+// "72.0% of 1.00 Gbps down, 35.1% up"
+pub fn utilization_line(upload_rate: f64, download_rate: f64, link_bandwidth: f64) -> String {
+    format!(
+        "{:.1}% of {} down, {:.1}% up",
+        link_utilization(download_rate, link_bandwidth) * 100.0,
+        format_rate_bits(link_bandwidth / 8.0),
+        link_utilization(upload_rate, link_bandwidth) * 100.0
+    )
+}
+
+// "connect 120.00µs (4.0%), send 1.50ms (50.0%), wait 900.00µs (30.0%), receive ..."
+pub fn phase_line(phases: &PhaseBreakdown) -> String {
+    let names = ["connect", "send", "wait", "receive"];
+    let times = [phases.connect, phases.send, phases.wait, phases.receive];
+    let shares = phases.shares();
+    (0..4)
+        .map(|i| format!("{} {:.2?} ({:.1}%)", names[i], times[i], shares[i] * 100.0))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Grand totals over every measured size of a sweep
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RunSummary {
+    pub sizes: usize,
+    pub bytes_sent: usize,
+    pub bytes_received: usize,
+    pub elapsed_time: Duration, // the sizes' elapsed_time added up
+    // Total bytes over total time, i.e. each size's rate weighted by the bytes it moved
+    // (as a harmonic mean), so one fast small size can't dominate the average
+    pub upload_rate: f64,
+    pub download_rate: f64,
+    // The sizes' phases averaged over all of their iterations, so each size counts by
+    // how many iterations it ran
+    pub phases: PhaseBreakdown,
+}
+
+impl RunSummary {
+    // None when nothing was measured
+    pub fn from_stats<'a>(stats: impl IntoIterator<Item = &'a NetworkStats>) -> Option<Self> {
+        let mut summary = RunSummary::default();
+        let mut phase_totals = [Duration::ZERO; 4];
+        let mut iterations = 0;
+        for stats in stats {
+            summary.sizes += 1;
+            summary.bytes_sent += stats.bytes_sent;
+            summary.bytes_received += stats.bytes_received;
+            summary.elapsed_time += stats.elapsed_time;
+            let ran = (stats.successful_iterations + stats.failed_iterations) as u32;
+            let phases = &stats.phases;
+            for (total, phase) in phase_totals
+                .iter_mut()
+                .zip([phases.connect, phases.send, phases.wait, phases.receive])
+            {
+                *total += phase * ran;
+            }
+            iterations += ran;
+        }
+        if summary.sizes == 0 {
+            return None;
+        }
+        if iterations > 0 {
+            let [connect, send, wait, receive] = phase_totals.map(|total| total / iterations);
+            summary.phases = PhaseBreakdown { connect, send, wait, receive };
+        }
+        let secs = summary.elapsed_time.as_secs_f64();
+        if secs > 0.0 {
+            summary.upload_rate = summary.bytes_sent as f64 / secs;
+            summary.download_rate = summary.bytes_received as f64 / secs;
+        }
+        Some(summary)
+    }
+
+    pub fn write_json_fields(&self, obj: &mut JsonObject) {
+        obj.uint("sizes", self.sizes as u64)
+            .uint("bytes_sent", self.bytes_sent as u64)
+            .uint("bytes_received", self.bytes_received as u64)
+            .float("elapsed_secs", self.elapsed_time.as_secs_f64())
+            .float("upload_rate", self.upload_rate)
+            .float("download_rate", self.download_rate)
+            .float("phase_connect_secs", self.phases.connect.as_secs_f64())
+            .float("phase_send_secs", self.phases.send.as_secs_f64())
+            .float("phase_wait_secs", self.phases.wait.as_secs_f64())
+            .float("phase_receive_secs", self.phases.receive.as_secs_f64());
+    }
+
+    // "Moved 1.50 MiB up and 768.00 KiB down in 1.20s (1.25 MiB/s up, 640.00 KiB/s down)"
+    pub fn headline(&self, units: RateUnits) -> String {
+        format!(
+            "Moved {} up and {} down in {:.2?} ({} up, {} down)",
+            format_bytes(self.bytes_sent),
+            format_bytes(self.bytes_received),
+            self.elapsed_time,
+            format_rate_as(self.upload_rate, units),
+            format_rate_as(self.download_rate, units)
+        )
+    }
+}
+
+// One row per size and a Total row, inside a border drawn with box characters when
+// `fancy` and with +-| otherwise, as for the rate chart
+pub fn render_summary_table(
+    results: &[(usize, NetworkStats)],
+    units: RateUnits,
+    fancy: bool,
+) -> String {
+    let Some(summary) = RunSummary::from_stats(results.iter().map(|(_, stats)| stats)) else {
+        return String::new();
+    };
+    let row = |label: String, sent, received, elapsed: Duration, upload, download| {
+        [
+            label,
+            format_bytes(sent),
+            format_bytes(received),
+            format!("{:.2?}", elapsed),
+            format_measured_rate(upload, elapsed, units),
+            format_measured_rate(download, elapsed, units),
+        ]
+    };
+    let header = ["Size", "Sent", "Received", "Time", "Upload", "Download"].map(String::from);
+    let rows: Vec<[String; 6]> = results
+        .iter()
+        .map(|(size, s)| {
+            row(
+                format_bytes(*size),
+                s.bytes_sent,
+                s.bytes_received,
+                s.elapsed_time,
+                s.upload_rate,
+                s.download_rate,
+            )
+        })
+        .collect();
+    let total = row(
+        "Total".to_string(),
+        summary.bytes_sent,
+        summary.bytes_received,
+        summary.elapsed_time,
+        summary.upload_rate,
+        summary.download_rate,
+    );
+
+    let mut widths = header.clone().map(|h| h.chars().count());
+    for cells in rows.iter().chain([&total]) {
+        for (width, cell) in widths.iter_mut().zip(cells) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let (horizontal, vertical) = if fancy { ("─", "│") } else { ("-", "|") };
+    // Corners and junctions for the top, middle and bottom rules
+    let corners = if fancy {
+        [["┌", "┬", "┐"], ["├", "┼", "┤"], ["└", "┴", "┘"]]
+    } else {
+        [["+", "+", "+"]; 3]
+    };
+    let rule = |[left, middle, right]: [&str; 3]| {
+        let segments: Vec<String> = widths.iter().map(|w| horizontal.repeat(w + 2)).collect();
+        format!("  {}{}{}\n", left, segments.join(middle), right)
+    };
+    let line = |cells: &[String; 6]| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(column, (cell, width))| {
+                // The size label reads best left-aligned, numbers right-aligned
+                if column == 0 {
+                    format!(" {:<width$} ", cell)
+                } else {
+                    format!(" {:>width$} ", cell)
+                }
+            })
+            .collect();
+        format!("  {}{}{}\n", vertical, cells.join(vertical), vertical)
+    };
+
+    let mut out = rule(corners[0]);
+    out.push_str(&line(&header));
+    out.push_str(&rule(corners[1]));
+    for cells in &rows {
+        out.push_str(&line(cells));
+    }
+    out.push_str(&rule(corners[1]));
+    out.push_str(&line(&total));
+    out.push_str(&rule(corners[2]));
+    out
+}
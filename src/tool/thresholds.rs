@@ -0,0 +1,71 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use super::tool::{format_rate, NetworkStats};
+
+// Limits a run must stay within, checked against the computed stats. Unset limits
+// are not checked.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Thresholds {
+    pub min_download_rate: Option<f64>, // bytes per second
+    pub max_ratio: Option<f64>,
+    pub max_p99_latency: Option<Duration>,
+}
+
+impl Thresholds {
+    pub fn is_empty(&self) -> bool {
+        *self == Thresholds::default()
+    }
+
+    // One message per limit `stats` breaks, naming the limit and the measured value
+    pub fn check(&self, stats: &NetworkStats) -> Vec<String> {
+        let mut violations = Vec::new();
+        if let Some(min) = self.min_download_rate {
+            if stats.download_rate < min {
+                violations.push(format!(
+                    "download rate {} is below --min-download-rate {}",
+                    format_rate(stats.download_rate),
+                    format_rate(min)
+                ));
+            }
+        }
+        if let Some(max) = self.max_ratio {
+            if stats.ratio > max {
+                violations.push(format!(
+                    "ratio {:.2} is above --max-ratio {:.2}",
+                    stats.ratio, max
+                ));
+            }
+        }
+        if let Some(max) = self.max_p99_latency {
+            let p99 = stats.p99_latency();
+            if p99 > max {
+                violations.push(format!(
+                    "p99 latency {:.2?} is above --max-p99-latency {:.2?}",
+                    p99, max
+                ));
+            }
+        }
+        violations
+    }
+}
+
+// Returned by a run whose results broke at least one threshold, so the binary can
+// exit non-zero
+#[derive(Debug)]
+pub struct ThresholdsViolated {
+    pub violations: Vec<String>, // prefixed with the payload size they happened at
+}
+
+impl fmt::Display for ThresholdsViolated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} threshold(s) violated", self.violations.len())?;
+        for violation in &self.violations {
+            write!(f, "\n  {}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ThresholdsViolated {}
@@ -1,187 +1,4130 @@
 use std::error::Error;
-use std::time::{Duration, Instant};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
-use tokio::time::sleep;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, IsTerminal, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpSocket, TcpStream, UdpSocket};
+use tokio::sync::{watch, Barrier};
+use tokio::task::JoinSet;
+use tokio::time::{sleep, timeout};
+
+use super::address::validate_address;
+use super::chart::render_rate_chart;
+use super::cli::{Config, OutputFormat, RateUnits, RunMode};
+use super::clock::{Clock, SystemClock};
+use super::color;
+use super::dedup::run_dedup;
+use super::error::TransferError;
+use super::framing;
+use super::goodput::{estimate_goodput, goodput_json, print_goodput};
+use super::happy_eyeballs::{connect_first, DEFAULT_HEAD_START};
+use super::http::{measure_http, HttpOptions};
+use super::metrics::{serve_metrics, Metrics, DEFAULT_METRICS_BIND};
+use super::keepalive::run_keepalive;
+use super::load::run_under_load;
+use super::mock_server::{run_mock_server, MockServerConfig};
+use super::monitor::{parse_utc_timestamp, run_watch, utc_timestamp};
+use super::mpc::{monitor_mpc_measured, monitor_mpc_simulation, MpcOverheadModel};
+use super::compare::StatsDiff;
+use super::confidence;
+use super::cpu;
+use super::json::{self, quote, JsonObject, JsonValue};
+use super::estimate::print_dry_run;
+use super::multi::run_hosts;
+#[cfg(feature = "otlp")]
+use super::otlp::OtlpExporter;
+#[cfg(feature = "sqlite")]
+use super::db::RunDatabase;
+use super::mix::{run_mix, SizeMix};
+use super::output::open_output;
+use super::parallel::run_parallel;
+use super::payload::{Filler, Generated, PayloadSource, SplitMix64, DEFAULT_SEED};
+use super::log::{debug, error, info, warning};
+use super::progress::Progress;
+use super::ramp::run_ramp;
+use super::repeat::run_repeat;
+use super::replay::run_replay;
+use super::soak::run_soak;
+use super::thresholds::ThresholdsViolated;
+use super::sampler::BandwidthSampler;
+use super::socks::{self, SocksConfig};
+use super::summary::{
+    link_utilization, phase_line, render_summary_table, utilization_line, RunSummary,
+};
+use super::verify;
+
+// How long measure_transfer_udp waits for an echo before treating the datagram as lost
+const UDP_ECHO_TIMEOUT: Duration = Duration::from_secs(1);
+// Each UDP datagram opens with its iteration's number, big-endian
+const UDP_SEQUENCE_LEN: usize = 8;
+
+// Write size for measure_stream; small enough that each write lands in one window
+const STREAM_CHUNK: usize = 64 * 1024;
+
+// Bounds of NetworkStats::suggested_socket_buffer: below 64 KiB the OS default already
+// does, and --send-buffer takes no more than 2 GiB
+const MIN_SUGGESTED_SOCKET_BUFFER: usize = 64 * 1024;
+const MAX_SUGGESTED_SOCKET_BUFFER: usize = 1 << 31;
+
+// Where an iteration's time goes on average, over every iteration measured, failed ones
+// included: connecting (resolve and retries too), writing the request, waiting for the
+// first bytes of the response and reading the rest. A reused connection's connect
+// is shared out over its iterations; think time between requests is in no phase.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PhaseBreakdown {
+    pub connect: Duration,
+    pub send: Duration,
+    pub wait: Duration,
+    pub receive: Duration,
+}
+
+impl PhaseBreakdown {
+    pub fn total(&self) -> Duration {
+        self.connect + self.send + self.wait + self.receive
+    }
+
+    // Each phase's share of total(), as connect, send, wait, receive; zeros when
+    // nothing was timed
+    pub fn shares(&self) -> [f64; 4] {
+        let total = self.total().as_secs_f64();
+        [self.connect, self.send, self.wait, self.receive].map(|phase| {
+            if total > 0.0 {
+                phase.as_secs_f64() / total
+            } else {
+                0.0
+            }
+        })
+    }
+}
+
+// How evenly the iterations run_concurrent kept in flight shared the link, from each
+// successful iteration's bytes over its own round trip
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IterationFairness {
+    pub index: f64,    // Jain's index of the per-iteration rates
+    pub min_rate: f64, // bytes per second, of the slowest iteration
+    pub max_rate: f64, // bytes per second, of the fastest
+}
+
+impl IterationFairness {
+    // None with fewer than two rates, or only zeros
+    pub fn of(rates: &[f64]) -> Option<IterationFairness> {
+        if rates.len() < 2 {
+            return None;
+        }
+        Some(IterationFairness {
+            index: confidence::jain_index(rates)?,
+            min_rate: rates.iter().copied().fold(f64::INFINITY, f64::min),
+            max_rate: rates.iter().copied().fold(0.0, f64::max),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct NetworkStats {
+    pub bytes_sent: usize,
+    pub bytes_received: usize,
+    // Time spent transferring, excluding inter-iteration delays and connecting
+    pub elapsed_time: Duration,
+    pub upload_rate: f64,   // bytes per second
+    pub download_rate: f64, // bytes per second
+    pub ratio: f64,         // download/upload ratio
+    pub successful_iterations: usize,
+    pub failed_iterations: usize,
+    pub corrupted_iterations: usize, // failed iterations whose response broke the --verify pattern
+    // Payload bytes that went out in iterations whose write then failed; not part of
+    // bytes_sent or the rates
+    pub bytes_written_before_failure: usize,
+    pub errors: Vec<String>, // one message per failed iteration
+    pub retries: usize,      // connect attempts repeated under TransferOptions::retry
+    // Reads that found the connection closed before the response was complete, those
+    // retried under TransferOptions::eof_retries included
+    pub premature_eofs: usize,
+    // Failed iterations whose connection the peer reset while sending or reading
+    // (TransferError::ConnectionReset); many of them often mean an overloaded server
+    pub connection_resets: usize,
+    // Failed iterations that never got connected: refused, unreachable or timed out
+    pub failed_connects: usize,
+    // The iterations run_concurrent kept in flight after running out of file descriptors
+    // (TransferError::OutOfFileDescriptors); None when it never had to cut back
+    pub throttled_concurrency: Option<usize>,
+    // The most streams an HTTP/2 run (measure_http2) had open at once over its one
+    // connection; None for every other kind of measurement
+    pub http2_streams: Option<usize>,
+    // Of concurrent iterations (--concurrency or --burst); None for one at a time
+    pub iteration_fairness: Option<IterationFairness>,
+    pub dns_resolution_time: Duration, // resolving the target, summed over every connect
+    // Connecting (resolve, handshake and retry backoff), summed over every connect. It
+    // is left out of elapsed_time so tiny payloads' rates aren't mostly handshake.
+    pub connect_time: Duration,
+    pub mean_connect_time: Duration, // connect_time per connect
+    // Connections opened, failed attempts included; fewer than the iterations when
+    // connections were reused
+    pub connections: usize,
+    // Of the connections made, those to an IPv4 and to an IPv6 address. A name with
+    // both kinds of address connects over whichever answers first.
+    pub ipv4_connections: usize,
+    pub ipv6_connections: usize,
+    // Source IP of the connections, the latest one's when there were several; the one
+    // TransferOptions::bind_addr asked for, or the one the OS routed through. None for
+    // HTTP, UDP and anything else that doesn't go through measure_transfer's connect.
+    pub local_address: Option<IpAddr>,
+    pub min_latency: Duration,
+    pub max_latency: Duration,
+    pub mean_latency: Duration,
+    pub jitter: Duration, // standard deviation of the latencies; zero with fewer than two
+    // Mean wait from a request's last byte going out to its response's first bytes (the
+    // frame header) coming in: server think time plus one network round trip. Zero when
+    // no response was read, as with upload-only requests.
+    pub time_to_first_byte: Duration,
+    pub phases: PhaseBreakdown,
+    pub latencies: Vec<Duration>, // round trip of each successful iteration, in order
+    // Time from each measured iteration's start to the next one's, failures included,
+    // read through TransferOptions::clock. Sequentially that's one round trip plus
+    // `delay`, so the spread shows scheduler jitter and whether the delay was kept;
+    // concurrently it's how fast finished slots were refilled. Only measure_transfer's
+    // iteration loops record it; empty for HTTP, UDP, replay, mixes and JSON read back.
+    pub start_gaps: Vec<Duration>,
+    pub response_sizes: Vec<usize>, // bytes received by each successful iteration, in order
+    pub seed: Option<u64>, // what generated a random payload; None for other payloads
+    // Requests each iteration sent over its connection (TransferOptions); the bytes and
+    // latency of an iteration cover all of them
+    pub requests_per_connection: usize,
+    // Stopped early through TransferOptions::cancel (Ctrl-C); the numbers cover the
+    // iterations that finished
+    pub interrupted: bool,
+    // Stopped early because TransferOptions::max_duration ran out; likewise the numbers
+    // cover the iterations that finished
+    pub time_limited: bool,
+    // All iterations started at once under TransferOptions::burst, so min_latency and
+    // max_latency are the best and worst the server managed with all of them arriving
+    pub burst: bool,
+    // Every measured iteration, failures included, when TransferOptions::keep_records
+    // is set; otherwise empty. Not part of the JSON output.
+    pub records: Vec<IterationRecord>,
+    // Measured iterations when TransferOptions::auto_iterations chose the count
+    pub auto_iterations: Option<usize>,
+    // Measured iterations it took to send TransferOptions::total_bytes
+    pub total_bytes_iterations: Option<usize>,
+    // Cores this process kept busy on average while transferring (mock server included
+    // when it runs in-process); None off Linux or for transfers under 100ms
+    pub cpu_utilization: Option<f64>,
+    // TransferOptions::write_chunk_size the requests were written in
+    pub write_chunk_size: Option<usize>,
+    // Upload rate of the same measurement in single writes, when run_benchmarks took
+    // one to compare write_chunk_size against
+    pub single_write_upload_rate: Option<f64>,
+    // What produced the numbers, so a saved result describes itself: Config::label, when
+    // the measurement finished and Config::summary(). measure_transfer and the other
+    // library entry points only stamp the time; the label and summary come from a Config.
+    pub label: Option<String>,
+    pub timestamp: Option<SystemTime>,
+    pub config_summary: String,
+}
+
+// Buckets in NetworkStats::start_gap_histogram
+pub const START_GAP_BUCKETS: usize = 10;
+
+// Shorter transfers mostly measure the timer and scheduling, not the network
+pub const MIN_MEASURABLE_TIME: Duration = Duration::from_millis(1);
+
+// Version of the JSON that write_json_fields produces, saved as `schema_version`. Files
+// written before it existed count as version 1. Adding a key doesn't need a new version,
+// since from_json defaults missing keys; renaming or reinterpreting one does, and
+// from_json then upgrades the older versions to the current one before reading them.
+pub const STATS_SCHEMA_VERSION: u64 = 2;
+
+// The fields every version has written, which from_json requires
+const STATS_REQUIRED_FIELDS: [&str; 6] =
+    ["bytes_sent", "bytes_received", "elapsed_secs", "upload_rate", "download_rate", "ratio"];
+
+// Percentile `p` (0-100) of `samples`, linearly interpolated between the two nearest;
+// zero when there are none
+fn percentile(samples: &[Duration], p: f64) -> Duration {
+    let mut samples = samples.to_vec();
+    match samples.len() {
+        0 => Duration::ZERO,
+        1 => samples[0],
+        n => {
+            let rank = (p.clamp(0.0, 100.0) / 100.0) * (n - 1) as f64;
+            let lower = rank.floor() as usize;
+            let weight = rank - lower as f64;
+            // Only the two samples around `rank` have to be in sorted position, which
+            // selection finds in linear time where a full sort was n log n per call
+            let (_, low, above) = samples.select_nth_unstable(lower);
+            let low = low.as_secs_f64();
+            let high = match above.iter().min() {
+                Some(next) if weight > 0.0 => next.as_secs_f64(),
+                _ => low,
+            };
+            Duration::from_secs_f64(low + (high - low) * weight)
+        }
+    }
+}
+
+// Bytes per second over `elapsed`; zero rather than inf or NaN when no time passed,
+// as with a scripted clock or an instant loopback run
+fn rate_over(bytes: usize, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        0.0
+    } else {
+        bytes as f64 / elapsed.as_secs_f64()
+    }
+}
+
+// Duration::MAX for a rate of zero, or one that isn't a number
+fn time_at_rate(bytes: usize, rate: f64) -> Duration {
+    if rate > 0.0 && rate.is_finite() {
+        Duration::try_from_secs_f64(bytes as f64 / rate).unwrap_or(Duration::MAX)
+    } else {
+        Duration::MAX
+    }
+}
+
+// A version 1 object as version 2: each field version 2 writes that `value` lacks, bar
+// the required ones, takes what an empty run writes, though with one request per
+// connection rather than none
+fn upgrade_stats_v1(value: &JsonValue) -> JsonValue {
+    let empty = NetworkStats { requests_per_connection: 1, ..NetworkStats::default() };
+    let fields = |value: JsonValue| match value {
+        JsonValue::Object(fields) => fields,
+        _ => Vec::new(),
+    };
+    let mut upgraded = fields(value.clone());
+    upgraded.retain(|(key, _)| key != "schema_version");
+    for (key, default) in fields(json::parse(&empty.to_json()).unwrap_or(JsonValue::Null)) {
+        if value.get(&key).is_none() && !STATS_REQUIRED_FIELDS.contains(&key.as_str()) {
+            upgraded.push((key, default));
+        }
+    }
+    JsonValue::Object(upgraded)
+}
+
+// The `schema_version` of a saved stats object, 1 when it has none
+pub fn stats_schema_version(value: &JsonValue) -> Result<u64, String> {
+    match value.get("schema_version") {
+        None => Ok(1),
+        Some(JsonValue::Number(n)) if *n >= 1.0 && n.fract() == 0.0 => Ok(*n as u64),
+        Some(_) => Err("schema_version must be a whole number of at least 1".to_string()),
+    }
+}
+
+// Each iteration's response bytes over its round trip; zero-length round trips are
+// left out
+fn rate_samples(response_sizes: &[usize], latencies: &[Duration]) -> Vec<f64> {
+    response_sizes
+        .iter()
+        .zip(latencies)
+        .filter(|(_, latency)| !latency.is_zero())
+        .map(|(&size, latency)| size as f64 / latency.as_secs_f64())
+        .collect()
+}
+
+impl NetworkStats {
+    // Adds this run's fields to `obj`, so callers can prepend their own context (e.g. size)
+    pub fn write_json_fields(&self, obj: &mut JsonObject) {
+        let fairness = |field: fn(&IterationFairness) -> f64| {
+            self.iteration_fairness.as_ref().map_or("null".to_string(), |f| field(f).to_string())
+        };
+        obj.uint("schema_version", STATS_SCHEMA_VERSION)
+            .uint("bytes_sent", self.bytes_sent as u64)
+            .uint("bytes_received", self.bytes_received as u64)
+            .float("elapsed_secs", self.elapsed_time.as_secs_f64())
+            .float("upload_rate", self.upload_rate)
+            .float("download_rate", self.download_rate)
+            .float("ratio", self.ratio)
+            .uint("successful_iterations", self.successful_iterations as u64)
+            .uint("failed_iterations", self.failed_iterations as u64)
+            .uint("corrupted_iterations", self.corrupted_iterations as u64)
+            .uint("bytes_written_before_failure", self.bytes_written_before_failure as u64)
+            .strings("errors", &self.errors)
+            .uint("retries", self.retries as u64)
+            .uint("premature_eofs", self.premature_eofs as u64)
+            .uint("connection_resets", self.connection_resets as u64)
+            .uint("failed_connects", self.failed_connects as u64)
+            .raw(
+                "throttled_concurrency",
+                &self.throttled_concurrency.map_or("null".to_string(), |n| n.to_string()),
+            )
+            .raw(
+                "http2_streams",
+                &self.http2_streams.map_or("null".to_string(), |n| n.to_string()),
+            )
+            .raw("iteration_fairness", &fairness(|f| f.index))
+            .raw("min_iteration_rate", &fairness(|f| f.min_rate))
+            .raw("max_iteration_rate", &fairness(|f| f.max_rate))
+            .float("dns_resolution_secs", self.dns_resolution_time.as_secs_f64())
+            .float("connect_secs", self.connect_time.as_secs_f64())
+            .float("mean_connect_secs", self.mean_connect_time.as_secs_f64())
+            .uint("connections", self.connections as u64)
+            .uint("ipv4_connections", self.ipv4_connections as u64)
+            .uint("ipv6_connections", self.ipv6_connections as u64)
+            .raw(
+                "local_address",
+                &self.local_address.map_or("null".to_string(), |ip| quote(&ip.to_string())),
+            )
+            .raw("seed", &self.seed.map_or("null".to_string(), |seed| seed.to_string()))
+            .uint("requests_per_connection", self.requests_per_connection as u64)
+            .raw("interrupted", if self.interrupted { "true" } else { "false" })
+            .raw("time_limited", if self.time_limited { "true" } else { "false" })
+            .raw("burst", if self.burst { "true" } else { "false" })
+            .raw(
+                "auto_iterations",
+                &self.auto_iterations.map_or("null".to_string(), |n| n.to_string()),
+            )
+            .raw(
+                "total_bytes_iterations",
+                &self.total_bytes_iterations.map_or("null".to_string(), |n| n.to_string()),
+            )
+            .raw(
+                "cpu_utilization",
+                &self.cpu_utilization.map_or("null".to_string(), |cores| cores.to_string()),
+            )
+            .raw(
+                "write_chunk_size",
+                &self.write_chunk_size.map_or("null".to_string(), |n| n.to_string()),
+            )
+            .raw(
+                "single_write_upload_rate",
+                &self.single_write_upload_rate.map_or("null".to_string(), |r| r.to_string()),
+            )
+            .float("min_latency_secs", self.min_latency.as_secs_f64())
+            .float("max_latency_secs", self.max_latency.as_secs_f64())
+            .float("mean_latency_secs", self.mean_latency.as_secs_f64())
+            .float("jitter_secs", self.jitter.as_secs_f64())
+            .float("time_to_first_byte_secs", self.time_to_first_byte.as_secs_f64())
+            .float("phase_connect_secs", self.phases.connect.as_secs_f64())
+            .float("phase_send_secs", self.phases.send.as_secs_f64())
+            .float("phase_wait_secs", self.phases.wait.as_secs_f64())
+            .float("phase_receive_secs", self.phases.receive.as_secs_f64())
+            .float("p50_latency_secs", self.p50_latency().as_secs_f64())
+            .float("p95_latency_secs", self.p95_latency().as_secs_f64())
+            .float("p99_latency_secs", self.p99_latency().as_secs_f64())
+            .float("mean_start_gap_secs", self.mean_start_gap().as_secs_f64())
+            .float("p95_start_gap_secs", self.p95_start_gap().as_secs_f64())
+            .raw("start_gap_histogram", &gap_histogram_json(&self.start_gap_histogram()))
+            .uint("bandwidth_delay_product", self.bandwidth_delay_product() as u64)
+            .raw("response_histogram", &histogram_json(&self.response_histogram()))
+            .raw("label", &self.label.as_deref().map_or("null".to_string(), quote))
+            .raw(
+                "timestamp",
+                &self.timestamp.map_or("null".to_string(), |time| quote(&utc_timestamp(time))),
+            )
+            .string("config_summary", &self.config_summary);
+    }
+
+    // Latency at percentile `p` (0-100) of the successful iterations, linearly
+    // interpolated between the two nearest samples. Zero when nothing succeeded.
+    pub fn latency_percentile(&self, p: f64) -> Duration {
+        percentile(&self.latencies, p)
+    }
+
+    // As latency_percentile, over start_gaps
+    pub fn start_gap_percentile(&self, p: f64) -> Duration {
+        percentile(&self.start_gaps, p)
+    }
+
+    pub fn p95_start_gap(&self) -> Duration {
+        self.start_gap_percentile(95.0)
+    }
+
+    // Zero with fewer than two iterations started
+    pub fn mean_start_gap(&self) -> Duration {
+        match self.start_gaps.len() {
+            0 => Duration::ZERO,
+            n => self.start_gaps.iter().sum::<Duration>() / n as u32,
+        }
+    }
+
+    // start_gaps in START_GAP_BUCKETS equal-width buckets from the shortest gap to the
+    // longest, as (bucket lower bound, count); a single bucket when every gap is the
+    // same. Unlike response sizes, gaps cluster around one value, so power-of-two
+    // buckets would put them all in one.
+    pub fn start_gap_histogram(&self) -> Vec<(Duration, usize)> {
+        let (Some(&shortest), Some(&longest)) =
+            (self.start_gaps.iter().min(), self.start_gaps.iter().max())
+        else {
+            return Vec::new();
+        };
+        let width = (longest - shortest) / START_GAP_BUCKETS as u32;
+        if width.is_zero() {
+            return vec![(shortest, self.start_gaps.len())];
+        }
+        let mut counts = vec![0; START_GAP_BUCKETS];
+        for &gap in &self.start_gaps {
+            let bucket = ((gap - shortest).as_nanos() / width.as_nanos()) as usize;
+            // The longest gap closes the last bucket rather than opening another
+            counts[bucket.min(START_GAP_BUCKETS - 1)] += 1;
+        }
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| (shortest + width * i as u32, count))
+            .collect()
+    }
+
+    // One line per start_gap_histogram bucket with a bar scaled to the fullest one
+    pub fn render_start_gap_histogram(&self) -> String {
+        let histogram = self.start_gap_histogram();
+        let width = match histogram.as_slice() {
+            [(first, _), (second, _), ..] => *second - *first,
+            _ => Duration::ZERO,
+        };
+        let most = histogram.iter().map(|&(_, count)| count).max().unwrap_or(0);
+        let mut out = String::new();
+        for (lower, count) in histogram {
+            let range = if width.is_zero() {
+                format!("{:.2?}", lower)
+            } else {
+                format!("{:.2?} .. {:.2?}", lower, lower + width)
+            };
+            let bar = (count * 30).div_ceil(most.max(1));
+            out.push_str(&format!("{:>24}  {:<30}  {}\n", range, "#".repeat(bar), count));
+        }
+        out
+    }
+
+    // Download rate of each successful iteration: its response bytes over its round
+    // trip, connect included. Empty for stats read back from JSON, which don't keep
+    // the per-iteration numbers.
+    pub fn download_rate_samples(&self) -> Vec<f64> {
+        rate_samples(&self.response_sizes, &self.latencies)
+    }
+
+    // Mean of download_rate_samples. Unlike download_rate, which is total bytes over
+    // total transfer time, every iteration weighs the same.
+    pub fn mean_download_rate(&self) -> Option<f64> {
+        confidence::mean(&self.download_rate_samples())
+    }
+
+    // The spread figures below need at least two samples and are None otherwise
+    pub fn download_rate_std_dev(&self) -> Option<f64> {
+        confidence::sample_std_dev(&self.download_rate_samples())
+    }
+
+    pub fn download_rate_standard_error(&self) -> Option<f64> {
+        confidence::standard_error(&self.download_rate_samples())
+    }
+
+    // (low, high) 95% confidence interval for mean_download_rate
+    pub fn download_rate_confidence_interval(&self) -> Option<(f64, f64)> {
+        confidence::confidence_interval_95(&self.download_rate_samples())
+    }
+
+    // Half download_rate_confidence_interval's width over mean_download_rate
+    pub fn download_rate_relative_margin(&self) -> Option<f64> {
+        confidence::relative_margin_95(&self.download_rate_samples())
+    }
+
+    pub fn p50_latency(&self) -> Duration {
+        self.latency_percentile(50.0)
+    }
+
+    pub fn p95_latency(&self) -> Duration {
+        self.latency_percentile(95.0)
+    }
+
+    pub fn p99_latency(&self) -> Duration {
+        self.latency_percentile(99.0)
+    }
+
+    // Response sizes bucketed by powers of two, as (bucket lower bound, count) from the
+    // smallest bucket seen to the largest. Empty buckets in between are kept so a
+    // bimodal spread stays visible; zero-byte responses get a bucket of their own.
+    pub fn response_histogram(&self) -> Vec<(usize, usize)> {
+        let bucket_of = |size: usize| if size == 0 { 0 } else { size.ilog2() as usize + 1 };
+        let lowest = self.response_sizes.iter().map(|&s| bucket_of(s)).min();
+        let highest = self.response_sizes.iter().map(|&s| bucket_of(s)).max();
+        let (Some(lowest), Some(highest)) = (lowest, highest) else {
+            return Vec::new();
+        };
+
+        let mut counts = vec![0; highest - lowest + 1];
+        for &size in &self.response_sizes {
+            counts[bucket_of(size) - lowest] += 1;
+        }
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| {
+                let bucket = lowest + i;
+                let lower = if bucket == 0 { 0 } else { 1usize << (bucket - 1) };
+                (lower, count)
+            })
+            .collect()
+    }
+
+    // One line per response_histogram bucket with a bar scaled to the fullest one
+    pub fn render_response_histogram(&self) -> String {
+        let histogram = self.response_histogram();
+        let most = histogram.iter().map(|&(_, count)| count).max().unwrap_or(0);
+        let mut out = String::new();
+        for (lower, count) in histogram {
+            // Half-open ranges: lower bound included, upper excluded
+            let range = match lower {
+                0 => "0 B".to_string(),
+                _ => format!("{} .. {}", format_bytes(lower), format_bytes(lower * 2)),
+            };
+            let bar = (count * 30).div_ceil(most.max(1));
+            out.push_str(&format!("{:>24}  {:<30}  {}\n", range, "#".repeat(bar), count));
+        }
+        out
+    }
+
+    pub const CSV_HEADER: &'static str =
+        "size,bytes_sent,bytes_received,elapsed_secs,upload_rate,download_rate,ratio";
+
+    // Raw numbers matching CSV_HEADER, for the run that used payload `size`
+    pub fn csv_row(&self, size: usize) -> String {
+        format!(
+            "{},{},{},{},{},{},{}",
+            size,
+            self.bytes_sent,
+            self.bytes_received,
+            self.elapsed_time.as_secs_f64(),
+            self.upload_rate,
+            self.download_rate,
+            self.ratio
+        )
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut obj = JsonObject::new();
+        self.write_json_fields(&mut obj);
+        obj.finish()
+    }
+
+    // Rebuilds stats from an object written by write_json_fields. Per-iteration
+    // latencies aren't serialized, so `latencies` comes back empty. Fails for objects
+    // from a newer schema, whose keys may no longer mean what this build expects.
+    pub fn from_json(value: &JsonValue) -> Result<NetworkStats, String> {
+        let version = stats_schema_version(value)?;
+        if version > STATS_SCHEMA_VERSION {
+            return Err(format!(
+                "schema_version {} is newer than this build reads (up to {}); load it with \
+                 a newer traffic-monitor",
+                version, STATS_SCHEMA_VERSION
+            ));
+        }
+        let upgraded;
+        let value = match version {
+            1 => {
+                upgraded = upgrade_stats_v1(value);
+                &upgraded
+            }
+            _ => value,
+        };
+        let number = |key: &str| {
+            value
+                .get(key)
+                .and_then(JsonValue::as_f64)
+                .ok_or_else(|| format!("missing numeric field '{}'", key))
+        };
+        // Fields added since the file's version default to zero rather than failing
+        let optional = |key: &str| value.get(key).and_then(JsonValue::as_f64).unwrap_or(0.0);
+        let secs = |raw: f64| Duration::try_from_secs_f64(raw).unwrap_or_default();
+        let errors = value
+            .get("errors")
+            .and_then(JsonValue::as_array)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|e| e.as_str().map(str::to_string))
+            .collect();
+
+        Ok(NetworkStats {
+            bytes_sent: number("bytes_sent")? as usize,
+            bytes_received: number("bytes_received")? as usize,
+            elapsed_time: secs(number("elapsed_secs")?),
+            upload_rate: number("upload_rate")?,
+            download_rate: number("download_rate")?,
+            ratio: number("ratio")?,
+            successful_iterations: optional("successful_iterations") as usize,
+            failed_iterations: optional("failed_iterations") as usize,
+            corrupted_iterations: optional("corrupted_iterations") as usize,
+            bytes_written_before_failure: optional("bytes_written_before_failure") as usize,
+            errors,
+            retries: optional("retries") as usize,
+            premature_eofs: optional("premature_eofs") as usize,
+            connection_resets: optional("connection_resets") as usize,
+            failed_connects: optional("failed_connects") as usize,
+            throttled_concurrency: value
+                .get("throttled_concurrency")
+                .and_then(JsonValue::as_f64)
+                .map(|n| n as usize),
+            http2_streams: value
+                .get("http2_streams")
+                .and_then(JsonValue::as_f64)
+                .map(|n| n as usize),
+            iteration_fairness: value.get("iteration_fairness").and_then(JsonValue::as_f64).map(
+                |index| IterationFairness {
+                    index,
+                    min_rate: optional("min_iteration_rate"),
+                    max_rate: optional("max_iteration_rate"),
+                },
+            ),
+            dns_resolution_time: secs(optional("dns_resolution_secs")),
+            connect_time: secs(optional("connect_secs")),
+            mean_connect_time: secs(optional("mean_connect_secs")),
+            connections: optional("connections") as usize,
+            ipv4_connections: optional("ipv4_connections") as usize,
+            ipv6_connections: optional("ipv6_connections") as usize,
+            local_address: value
+                .get("local_address")
+                .and_then(JsonValue::as_str)
+                .and_then(|ip| ip.parse().ok()),
+            min_latency: secs(optional("min_latency_secs")),
+            max_latency: secs(optional("max_latency_secs")),
+            mean_latency: secs(optional("mean_latency_secs")),
+            jitter: secs(optional("jitter_secs")),
+            time_to_first_byte: secs(optional("time_to_first_byte_secs")),
+            phases: PhaseBreakdown {
+                connect: secs(optional("phase_connect_secs")),
+                send: secs(optional("phase_send_secs")),
+                wait: secs(optional("phase_wait_secs")),
+                receive: secs(optional("phase_receive_secs")),
+            },
+            latencies: Vec::new(),
+            start_gaps: Vec::new(),
+            response_sizes: Vec::new(),
+            // JSON numbers are floats, so seeds past 2^53 don't survive the round trip
+            seed: value.get("seed").and_then(JsonValue::as_f64).map(|seed| seed as u64),
+            requests_per_connection: optional("requests_per_connection").max(1.0) as usize,
+            interrupted: value.get("interrupted") == Some(&JsonValue::Bool(true)),
+            time_limited: value.get("time_limited") == Some(&JsonValue::Bool(true)),
+            burst: value.get("burst") == Some(&JsonValue::Bool(true)),
+            records: Vec::new(),
+            auto_iterations: value
+                .get("auto_iterations")
+                .and_then(JsonValue::as_f64)
+                .map(|n| n as usize),
+            total_bytes_iterations: value
+                .get("total_bytes_iterations")
+                .and_then(JsonValue::as_f64)
+                .map(|n| n as usize),
+            cpu_utilization: value.get("cpu_utilization").and_then(JsonValue::as_f64),
+            write_chunk_size: value
+                .get("write_chunk_size")
+                .and_then(JsonValue::as_f64)
+                .map(|n| n as usize),
+            single_write_upload_rate: value
+                .get("single_write_upload_rate")
+                .and_then(JsonValue::as_f64),
+            label: value.get("label").and_then(JsonValue::as_str).map(String::from),
+            timestamp: value
+                .get("timestamp")
+                .and_then(JsonValue::as_str)
+                .and_then(parse_utc_timestamp),
+            config_summary: value
+                .get("config_summary")
+                .and_then(JsonValue::as_str)
+                .unwrap_or_default()
+                .to_string(),
+        })
+    }
+
+    // Whether the rates came from too little time to mean much, under
+    // MIN_MEASURABLE_TIME; the text output shows them as N/A
+    pub fn too_fast_to_measure(&self) -> bool {
+        self.elapsed_time < MIN_MEASURABLE_TIME
+    }
+
+    // The same for the per-iteration rates, each of which comes from its own
+    // iteration's time: true when any of those was under MIN_MEASURABLE_TIME
+    pub fn iterations_too_fast_to_measure(&self) -> bool {
+        self.response_sizes
+            .iter()
+            .zip(&self.latencies)
+            .any(|(_, latency)| !latency.is_zero() && *latency < MIN_MEASURABLE_TIME)
+    }
+
+    // How long moving `bytes` would take at the measured download rate. Duration::MAX
+    // stands for never when nothing was downloaded, e.g. in an upload-only run.
+    pub fn estimate_transfer_time(&self, bytes: usize) -> Duration {
+        time_at_rate(bytes, self.download_rate)
+    }
+
+    // estimate_transfer_time at the measured upload rate
+    pub fn estimate_upload_time(&self, bytes: usize) -> Duration {
+        time_at_rate(bytes, self.upload_rate)
+    }
+
+    // Bytes that must be in flight to keep the path busy: the faster direction's rate
+    // times the mean round trip. Each round trip includes sending the payload, so the
+    // product comes out high for payloads that take many round trips to send.
+    pub fn bandwidth_delay_product(&self) -> usize {
+        (self.upload_rate.max(self.download_rate) * self.mean_latency.as_secs_f64()) as usize
+    }
+
+    // A SO_SNDBUF/SO_RCVBUF size that holds the bandwidth-delay product, rounded up to a
+    // power of two
+    pub fn suggested_socket_buffer(&self) -> usize {
+        let bdp = self.bandwidth_delay_product();
+        bdp.checked_next_power_of_two()
+            .unwrap_or(MAX_SUGGESTED_SOCKET_BUFFER)
+            .clamp(MIN_SUGGESTED_SOCKET_BUFFER, MAX_SUGGESTED_SOCKET_BUFFER)
+    }
+
+    // At least a core's worth of CPU went into the transfer, so the rates may be the
+    // tool's own ceiling rather than the network's
+    pub fn cpu_bound(&self) -> bool {
+        self.cpu_utilization.is_some_and(|cores| cores >= cpu::CPU_BOUND_UTILIZATION)
+    }
+
+    // Percentage changes going from `self` (the baseline) to `other`
+    pub fn compare(&self, other: &NetworkStats) -> StatsDiff {
+        StatsDiff::between(self, other)
+    }
+}
+
+// Tunables for measure_transfer that have sensible defaults
+#[derive(Debug, Clone)]
+pub struct TransferOptions {
+    pub recv_buffer_size: usize, // size of each read; the full response is read regardless
+    pub delay: Duration,         // pause between iterations, excluded from the rates; may be zero
+    pub timeout: Duration,       // limit on each of connect, write and read; exceeding it fails the iteration
+    pub concurrency: usize,      // iterations in flight at once; 1 runs them sequentially
+    pub warmup: usize,           // leading iterations excluded from the stats; must be less than iterations
+    // Send every iteration over one connection instead of reconnecting each time.
+    // Always sequential.
+    pub reuse_connection: bool,
+    pub retry: RetryPolicy, // applies to connecting only; writes and reads are never retried
+    // When the target resolves to several addresses, how long each connection attempt
+    // runs alone before the next address is tried alongside it (happy_eyeballs.rs)
+    pub connect_head_start: Duration,
+    // Reach the target through this SOCKS5 proxy (socks.rs), which resolves the host
+    // name itself; the retry policy, head start and timeout apply to reaching the proxy
+    pub proxy: Option<SocksConfig>,
+    // Times an iteration is repeated, on a new connection, when the server closed before
+    // its response was complete; the repeats' time counts towards the iteration's
+    pub eof_retries: usize,
+    // Caps the send rate in bytes per second by writing in slices and sleeping between
+    // them, so upload_rate settles near it. None sends as fast as possible.
+    pub target_rate: Option<f64>,
+    // Write each request in pieces of this many bytes, one write_all apiece, rather than
+    // in one call, to see how application-level chunking interacts with the network; the
+    // frame header goes out with the first piece. With target_rate the smaller size wins.
+    pub write_chunk_size: Option<usize>,
+    // Flush after every piece of write_chunk_size. A TcpStream sends on each write
+    // either way, so this only matters to streams that buffer.
+    pub flush_chunks: bool,
+    // Show a one-line iteration count, upload rate and ETA on stdout while measuring.
+    // Only meant for text output on a terminal; run_benchmarks decides.
+    pub progress: bool,
+    // Send the verify.rs pattern instead of zeros and fail iterations whose response
+    // doesn't continue it. The server has to echo (see MockServerConfig::echo).
+    pub verify: bool,
+    // Shut down the write half once the request is out, for servers that read until
+    // EOF before replying. The connection can't carry another request after that, so
+    // this can't be combined with reuse_connection.
+    pub half_close_after_send: bool,
+    // Disable Nagle's algorithm on every connection. Small payloads usually see lower
+    // latency; bulk transfers send more, smaller segments and lose some efficiency.
+    pub tcp_nodelay: bool,
+    // SO_SNDBUF / SO_RCVBUF for every connection, set before connecting so the receive
+    // buffer also bounds the advertised window. None keeps the OS default; Linux
+    // doubles the value given and enforces its own minimum and maximum.
+    pub socket_send_buffer: Option<u32>,
+    pub socket_recv_buffer: Option<u32>,
+    // Local address every connection is bound to before connecting, to send from one
+    // interface of a multi-homed host. Port 0 leaves the port to the OS, which a fixed
+    // port needs for more than one connection at once. Only the resolved addresses of
+    // the same family are tried. None lets the OS pick by its routing table.
+    pub bind_addr: Option<SocketAddr>,
+    // Requests sent over each connection one after another, every one answered before
+    // the next, like a keep-alive HTTP or RPC session. An iteration is the whole
+    // conversation: its bytes are summed and its latency spans all of the requests.
+    pub requests_per_connection: usize,
+    // Pause between the requests of one connection, as a user would between actions;
+    // excluded from the latencies and rates like `delay`
+    pub think_time: Duration,
+    // Stop at the first failed iteration and return its error, for CI gating. Otherwise
+    // failures are tallied in the stats and the remaining iterations still run; only a
+    // measurement where every iteration failed is an error.
+    pub fail_fast: bool,
+    // Read between iterations: once it holds true, no further iteration starts and the
+    // stats cover those already finished. At least one iteration always runs.
+    pub cancel: Option<watch::Receiver<bool>>,
+    // Like `cancel`, on a deadline this long after the first measured iteration starts
+    // (warmup not included): iterations in flight finish, no more start, and the stats
+    // say they were time-limited. `delay` is cut short rather than slept past it.
+    pub max_duration: Option<Duration>,
+    // Instead of a fixed count, iterate until the successful ones have sent exactly this
+    // many payload bytes; the last sends whatever remains of the payload size. A failed
+    // iteration's share is sent again by another, until as many have failed as the total
+    // takes iterations. `iterations` is then ignored, so warmup needn't be less.
+    pub total_bytes: Option<usize>,
+    // Spawn every iteration at once, held at one Barrier until all are ready, so their
+    // connects reach the server together (a thundering herd) instead of `concurrency`
+    // at a time. `delay` doesn't apply, and the count has to be known up front.
+    pub burst: bool,
+    // Fill NetworkStats::records with one entry per iteration. Off by default since a
+    // long run's records can take far more memory than the aggregates.
+    pub keep_records: bool,
+    // Response payload length every reply must announce. Responses are framed, so the
+    // client always reads exactly the announced bytes and never waits for EOF; this
+    // pins that length, failing an iteration whose frame announces another before any
+    // of its payload is read. None accepts whatever the server announces.
+    pub expected_response_len: Option<usize>,
+    // Which way each request moves its payload; see Direction
+    pub direction: Direction,
+    // Ask the server for responses of exactly this many payload bytes, whatever the
+    // request size, through a SIZED_REPLY request (see framing.rs). None leaves the
+    // length to the server; download-only requests then ask for the payload size.
+    pub response_size: Option<usize>,
+    // Stamp the first bytes of every request body with the next number from this
+    // counter (see framing::stamp_sequence), for `proxy --check-sequence`. Clones share
+    // the counter, so concurrent connections each see increasing numbers.
+    pub sequence: Option<Arc<AtomicU64>>,
+    // Choose the iteration count instead of taking it as given; see AutoIterations
+    pub auto_iterations: Option<AutoIterations>,
+    // Times the round trips and connects, the system clock by default; see clock.rs
+    pub clock: Arc<dyn Clock>,
+}
+
+// Measure in batches until the download rate is known precisely enough. The requested
+// iterations (less warmup) are the first batch; each further batch doubles the total
+// so far, until the 95% confidence interval's half-width is within target_precision
+// of the mean or max_iterations have run. The stats then report how many it took.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoIterations {
+    pub target_precision: f64, // relative margin of error, e.g. 0.05 for ±5%
+    pub max_iterations: usize, // measured iterations to stop at regardless
+}
+
+impl Default for AutoIterations {
+    fn default() -> Self {
+        AutoIterations { target_precision: 0.05, max_iterations: 1000 }
+    }
+}
+
+// What a TCP request carries each way. The one-way modes need a server that reads
+// the framing.rs flags, as the mock server does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Direction {
+    #[default]
+    Both, // send the payload and read the server's reply to it
+    // Send the payload and don't wait for anything back: the server discards it, so
+    // bytes_received and download_rate stay zero
+    UploadOnly,
+    // Send only the payload size, or response_size when set, and read a response that
+    // long: the request is counted like a frame header, so bytes_sent and upload_rate
+    // stay zero
+    DownloadOnly,
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        TransferOptions {
+            recv_buffer_size: 8192, // 8KB buffer
+            delay: Duration::from_millis(100),
+            timeout: Duration::from_secs(10),
+            concurrency: 1,
+            warmup: 0,
+            reuse_connection: false,
+            retry: RetryPolicy::default(),
+            connect_head_start: DEFAULT_HEAD_START,
+            proxy: None,
+            eof_retries: 0,
+            target_rate: None,
+            write_chunk_size: None,
+            flush_chunks: false,
+            progress: false,
+            verify: false,
+            half_close_after_send: false,
+            tcp_nodelay: false,
+            socket_send_buffer: None,
+            socket_recv_buffer: None,
+            bind_addr: None,
+            requests_per_connection: 1,
+            think_time: Duration::ZERO,
+            fail_fast: false,
+            cancel: None,
+            max_duration: None,
+            total_bytes: None,
+            burst: false,
+            keep_records: false,
+            expected_response_len: None,
+            direction: Direction::Both,
+            response_size: None,
+            sequence: None,
+            auto_iterations: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+// How failed connects are retried before the iteration counts as failed
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: usize,        // attempts after the first; 0 fails on the first error
+    pub initial_backoff: Duration, // wait before the first retry
+    pub backoff_multiplier: f64,   // each wait is the previous one times this
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            initial_backoff: Duration::from_millis(100),
+            backoff_multiplier: 2.0,
+        }
+    }
+}
+
+// Every request and response is a length-prefixed frame (see framing.rs), so the
+// server at `address` must speak the framing; the mock server does.
+pub async fn measure_transfer(
+    address: &str,
+    data_size: usize,
+    iterations: usize,
+    options: &TransferOptions,
+) -> Result<NetworkStats, TransferError> {
+    measure_payload(address, &PayloadSource::Zeros(data_size), iterations, options).await
+}
+
+// measure_transfer with the payload taken from `payload`, loaded once up front and sent
+// unchanged every iteration. --verify needs the generated pattern, so it only combines
+// with PayloadSource::Zeros, whose zeros it replaces.
+pub async fn measure_payload(
+    address: &str,
+    payload: &PayloadSource,
+    iterations: usize,
+    options: &TransferOptions,
+) -> Result<NetworkStats, TransferError> {
+    measure_payload_with_progress(address, payload, iterations, options, |_| {}).await
+}
+
+// measure_transfer that also hands each measured iteration to `on_progress` as it
+// finishes, e.g. to drive a live display; warmup iterations are not reported
+pub async fn measure_transfer_with_progress(
+    address: &str,
+    data_size: usize,
+    iterations: usize,
+    options: &TransferOptions,
+    on_progress: impl FnMut(&IterationResult) + Send,
+) -> Result<NetworkStats, TransferError> {
+    let payload = PayloadSource::Zeros(data_size);
+    measure_payload_with_progress(address, &payload, iterations, options, on_progress).await
+}
+
+pub async fn measure_payload_with_progress(
+    address: &str,
+    payload: &PayloadSource,
+    iterations: usize,
+    options: &TransferOptions,
+    mut on_progress: impl FnMut(&IterationResult) + Send,
+) -> Result<NetworkStats, TransferError> {
+    // A byte total sets the iteration count itself
+    if options.total_bytes.is_none() {
+        check_iterations(iterations)?;
+    }
+    if options.total_bytes.is_none() && options.warmup > 0 && options.warmup >= iterations {
+        return Err(TransferError::InvalidOptions(format!(
+            "warmup ({}) must be less than iterations ({})",
+            options.warmup, iterations
+        )));
+    }
+    check_target_rate(options)?;
+    if options.requests_per_connection == 0 {
+        return Err(TransferError::InvalidOptions(
+            "requests per connection must be at least 1".to_string(),
+        ));
+    }
+    if options.half_close_after_send
+        && (options.reuse_connection || options.requests_per_connection > 1)
+    {
+        return Err(TransferError::InvalidOptions(
+            "a half-closed connection can't carry another request".to_string(),
+        ));
+    }
+    if options.direction == Direction::UploadOnly
+        && (options.verify
+            || options.expected_response_len.is_some()
+            || options.response_size.is_some())
+    {
+        return Err(TransferError::InvalidOptions(
+            "upload-only requests get no response to size, verify or check the length of"
+                .to_string(),
+        ));
+    }
+    if options.burst
+        && (options.reuse_connection
+            || options.auto_iterations.is_some()
+            || options.total_bytes.is_some())
+    {
+        return Err(TransferError::InvalidOptions(
+            "a burst starts every iteration at once on its own connection, so it can't reuse \
+             connections or leave the count to auto iterations or a byte total"
+                .to_string(),
+        ));
+    }
+    if options.total_bytes.is_some()
+        && (options.auto_iterations.is_some()
+            || options.direction == Direction::DownloadOnly
+            || options.requests_per_connection > 1)
+    {
+        return Err(TransferError::InvalidOptions(
+            "a byte total sets the iteration count from one payload per iteration, so it \
+             can't be combined with auto iterations, download-only or several requests per \
+             connection"
+                .to_string(),
+        ));
+    }
+    validate_address(address).await?;
+
+    if options.verify && !matches!(payload, PayloadSource::Zeros(_)) {
+        return Err(TransferError::InvalidOptions(
+            "verify mode sends its own pattern and can't use a random or file payload"
+                .to_string(),
+        ));
+    }
+    // Big payloads are generated as they go out, so asking for one can't exhaust memory
+    let data = match (payload.generated(options.verify), payload) {
+        (Some(generated), _) => Payload::Generated(generated),
+        (None, PayloadSource::Zeros(len)) if options.verify => {
+            Payload::Loaded(Arc::new(verify::fill_pattern(*len)))
+        }
+        (None, _) => Payload::Loaded(Arc::new(payload.load()?)),
+    };
+    check_frame_len(data.len())?;
+    if options.total_bytes == Some(0) || (options.total_bytes.is_some() && data.len() == 0) {
+        return Err(TransferError::InvalidOptions(
+            "a byte total needs at least one byte, and a payload to send it in".to_string(),
+        ));
+    }
+    if options.sequence.is_some()
+        && (options.verify
+            || options.direction == Direction::DownloadOnly
+            || data.len() < framing::SEQUENCE_LEN)
+    {
+        return Err(TransferError::InvalidOptions(format!(
+            "sequence numbers take the first {} bytes of each request body, so they need a \
+             payload at least that long and can't be combined with verify or download-only",
+            framing::SEQUENCE_LEN
+        )));
+    }
+
+    // Warmup iterations take the full connect/write/read path but are never counted
+    let mut warmup_connection = None;
+    let mut warmup_costs = ConnectCosts::default();
+    let mut warmup_phases = PhaseTimes::default();
+    for _ in 0..options.warmup {
+        let _ = one_iteration(
+            &mut warmup_connection,
+            address,
+            data.body(),
+            options,
+            &mut warmup_costs,
+            &mut warmup_phases,
+        )
+        .await;
+    }
+
+    let measured = match options.total_bytes {
+        Some(total) => total.div_ceil(data.len()),
+        None => iterations - options.warmup,
+    };
+    // The --progress line is just another listener; an adaptive count can grow to the cap
+    let expected = options.auto_iterations.map_or(measured, |auto| auto.max_iterations);
+    let mut progress = options.progress.then(|| Progress::new(expected.max(measured)));
+    let mut sent = 0;
+    // What the iterations reported, to check against the totals once they're in
+    let mut reported = (0, 0);
+    debug!("measuring size", address = address, size = data.len(), iterations = measured);
+    let mut on_iteration = |result: &IterationResult| {
+        reported.0 += result.bytes_sent;
+        reported.1 += result.bytes_received;
+        match &result.error {
+            None => debug!(
+                "iteration",
+                size = data.len(),
+                index = result.index,
+                sent = result.bytes_sent,
+                received = result.bytes_received,
+                duration_secs = result.duration.as_secs_f64(),
+            ),
+            Some(error) => debug!(
+                "iteration failed",
+                size = data.len(),
+                index = result.index,
+                duration_secs = result.duration.as_secs_f64(),
+                error = error,
+            ),
+        }
+        on_progress(result);
+        if let Some(progress) = &mut progress {
+            sent += result.bytes_sent;
+            progress.update(result.index + 1, sent);
+        }
+    };
+    let cpu_start = cpu::process_cpu_time();
+    let outcome = if !options.burst && (options.concurrency <= 1 || options.reuse_connection) {
+        run_sequential(address, &data, measured, options, &mut on_iteration).await
+    } else {
+        run_concurrent(address, &data, measured, options, &mut on_iteration).await
+    };
+    if let Some(progress) = &progress {
+        progress.finish();
+    }
+    let (tally, elapsed) = outcome?;
+    let mut stats = tally.into_stats(elapsed)?;
+    check_byte_totals(&stats, reported);
+    stats.cpu_utilization = cpu::utilization_since(cpu_start, elapsed);
+    stats.seed = payload.seed();
+    stats.requests_per_connection = options.requests_per_connection;
+    stats.write_chunk_size = options.write_chunk_size;
+    stats.burst = options.burst;
+    if options.auto_iterations.is_some() {
+        stats.auto_iterations = Some(stats.successful_iterations + stats.failed_iterations);
+    }
+    if options.total_bytes.is_some() {
+        stats.total_bytes_iterations =
+            Some(stats.successful_iterations + stats.failed_iterations);
+    }
+    info!(
+        "size measured",
+        address = address,
+        size = data.len(),
+        sent = stats.bytes_sent,
+        received = stats.bytes_received,
+        elapsed_secs = stats.elapsed_time.as_secs_f64(),
+        upload_rate = stats.upload_rate,
+        download_rate = stats.download_rate,
+        failed = stats.failed_iterations,
+    );
+    Ok(stats)
+}
+
+// Every byte in bytes_sent/bytes_received was reported by exactly one iteration, and a
+// failed iteration reports none, so the per-iteration sums always equal the totals. A
+// mismatch is an accounting bug: fatal in debug builds, a warning otherwise.
+fn check_byte_totals(stats: &NetworkStats, (sent, received): (usize, usize)) {
+    let matches = sent == stats.bytes_sent && received == stats.bytes_received;
+    debug_assert!(
+        matches,
+        "iterations reported {} bytes sent and {} received, but the totals are {} and {}",
+        sent, received, stats.bytes_sent, stats.bytes_received
+    );
+    if !matches {
+        warning!(
+            "per-iteration bytes don't add up to the totals",
+            reported_sent = sent,
+            reported_received = received,
+            total_sent = stats.bytes_sent,
+            total_received = stats.bytes_received,
+        );
+    }
+}
+
+// Iterations one after another; returns the time spent transferring, which
+// excludes the pauses in between and the time spent connecting. With fail_fast the
+// first failed iteration's error is returned instead.
+async fn run_sequential(
+    address: &str,
+    data: &Payload,
+    iterations: usize,
+    options: &TransferOptions,
+    on_iteration: &mut (dyn FnMut(&IterationResult) + Send),
+) -> Result<(Tally, Duration), TransferError> {
+    let mut tally = Tally::default();
+    let mut active_time = Duration::ZERO;
+    // Only used with reuse_connection; dropped after a failure and redialled next time
+    let mut connection = None;
+
+    let deadline = deadline_after(options.max_duration);
+    let mut planned = if options.total_bytes.is_some() { usize::MAX } else { iterations };
+    let mut index = 0;
+    while index < planned {
+        if index > 0
+            && (tally.stop_requested(options.cancel.as_ref()) || tally.deadline_passed(deadline))
+        {
+            break;
+        }
+        let Some(len) = next_share(options, data.len(), &tally, 0) else { break };
+        let sent = request_bytes(len, options) * options.requests_per_connection;
+        // A failed iteration is recorded and skipped so earlier results survive
+        let iteration_start = options.clock.now();
+        tally.starts.push(iteration_start);
+        let connect_before = tally.connect.connect_time;
+        let result = one_iteration(
+            &mut connection,
+            address,
+            data.body().prefix(len),
+            options,
+            &mut tally.connect,
+            &mut tally.phases,
+        )
+        .await;
+        let latency = options
+            .clock
+            .elapsed_since(iteration_start)
+            .saturating_sub(think_time_per_iteration(options));
+        let iteration = IterationResult::new(index, sent, &result, latency);
+        on_iteration(&iteration);
+        if options.keep_records {
+            let connect_time = tally.connect.connect_time - connect_before;
+            tally.records.push(IterationRecord::new(&iteration, connect_time));
+        }
+        match result {
+            Ok(received) => tally.record_success(sent, received, latency),
+            Err(e) if options.fail_fast => return Err(abort(index, e)),
+            Err(e) => tally.record_error(e),
+        }
+        active_time += latency;
+        index += 1;
+        if index == planned {
+            planned = tally.next_batch(planned, options);
+        }
+
+        // Add a small delay between iterations
+        pause(options.delay, deadline).await;
+    }
+
+    let transfer_time = active_time.saturating_sub(tally.connect.connect_time);
+    Ok((tally, transfer_time))
+}
+
+// Keeps up to `options.concurrency` iterations in flight, starting a new one as
+// each finishes. `options.delay` is not applied. Returns wall-clock time from the
+// first spawn to the last completion so the rates reflect the aggregate load, less
+// the connect time; connects overlap, so each slot is charged an equal share of it.
+// With fail_fast the first failure is returned and the iterations in flight are
+// aborted. In a burst every iteration is a slot: all are spawned and held at a barrier,
+// and the time runs from the first start, once they are released.
+async fn run_concurrent(
+    address: &str,
+    data: &Payload,
+    iterations: usize,
+    options: &TransferOptions,
+    on_iteration: &mut (dyn FnMut(&IterationResult) + Send),
+) -> Result<(Tally, Duration), TransferError> {
+    let mut tally = Tally::default();
+    let mut tasks = JoinSet::new();
+    let mut started = 0;
+    let mut done = 0;
+    let start_time = options.clock.now();
+    let think_time = think_time_per_iteration(options);
+    // Of a total_bytes measurement, the shares of the iterations in flight
+    let mut in_flight = 0;
+
+    let deadline = deadline_after(options.max_duration);
+    let mut planned = if options.total_bytes.is_some() { usize::MAX } else { iterations };
+    let slots = if options.burst { iterations } else { options.concurrency }.max(1);
+    let barrier = options.burst.then(|| Arc::new(Barrier::new(slots)));
+    // Lowered when an iteration runs out of file descriptors, see throttle()
+    let mut limit = slots;
+    while started < planned || !tasks.is_empty() {
+        // Once cancelled or out of time, the iterations in flight finish but no more start
+        if started > 0
+            && started < planned
+            && (tally.stop_requested(options.cancel.as_ref()) || tally.deadline_passed(deadline))
+        {
+            planned = started;
+        }
+        while started < planned && tasks.len() < limit {
+            let Some(len) = next_share(options, data.len(), &tally, in_flight) else { break };
+            in_flight += len;
+            let address = address.to_string();
+            let data = data.clone();
+            let options = options.clone();
+            let barrier = barrier.clone();
+            tasks.spawn(async move {
+                if let Some(barrier) = barrier {
+                    barrier.wait().await;
+                }
+                let iteration_start = options.clock.now();
+                let mut costs = ConnectCosts::default();
+                let mut phases = PhaseTimes::default();
+                let body = data.body().prefix(len);
+                let result = transfer_once(&address, body, &options, &mut costs, &mut phases).await;
+                let latency = options.clock.elapsed_since(iteration_start);
+                (result, len, iteration_start, latency.saturating_sub(think_time), costs, phases)
+            });
+            started += 1;
+        }
+
+        match tasks.join_next().await {
+            Some(Ok((result, len, iteration_start, latency, costs, phases))) => {
+                in_flight -= len;
+                let sent = request_bytes(len, options) * options.requests_per_connection;
+                tally.starts.push(iteration_start);
+                let iteration = IterationResult::new(done, sent, &result, latency);
+                on_iteration(&iteration);
+                if options.keep_records {
+                    tally.records.push(IterationRecord::new(&iteration, costs.connect_time));
+                }
+                tally.connect.add(costs);
+                tally.phases.add(phases);
+                if result.as_ref().is_err_and(TransferError::is_out_of_file_descriptors) {
+                    throttle(&mut limit, tasks.len(), &mut tally);
+                }
+                match result {
+                    Ok(received) => {
+                        tally.iteration_rates.push(rate_over(sent + received, latency));
+                        tally.record_success(sent, received, latency)
+                    }
+                    // Dropping `tasks` on the way out aborts the rest
+                    Err(e) if options.fail_fast => return Err(abort(done, e)),
+                    Err(e) => tally.record_error(e),
+                }
+            }
+            Some(Err(e)) => {
+                let error = format!("iteration task failed: {}", e);
+                let iteration = IterationResult {
+                    index: done,
+                    bytes_sent: 0,
+                    bytes_received: 0,
+                    duration: Duration::ZERO,
+                    error: Some(error.clone()),
+                };
+                on_iteration(&iteration);
+                if options.keep_records {
+                    tally.records.push(IterationRecord::new(&iteration, Duration::ZERO));
+                }
+                if options.fail_fast {
+                    return Err(abort(done, TransferError::Io(std::io::Error::other(error))));
+                }
+                tally.record_failure(error);
+            }
+            None => break,
+        }
+        done += 1;
+        // An adaptive count only grows once the whole batch is in
+        if started == planned && tasks.is_empty() {
+            planned = tally.next_batch(planned, options);
+        }
+    }
+
+    // Connects and think time overlap across slots alike
+    let slots = slots as u32;
+    let overlapped_connects = tally.connect.connect_time / slots;
+    let overlapped_thinking = think_time * done as u32 / slots;
+    let first_start = match tally.starts.iter().min() {
+        Some(&first) if options.burst => first,
+        _ => start_time,
+    };
+    let wall_time = options.clock.elapsed_since(first_start);
+    Ok((tally, wall_time.saturating_sub(overlapped_connects + overlapped_thinking)))
+}
+
+// Out of file descriptors, the slots beyond the `in_flight` iterations still holding
+// theirs would only fail the same way, so no more than those are started from here on
+fn throttle(limit: &mut usize, in_flight: usize, tally: &mut Tally) {
+    let lowered = in_flight.max(1);
+    if lowered >= *limit {
+        return;
+    }
+    // Iterations that ran out together lower it one by one, so only the first is news
+    if tally.throttled_concurrency.is_none() {
+        warning!(
+            "out of file descriptors; running fewer iterations at once",
+            concurrency = *limit,
+            lowered_to = lowered,
+        );
+    } else {
+        debug!("running fewer iterations at once", lowered_to = lowered);
+    }
+    *limit = lowered;
+    tally.throttled_concurrency = Some(lowered);
+}
+
+// Payload bytes for the next iteration: the whole payload, or with total_bytes what is
+// left of the total after the bytes already sent and the `in_flight` shares of iterations
+// still running, at most a payload's worth. None once nothing is left, or once as many
+// iterations have failed as the total takes.
+fn next_share(
+    options: &TransferOptions,
+    size: usize,
+    tally: &Tally,
+    in_flight: usize,
+) -> Option<usize> {
+    let Some(total) = options.total_bytes else { return Some(size) };
+    let left = total.saturating_sub(tally.total_sent + in_flight);
+    (left > 0 && tally.errors.len() < total.div_ceil(size)).then(|| left.min(size))
+}
+
+// When a measurement limited to `max_duration` stops starting iterations. Real time,
+// like the other timeouts; None without a limit or with one too far off to represent.
+pub(crate) fn deadline_after(max_duration: Option<Duration>) -> Option<Instant> {
+    max_duration.and_then(|limit| Instant::now().checked_add(limit))
+}
+
+// Sleeps for `delay` between iterations, or only until `deadline` if that comes first
+async fn pause(delay: Duration, deadline: Option<Instant>) {
+    let delay = match deadline {
+        Some(deadline) => delay.min(deadline.saturating_duration_since(Instant::now())),
+        None => delay,
+    };
+    if !delay.is_zero() {
+        sleep(delay).await;
+    }
+}
+
+// The error that stops a fail_fast measurement, returned as is so callers can match on it
+fn abort(index: usize, error: TransferError) -> TransferError {
+    info!("aborting measurement after first failure", iteration = index, error = error);
+    error
+}
+
+// Raw numbers of one iteration, kept for offline analysis (TransferOptions::keep_records)
+#[derive(Debug, Clone, PartialEq)]
+pub struct IterationRecord {
+    pub index: usize, // as in IterationResult
+    pub bytes_sent: usize,
+    pub bytes_received: usize,
+    pub connect_time: Duration, // zero when a reused connection was already open
+    pub round_trip: Duration,   // the iteration's latency, connect included
+    pub error: Option<String>,
+}
+
+impl IterationRecord {
+    pub const CSV_HEADER: &'static str =
+        "size,iteration,bytes_sent,bytes_received,connect_secs,round_trip_secs,error";
+
+    // One CSV_HEADER row for an iteration that sent payload `size`; the error is quoted
+    pub fn csv_row(&self, size: usize) -> String {
+        let error = self
+            .error
+            .as_ref()
+            .map_or(String::new(), |e| format!("\"{}\"", e.replace('"', "\"\"")));
+        format!(
+            "{},{},{},{},{},{},{}",
+            size,
+            self.index,
+            self.bytes_sent,
+            self.bytes_received,
+            self.connect_time.as_secs_f64(),
+            self.round_trip.as_secs_f64(),
+            error
+        )
+    }
+
+    fn new(result: &IterationResult, connect_time: Duration) -> Self {
+        IterationRecord {
+            index: result.index,
+            bytes_sent: result.bytes_sent,
+            bytes_received: result.bytes_received,
+            connect_time,
+            round_trip: result.duration,
+            error: result.error.clone(),
+        }
+    }
+}
+
+// One finished iteration, as reported to measure_transfer_with_progress's callback
+#[derive(Debug, Clone, PartialEq)]
+pub struct IterationResult {
+    pub index: usize, // 0-based in completion order, which can differ from start order when concurrent
+    // Bytes this iteration added to the totals; both zero when it failed
+    pub bytes_sent: usize,
+    pub bytes_received: usize,
+    pub duration: Duration, // the iteration's round trip, connect included
+    pub error: Option<String>,
+}
+
+impl IterationResult {
+    fn new(
+        index: usize,
+        sent: usize,
+        result: &Result<usize, TransferError>,
+        duration: Duration,
+    ) -> Self {
+        let (bytes_sent, bytes_received, error) = match result {
+            Ok(received) => (sent, *received, None),
+            Err(e) => (0, 0, Some(e.to_string())),
+        };
+        IterationResult {
+            index,
+            bytes_sent,
+            bytes_received,
+            duration,
+            error,
+        }
+    }
+}
+
+// Running totals for one measurement, shared by the TCP and UDP paths so their
+// rates are directly comparable
+#[derive(Debug, Default)]
+pub(crate) struct Tally {
+    total_sent: usize,
+    total_received: usize,
+    successful_iterations: usize,
+    errors: Vec<String>,
+    latencies: Vec<Duration>,
+    starts: Vec<Instant>, // of every measured iteration, in no particular order
+    response_sizes: Vec<usize>,
+    corrupted_iterations: usize,
+    bytes_written_before_failure: usize,
+    premature_eofs: usize,    // of the recorded errors
+    connection_resets: usize, // of the recorded errors, PartialWrite causes included
+    failed_connects: usize,   // of the recorded errors
+    throttled_concurrency: Option<usize>,
+    http2_streams: Option<usize>,
+    iteration_rates: Vec<f64>, // of successful iterations, only recorded by run_concurrent
+    connect: ConnectCosts,
+    phases: PhaseTimes,
+    last_error: Option<TransferError>, // typed form of the newest entry in `errors`, if any
+    interrupted: bool,
+    time_limited: bool,
+    records: Vec<IterationRecord>,
+}
+
+impl Tally {
+    pub(crate) fn record_success(&mut self, sent: usize, received: usize, latency: Duration) {
+        self.total_sent += sent;
+        self.total_received += received;
+        self.successful_iterations += 1;
+        self.latencies.push(latency);
+        self.response_sizes.push(received);
+    }
+
+    // The iterations to plan in total once `planned` have finished: more only with
+    // auto_iterations, while some succeeded but the margin is still too wide
+    pub(crate) fn next_batch(&self, planned: usize, options: &TransferOptions) -> usize {
+        let Some(auto) = options.auto_iterations else { return planned };
+        if self.interrupted
+            || self.time_limited
+            || self.successful_iterations == 0
+            || planned >= auto.max_iterations
+        {
+            return planned;
+        }
+        match confidence::relative_margin_95(&rate_samples(&self.response_sizes, &self.latencies))
+        {
+            Some(margin) if margin <= auto.target_precision => planned,
+            _ => (planned * 2).max(planned + 1).min(auto.max_iterations),
+        }
+    }
+
+    // The phase totals spread over the iterations measured
+    fn phase_breakdown(&self) -> PhaseBreakdown {
+        let iterations = (self.successful_iterations + self.errors.len()) as u32;
+        if iterations == 0 {
+            return PhaseBreakdown::default();
+        }
+        PhaseBreakdown {
+            connect: self.connect.connect_time / iterations,
+            send: self.phases.send / iterations,
+            wait: self.phases.wait / iterations,
+            receive: self.phases.receive / iterations,
+        }
+    }
+
+    // Connections opened by a path that doesn't go through connect(), e.g. HTTP's pool
+    pub(crate) fn record_connections(&mut self, opened: usize) {
+        self.connect.connects += opened;
+    }
+
+    pub(crate) fn record_http2_streams(&mut self, peak: usize) {
+        self.http2_streams = Some(peak);
+    }
+
+    // Whether `cancel` asks to stop, noting it in the stats when it does
+    pub(crate) fn stop_requested(&mut self, cancel: Option<&watch::Receiver<bool>>) -> bool {
+        self.interrupted |= cancel.is_some_and(|cancel| *cancel.borrow());
+        self.interrupted
+    }
+
+    // Whether `deadline` (see deadline_after) has passed, noting it in the stats when it
+    // has
+    pub(crate) fn deadline_passed(&mut self, deadline: Option<Instant>) -> bool {
+        self.time_limited |= deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        self.time_limited
+    }
+
+    pub(crate) fn record_failure(&mut self, error: String) {
+        self.errors.push(error);
+        self.last_error = None;
+    }
+
+    pub(crate) fn record_error(&mut self, error: TransferError) {
+        match error {
+            TransferError::Corrupted { .. } => self.corrupted_iterations += 1,
+            TransferError::PartialWrite { written, .. } => {
+                self.bytes_written_before_failure += written
+            }
+            _ if error.is_premature_eof() => self.premature_eofs += 1,
+            _ => {}
+        }
+        if error.is_connection_reset() {
+            self.connection_resets += 1;
+        }
+        let connect_timeout = matches!(error, TransferError::Timeout { phase: "connect", .. });
+        if connect_timeout || matches!(error, TransferError::Connect(_)) {
+            self.failed_connects += 1;
+        }
+        self.errors.push(error.to_string());
+        self.last_error = Some(error);
+    }
+
+    pub(crate) fn into_stats(mut self, elapsed: Duration) -> Result<NetworkStats, TransferError> {
+        if self.successful_iterations == 0 {
+            if let Some(last) = self.errors.pop() {
+                return Err(TransferError::AllFailed {
+                    iterations: self.errors.len() + 1,
+                    retries: self.connect.retries,
+                    last,
+                    cause: self.last_error.map(Box::new),
+                });
+            }
+        }
+
+        let upload_rate = rate_over(self.total_sent, elapsed);
+        let download_rate = rate_over(self.total_received, elapsed);
+        let ratio = if self.total_sent > 0 {
+            self.total_received as f64 / self.total_sent as f64
+        } else {
+            0.0
+        };
+
+        let min_latency = self.latencies.iter().min().copied().unwrap_or_default();
+        let max_latency = self.latencies.iter().max().copied().unwrap_or_default();
+        let mean_latency = if self.latencies.is_empty() {
+            Duration::ZERO
+        } else {
+            self.latencies.iter().sum::<Duration>() / self.latencies.len() as u32
+        };
+        let jitter = if self.latencies.len() < 2 {
+            Duration::ZERO
+        } else {
+            let mean = mean_latency.as_secs_f64();
+            let variance = self
+                .latencies
+                .iter()
+                .map(|latency| (latency.as_secs_f64() - mean).powi(2))
+                .sum::<f64>()
+                / self.latencies.len() as f64;
+            Duration::from_secs_f64(variance.sqrt())
+        };
+
+        let phases = self.phase_breakdown();
+        self.starts.sort();
+        let start_gaps = self.starts.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        Ok(NetworkStats {
+            bytes_sent: self.total_sent,
+            bytes_received: self.total_received,
+            elapsed_time: elapsed,
+            upload_rate,
+            download_rate,
+            ratio,
+            successful_iterations: self.successful_iterations,
+            failed_iterations: self.errors.len(),
+            corrupted_iterations: self.corrupted_iterations,
+            bytes_written_before_failure: self.bytes_written_before_failure,
+            errors: self.errors,
+            retries: self.connect.retries,
+            premature_eofs: self.premature_eofs + self.connect.eof_retries,
+            connection_resets: self.connection_resets,
+            failed_connects: self.failed_connects,
+            throttled_concurrency: self.throttled_concurrency,
+            http2_streams: self.http2_streams,
+            iteration_fairness: IterationFairness::of(&self.iteration_rates),
+            dns_resolution_time: self.connect.dns_time,
+            connect_time: self.connect.connect_time,
+            mean_connect_time: if self.connect.connects == 0 {
+                Duration::ZERO
+            } else {
+                self.connect.connect_time / self.connect.connects as u32
+            },
+            connections: self.connect.connects,
+            ipv4_connections: self.connect.ipv4_connects,
+            ipv6_connections: self.connect.ipv6_connects,
+            local_address: self.connect.local_address,
+            min_latency,
+            max_latency,
+            mean_latency,
+            jitter,
+            time_to_first_byte: self.phases.mean_wait(),
+            phases,
+            latencies: self.latencies,
+            start_gaps,
+            response_sizes: self.response_sizes,
+            seed: None,
+            requests_per_connection: 1,
+            interrupted: self.interrupted,
+            time_limited: self.time_limited,
+            burst: false,
+            records: self.records,
+            auto_iterations: None,
+            total_bytes_iterations: None,
+            cpu_utilization: None,
+            write_chunk_size: None,
+            single_write_upload_rate: None,
+            label: None,
+            timestamp: Some(SystemTime::now()),
+            config_summary: String::new(),
+        })
+    }
+}
+
+// Same measurement as measure_transfer but against a UDP echo service: one datagram out,
+// one echo back per iteration. Dropped datagrams count as failed iterations.
+pub async fn measure_transfer_udp(
+    address: &str,
+    data_size: usize,
+    iterations: usize,
+) -> Result<NetworkStats, TransferError> {
+    let options = TransferOptions { timeout: UDP_ECHO_TIMEOUT, ..TransferOptions::default() };
+    measure_transfer_udp_with_options(address, data_size, iterations, &options).await
+}
+
+// measure_transfer_udp with the timeout, delay, cancel, max_duration and fail_fast
+// settings of `options`; an echo that takes longer than the timeout counts as lost. The
+// sequence number each datagram opens with tells a late echo of an earlier iteration
+// apart from the one being waited for, so it's dropped instead of counted again.
+pub async fn measure_transfer_udp_with_options(
+    address: &str,
+    data_size: usize,
+    iterations: usize,
+    options: &TransferOptions,
+) -> Result<NetworkStats, TransferError> {
+    check_iterations(iterations)?;
+    if data_size < UDP_SEQUENCE_LEN {
+        return Err(TransferError::InvalidOptions(format!(
+            "UDP datagrams need at least {} bytes for their sequence number, got {}",
+            UDP_SEQUENCE_LEN, data_size
+        )));
+    }
+    let target = with_timeout("resolve", options.timeout, async {
+        Ok(tokio::net::lookup_host(address).await?.next())
+    })
+    .await?
+    .ok_or_else(|| {
+        TransferError::Resolve(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} did not resolve to any address", address),
+        ))
+    })?;
+    let local = if target.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+    let socket = UdpSocket::bind(local).await.map_err(|e| TransferError::during("connect", e))?;
+    socket.connect(target).await.map_err(|e| TransferError::during("connect", e))?;
+
+    let mut tally = Tally::default();
+    let mut active_time = Duration::ZERO;
+    let mut data = vec![0u8; data_size];
+    let mut buffer = vec![0u8; 65536]; // large enough for any UDP datagram
+    let deadline = deadline_after(options.max_duration);
+    for index in 0..iterations {
+        if index > 0
+            && (tally.stop_requested(options.cancel.as_ref()) || tally.deadline_passed(deadline))
+        {
+            break;
+        }
+        let sequence = (index as u64).to_be_bytes();
+        data[..UDP_SEQUENCE_LEN].copy_from_slice(&sequence);
+        let iteration_start = Instant::now();
+        let result = async {
+            let sent = socket.send(&data).await.map_err(|e| TransferError::during("write", e))?;
+            let received = with_timeout("read", options.timeout, async {
+                loop {
+                    let n = socket.recv(&mut buffer).await?;
+                    if buffer[..n].starts_with(&sequence) {
+                        return Ok(n);
+                    }
+                }
+            })
+            .await?;
+            Ok((sent, received))
+        }
+        .await;
+        let latency = iteration_start.elapsed();
+        match result {
+            Ok((sent, received)) => tally.record_success(sent, received, latency),
+            Err(e) if options.fail_fast => return Err(abort(index, e)),
+            Err(e) => tally.record_error(e),
+        }
+        active_time += latency;
+
+        pause(options.delay, deadline).await;
+    }
+
+    tally.into_stats(active_time)
+}
+
+// measure_transfer over the Unix domain socket at `path`, e.g. one served by
+// run_mock_server_uds: the same framing and NetworkStats without TCP/IP in the way,
+// as a baseline for the TCP numbers
+#[cfg(unix)]
+pub async fn measure_transfer_uds(
+    path: impl AsRef<Path>,
+    data_size: usize,
+    iterations: usize,
+) -> Result<NetworkStats, TransferError> {
+    let options = TransferOptions::default();
+    measure_transfer_uds_with_options(path, data_size, iterations, &options).await
+}
+
+// measure_transfer_uds with the timeout, delay, cancel, pacing, direction, verify and
+// connection reuse settings of `options`. Iterations always run one after another;
+// warmup, retries and the TCP socket options don't apply.
+#[cfg(unix)]
+pub async fn measure_transfer_uds_with_options(
+    path: impl AsRef<Path>,
+    data_size: usize,
+    iterations: usize,
+    options: &TransferOptions,
+) -> Result<NetworkStats, TransferError> {
+    check_iterations(iterations)?;
+    check_target_rate(options)?;
+    let path = path.as_ref();
+    let data = if options.verify {
+        verify::fill_pattern(data_size)
+    } else {
+        vec![0u8; data_size]
+    };
+    let sent = request_bytes(data_size, options) * options.requests_per_connection;
+
+    let mut tally = Tally::default();
+    let mut active_time = Duration::ZERO;
+    let mut connection = None;
+    let cpu_start = cpu::process_cpu_time();
+    let deadline = deadline_after(options.max_duration);
+    for index in 0..iterations {
+        if index > 0
+            && (tally.stop_requested(options.cancel.as_ref()) || tally.deadline_passed(deadline))
+        {
+            break;
+        }
+        let iteration_start = options.clock.now();
+        let result = async {
+            let stream = match &mut connection {
+                Some(stream) => stream,
+                None => {
+                    let connect_start = options.clock.now();
+                    let stream = with_timeout(
+                        "connect",
+                        options.timeout,
+                        tokio::net::UnixStream::connect(path),
+                    )
+                    .await;
+                    tally.connect.connects += 1;
+                    tally.connect.connect_time += options.clock.elapsed_since(connect_start);
+                    connection.insert(stream?)
+                }
+            };
+            conversation(stream, Body::Bytes(&data), options, &mut tally.phases).await
+        }
+        .await;
+        if result.is_err() || !options.reuse_connection {
+            connection = None;
+        }
+        let latency = options
+            .clock
+            .elapsed_since(iteration_start)
+            .saturating_sub(think_time_per_iteration(options));
+        match result {
+            Ok(received) => tally.record_success(sent, received, latency),
+            Err(e) if options.fail_fast => return Err(abort(index, e)),
+            Err(e) => tally.record_error(e),
+        }
+        active_time += latency;
+
+        pause(options.delay, deadline).await;
+    }
+
+    let transfer_time = active_time.saturating_sub(tally.connect.connect_time);
+    let mut stats = tally.into_stats(transfer_time)?;
+    stats.cpu_utilization = cpu::utilization_since(cpu_start, transfer_time);
+    Ok(stats)
+}
+
+// Result of measure_stream: the usual totals plus the rate in each sampling window
+#[derive(Debug)]
+pub struct StreamSamples {
+    pub stats: NetworkStats,
+    pub interval: Duration,
+    pub upload: Vec<(Duration, f64)>,   // (offset from connect, bytes per second)
+    pub download: Vec<(Duration, f64)>, // same time origin as `upload`
+}
+
+// Sends one `data_size` payload in chunks over a single connection and reads the reply,
+// sampling throughput every `interval` so ramp-up and stalls are visible
+pub async fn measure_stream(
+    address: &str,
+    data_size: usize,
+    interval: Duration,
+    options: &TransferOptions,
+) -> Result<StreamSamples, TransferError> {
+    check_target_rate(options)?;
+    if options.direction != Direction::Both {
+        return Err(TransferError::InvalidOptions(
+            "streamed measurements always send and receive".to_string(),
+        ));
+    }
+    validate_address(address).await?;
+    let mut costs = ConnectCosts::default();
+    let mut stream = connect(address, options, &mut costs).await?;
+
+    let start = Instant::now();
+    let cpu_start = cpu::process_cpu_time();
+    let mut upload = BandwidthSampler::starting_at(start, interval);
+    let mut download = BandwidthSampler::starting_at(start, interval);
+
+    let pacer = options.target_rate.map(Pacer::new);
+    let chunk_len = pacer.as_ref().map_or(STREAM_CHUNK, Pacer::chunk_len);
+    let chunk = vec![0u8; chunk_len.min(data_size.max(1))];
+    // The payload goes out as one frame whose header is written up front, so the
+    // chunks can still be timed individually
+    let header = framing::frame_header(data_size)?;
+    let mut sent = 0;
+    with_timeout("write", options.timeout, write_counted(&mut stream, &header, &mut 0))
+        .await
+        .map_err(|e| partial_write(0, data_size, e))?;
+    while sent < data_size {
+        let len = chunk.len().min(data_size - sent);
+        let mut written = 0;
+        let write = write_counted(&mut stream, &chunk[..len], &mut written);
+        with_timeout("write", options.timeout, write)
+            .await
+            .map_err(|e| partial_write(sent + written, data_size, e))?;
+        upload.record(len);
+        sent += len;
+        if let Some(pacer) = &pacer {
+            pacer.wait(sent).await;
+        }
+    }
+    finish_request(&mut stream, options).await?;
+
+    let mut buffer = vec![0u8; options.recv_buffer_size.max(1)];
+    let sent_at = Instant::now();
+    let expected = with_timeout("read", options.timeout, framing::read_frame_len(&mut stream))
+        .await?
+        .ok_or_else(|| TransferError::Read(std::io::ErrorKind::UnexpectedEof.into()))?;
+    let first_at = Instant::now();
+    let expected = check_response_len(expected, options).map_err(TransferError::Read)?;
+    let mut received = 0;
+    while received < expected {
+        let want = (expected - received).min(buffer.len());
+        let n = with_timeout("read", options.timeout, stream.read(&mut buffer[..want])).await?;
+        if n == 0 {
+            return Err(TransferError::Read(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+        download.record(n);
+        received += n;
+    }
+    let phases = PhaseTimes {
+        send: sent_at - start,
+        wait: first_at - sent_at,
+        receive: first_at.elapsed(),
+        responses: 1,
+    };
+
+    let elapsed = start.elapsed();
+    let mut tally = Tally {
+        connect: costs,
+        phases,
+        ..Tally::default()
+    };
+    tally.record_success(sent, received, elapsed);
+    let mut stats = tally.into_stats(elapsed)?;
+    stats.cpu_utilization = cpu::utilization_since(cpu_start, elapsed);
+    Ok(StreamSamples {
+        stats,
+        interval: upload.interval(),
+        upload: upload.rates(),
+        download: download.rates(),
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct KeepaliveOptions {
+    pub interval: Duration, // idle time before each ping
+    pub ping_size: usize,
+    // Stop with the connection still up once it has lasted this long; None keeps going
+    // until it drops or `transfer.cancel` is set
+    pub limit: Option<Duration>,
+    // Timeout, retries, socket options, clock and cancel; the pings are plain exchanges
+    pub transfer: TransferOptions,
+}
+
+impl Default for KeepaliveOptions {
+    fn default() -> Self {
+        KeepaliveOptions {
+            interval: Duration::from_secs(1),
+            ping_size: 64,
+            limit: None,
+            transfer: TransferOptions::default(),
+        }
+    }
+}
+
+// How a keepalive measurement ended
+#[derive(Debug)]
+pub enum KeepaliveEnd {
+    // A ping failed, `noticed_after` the connect: the server closed or reset the
+    // connection, or stopped answering within the timeout
+    Dropped { error: TransferError, noticed_after: Duration },
+    Limit, // KeepaliveOptions::limit was reached with the connection up
+    Cancelled,
+}
+
+#[derive(Debug)]
+pub struct KeepaliveReport {
+    // Connect to the last ping answered; a drop happened within one interval after it
+    pub alive_for: Duration,
+    pub end: KeepaliveEnd,
+    pub pings: Option<NetworkStats>, // the answered pings; None if none were
+}
+
+// Opens one connection and keeps it busy with a small ping every `interval`, until a
+// ping fails, to find out how long a server lets a quiet connection live. Connecting
+// failing is an error; the connection dropping later is the result.
+pub async fn measure_keepalive(
+    address: &str,
+    options: &KeepaliveOptions,
+) -> Result<KeepaliveReport, TransferError> {
+    let transfer = &options.transfer;
+    check_target_rate(transfer)?;
+    if transfer.direction != Direction::Both {
+        return Err(TransferError::InvalidOptions(
+            "keepalive pings need their responses to tell the connection is up".to_string(),
+        ));
+    }
+    validate_address(address).await?;
+    let mut costs = ConnectCosts::default();
+    let mut stream = connect(address, transfer, &mut costs).await?;
+    let clock = &transfer.clock;
+    let connected = clock.now();
+
+    let ping = vec![0u8; options.ping_size];
+    let mut tally = Tally { connect: costs, ..Tally::default() };
+    let mut active_time = Duration::ZERO;
+    let mut alive_for = Duration::ZERO;
+    let end = loop {
+        if options.limit.is_some_and(|limit| clock.elapsed_since(connected) >= limit) {
+            break KeepaliveEnd::Limit;
+        }
+        if sleep_unless_cancelled(options.interval, transfer.cancel.as_ref()).await {
+            tally.interrupted = true;
+            break KeepaliveEnd::Cancelled;
+        }
+        let start = clock.now();
+        match exchange(&mut stream, Body::Bytes(&ping), transfer, &mut tally.phases).await {
+            Ok(received) => {
+                let latency = clock.elapsed_since(start);
+                tally.record_success(ping.len(), received, latency);
+                active_time += latency;
+                alive_for = clock.elapsed_since(connected);
+            }
+            Err(error) => {
+                let noticed_after = clock.elapsed_since(connected);
+                break KeepaliveEnd::Dropped { error, noticed_after };
+            }
+        }
+    };
+
+    let pings = match tally.successful_iterations {
+        0 => None,
+        _ => Some(tally.into_stats(active_time)?),
+    };
+    Ok(KeepaliveReport { alive_for, end, pings })
+}
+
+// Sleeps for `duration`, returning true early if `cancel` is or becomes true
+async fn sleep_unless_cancelled(
+    duration: Duration,
+    cancel: Option<&watch::Receiver<bool>>,
+) -> bool {
+    let Some(mut cancel) = cancel.cloned() else {
+        sleep(duration).await;
+        return false;
+    };
+    // Only the outcome leaves the wait: the borrow it returns would make the future !Send
+    let stopped = async { cancel.wait_for(|stop| *stop).await.is_ok() };
+    tokio::select! {
+        _ = sleep(duration) => false,
+        stopped = stopped => {
+            if stopped {
+                return true;
+            }
+            // A dropped sender can't cancel any more
+            sleep(duration).await;
+            false
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParallelOptions {
+    pub streams: usize,     // connections pushing at once
+    pub duration: Duration, // how long each one pushes, by the system clock
+    pub frame_size: usize,  // payload of each NO_REPLY frame a stream writes
+    // Timeout, retries, socket options, pacing (per stream) and cancel. The streams
+    // always push upload-only frames, so `direction` and the iteration settings don't
+    // apply.
+    pub transfer: TransferOptions,
+}
+
+impl Default for ParallelOptions {
+    fn default() -> Self {
+        ParallelOptions {
+            streams: 4,
+            duration: Duration::from_secs(10),
+            frame_size: 1024 * 1024,
+            transfer: TransferOptions::default(),
+        }
+    }
+}
+
+// One stream of a parallel measurement
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamRate {
+    pub bytes_sent: usize,
+    pub elapsed: Duration, // from the common start to the server confirming the last frame
+    pub upload_rate: f64,
+    pub error: Option<String>, // why the stream ended early; its bytes up to then count
+}
+
+#[derive(Debug)]
+pub struct ParallelStats {
+    pub streams: Vec<StreamRate>, // in the order they were opened
+    pub bytes_sent: usize,
+    pub elapsed: Duration, // from the common start until the last stream finished
+    pub upload_rate: f64, // all the streams' bytes over `elapsed`
+    pub connect_time: Duration, // opening every stream, before the start
+    pub interrupted: bool, // stopped early through `transfer.cancel`
+}
+
+impl ParallelStats {
+    fn rates(&self) -> impl Iterator<Item = f64> + '_ {
+        self.streams.iter().map(|stream| stream.upload_rate)
+    }
+
+    // Zero without streams, as are the other per-stream figures
+    pub fn min_stream_rate(&self) -> f64 {
+        self.rates().reduce(f64::min).unwrap_or(0.0)
+    }
+
+    pub fn max_stream_rate(&self) -> f64 {
+        self.rates().reduce(f64::max).unwrap_or(0.0)
+    }
+
+    // Population standard deviation of the per-stream rates
+    pub fn stream_rate_std_dev(&self) -> f64 {
+        let n = self.streams.len() as f64;
+        if n == 0.0 {
+            return 0.0;
+        }
+        let mean = self.rates().sum::<f64>() / n;
+        (self.rates().map(|rate| (rate - mean).powi(2)).sum::<f64>() / n).sqrt()
+    }
+
+    // Jain's fairness index of the per-stream rates: 1 when every stream got the same
+    // share, down to 1/streams when one stream got everything
+    pub fn fairness(&self) -> f64 {
+        confidence::jain_index(&self.rates().collect::<Vec<_>>()).unwrap_or(0.0)
+    }
+}
+
+// Opens `options.streams` connections, then has each push back-to-back upload-only
+// frames for `options.duration`, all starting together, to see how much bandwidth they
+// get between them. Each stream ends with an answered request, so the server has
+// read all its bytes by the time its clock stops. A stream that can't connect fails
+// the measurement; one that fails later ends early, with what it sent until then.
+pub async fn measure_parallel(
+    address: &str,
+    options: &ParallelOptions,
+) -> Result<ParallelStats, TransferError> {
+    let transfer =
+        TransferOptions { direction: Direction::UploadOnly, ..options.transfer.clone() };
+    check_target_rate(&transfer)?;
+    if options.streams == 0 {
+        return Err(TransferError::InvalidOptions("parallel streams must be at least 1".into()));
+    }
+    check_frame_len(options.frame_size)?;
+    validate_address(address).await?;
+
+    let mut costs = ConnectCosts::default();
+    let mut connections = Vec::with_capacity(options.streams);
+    for _ in 0..options.streams {
+        connections.push(connect(address, &transfer, &mut costs).await?);
+    }
+    let frame = Arc::new(vec![0u8; options.frame_size]);
+    let start = Instant::now();
+    let mut tasks = JoinSet::new();
+    for (index, mut connection) in connections.into_iter().enumerate() {
+        let (frame, transfer) = (frame.clone(), transfer.clone());
+        let duration = options.duration;
+        tasks.spawn(async move {
+            let (mut sent, mut interrupted) = (0, false);
+            let mut phases = PhaseTimes::default();
+            let pushed = async {
+                while start.elapsed() < duration {
+                    if transfer.cancel.as_ref().is_some_and(|cancel| *cancel.borrow()) {
+                        interrupted = true;
+                        break;
+                    }
+                    exchange(&mut connection, Body::Bytes(&frame), &transfer, &mut phases)
+                        .await?;
+                    sent += frame.len();
+                }
+                // Answered only once everything before it has been read
+                let confirm = TransferOptions { direction: Direction::Both, ..transfer.clone() };
+                exchange(&mut connection, Body::Bytes(&[]), &confirm, &mut phases).await
+            }
+            .await;
+            let elapsed = start.elapsed();
+            let rate = StreamRate {
+                bytes_sent: sent,
+                elapsed,
+                upload_rate: rate_over(sent, elapsed),
+                error: pushed.err().map(|e| e.to_string()),
+            };
+            (index, rate, interrupted)
+        });
+    }
+
+    let mut streams = vec![None; options.streams];
+    let mut interrupted = false;
+    while let Some(joined) = tasks.join_next().await {
+        let (index, rate, stopped) =
+            joined.map_err(|e| TransferError::Io(std::io::Error::other(e)))?;
+        streams[index] = Some(rate);
+        interrupted |= stopped;
+    }
+    let streams: Vec<StreamRate> = streams.into_iter().flatten().collect();
+    let bytes_sent = streams.iter().map(|stream| stream.bytes_sent).sum();
+    let elapsed = streams.iter().map(|stream| stream.elapsed).max().unwrap_or_default();
+    Ok(ParallelStats {
+        upload_rate: rate_over(bytes_sent, elapsed),
+        streams,
+        bytes_sent,
+        elapsed,
+        connect_time: costs.connect_time,
+        interrupted,
+    })
+}
+
+// Reproduces a recorded request cadence over one connection: for each (delay, size)
+// entry, waits `delay` after the previous response, then sends a `size` payload and
+// reads the reply. Each entry is one iteration in the stats; the waits and connecting
+// are left out of elapsed_time and the rates, as between ordinary iterations.
+pub async fn replay(
+    address: &str,
+    schedule: &[(Duration, usize)],
+) -> Result<NetworkStats, TransferError> {
+    replay_with_options(address, schedule, &TransferOptions::default()).await
+}
+
+// replay with the timeout, retries, pacing, socket options and verify mode of `options`.
+// `delay`, concurrency, warmup and the conversation settings don't apply: the schedule
+// sets the timing and is sent one entry after another.
+pub async fn replay_with_options(
+    address: &str,
+    schedule: &[(Duration, usize)],
+    options: &TransferOptions,
+) -> Result<NetworkStats, TransferError> {
+    if schedule.is_empty() {
+        return Err(TransferError::InvalidOptions("the replay schedule is empty".to_string()));
+    }
+    if options.half_close_after_send {
+        return Err(TransferError::InvalidOptions(
+            "a half-closed connection can't carry another request".to_string(),
+        ));
+    }
+    check_target_rate(options)?;
+    validate_address(address).await?;
+
+    // Every request is a prefix of one payload, generated as it goes out when it's big;
+    // the verify pattern only depends on the offset, so its prefixes are valid requests too
+    let largest = schedule.iter().map(|&(_, size)| size).max().unwrap_or(0);
+    check_frame_len(largest)?;
+    let source = PayloadSource::Zeros(largest);
+    let data = match source.generated(options.verify) {
+        Some(generated) => Payload::Generated(generated),
+        None if options.verify => Payload::Loaded(Arc::new(verify::fill_pattern(largest))),
+        None => Payload::Loaded(Arc::new(source.load()?)),
+    };
+
+    let mut tally = Tally::default();
+    let mut active_time = Duration::ZERO;
+    let mut connection = None;
+    let deadline = deadline_after(options.max_duration);
+    for (index, &(delay, size)) in schedule.iter().enumerate() {
+        if index > 0 && tally.stop_requested(options.cancel.as_ref()) {
+            break;
+        }
+        // A request due after the deadline isn't sent
+        pause(delay, deadline).await;
+        if index > 0 && tally.deadline_passed(deadline) {
+            break;
+        }
+        let start = options.clock.now();
+        let connect_before = tally.connect.connect_time;
+        let body = data.body().prefix(size);
+        let result = replay_request(&mut connection, address, body, options, &mut tally).await;
+        let latency = options.clock.elapsed_since(start);
+        if options.keep_records {
+            let sent = request_bytes(size, options);
+            let iteration = IterationResult::new(index, sent, &result, latency);
+            let connect_time = tally.connect.connect_time - connect_before;
+            tally.records.push(IterationRecord::new(&iteration, connect_time));
+        }
+        match result {
+            Ok(received) => tally.record_success(request_bytes(size, options), received, latency),
+            Err(e) if options.fail_fast => return Err(abort(index, e)),
+            Err(e) => tally.record_error(e),
+        }
+        active_time += latency;
+    }
+
+    let transfer_time = active_time.saturating_sub(tally.connect.connect_time);
+    let stats = tally.into_stats(transfer_time)?;
+    info!(
+        "schedule replayed",
+        address = address,
+        requests = schedule.len(),
+        sent = stats.bytes_sent,
+        received = stats.bytes_received,
+        failed = stats.failed_iterations,
+    );
+    Ok(stats)
+}
+
+// One exchange, dialling first if the connection was never opened or the previous
+// request failed
+async fn replay_request(
+    connection: &mut Option<TcpStream>,
+    address: &str,
+    body: Body<'_>,
+    options: &TransferOptions,
+    tally: &mut Tally,
+) -> Result<usize, TransferError> {
+    let stream = match connection {
+        Some(stream) => stream,
+        None => connection.insert(connect(address, options, &mut tally.connect).await?),
+    };
+    let result = exchange(stream, body, options, &mut tally.phases).await;
+    if result.is_err() {
+        *connection = None;
+    }
+    result
+}
+
+#[derive(Debug, Clone)]
+pub struct MixOptions {
+    pub seed: u64,            // picks the sizes, and the bytes of random payloads
+    pub random_payload: bool, // seeded random bytes instead of zeros
+    // delay, reuse_connection, the conversation settings, verify, cancel and fail_fast
+    // apply as in measure_transfer; iterations run one at a time, without warmup
+    pub transfer: TransferOptions,
+}
+
+impl Default for MixOptions {
+    fn default() -> Self {
+        MixOptions {
+            seed: DEFAULT_SEED,
+            random_payload: false,
+            transfer: TransferOptions::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MixBucket {
+    pub size: usize,
+    pub weight: f64,  // share of the iterations it was drawn with
+    pub drawn: usize, // iterations that used it
+    pub stats: Option<NetworkStats>, // None unless at least one of them succeeded
+}
 
 #[derive(Debug)]
-struct NetworkStats {
-    bytes_sent: usize,
-    bytes_received: usize,
-    elapsed_time: Duration,
-    upload_rate: f64,   // bytes per second
-    download_rate: f64, // bytes per second
-    ratio: f64,         // download/upload ratio
+pub struct MixStats {
+    pub buckets: Vec<MixBucket>, // in the mix's order
+    pub overall: NetworkStats,   // every iteration, whatever its size
+}
+
+// measure_transfer with each iteration's payload size drawn from `mix`. Each size's
+// iterations get stats of their own, and `overall` covers them all, so its rates are
+// those of the mixed traffic.
+pub async fn measure_mix(
+    address: &str,
+    mix: &SizeMix,
+    iterations: usize,
+    options: &MixOptions,
+) -> Result<MixStats, TransferError> {
+    let transfer = &options.transfer;
+    check_iterations(iterations)?;
+    check_target_rate(transfer)?;
+    if transfer.half_close_after_send && transfer.reuse_connection {
+        return Err(TransferError::InvalidOptions(
+            "a half-closed connection can't carry another request".to_string(),
+        ));
+    }
+    if options.random_payload && transfer.verify {
+        return Err(TransferError::InvalidOptions(
+            "verify mode sends its own pattern and can't use a random payload".to_string(),
+        ));
+    }
+    validate_address(address).await?;
+
+    let payloads = mix
+        .buckets()
+        .iter()
+        .map(|&(size, _)| {
+            check_frame_len(size)?;
+            let source = match options.random_payload {
+                true => PayloadSource::Random { size, seed: options.seed },
+                false => PayloadSource::Zeros(size),
+            };
+            Ok(match source.generated(transfer.verify) {
+                Some(generated) => Payload::Generated(generated),
+                None if transfer.verify => Payload::Loaded(Arc::new(verify::fill_pattern(size))),
+                None => Payload::Loaded(Arc::new(source.load()?)),
+            })
+        })
+        .collect::<Result<Vec<_>, TransferError>>()?;
+    let mut rng = SplitMix64::new(options.seed);
+    let mut overall = Tally::default();
+    let mut overall_time = Duration::ZERO;
+    let mut buckets: Vec<(Tally, Duration, usize)> =
+        mix.buckets().iter().map(|_| (Tally::default(), Duration::ZERO, 0)).collect();
+    let mut connection = None;
+    let cpu_start = cpu::process_cpu_time();
+    let deadline = deadline_after(transfer.max_duration);
+    for index in 0..iterations {
+        if index > 0
+            && (overall.stop_requested(transfer.cancel.as_ref())
+                || overall.deadline_passed(deadline))
+        {
+            break;
+        }
+        let picked = mix.pick(&mut rng);
+        let data = &payloads[picked];
+        let sent = request_bytes(data.len(), transfer) * transfer.requests_per_connection;
+        let start = transfer.clock.now();
+        let mut costs = ConnectCosts::default();
+        let mut phases = PhaseTimes::default();
+        let result = one_iteration(
+            &mut connection,
+            address,
+            data.body(),
+            transfer,
+            &mut costs,
+            &mut phases,
+        )
+        .await;
+        let latency = transfer
+            .clock
+            .elapsed_since(start)
+            .saturating_sub(think_time_per_iteration(transfer));
+        if transfer.keep_records {
+            let iteration = IterationResult::new(index, sent, &result, latency);
+            overall.records.push(IterationRecord::new(&iteration, costs.connect_time));
+        }
+
+        let (bucket, bucket_time, drawn) = &mut buckets[picked];
+        overall.connect.add(costs);
+        bucket.connect.add(costs);
+        overall.phases.add(phases);
+        bucket.phases.add(phases);
+        match result {
+            Ok(received) => {
+                overall.record_success(sent, received, latency);
+                bucket.record_success(sent, received, latency);
+            }
+            Err(e) if transfer.fail_fast => return Err(abort(index, e)),
+            Err(e) => {
+                bucket.record_failure(e.to_string());
+                overall.record_error(e);
+            }
+        }
+        overall_time += latency;
+        *bucket_time += latency;
+        *drawn += 1;
+
+        pause(transfer.delay, deadline).await;
+    }
+
+    let buckets = mix
+        .buckets()
+        .iter()
+        .zip(buckets)
+        .map(|(&(size, weight), (tally, time, drawn))| {
+            let elapsed = time.saturating_sub(tally.connect.connect_time);
+            let stats = match tally.successful_iterations {
+                0 => None,
+                _ => Some(tally.into_stats(elapsed)?),
+            };
+            Ok(MixBucket { size, weight, drawn, stats })
+        })
+        .collect::<Result<Vec<_>, TransferError>>()?;
+    let elapsed = overall_time.saturating_sub(overall.connect.connect_time);
+    let mut overall = overall.into_stats(elapsed)?;
+    overall.cpu_utilization = cpu::utilization_since(cpu_start, elapsed);
+    overall.seed = options.random_payload.then_some(options.seed);
+    overall.requests_per_connection = transfer.requests_per_connection;
+    info!(
+        "size mix measured",
+        address = address,
+        sent = overall.bytes_sent,
+        received = overall.bytes_received,
+        upload_rate = overall.upload_rate,
+        download_rate = overall.download_rate,
+        failed = overall.failed_iterations,
+    );
+    Ok(MixStats { buckets, overall })
+}
+
+// A measurement's payload, as its iterations share it
+#[derive(Debug, Clone)]
+enum Payload {
+    Loaded(Arc<Vec<u8>>),
+    Generated(Generated), // at least GENERATED_PAYLOAD_MIN bytes, made as it is sent
+}
+
+impl Payload {
+    fn len(&self) -> usize {
+        self.body().len()
+    }
+
+    fn body(&self) -> Body<'_> {
+        match self {
+            Payload::Loaded(bytes) => Body::Bytes(bytes),
+            Payload::Generated(generated) => Body::Generated(*generated),
+        }
+    }
+}
+
+// What one request sends
+#[derive(Debug, Clone, Copy)]
+enum Body<'a> {
+    Bytes(&'a [u8]),
+    Generated(Generated),
+}
+
+impl<'a> Body<'a> {
+    fn len(&self) -> usize {
+        match self {
+            Body::Bytes(bytes) => bytes.len(),
+            Body::Generated(generated) => generated.len,
+        }
+    }
+
+    // The first `len` bytes, e.g. the remainder a total_bytes measurement ends on
+    fn prefix(self, len: usize) -> Body<'a> {
+        match self {
+            Body::Bytes(bytes) => Body::Bytes(&bytes[..len.min(bytes.len())]),
+            Body::Generated(generated) => Body::Generated(generated.prefix(len)),
+        }
+    }
+}
+
+// A request body has to fit in one frame, which is checked up front so a huge size
+// fails at once instead of in every iteration
+fn check_frame_len(len: usize) -> Result<(), TransferError> {
+    if len > framing::MAX_FRAME_LEN {
+        return Err(TransferError::InvalidOptions(format!(
+            "a {}-byte payload is over the {}-byte limit of one frame",
+            len,
+            framing::MAX_FRAME_LEN
+        )));
+    }
+    Ok(())
+}
+
+// `connection` carries the persistent stream between calls in reuse_connection mode
+async fn one_iteration(
+    connection: &mut Option<TcpStream>,
+    address: &str,
+    data: Body<'_>,
+    options: &TransferOptions,
+    costs: &mut ConnectCosts,
+    phases: &mut PhaseTimes,
+) -> Result<usize, TransferError> {
+    let mut eof_retries = 0;
+    loop {
+        let result = if options.reuse_connection {
+            exchange_on(connection, address, data, options, costs, phases).await
+        } else {
+            transfer_once(address, data, options, costs, phases).await
+        };
+        // exchange_on has already dropped the connection that closed, so both redial
+        match result {
+            Err(e) if e.is_premature_eof() && eof_retries < options.eof_retries => {
+                eof_retries += 1;
+                costs.eof_retries += 1;
+                debug!("retrying after premature EOF", address = address, attempt = eof_retries);
+            }
+            result => return result,
+        }
+    }
+}
+
+// One connect plus conversation on a fresh connection, returning the response
+// payload bytes
+async fn transfer_once(
+    address: &str,
+    data: Body<'_>,
+    options: &TransferOptions,
+    costs: &mut ConnectCosts,
+    phases: &mut PhaseTimes,
+) -> Result<usize, TransferError> {
+    let mut stream = connect(address, options, costs).await?;
+    conversation(&mut stream, data, options, phases).await
+}
+
+// `options.requests_per_connection` exchanges separated by the think time, returning
+// the response payload bytes of all of them. Any failed exchange fails the whole
+// conversation.
+async fn conversation<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    data: Body<'_>,
+    options: &TransferOptions,
+    phases: &mut PhaseTimes,
+) -> Result<usize, TransferError> {
+    let mut received = 0;
+    for request in 0..options.requests_per_connection {
+        if request > 0 && !options.think_time.is_zero() {
+            sleep(options.think_time).await;
+        }
+        received += exchange(stream, data, options, phases).await?;
+    }
+    Ok(received)
+}
+
+// Payload bytes one request of `size` counts as sent
+fn request_bytes(size: usize, options: &TransferOptions) -> usize {
+    match options.direction {
+        Direction::DownloadOnly => 0,
+        Direction::Both | Direction::UploadOnly => size,
+    }
+}
+
+fn think_time_per_iteration(options: &TransferOptions) -> Duration {
+    options.think_time * options.requests_per_connection.saturating_sub(1) as u32
+}
+
+// One conversation on a persistent connection, connecting first if needed.
+// A failed exchange drops the connection so the next one redials.
+async fn exchange_on(
+    connection: &mut Option<TcpStream>,
+    address: &str,
+    data: Body<'_>,
+    options: &TransferOptions,
+    costs: &mut ConnectCosts,
+    phases: &mut PhaseTimes,
+) -> Result<usize, TransferError> {
+    let stream = match connection {
+        Some(stream) => stream,
+        None => connection.insert(connect(address, options, costs).await?),
+    };
+
+    let result = conversation(stream, data, options, phases).await;
+    if result.is_err() {
+        *connection = None;
+    }
+    result
+}
+
+// Writes `data` as one frame and reads exactly one response frame, returning its
+// payload length; frame headers are not counted. The one-way directions change what
+// goes out and whether anything is read, see Direction.
+async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    data: Body<'_>,
+    options: &TransferOptions,
+    phases: &mut PhaseTimes,
+) -> Result<usize, TransferError> {
+    // A generated body goes out after the frame built here, which then carries its first
+    // chunk, so a sequence number can still be stamped on it
+    let mut rest = None;
+    let mut frame = match (options.direction, options.response_size, data) {
+        (Direction::DownloadOnly, len, _) => {
+            framing::encode_sized_request(&[], len.unwrap_or(data.len()))?
+        }
+        (direction, len, Body::Generated(payload)) => {
+            let (flags, len) = match direction {
+                Direction::UploadOnly => (framing::NO_REPLY, None),
+                _ => (0, len),
+            };
+            let mut frame = framing::encode_request_head(payload.len, flags, len)?;
+            let mut filler = payload.filler();
+            let mut first = vec![0u8; payload.len.min(STREAM_CHUNK)];
+            filler.fill(&mut first);
+            frame.extend_from_slice(&first);
+            rest = Some(filler);
+            frame
+        }
+        (Direction::Both, None, Body::Bytes(data)) => framing::encode_frame(data)?,
+        (Direction::Both, Some(len), Body::Bytes(data)) => {
+            framing::encode_sized_request(data, len)?
+        }
+        (Direction::UploadOnly, _, Body::Bytes(data)) => {
+            framing::encode_flagged_frame(data, framing::NO_REPLY)?
+        }
+    };
+    if let Some(counter) = &options.sequence {
+        framing::stamp_sequence(&mut frame, counter.fetch_add(1, Ordering::Relaxed))?;
+    }
+    let counted = request_bytes(data.len(), options);
+    let mut written = 0;
+    let send_start = Instant::now();
+    if let Err(e) = write_paced(stream, &frame, rest, options, &mut written).await {
+        // Frame headers are not counted, so neither is a header that made it out
+        let written = written.saturating_sub(framing::HEADER_LEN).min(counted);
+        return Err(partial_write(written, counted, e));
+    }
+    finish_request(stream, options).await?;
+    phases.send += send_start.elapsed();
+    if options.direction == Direction::UploadOnly {
+        return Ok(0);
+    }
+
+    let mut mismatch = None;
+    let sent_at = Instant::now();
+    let len = with_timeout("read", options.timeout, async {
+        let len = framing::read_frame_len(stream)
+            .await?
+            .ok_or(std::io::ErrorKind::UnexpectedEof)?;
+        let first_at = Instant::now();
+        phases.wait += first_at - sent_at;
+        phases.responses += 1;
+        let len = check_response_len(len, options)?;
+        let mut buffer = vec![0u8; options.recv_buffer_size.max(1)];
+        framing::read_payload_chunks(stream, len, &mut buffer, |chunk, offset| {
+            if options.verify && mismatch.is_none() {
+                mismatch = verify::first_mismatch(chunk, offset);
+            }
+        })
+        .await?;
+        phases.receive += first_at.elapsed();
+        Ok(len)
+    })
+    .await?;
+
+    match mismatch {
+        Some(offset) => Err(TransferError::Corrupted { offset }),
+        None => Ok(len),
+    }
+}
+
+// The announced length of a response frame, once it matches expected_response_len
+fn check_response_len(announced: usize, options: &TransferOptions) -> std::io::Result<usize> {
+    match options.expected_response_len {
+        Some(expected) if announced != expected => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("response announces {} bytes, expected {}", announced, expected),
+        )),
+        _ => Ok(announced),
+    }
+}
+
+// Sends EOF after the request when half_close_after_send is set, so a server that
+// reads until EOF knows the request is complete
+async fn finish_request<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    options: &TransferOptions,
+) -> Result<(), TransferError> {
+    if !options.half_close_after_send {
+        return Ok(());
+    }
+    with_timeout("write", options.timeout, async {
+        stream.flush().await?;
+        stream.shutdown().await
+    })
+    .await
+}
+
+// A run of no iterations would come back as a success that measured nothing
+pub(crate) fn check_iterations(iterations: usize) -> Result<(), TransferError> {
+    if iterations == 0 {
+        return Err(TransferError::InvalidOptions("iterations must be at least 1".to_string()));
+    }
+    Ok(())
+}
+
+fn check_target_rate(options: &TransferOptions) -> Result<(), TransferError> {
+    match options.target_rate {
+        Some(rate) if !(rate > 0.0 && rate.is_finite()) => Err(TransferError::InvalidOptions(
+            format!("target rate must be a positive number of bytes per second, got {}", rate),
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Holds a writer to `rate` bytes per second measured from when it was created
+struct Pacer {
+    start: Instant,
+    rate: f64,
+}
+
+impl Pacer {
+    fn new(rate: f64) -> Self {
+        Pacer {
+            start: Instant::now(),
+            rate,
+        }
+    }
+
+    // About a tenth of a second's worth, so the pacing stays smooth at any rate
+    fn chunk_len(&self) -> usize {
+        ((self.rate / 10.0) as usize).clamp(1, STREAM_CHUNK)
+    }
+
+    // Sleeps until `sent` bytes are due at the target rate
+    async fn wait(&self, sent: usize) {
+        let due = Duration::from_secs_f64(sent as f64 / self.rate);
+        let elapsed = self.start.elapsed();
+        if due > elapsed {
+            sleep(due - elapsed).await;
+        }
+    }
+}
+
+// write_all of `data` and then whatever `rest` still generates, throttled to
+// options.target_rate when one is set and split into options.write_chunk_size pieces.
+// `written` counts the bytes the socket accepted, including when an error cuts the
+// write short.
+async fn write_paced<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    data: &[u8],
+    rest: Option<Filler>,
+    options: &TransferOptions,
+    written: &mut usize,
+) -> Result<(), TransferError> {
+    let pacer = options.target_rate.map(Pacer::new);
+    // None writes `data` in one call; a chunk size of 0 is taken as 1
+    let piece_len = match (pacer.as_ref().map(Pacer::chunk_len), options.write_chunk_size) {
+        (Some(paced), Some(chunk)) => Some(paced.min(chunk.max(1))),
+        (paced, chunk) => paced.or(chunk.map(|chunk| chunk.max(1))),
+    };
+    for piece in data.chunks(piece_len.unwrap_or(data.len().max(1))) {
+        write_piece(stream, piece, pacer.as_ref(), options, written).await?;
+    }
+    let Some(mut rest) = rest else { return Ok(()) };
+    let mut chunk = vec![0u8; piece_len.unwrap_or(STREAM_CHUNK)];
+    while rest.remaining() > 0 {
+        let len = rest.fill(&mut chunk);
+        write_piece(stream, &chunk[..len], pacer.as_ref(), options, written).await?;
+    }
+    Ok(())
+}
+
+// One write_all of write_paced, with the flush and pacing that follow it
+async fn write_piece<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    piece: &[u8],
+    pacer: Option<&Pacer>,
+    options: &TransferOptions,
+    written: &mut usize,
+) -> Result<(), TransferError> {
+    with_timeout("write", options.timeout, async {
+        write_counted(stream, piece, written).await?;
+        if options.flush_chunks && options.write_chunk_size.is_some() {
+            stream.flush().await?;
+        }
+        Ok(())
+    })
+    .await?;
+    if let Some(pacer) = pacer {
+        pacer.wait(*written).await;
+    }
+    Ok(())
+}
+
+// write_all that keeps `written` up to date, so a failure can say how far it got
+async fn write_counted<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    data: &[u8],
+    written: &mut usize,
+) -> std::io::Result<()> {
+    let mut offset = 0;
+    while offset < data.len() {
+        let n = stream.write(&data[offset..]).await?;
+        if n == 0 {
+            return Err(std::io::ErrorKind::WriteZero.into());
+        }
+        offset += n;
+        *written += n;
+    }
+    Ok(())
+}
+
+// Attaches how much of the payload went out to a write that failed part-way
+fn partial_write(written: usize, total: usize, error: TransferError) -> TransferError {
+    TransferError::PartialWrite {
+        written,
+        total,
+        cause: Box::new(error),
+    }
+}
+
+// Overheads of getting connected, kept apart from the transfer itself
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct ConnectCosts {
+    retries: usize,
+    eof_retries: usize, // iterations repeated on a new connection after a premature EOF
+    dns_time: Duration,
+    connects: usize, // calls to connect, successful or not
+    connect_time: Duration,
+    ipv4_connects: usize, // successful connects, by the family of the address that won
+    ipv6_connects: usize,
+    local_address: Option<IpAddr>, // of the latest successful connect
+}
+
+impl ConnectCosts {
+    fn add(&mut self, other: ConnectCosts) {
+        self.retries += other.retries;
+        self.eof_retries += other.eof_retries;
+        self.dns_time += other.dns_time;
+        self.connects += other.connects;
+        self.connect_time += other.connect_time;
+        self.ipv4_connects += other.ipv4_connects;
+        self.ipv6_connects += other.ipv6_connects;
+        self.local_address = other.local_address.or(self.local_address);
+    }
 }
 
-async fn measure_transfer(
+// Time spent in each phase of the exchanges on a connection, summed: writing requests,
+// waiting from a request's last byte to its response's first bytes, and reading the
+// rest, with how many responses there were. Read from the system clock, like pacing,
+// so a ScriptedClock's readings all go to round trips and connects.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct PhaseTimes {
+    send: Duration,
+    wait: Duration,
+    receive: Duration,
+    responses: usize,
+}
+
+impl PhaseTimes {
+    fn add(&mut self, other: PhaseTimes) {
+        self.send += other.send;
+        self.wait += other.wait;
+        self.receive += other.receive;
+        self.responses += other.responses;
+    }
+
+    fn mean_wait(&self) -> Duration {
+        match self.responses {
+            0 => Duration::ZERO,
+            n => self.wait / n as u32,
+        }
+    }
+}
+
+// Resolves `address` and connects to what it resolved to, both under `options.timeout`,
+// retrying failures per `options.retry` with a growing backoff. Lookup time and
+// retries are added to `costs`, along with the time the whole thing took.
+async fn connect(
     address: &str,
-    data_size: usize,
-    iterations: usize,
-) -> Result<NetworkStats, Box<dyn Error>> {
-    let mut total_sent = 0;
-    let mut total_received = 0;
-    let start_time = Instant::now();
+    options: &TransferOptions,
+    costs: &mut ConnectCosts,
+) -> Result<TcpStream, TransferError> {
+    let start = options.clock.now();
+    let result = connect_with_retries(address, options, costs).await;
+    costs.connects += 1;
+    costs.connect_time += options.clock.elapsed_since(start);
+    result
+}
 
-    for _ in 0..iterations {
-        let mut stream = TcpStream::connect(address).await?;
-        
-        // Create some dummy data to send
-        let data = vec![0u8; data_size];
-        
-        // Send data
-        stream.write_all(&data).await?;
-        total_sent += data.len();
-        
-        // Receive response
-        let mut buffer = vec![0u8; 8192]; // 8KB buffer
-        let n = stream.read(&mut buffer).await?;
-        total_received += n;
-        
-        // Add a small delay between iterations
-        sleep(Duration::from_millis(100)).await;
+async fn connect_with_retries(
+    address: &str,
+    options: &TransferOptions,
+    costs: &mut ConnectCosts,
+) -> Result<TcpStream, TransferError> {
+    let mut backoff = options.retry.initial_backoff;
+    let mut attempt = 0;
+    loop {
+        match resolve_and_connect(address, options, costs).await {
+            Ok(stream) => return Ok(stream),
+            // A malformed address won't get better by waiting
+            Err(TransferError::Resolve(e)) if e.kind() == std::io::ErrorKind::InvalidInput => {
+                return Err(TransferError::InvalidAddress {
+                    address: address.to_string(),
+                    reason: e.to_string(),
+                });
+            }
+            // Nor will credentials the proxy turned down
+            Err(e @ TransferError::ProxyAuth(_)) => return Err(e),
+            Err(_) if attempt < options.retry.max_retries => {
+                attempt += 1;
+                costs.retries += 1;
+                sleep(backoff).await;
+                let next = backoff.as_secs_f64() * options.retry.backoff_multiplier.max(1.0);
+                backoff = Duration::try_from_secs_f64(next).unwrap_or(Duration::MAX);
+            }
+            Err(e) => return Err(e),
+        }
     }
+}
 
-    let elapsed = start_time.elapsed();
-    let upload_rate = total_sent as f64 / elapsed.as_secs_f64();
-    let download_rate = total_received as f64 / elapsed.as_secs_f64();
-    let ratio = if total_sent > 0 {
-        total_received as f64 / total_sent as f64
-    } else {
-        0.0
+async fn resolve_and_connect(
+    address: &str,
+    options: &TransferOptions,
+    costs: &mut ConnectCosts,
+) -> Result<TcpStream, TransferError> {
+    let Some(proxy) = &options.proxy else {
+        return dial(address, options, costs).await;
     };
+    // Failing to reach the proxy is told apart from failing to reach the target, which
+    // only the proxy's reply can report
+    let mut stream = dial(&proxy.proxy, options, costs).await.map_err(|e| match e {
+        TransferError::Resolve(e) | TransferError::Connect(e) => TransferError::ProxyConnect(e),
+        TransferError::Timeout { after, .. } => {
+            TransferError::Timeout { phase: "proxy connect", after }
+        }
+        other => other,
+    })?;
+    match timeout(options.timeout, socks::handshake(&mut stream, address, proxy.auth.as_ref()))
+        .await
+    {
+        Ok(handshake) => handshake?,
+        Err(_) => {
+            return Err(TransferError::Timeout { phase: "proxy handshake", after: options.timeout })
+        }
+    }
+    Ok(stream)
+}
+
+// Resolves `address` and connects to it directly. The family counted is that of the
+// address connected to, which is the proxy's when there is one.
+async fn dial(
+    address: &str,
+    options: &TransferOptions,
+    costs: &mut ConnectCosts,
+) -> Result<TcpStream, TransferError> {
+    let lookup_start = Instant::now();
+    let resolved = with_timeout("resolve", options.timeout, async {
+        Ok(tokio::net::lookup_host(address).await?.collect::<Vec<SocketAddr>>())
+    })
+    .await;
+    costs.dns_time += lookup_start.elapsed();
+
+    let resolved = resolved?;
+    if resolved.is_empty() {
+        return Err(TransferError::Resolve(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} did not resolve to any address", address),
+        )));
+    }
+    let stream = with_timeout("connect", options.timeout, connect_any(&resolved, options)).await?;
+    match stream.peer_addr() {
+        Ok(peer) if peer.is_ipv6() => costs.ipv6_connects += 1,
+        Ok(_) => costs.ipv4_connects += 1,
+        Err(_) => {}
+    }
+    if let Ok(local) = stream.local_addr() {
+        costs.local_address = Some(local.ip());
+    }
+    Ok(stream)
+}
 
-    Ok(NetworkStats {
-        bytes_sent: total_sent,
-        bytes_received: total_received,
-        elapsed_time: elapsed,
-        upload_rate,
-        download_rate,
-        ratio,
+// Races the resolved addresses, IPv6 and IPv4 alternately (see happy_eyeballs.rs)
+async fn connect_any(
+    resolved: &[SocketAddr],
+    options: &TransferOptions,
+) -> std::io::Result<TcpStream> {
+    // A socket bound to an address of one family can't reach the other
+    let resolved: Vec<SocketAddr> = match options.bind_addr {
+        Some(local) => {
+            resolved.iter().copied().filter(|peer| peer.is_ipv4() == local.is_ipv4()).collect()
+        }
+        None => resolved.to_vec(),
+    };
+    if let (Some(local), true) = (options.bind_addr, resolved.is_empty()) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AddrNotAvailable,
+            format!("the target has no {} address to reach from {}", family(local), local.ip()),
+        ));
+    }
+    connect_first(&resolved, options.connect_head_start, |address| {
+        let options = options.clone();
+        async move { connect_socket(address, &options).await }
     })
+    .await
 }
 
-fn format_bytes(bytes: usize) -> String {
-    if bytes < 1024 {
-        format!("{} B", bytes)
-    } else if bytes < 1024 * 1024 {
-        format!("{:.2} KB", bytes as f64 / 1024.0)
-    } else if bytes < 1024 * 1024 * 1024 {
-        format!("{:.2} MB", bytes as f64 / (1024.0 * 1024.0))
+async fn connect_socket(
+    address: SocketAddr,
+    options: &TransferOptions,
+) -> std::io::Result<TcpStream> {
+    let socket = if address.is_ipv4() {
+        TcpSocket::new_v4()?
     } else {
-        format!("{:.2} GB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+        TcpSocket::new_v6()?
+    };
+    if let Some(size) = options.socket_send_buffer {
+        socket.set_send_buffer_size(size)?;
+    }
+    if let Some(size) = options.socket_recv_buffer {
+        socket.set_recv_buffer_size(size)?;
+    }
+    if options.socket_send_buffer.is_some() || options.socket_recv_buffer.is_some() {
+        debug!(
+            "socket buffers set",
+            send = socket.send_buffer_size()?,
+            recv = socket.recv_buffer_size()?
+        );
     }
+    if let Some(local) = options.bind_addr {
+        socket.bind(local).map_err(|e| {
+            std::io::Error::new(e.kind(), format!("cannot bind to {}: {}", local, e))
+        })?;
+    }
+    let stream = socket.connect(address).await?;
+    stream.set_nodelay(options.tcp_nodelay)?;
+    Ok(stream)
 }
 
-fn format_rate(bytes_per_sec: f64) -> String {
-    if bytes_per_sec < 1024.0 {
-        format!("{:.2} B/s", bytes_per_sec)
-    } else if bytes_per_sec < 1024.0 * 1024.0 {
-        format!("{:.2} KB/s", bytes_per_sec / 1024.0)
-    } else if bytes_per_sec < 1024.0 * 1024.0 * 1024.0 {
-        format!("{:.2} MB/s", bytes_per_sec / (1024.0 * 1024.0))
+fn family(address: SocketAddr) -> &'static str {
+    if address.is_ipv4() {
+        "IPv4"
     } else {
-        format!("{:.2} GB/s", bytes_per_sec / (1024.0 * 1024.0 * 1024.0))
+        "IPv6"
     }
 }
 
-// Mock server for testing
-// async fn run_mock_server() -> Result<(), Box<dyn Error>> {
-async fn run_mock_server() -> Result<(), Box<dyn Error + Send + Sync>> {
- 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:8080").await?;
-    println!("Mock server listening on127.0.0.1:8080");
+pub(crate) async fn with_timeout<T>(
+    phase: &'static str,
+    limit: Duration,
+    operation: impl std::future::Future<Output = std::io::Result<T>>,
+) -> Result<T, TransferError> {
+    match timeout(limit, operation).await {
+        Ok(result) => result.map_err(|e| TransferError::during(phase, e)),
+        Err(_) => Err(TransferError::Timeout { phase, after: limit }),
+    }
+}
 
-    loop {
-        let (mut socket, _) = listener.accept().await?;
-        
-        tokio::spawn(async move {
-            let mut buf = vec![0u8; 8192];
-            match socket.read(&mut buf).await {
-                Ok(n) => {
-                    if n > 0 {
-                        // Echo back a response that's half the size of what we received
-                        let response = vec![1u8; n / 2]; //  responds with a vector of 1u8 bytes that is half the size of what was received
-                        // 1u8 is the value (a byte with value 1), n / 2 is the number of times to repeat this value
-                        let _ = socket.write_all(&response).await;
-                    }
-                }
-                Err(e) => eprintln!("Socket read error: {}", e),
-            }
-        });
+// Binary divides by 1024 and labels KiB/MiB/GiB; Decimal divides by 1000 and labels KB/MB/GB
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnitStyle {
+    #[default]
+    Binary,
+    Decimal,
+}
+
+pub fn format_bytes(bytes: usize) -> String {
+    format_bytes_with(bytes, UnitStyle::default())
+}
+
+pub fn format_bytes_with(bytes: usize, style: UnitStyle) -> String {
+    let mut out = String::new();
+    let _ = write_bytes(&mut out, bytes, style);
+    out
+}
+
+pub fn format_rate(bytes_per_sec: f64) -> String {
+    format_rate_as(bytes_per_sec, RateUnits::Bytes)
+}
+
+// Bits per second use decimal multiples (1 Kbps = 1000 bps), as networking gear
+// and ISPs quote them, unlike format_rate's 1024-based KiB/s and MiB/s
+pub fn format_rate_bits(bytes_per_sec: f64) -> String {
+    format_rate_as(bytes_per_sec, RateUnits::Bits)
+}
+
+pub fn format_rate_as(bytes_per_sec: f64, units: RateUnits) -> String {
+    let mut out = String::new();
+    let _ = write_rate_as(&mut out, bytes_per_sec, units);
+    out
+}
+
+// What the format_rate functions show for an inf or NaN rate
+const NO_RATE: &str = "N/A";
+
+// format_rate_as, or N/A when `elapsed` is under MIN_MEASURABLE_TIME, so a rate from a
+// handful of microseconds isn't passed off as a measurement
+pub fn format_measured_rate(bytes_per_sec: f64, elapsed: Duration, units: RateUnits) -> String {
+    if elapsed < MIN_MEASURABLE_TIME {
+        "N/A (too fast to measure)".to_string()
+    } else {
+        format_rate_as(bytes_per_sec, units)
     }
 }
 
-// A more comprehensive monitoring example that could track a TLSNotary-like application
-async fn monitor_mpc_simulation(data_sizes: &[usize]) -> Result<(), Box<dyn Error>> {
-    println!("MPC Communication Simulation");
-    println!("============================\n");
-    
-    for &size in data_sizes {
-        println!("Testing with {} payload", format_bytes(size));
-        
-        // Simulate MPC overhead based on TLSNotary's documentation
-        let base_overhead = 25 * 1024 * 1024; // 25MB fixed cost
-        let upload_overhead_factor = 10.0; // 10MB per 1KB of outgoing data
-        let download_overhead_factor = 0.04; // 40KB per 1KB of incoming data
-        
-        let upload_total = base_overhead + (size as f64 * upload_overhead_factor) as usize;
-        let response_size = size * 10; // Assume response is 10x the request size
-        let download_total = (response_size as f64 * download_overhead_factor) as usize;
-        
-        println!("  Simulated request size: {}", format_bytes(size));
-        println!("  Simulated response size: {}", format_bytes(response_size));
-        println!("  Estimated MPC upload overhead: {}", format_bytes(upload_total));
-        println!("  Estimated MPC download overhead: {}", format_bytes(download_total));
-        println!("  Overhead ratio: {:.2}x upload, {:.2}x download", 
-                upload_total as f64 / size as f64,
-                download_total as f64 / response_size as f64);
-        println!();
-        
-        // Could add actual network transfer test here with mock MPC
+// The mean of the per-iteration download rates, with its 95% confidence interval when
+// there are two or more of them, or N/A without either when an iteration was too quick
+// for its rate to mean much. None when no iteration downloaded anything.
+pub fn format_per_iteration_rate(
+    stats: &NetworkStats,
+    units: RateUnits,
+    color: bool,
+) -> Option<String> {
+    let mean = stats.mean_download_rate()?;
+    let paint = |text: &str| color::paint(text, color::GREEN, color);
+    if stats.iterations_too_fast_to_measure() {
+        return Some(paint("N/A (too fast to measure)"));
+    }
+    let mean = paint(&format_rate_as(mean, units));
+    Some(match stats.download_rate_confidence_interval() {
+        Some((low, high)) => {
+            format!("{} \u{b1} {} (95% CI)", mean, format_rate_as((high - low) / 2.0, units))
+        }
+        None => format!("{} (one iteration, so no confidence interval)", mean),
+    })
+}
+
+// The write_* forms append to `out` instead of returning a new String, for callers
+// that redraw a line on every update (the progress display) or assemble a larger
+// string, so one buffer can be reused
+pub fn write_bytes(out: &mut impl fmt::Write, bytes: usize, style: UnitStyle) -> fmt::Result {
+    let (base, units) = match style {
+        UnitStyle::Binary => (1024.0, ["B", "KiB", "MiB", "GiB"]),
+        UnitStyle::Decimal => (1000.0, ["B", "KB", "MB", "GB"]),
+    };
+    if (bytes as f64) < base {
+        write!(out, "{} B", bytes)
+    } else {
+        write_scaled(out, bytes as f64, base, units)
+    }
+}
+
+pub fn write_rate_as(
+    out: &mut impl fmt::Write,
+    bytes_per_sec: f64,
+    units: RateUnits,
+) -> fmt::Result {
+    if !bytes_per_sec.is_finite() {
+        return out.write_str(NO_RATE);
+    }
+    match units {
+        RateUnits::Bytes => {
+            write_scaled(out, bytes_per_sec, 1024.0, ["B/s", "KiB/s", "MiB/s", "GiB/s"])
+        }
+        RateUnits::Bits => {
+            write_scaled(out, bytes_per_sec * 8.0, 1000.0, ["bps", "Kbps", "Mbps", "Gbps"])
+        }
     }
-    
-    Ok(())
+}
+
+// `value` in the largest of `units` (each `base` times the previous) it reaches
+fn write_scaled(
+    out: &mut impl fmt::Write,
+    value: f64,
+    base: f64,
+    units: [&str; 4],
+) -> fmt::Result {
+    let mut divisor = 1.0;
+    let mut unit = 0;
+    while unit < units.len() - 1 && value >= divisor * base {
+        divisor *= base;
+        unit += 1;
+    }
+    write!(out, "{:.2} {}", value / divisor, units[unit])
 }
 
 // #[tokio::main]
-pub async fn run_main() -> Result<(), Box<dyn Error>> {
-    // Start mock server in the background
-    tokio::spawn(run_mock_server());
-    
-    // Wait for the server to start
-    sleep(Duration::from_millis(500)).await;
-    
+pub async fn run_main(config: &Config) -> Result<(), Box<dyn Error>> {
+    // One signal stops every background server started here
+    let (stop, stop_signal) = watch::channel(false);
+    let mut servers = Vec::new();
+
+    let metrics = match config.metrics_port {
+        Some(port) => {
+            let metrics = Arc::new(Metrics::new());
+            let ip = config.metrics_bind.unwrap_or(DEFAULT_METRICS_BIND);
+            let address = SocketAddr::new(ip, port).to_string();
+            let (_, server) =
+                serve_metrics(&address, Arc::clone(&metrics), stop_signal.clone()).await?;
+            servers.push(("Metrics server", server));
+            Some(metrics)
+        }
+        None => None,
+    };
+
+    let mut config = config.clone();
+    // A file payload has one size, its own, whatever --sizes says
+    if let Some(path) = &config.payload_file {
+        config.sizes = vec![std::fs::metadata(path)?.len() as usize];
+    }
+    if config.dry_run {
+        print_dry_run(&config);
+        return Ok(());
+    }
+    // --hosts names its own targets, so there's nothing for a mock server to stand in for
+    if config.mock_server && config.hosts.is_empty() {
+        // Start mock server in the background; it has bound its port once this returns
+        let (bound, server) = run_mock_server(
+            MockServerConfig {
+                address: config.mock_address.clone(),
+                response_ratio: config.response_ratio,
+                echo: config.verify,
+                sink: config.mock_sink,
+                max_response_len: config.mock_max_response,
+                idle_timeout: config.mock_idle_timeout,
+                backlog: config.mock_backlog,
+                accept_error_pause: config.mock_accept_pause,
+                ..MockServerConfig::default()
+            },
+            stop_signal,
+        )
+        .await
+        .map_err(mock_server_error)?;
+        servers.push(("Mock server", server));
+        config.address.get_or_insert_with(|| bound.to_string());
+    }
+
+    // Ctrl-C ends the run early with what finished reported, instead of killing it;
+    // --hosts and --watch handle it themselves
+    let cancel = || Some(cancel_on_ctrl_c());
+    let metrics = metrics.as_deref();
+    let result = match config.mode() {
+        RunMode::Sweep => run_benchmarks_until(&config, metrics, cancel()).await,
+        RunMode::Hosts => run_hosts(&config).await,
+        RunMode::Watch => run_watch(&config, metrics).await,
+        RunMode::Replay(path) => run_replay(&config, path, cancel()).await,
+        RunMode::UnderLoad(bulk_size) => run_under_load(&config, bulk_size, cancel()).await,
+        RunMode::Keepalive(interval) => run_keepalive(&config, interval, cancel()).await,
+        RunMode::SizeMix(mix) => run_mix(&config, mix, cancel()).await,
+        RunMode::Repeat(repeats) => run_repeat(&config, repeats, metrics, cancel()).await,
+        RunMode::Parallel(streams) => run_parallel(&config, streams, cancel()).await,
+        RunMode::Soak(duration) => run_soak(&config, duration, cancel()).await,
+        RunMode::Ramp(levels) => run_ramp(&config, levels, cancel()).await,
+        RunMode::DedupCheck => run_dedup(&config, cancel()).await,
+    };
+
+    let _ = stop.send(true);
+    for (name, server) in servers {
+        if let Ok(Err(e)) = server.await {
+            error!("background server stopped with an error", server = name, error = e);
+        }
+    }
+    result
+}
+
+// Set by the first Ctrl-C; a second one exits straight away, for when the iteration in
+// flight hangs
+fn cancel_on_ctrl_c() -> watch::Receiver<bool> {
+    let (interrupt, cancel) = watch::channel(false);
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        eprintln!("\nInterrupted, finishing the current iteration (Ctrl-C again to quit now)");
+        let _ = interrupt.send(true);
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+    cancel
+}
+
+// Nothing can be measured without the mock server, so stop with a pointer to the flags
+// that get around the failure instead of sending into a closed port
+fn mock_server_error(e: std::io::Error) -> Box<dyn Error> {
+    let hint = match e.kind() {
+        std::io::ErrorKind::AddrInUse => {
+            "; pick another port with --mock-address (port 0 chooses a free one)"
+        }
+        std::io::ErrorKind::AddrNotAvailable => {
+            "; --mock-address must be an address of this machine"
+        }
+        _ => "",
+    };
+    format!("{}{}, or measure a running server with --no-mock-server", e, hint).into()
+}
+
+// Size sweep against `config.address` (or `config.url` over HTTP) followed by the
+// MPC estimate; prints to stdout
+pub async fn run_benchmarks(config: &Config) -> Result<(), Box<dyn Error>> {
+    run_benchmarks_with_metrics(config, None).await
+}
+
+// run_benchmarks, also publishing each size's result to `metrics` when given
+pub async fn run_benchmarks_with_metrics(
+    config: &Config,
+    metrics: Option<&Metrics>,
+) -> Result<(), Box<dyn Error>> {
+    run_benchmarks_until(config, metrics, None).await
+}
+
+// run_benchmarks_with_metrics that stops early once `cancel` holds true: the size being
+// measured reports the iterations it finished, the remaining sizes are skipped, and the
+// chart and summary cover what was measured
+pub async fn run_benchmarks_until(
+    config: &Config,
+    metrics: Option<&Metrics>,
+    cancel: Option<watch::Receiver<bool>>,
+) -> Result<(), Box<dyn Error>> {
+    let text = config.format == OutputFormat::Text;
+    // Headers, per-size blocks, chart and MPC estimate; --quiet leaves only the totals
+    let chatty = text && !config.quiet;
+    let color = config.color_for(std::io::stdout().is_terminal());
+    let error_color = config.color_for(std::io::stderr().is_terminal());
+    let cancelled = || cancel.as_ref().is_some_and(|cancel| *cancel.borrow());
+    // Opened up front, so a missing sqlite3 or an unwritable file fails before measuring
+    #[cfg(feature = "sqlite")]
+    let db = match &config.db {
+        Some(path) => Some(RunDatabase::open(path).await?),
+        None => None,
+    };
+
     // First, measure some actual transfers
-    println!("Network Transfer Test");
-    println!("====================\n");
+    if chatty {
+        println!("Network Transfer Test");
+        println!("====================\n");
+    }
     
-    let test_sizes = [1024, 10 * 1024, 100 * 1024];
-    for size in test_sizes {
-        println!("Testing with {} payload", format_bytes(size));
+    let options = TransferOptions { cancel: cancel.clone(), ..transfer_options(config) };
+    let http_options = HttpOptions { cancel: cancel.clone(), ..http_options(config) };
+
+    // Machine-readable results go to --output when given, stdout otherwise
+    let header = (config.format == OutputFormat::Csv).then_some(NetworkStats::CSV_HEADER);
+    let mut out: Box<dyn Write> = match &config.output {
+        Some(path) if !text => Box::new(open_output(Path::new(path), config.append, header)?),
+        _ => {
+            if let Some(header) = header {
+                println!("{}", header);
+            }
+            Box::new(std::io::stdout())
+        }
+    };
+    let mut records = config
+        .export_records
+        .as_deref()
+        .map(|path| records_file(path, config.append))
+        .transpose()?;
+    // Every size that succeeded, for the chart and summary table (text output) or the
+    // --quiet totals
+    let mut measured: Vec<(usize, NetworkStats)> = Vec::new();
+    let mut violations = Vec::new();
+    let mut failed_sizes = 0;
+    #[cfg(feature = "otlp")]
+    let mut otlp = config.otlp_endpoint.as_deref().map(OtlpExporter::new);
+
+    let deadline = deadline_after(config.max_duration);
+    for &size in &config.sizes {
+        if cancelled() {
+            if chatty {
+                println!("Interrupted, skipping the remaining sizes\n");
+            }
+            break;
+        }
+        // --max-duration covers the whole run, so each size gets what the earlier ones left
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if remaining.is_some_and(|remaining| remaining.is_zero()) {
+            if chatty {
+                println!("Time limit reached, skipping the remaining sizes\n");
+            }
+            break;
+        }
+        let options = TransferOptions { max_duration: remaining, ..options.clone() };
+        let http_options = HttpOptions { max_duration: remaining, ..http_options.clone() };
+        if chatty {
+            println!("Testing with {} payload", format_bytes(size));
+        }
         
-        match measure_transfer("127.0.0.1:8080", size, 5).await {
-            Ok(stats) => {
-                println!("  Sent: {}", format_bytes(stats.bytes_sent));
-                println!("  Received: {}", format_bytes(stats.bytes_received));
-                println!("  Time: {:.2?}", stats.elapsed_time);
-                println!("  Upload: {}", format_rate(stats.upload_rate));
-                println!("  Download: {}", format_rate(stats.download_rate));
-                println!("  Ratio (received/sent): {:.2}", stats.ratio);
-                println!();
+        // Streaming mode reports its own timeline and replaces the iteration loop
+        if let (None, Some(interval)) = (&config.url, config.sample_interval) {
+            match measure_stream(config.target_address(), size, interval, &options).await {
+                Ok(mut samples) => {
+                    samples.stats = describe_run(samples.stats, config);
+                    if let Some(metrics) = metrics {
+                        metrics.record(size, &samples.stats);
+                    }
+                    violations.extend(threshold_violations(config, size, &samples.stats));
+                    #[cfg(feature = "otlp")]
+                    if let Some(otlp) = &mut otlp {
+                        export_otlp(otlp, size, &samples.stats).await;
+                    }
+                    #[cfg(feature = "sqlite")]
+                    if let Some(db) = &db {
+                        record_run(db, size, &samples.stats).await;
+                    }
+                    match config.format {
+                        _ if config.quiet => measured.push((size, samples.stats)),
+                        OutputFormat::Text => {
+                            print_stats(&samples.stats, config.units, color);
+                            if let Some(model) = &config.goodput {
+                                let estimate = estimate_goodput(&samples.stats, model);
+                                print_goodput(&estimate, model, config.units, color);
+                            }
+                            print_timeline("Upload", &samples.upload, config.units);
+                            print_timeline("Download", &samples.download, config.units);
+                            measured.push((size, samples.stats));
+                        }
+                        // jsonl is --watch only; a config built in code gets json
+                        OutputFormat::Json | OutputFormat::JsonLines => {
+                            let mut obj = JsonObject::new();
+                            obj.uint("size", size as u64);
+                            samples.stats.write_json_fields(&mut obj);
+                            if let Some(model) = &config.goodput {
+                                obj.raw("goodput", &goodput_json(&samples.stats, model));
+                            }
+                            if let Some(link) = config.link_bandwidth {
+                                write_link_utilization(&mut obj, &samples.stats, link);
+                            }
+                            obj.float("sample_interval_secs", samples.interval.as_secs_f64())
+                                .raw("upload_samples", &samples_json(&samples.upload))
+                                .raw("download_samples", &samples_json(&samples.download));
+                            writeln!(out, "{}", obj.finish())?;
+                        }
+                        OutputFormat::Csv => writeln!(out, "{}", samples.stats.csv_row(size))?,
+                    }
+                }
+                Err(e) => {
+                    if let Some(metrics) = metrics {
+                        metrics.record_failures(failed_iterations(&e, 1));
+                    }
+                    if config.fail_fast {
+                        return Err(e.into());
+                    }
+                    failed_sizes += 1;
+                    eprintln!("Error measuring transfer ({}): {}", e.category(), e);
+                }
+            }
+            continue;
+        }
+
+        let mut result = measure_size(config, size, &options, &http_options, metrics).await;
+        // --write-chunk-size: the same size again in single writes, to show what chunking did
+        if let (Ok(stats), Some(_)) = (&mut result, options.write_chunk_size) {
+            let single = TransferOptions {
+                write_chunk_size: None,
+                flush_chunks: false,
+                keep_records: false,
+                ..options.clone()
+            };
+            match measure_size(config, size, &single, &http_options, None).await {
+                Ok(baseline) => stats.single_write_upload_rate = Some(baseline.upload_rate),
+                Err(e) => eprintln!("Error measuring single writes to compare against: {}", e),
             }
+        }
+        if let Ok(stats) = &result {
+            violations.extend(threshold_violations(config, size, stats));
+            if let Some(out) = &mut records {
+                for record in &stats.records {
+                    writeln!(out, "{}", record.csv_row(size))?;
+                }
+                out.flush()?;
+            }
+            #[cfg(feature = "otlp")]
+            if let Some(otlp) = &mut otlp {
+                export_otlp(otlp, size, stats).await;
+            }
+            #[cfg(feature = "sqlite")]
+            if let Some(db) = &db {
+                record_run(db, size, stats).await;
+            }
+        }
+        match result {
+            Ok(stats) => match config.format {
+                _ if config.quiet => measured.push((size, stats)),
+                OutputFormat::Text => {
+                    print_stats(&stats, config.units, color);
+                    if let Some(model) = &config.goodput {
+                        print_goodput(&estimate_goodput(&stats, model), model, config.units, color);
+                    }
+                    measured.push((size, stats));
+                }
+                OutputFormat::Json | OutputFormat::JsonLines => {
+                    let mut obj = JsonObject::new();
+                    obj.uint("size", size as u64);
+                    stats.write_json_fields(&mut obj);
+                    if let Some(model) = &config.goodput {
+                        obj.raw("goodput", &goodput_json(&stats, model));
+                    }
+                    if let Some(link) = config.link_bandwidth {
+                        write_link_utilization(&mut obj, &stats, link);
+                    }
+                    writeln!(out, "{}", obj.finish())?;
+                }
+                OutputFormat::Csv => writeln!(out, "{}", stats.csv_row(size))?,
+            },
+            // The other sizes aren't measured either
+            Err(e) if config.fail_fast => return Err(e),
             Err(e) => {
-                eprintln!("Error measuring transfer: {}", e);
+                failed_sizes += 1;
+                report_error(e.as_ref(), error_color);
             }
         }
     }
     
-    // Then simulate MPC overhead calculations
-    monitor_mpc_simulation(&[1024, 10 * 1024]).await?;
+    if config.quiet {
+        let summary = RunSummary::from_stats(measured.iter().map(|(_, stats)| stats));
+        let summary = summary.unwrap_or_default();
+        if text {
+            println!("{}", summary.headline(config.units));
+        } else {
+            let mut obj = JsonObject::new();
+            summary.write_json_fields(&mut obj);
+            obj.uint("failed_sizes", failed_sizes)
+                .raw("interrupted", if cancelled() { "true" } else { "false" });
+            writeln!(out, "{}", obj.finish())?;
+        }
+    }
+    if chatty && !measured.is_empty() {
+        let fancy = std::io::stdout().is_terminal();
+        let download_rates: Vec<(usize, f64)> =
+            measured.iter().map(|(size, stats)| (*size, stats.download_rate)).collect();
+        println!("Download rate by payload size");
+        print!("{}", render_rate_chart(&download_rates, config.units, fancy));
+        println!();
+
+        println!("Summary");
+        print!("{}", render_summary_table(&measured, config.units, fancy));
+        if let Some(summary) = RunSummary::from_stats(measured.iter().map(|(_, stats)| stats)) {
+            println!("  {}", summary.headline(config.units));
+            if let Some(link) = config.link_bandwidth {
+                let line = utilization_line(summary.upload_rate, summary.download_rate, link);
+                println!("  Link utilization: {}", line);
+            }
+            if has_exchange_phases(&summary.phases) {
+                println!("  Time per iteration: {}", phase_line(&summary.phases));
+            }
+        }
+        // One buffer size has to serve every size, so size it for the largest product
+        let widest = measured.iter().map(|(_, stats)| stats).max_by_key(|stats| {
+            stats.bandwidth_delay_product()
+        });
+        if let Some(stats) = widest.filter(|stats| stats.bandwidth_delay_product() > 0) {
+            let buffer = stats.suggested_socket_buffer();
+            println!(
+                "  Suggested socket buffers: {} (--send-buffer {} --recv-buffer {})",
+                format_bytes(buffer),
+                buffer,
+                buffer
+            );
+        }
+        println!();
+    }
+
+    // Then apply the MPC overhead model, to what was measured when anything was
+    if chatty && !cancelled() {
+        let model = MpcOverheadModel::default();
+        if measured.iter().any(|(_, stats)| stats.successful_iterations > 0) {
+            monitor_mpc_measured(&measured, &model);
+        } else {
+            monitor_mpc_simulation(&[1024, 10 * 1024], &model).await?;
+        }
+    }
     
+    if !violations.is_empty() {
+        return Err(ThresholdsViolated { violations }.into());
+    }
     Ok(())
 }
 
+// --export-records destination, starting with its header row unless appending to a
+// file that has it already
+pub(crate) fn records_file(path: &Path, append: bool) -> std::io::Result<BufWriter<File>> {
+    Ok(BufWriter::new(open_output(path, append, Some(IterationRecord::CSV_HEADER))?))
+}
+
+// A collector being down is reported but doesn't fail the run
+#[cfg(feature = "otlp")]
+async fn export_otlp(exporter: &mut OtlpExporter, size: usize, stats: &NetworkStats) {
+    if let Err(e) = exporter.export(size, stats).await {
+        warning!("OTLP export failed", url = exporter.url(), error = e);
+    }
+}
+
+// Like an OTLP export, a failed insert is reported but doesn't fail the run
+#[cfg(feature = "sqlite")]
+async fn record_run(db: &RunDatabase, size: usize, stats: &NetworkStats) {
+    if let Err(e) = db.insert(Some(size), stats).await {
+        warning!("recording the run failed", db = db.path().display(), error = e);
+    }
+}
+
+// The size's breaches of the configured thresholds, labelled with the size
+fn threshold_violations(config: &Config, size: usize, stats: &NetworkStats) -> Vec<String> {
+    config
+        .thresholds
+        .check(stats)
+        .into_iter()
+        .map(|violation| format!("{} payload: {}", format_bytes(size), violation))
+        .collect()
+}
+
+pub(crate) fn transfer_options(config: &Config) -> TransferOptions {
+    TransferOptions {
+        delay: config.delay,
+        timeout: config.timeout,
+        concurrency: config.concurrency,
+        warmup: config.warmup,
+        reuse_connection: config.reuse_connection,
+        target_rate: config.target_rate,
+        write_chunk_size: config.write_chunk_size,
+        flush_chunks: config.flush_chunks,
+        progress: config.progress
+            && config.format == OutputFormat::Text
+            && std::io::stdout().is_terminal(),
+        verify: config.verify,
+        half_close_after_send: config.half_close,
+        tcp_nodelay: config.tcp_nodelay,
+        socket_send_buffer: config.socket_send_buffer,
+        socket_recv_buffer: config.socket_recv_buffer,
+        bind_addr: config.bind_addr,
+        requests_per_connection: config.requests_per_connection,
+        think_time: config.think_time,
+        fail_fast: config.fail_fast,
+        keep_records: config.export_records.is_some(),
+        expected_response_len: config.expected_response_len,
+        direction: config.direction,
+        response_size: config.response_size,
+        sequence: config.sequence.then(|| Arc::new(AtomicU64::new(0))),
+        auto_iterations: config.auto_iterations,
+        eof_retries: config.eof_retries,
+        connect_head_start: config.connect_head_start,
+        max_duration: config.max_duration,
+        total_bytes: config.total_bytes,
+        burst: config.burst,
+        proxy: config.socks5.clone(),
+        retry: RetryPolicy {
+            max_retries: config.retries,
+            initial_backoff: config.backoff,
+            ..RetryPolicy::default()
+        },
+        ..TransferOptions::default()
+    }
+}
+
+pub(crate) fn payload_source(config: &Config, size: usize) -> PayloadSource {
+    match &config.payload_file {
+        Some(path) => PayloadSource::File(path.clone()),
+        None if config.random_payload => PayloadSource::Random {
+            size,
+            seed: config.seed,
+        },
+        None => PayloadSource::Zeros(size),
+    }
+}
+
+pub(crate) fn http_options(config: &Config) -> HttpOptions {
+    HttpOptions {
+        follow_redirects: config.follow_redirects,
+        timeout: config.timeout,
+        keep_alive: !config.http_close,
+        fail_fast: config.fail_fast,
+        max_duration: config.max_duration,
+        pool_idle_timeout: config.http_idle_timeout,
+        http2_streams: config.http2,
+        ..HttpOptions::default()
+    }
+}
+
+// One payload size over HTTP when `config.url` is set, raw TCP otherwise, with the
+// outcome published to `metrics`
+pub(crate) async fn measure_size(
+    config: &Config,
+    size: usize,
+    options: &TransferOptions,
+    http_options: &HttpOptions,
+    metrics: Option<&Metrics>,
+) -> Result<NetworkStats, Box<dyn Error>> {
+    let payload = payload_source(config, size);
+    let result = match &config.url {
+        // Each payload becomes the request body
+        Some(url) => match payload.load() {
+            Ok(body) => {
+                measure_http(url, &config.method, &body, config.iterations, http_options).await
+            }
+            Err(e) => Err(e.into()),
+        },
+        None => measure_payload(config.target_address(), &payload, config.iterations, options)
+            .await
+            .map_err(Into::into),
+    };
+    let result = result.map(|stats| describe_run(stats, config));
+    if let Some(metrics) = metrics {
+        match &result {
+            Ok(stats) => metrics.record(size, stats),
+            Err(e) => {
+                let failed = match e.downcast_ref::<TransferError>() {
+                    Some(typed) => failed_iterations(typed, config.iterations),
+                    None => config.iterations,
+                };
+                metrics.record_failures(failed);
+            }
+        }
+    }
+    result
+}
+
+// Stamps `stats` with the label and settings of the run that measured them
+pub(crate) fn describe_run(stats: NetworkStats, config: &Config) -> NetworkStats {
+    NetworkStats { label: config.label.clone(), config_summary: config.summary(), ..stats }
+}
+
+pub(crate) fn report_error(e: &(dyn Error + 'static), color: bool) {
+    let message = match e.downcast_ref::<TransferError>() {
+        Some(typed) => format!("Error measuring transfer ({}): {}", typed.category(), e),
+        None => format!("Error measuring transfer: {}", e),
+    };
+    eprintln!("{}", color::paint(&message, color::RED, color));
+}
+
+// Iterations lost to a measurement that produced no stats; `attempted` when the
+// error doesn't say, e.g. it was rejected before running
+fn failed_iterations(error: &TransferError, attempted: usize) -> usize {
+    match error {
+        TransferError::AllFailed { iterations, .. } => *iterations,
+        _ => attempted,
+    }
+}
+
+// HTTP and UDP time no send, wait or receive phases, and a breakdown that is all
+// connect would only mislead
+fn has_exchange_phases(phases: &PhaseBreakdown) -> bool {
+    phases.total() > phases.connect
+}
+
+// ", over IPv4", or both families' counts when a name's addresses took turns winning
+fn address_families(stats: &NetworkStats) -> String {
+    match (stats.ipv4_connections, stats.ipv6_connections) {
+        (0, 0) => String::new(),
+        (_, 0) => ", over IPv4".to_string(),
+        (0, _) => ", over IPv6".to_string(),
+        (v4, v6) => format!(", {} over IPv6 and {} over IPv4", v6, v4),
+    }
+}
+
+// With `color`, rates are green, the ratio yellow and failures red
+pub(crate) fn print_stats(stats: &NetworkStats, units: RateUnits, color: bool) {
+    let rate = |bytes_per_sec| {
+        let shown = format_measured_rate(bytes_per_sec, stats.elapsed_time, units);
+        color::paint(&shown, color::GREEN, color)
+    };
+    if stats.interrupted {
+        println!(
+            "  Interrupted: these cover the {} iterations finished before stopping",
+            stats.successful_iterations + stats.failed_iterations
+        );
+    } else if stats.time_limited {
+        println!(
+            "  Time-limited: these cover the {} iterations finished within the time limit",
+            stats.successful_iterations + stats.failed_iterations
+        );
+    }
+    println!("  Sent: {}", format_bytes(stats.bytes_sent));
+    println!("  Received: {}", format_bytes(stats.bytes_received));
+    println!("  Time: {:.2?}", stats.elapsed_time);
+    println!("  Upload: {}", rate(stats.upload_rate));
+    println!("  Download: {}", rate(stats.download_rate));
+    if let Some(line) = format_per_iteration_rate(stats, units, color) {
+        println!("  Download per iteration: {}", line);
+    }
+    if let Some(iterations) = stats.auto_iterations {
+        match stats.download_rate_relative_margin() {
+            Some(margin) => println!(
+                "  Iterations: {}, chosen automatically (\u{b1}{:.1}% at 95%)",
+                iterations,
+                margin * 100.0
+            ),
+            None => println!("  Iterations: {}, chosen automatically", iterations),
+        }
+    }
+    if let Some(iterations) = stats.total_bytes_iterations {
+        println!("  Iterations: {}, to send the byte total", iterations);
+    }
+    if stats.burst {
+        println!(
+            "  Burst: {} iterations started at once; fastest {:.2?}, slowest {:.2?}, {} failed \
+             to connect",
+            stats.successful_iterations + stats.failed_iterations,
+            stats.min_latency,
+            stats.max_latency,
+            stats.failed_connects
+        );
+    }
+    if let Some(fairness) = stats.iteration_fairness {
+        println!(
+            "  Fairness: {} across concurrent iterations (Jain's index; 1 is an even share), \
+             per-iteration rate {} to {}",
+            color::paint(&format!("{:.3}", fairness.index), color::YELLOW, color),
+            format_rate_as(fairness.min_rate, units),
+            format_rate_as(fairness.max_rate, units)
+        );
+    }
+    if let Some(chunk) = stats.write_chunk_size {
+        match stats.single_write_upload_rate {
+            Some(single) if single > 0.0 => println!(
+                "  Writes: {}-byte chunks, upload {:+.1}% against single writes ({})",
+                chunk,
+                (stats.upload_rate - single) / single * 100.0,
+                format_rate_as(single, units)
+            ),
+            _ => println!("  Writes: {}-byte chunks", chunk),
+        }
+    }
+    let ratio = format!("{:.2}", stats.ratio);
+    println!("  Ratio (received/sent): {}", color::paint(&ratio, color::YELLOW, color));
+    if let Some(cores) = stats.cpu_utilization {
+        println!("  CPU: {:.2} cores busy while transferring", cores);
+    }
+    if stats.cpu_bound() {
+        let warning = "Warning: the tool itself kept about a core busy, so these rates may be \
+                       its own limit rather than the network's";
+        println!("  {}", color::paint(warning, color::YELLOW, color));
+    }
+    println!(
+        "  Latency: min {:.2?}, mean {:.2?}, max {:.2?}",
+        stats.min_latency, stats.mean_latency, stats.max_latency
+    );
+    if has_exchange_phases(&stats.phases) {
+        println!("  Time per iteration: {}", phase_line(&stats.phases));
+    }
+    if !stats.time_to_first_byte.is_zero() {
+        println!("  Time to first byte: {:.2?} mean", stats.time_to_first_byte);
+    }
+    if stats.latencies.len() >= 2 {
+        println!("  Jitter: {:.2?}", stats.jitter);
+        println!(
+            "  Percentiles: p50 {:.2?}, p95 {:.2?}, p99 {:.2?}",
+            stats.p50_latency(),
+            stats.p95_latency(),
+            stats.p99_latency()
+        );
+    }
+    if stats.start_gaps.len() >= 2 {
+        println!(
+            "  Iteration starts: every {:.2?} on average, p95 {:.2?}, min {:.2?}, max {:.2?}",
+            stats.mean_start_gap(),
+            stats.p95_start_gap(),
+            stats.start_gaps.iter().min().copied().unwrap_or_default(),
+            stats.start_gaps.iter().max().copied().unwrap_or_default()
+        );
+    }
+    // A handful of gaps make a poor distribution
+    if stats.start_gaps.len() >= MIN_GAPS_FOR_HISTOGRAM {
+        println!("  Time between iteration starts:");
+        print!("{}", stats.render_start_gap_histogram());
+    }
+    let bdp = stats.bandwidth_delay_product();
+    if bdp > 0 {
+        println!(
+            "  Bandwidth-delay product: {} (socket buffers of {} would hold it)",
+            format_bytes(bdp),
+            format_bytes(stats.suggested_socket_buffer())
+        );
+    }
+    println!("  DNS resolution: {:.2?} total", stats.dns_resolution_time);
+    println!(
+        "  Connect time: {:.2?} mean, {:.2?} total (not counted in the rates)",
+        stats.mean_connect_time, stats.connect_time
+    );
+    // UDP has no connections, and runs saved before the count existed read as zero
+    if stats.connections > 0 {
+        println!(
+            "  Connections opened: {} for {} iterations{}{}",
+            stats.connections,
+            stats.successful_iterations + stats.failed_iterations,
+            address_families(stats),
+            stats.local_address.map_or(String::new(), |ip| format!(", from {}", ip))
+        );
+    }
+    if let Some(streams) = stats.http2_streams {
+        println!("  HTTP/2 streams: up to {} at once over the one connection", streams);
+    }
+    if let Some(seed) = stats.seed {
+        println!("  Payload seed: {}", seed);
+    }
+    if stats.requests_per_connection > 1 {
+        println!(
+            "  Requests per connection: {} (latencies cover the whole conversation)",
+            stats.requests_per_connection
+        );
+    }
+    // Only worth a histogram when the responses actually differ
+    if stats.response_sizes.iter().any(|&size| size != stats.response_sizes[0]) {
+        println!("  Response sizes:");
+        print!("{}", stats.render_response_histogram());
+    }
+    if stats.retries > 0 {
+        println!("  Connect retries: {}", stats.retries);
+    }
+    if stats.premature_eofs > 0 {
+        let eofs = format!(
+            "Premature EOFs: {} (the connection closed before the response was complete)",
+            stats.premature_eofs
+        );
+        println!("  {}", color::paint(&eofs, color::YELLOW, color));
+    }
+    if let Some(concurrency) = stats.throttled_concurrency {
+        let throttled = format!(
+            "Concurrency: lowered to {} after running out of file descriptors; raise the \
+             open file limit (ulimit -n) to run more at once",
+            concurrency
+        );
+        println!("  {}", color::paint(&throttled, color::YELLOW, color));
+    }
+    if stats.connection_resets > 0 {
+        let resets = format!(
+            "Connection resets: {} (the peer reset the connection mid-transfer, often a sign \
+             of an overloaded server)",
+            stats.connection_resets
+        );
+        println!("  {}", color::paint(&resets, color::YELLOW, color));
+    }
+    if stats.failed_iterations > 0 {
+        let failed = format!(
+            "Failed iterations: {}/{}",
+            stats.failed_iterations,
+            stats.successful_iterations + stats.failed_iterations
+        );
+        println!("  {}", color::paint(&failed, color::RED, color));
+        if stats.corrupted_iterations > 0 {
+            let corrupted = format!("Corrupted responses: {}", stats.corrupted_iterations);
+            println!("  {}", color::paint(&corrupted, color::RED, color));
+        }
+        if stats.bytes_written_before_failure > 0 {
+            println!(
+                "  Sent before write failures: {} (not in the totals above)",
+                format_bytes(stats.bytes_written_before_failure)
+            );
+        }
+        for error in &stats.errors {
+            println!("    - {}", color::paint(error, color::RED, color));
+        }
+    }
+    println!();
+}
+
+// One line per sampling window, offsets relative to the connection being established
+fn print_timeline(label: &str, samples: &[(Duration, f64)], units: RateUnits) {
+    println!("  {} timeline:", label);
+    for (offset, rate) in samples {
+        let offset = format!("+{}ms", offset.as_millis());
+        println!("    {:>9}  {}", offset, format_rate_as(*rate, units));
+    }
+    println!();
+}
+
+// Gaps, one fewer than the iterations, before print_stats shows their histogram
+const MIN_GAPS_FOR_HISTOGRAM: usize = 20;
+
+// [[lower_secs, count], ...]
+fn gap_histogram_json(histogram: &[(Duration, usize)]) -> String {
+    let pairs: Vec<String> = histogram
+        .iter()
+        .map(|(lower, count)| format!("[{},{}]", lower.as_secs_f64(), count))
+        .collect();
+    format!("[{}]", pairs.join(","))
+}
+
+// [[bucket_lower_bound, count], ...]
+fn histogram_json(histogram: &[(usize, usize)]) -> String {
+    let pairs: Vec<String> = histogram
+        .iter()
+        .map(|(lower, count)| format!("[{},{}]", lower, count))
+        .collect();
+    format!("[{}]", pairs.join(","))
+}
+
+fn write_link_utilization(obj: &mut JsonObject, stats: &NetworkStats, link_bandwidth: f64) {
+    obj.float("link_bandwidth_bits", link_bandwidth)
+        .float("upload_link_utilization", link_utilization(stats.upload_rate, link_bandwidth))
+        .float("download_link_utilization", link_utilization(stats.download_rate, link_bandwidth));
+}
+
+// [[offset_secs, bytes_per_sec], ...]
+fn samples_json(samples: &[(Duration, f64)]) -> String {
+    let pairs: Vec<String> = samples
+        .iter()
+        .map(|(offset, rate)| format!("[{},{}]", offset.as_secs_f64(), rate))
+        .collect();
+    format!("[{}]", pairs.join(","))
+}
+
 // To extend this exercise:
 // 1. Add real TLS communication with reqwest or hyper crates
 // 2. Implement a proxy to measure traffic between two endpoints
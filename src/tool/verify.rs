@@ -0,0 +1,29 @@
+// Known payload pattern for --verify. Every byte is a function of its offset, so a
+// response can be checked without keeping the request around, and a server that
+// answers with a prefix of what it read (like the mock server's echo) still verifies.
+
+// 251 is prime, so the pattern doesn't line up with power-of-two buffer sizes
+pub fn pattern_byte(offset: usize) -> u8 {
+    (offset % 251) as u8
+}
+
+pub fn fill_pattern(len: usize) -> Vec<u8> {
+    (0..len).map(pattern_byte).collect()
+}
+
+// Offset of the first byte of `chunk` that breaks the pattern, given that the chunk
+// starts at `offset` in the stream
+pub fn first_mismatch(chunk: &[u8], offset: usize) -> Option<usize> {
+    chunk
+        .iter()
+        .enumerate()
+        .find(|&(i, &byte)| byte != pattern_byte(offset + i))
+        .map(|(i, _)| offset + i)
+}
+
+// `len` bytes that start with what was received and continue the pattern past it
+pub fn echo_response(received: &[u8], len: usize) -> Vec<u8> {
+    (0..len)
+        .map(|i| received.get(i).copied().unwrap_or_else(|| pattern_byte(i)))
+        .collect()
+}
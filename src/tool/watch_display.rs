@@ -0,0 +1,75 @@
+// The --watch block redrawn in place on a terminal: one row per size with the latest
+// and rolling rates, and the bytes moved so far, rewritten every cycle by moving the
+// cursor back up over the previous block. Off a terminal --watch appends a line per
+// cycle instead, which logs and pipes can keep.
+use super::cli::RateUnits;
+use super::color;
+use super::tool::{format_bytes, format_rate_as};
+
+// One size as of the latest cycle
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchRow {
+    pub size: usize,
+    // The latest cycle's (upload, download) rates, or why it failed
+    pub latest: Result<(f64, f64), String>,
+    pub rolling_upload: f64, // over the last few successful cycles
+    pub rolling_download: f64,
+    pub bytes_sent: usize, // over every cycle so far
+    pub bytes_received: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct WatchDisplay {
+    drawn: usize, // lines of the block on screen, to move back over
+}
+
+impl WatchDisplay {
+    pub fn new() -> Self {
+        WatchDisplay::default()
+    }
+
+    // The text that replaces the block drawn last time with `header` and `rows`. The
+    // first frame is printed where the cursor is; each line clears what a longer line
+    // of the previous frame left. Rows stay under 80 columns for a typical size, as
+    // a line that wraps would throw the count off; long error messages still can.
+    pub fn frame(&mut self, header: &str, rows: &[WatchRow], units: RateUnits, on: bool) -> String {
+        let mut lines = vec![header.to_string()];
+        for row in rows {
+            lines.push(format_row(row, units, on));
+        }
+        let sent: usize = rows.iter().map(|row| row.bytes_sent).sum();
+        let received: usize = rows.iter().map(|row| row.bytes_received).sum();
+        lines.push(format!(
+            "  Total: {} sent, {} received",
+            format_bytes(sent),
+            format_bytes(received)
+        ));
+
+        // \x1b[nF moves to the start of the line n lines up
+        let mut out = match self.drawn {
+            0 => String::new(),
+            n => format!("\x1b[{}F", n),
+        };
+        for line in &lines {
+            out.push_str(line);
+            out.push_str("\x1b[K\n");
+        }
+        self.drawn = lines.len();
+        out
+    }
+}
+
+fn format_row(row: &WatchRow, units: RateUnits, on: bool) -> String {
+    let rate = |rate: f64| format_rate_as(rate, units);
+    let now = match &row.latest {
+        Ok((upload, download)) => format!(
+            "down {} (avg {}), up {} (avg {})",
+            color::paint(&rate(*download), color::GREEN, on),
+            rate(row.rolling_download),
+            rate(*upload),
+            rate(row.rolling_upload)
+        ),
+        Err(e) => color::paint(&format!("failed: {}", e), color::RED, on),
+    };
+    format!("  {}: {}", format_bytes(row.size), now)
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::tool::output::open_output;
+use network_traffic_monitoring::{run_benchmarks, NetworkStats};
+
+use common::{parse, start};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("append-{}-{}", std::process::id(), name))
+}
+
+// Two runs of one size each into the same --output file
+async fn two_runs(format: &str, path: &Path) {
+    let server = start().await;
+    let address = server.address.clone();
+    let output = path.display().to_string();
+    for size in ["1000", "2000"] {
+        let args = [
+            "--format", format, "--output", &output, "--append", "--address", &address,
+            "--sizes", size, "--iterations", "1", "--delay-ms", "0",
+        ];
+        let Ok(Command::Run(config)) = parse(&args) else {
+            panic!("expected a run");
+        };
+        run_benchmarks(&config).await.unwrap();
+    }
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn csv_runs_share_one_header() {
+    let path = temp_path("runs.csv");
+    two_runs("csv", &path).await;
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", text);
+    assert_eq!(lines[0], NetworkStats::CSV_HEADER);
+    assert!(lines[1].starts_with("1000,") && lines[2].starts_with("2000,"), "{}", text);
+}
+
+#[tokio::test]
+async fn json_runs_add_lines() {
+    let path = temp_path("runs.jsonl");
+    std::fs::write(&path, "{\"size\":1}").unwrap();
+    two_runs("json", &path).await;
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    // The existing line lacked its newline, which was added before the new ones
+    assert_eq!(lines.len(), 3, "{}", text);
+    assert_eq!(lines[0], "{\"size\":1}");
+    assert!(lines[1].starts_with("{\"size\":1000,"), "{}", lines[1]);
+    assert!(lines[2].starts_with("{\"size\":2000,"), "{}", lines[2]);
+}
+
+#[test]
+fn a_file_of_other_records_is_not_appended_to() {
+    let path = temp_path("other.csv");
+    std::fs::write(&path, "a,b,c\n1,2,3\n").unwrap();
+
+    let err = open_output(&path, true, Some("size,bytes_sent")).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("different CSV header ('a,b,c'"), "{}", err);
+    let err = open_output(&path, true, None).unwrap_err();
+    assert!(err.to_string().contains("doesn't hold JSON lines"), "{}", err);
+    // Nothing was written to it
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "a,b,c\n1,2,3\n");
+
+    // Without --append the file is replaced
+    let mut file = open_output(&path, false, Some("size,bytes_sent")).unwrap();
+    writeln!(file, "10,20").unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "size,bytes_sent\n10,20\n");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn a_missing_file_is_created_with_its_header() {
+    let path = temp_path("new.csv");
+    let _ = std::fs::remove_file(&path);
+    let mut file = open_output(&path, true, Some("size,bytes_sent")).unwrap();
+    writeln!(file, "10,20").unwrap();
+    drop(file);
+    let mut file = open_output(&path, true, Some("size,bytes_sent")).unwrap();
+    writeln!(file, "30,40").unwrap();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "size,bytes_sent\n10,20\n30,40\n");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn append_needs_a_file_to_add_to() {
+    assert!(parse(&["--append", "--format", "json", "--output", "out.jsonl"]).is_ok());
+    assert!(parse(&["--append", "--export-records", "records.csv"]).is_ok());
+    let without_a_file = [
+        &["--append"][..],
+        &["--append", "--output", "out.txt"],
+        &["--append", "--format", "csv"],
+    ];
+    for args in without_a_file {
+        let error = parse(args).unwrap_err();
+        assert!(error.starts_with("--append adds to an --output file"), "{}", error);
+    }
+}
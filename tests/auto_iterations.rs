@@ -0,0 +1,63 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::{measure_transfer, AutoIterations, TransferOptions};
+
+use common::{parse, start};
+
+fn options(target_precision: f64, max_iterations: usize, concurrency: usize) -> TransferOptions {
+    TransferOptions {
+        delay: Duration::ZERO,
+        concurrency,
+        auto_iterations: Some(AutoIterations { target_precision, max_iterations }),
+        ..TransferOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn stops_after_the_first_batch_once_precise_enough() {
+    let server = start().await;
+
+    let loose = options(f64::MAX, 100, 1);
+    let stats = measure_transfer(&server.address, 1024, 3, &loose).await.unwrap();
+    assert_eq!(stats.auto_iterations, Some(3));
+    assert_eq!(stats.successful_iterations, 3);
+
+    server.stop().await;
+}
+
+// Loopback rates never agree to a billionth, so the batches double up to the cap
+#[tokio::test]
+async fn doubles_the_batches_up_to_the_cap() {
+    let server = start().await;
+    let address = server.address.clone();
+
+    for concurrency in [1, 3] {
+        let strict = options(1e-9, 10, concurrency);
+        let stats = measure_transfer(&address, 1024, 2, &strict).await.unwrap();
+        assert_eq!(stats.auto_iterations, Some(10), "concurrency {}", concurrency);
+        assert_eq!(stats.successful_iterations, 10);
+    }
+
+    let fixed = TransferOptions { auto_iterations: None, ..options(1e-9, 10, 1) };
+    let stats = measure_transfer(&address, 1024, 2, &fixed).await.unwrap();
+    assert_eq!((stats.auto_iterations, stats.successful_iterations), (None, 2));
+
+    server.stop().await;
+}
+
+#[test]
+fn auto_iterations_from_the_command_line() {
+    match parse(&["--target-precision", "2%", "--iterations", "auto", "--max-iterations", "50"]) {
+        Ok(Command::Run(config)) => assert_eq!(
+            config.auto_iterations,
+            Some(AutoIterations { target_precision: 0.02, max_iterations: 50 })
+        ),
+        other => panic!("{:?}", other.err()),
+    }
+    assert!(parse(&["--target-precision", "0.02"]).unwrap_err().contains("--iterations auto"));
+    assert!(parse(&["--iterations", "auto", "--target-precision", "150%"]).is_err());
+    assert!(parse(&["--iterations", "auto", "--max-iterations", "2"]).is_err());
+}
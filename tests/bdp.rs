@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::json;
+use network_traffic_monitoring::{JsonObject, NetworkStats};
+
+fn stats(upload_rate: f64, download_rate: f64, mean_latency: Duration) -> NetworkStats {
+    NetworkStats { upload_rate, download_rate, mean_latency, ..NetworkStats::default() }
+}
+
+#[test]
+fn product_uses_the_faster_direction_and_the_mean_round_trip() {
+    let stats = stats(10_000_000.0, 2_000_000.0, Duration::from_millis(50));
+    assert_eq!(stats.bandwidth_delay_product(), 500_000);
+    // Rounded up to a power of two
+    assert_eq!(stats.suggested_socket_buffer(), 512 * 1024);
+}
+
+#[test]
+fn suggestions_stay_within_what_a_socket_takes() {
+    let tiny = stats(1_000.0, 1_000.0, Duration::from_millis(1));
+    assert_eq!(tiny.bandwidth_delay_product(), 1);
+    assert_eq!(tiny.suggested_socket_buffer(), 64 * 1024);
+    assert_eq!(NetworkStats::default().bandwidth_delay_product(), 0);
+
+    let huge = stats(f64::INFINITY, 0.0, Duration::from_secs(1));
+    assert_eq!(huge.suggested_socket_buffer(), 1 << 31);
+}
+
+#[test]
+fn product_is_in_the_json_fields() {
+    let stats = stats(1_000_000.0, 0.0, Duration::from_millis(10));
+    let mut obj = JsonObject::new();
+    stats.write_json_fields(&mut obj);
+
+    let parsed = json::parse(&obj.finish()).unwrap();
+    let product = parsed.get("bandwidth_delay_product").and_then(|value| value.as_f64());
+    assert_eq!(product, Some(10_000.0));
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::{measure_transfer, NetworkStats, TransferError, TransferOptions};
+use tokio::net::TcpListener;
+
+use common::{parse, round_trip, start};
+
+fn bound_to(ip: IpAddr) -> TransferOptions {
+    TransferOptions {
+        delay: Duration::ZERO,
+        bind_addr: Some(SocketAddr::new(ip, 0)),
+        ..TransferOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn the_source_address_is_reported() {
+    let server = start().await;
+    let address = server.address.clone();
+
+    let options = TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() };
+    let stats = measure_transfer(&address, 1000, 2, &options).await.unwrap();
+    assert_eq!(stats.local_address, Some(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+
+    // Linux answers on the whole of 127.0.0.0/8, so there's a second source to pick
+    #[cfg(target_os = "linux")]
+    {
+        let source = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2));
+        let stats = measure_transfer(&address, 1000, 3, &bound_to(source)).await.unwrap();
+        assert_eq!(stats.successful_iterations, 3);
+        assert_eq!(stats.local_address, Some(source));
+    }
+
+    server.stop().await;
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn the_server_sees_the_bound_address() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let accepted = tokio::spawn(async move { listener.accept().await.unwrap().1 });
+    let source = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 3));
+    let options = TransferOptions { timeout: Duration::from_millis(200), ..bound_to(source) };
+    // The listener never answers, so the iteration times out once connected
+    let _ = measure_transfer(&address, 10, 1, &options).await;
+    assert_eq!(accepted.await.unwrap().ip(), source);
+}
+
+#[tokio::test]
+async fn a_source_of_the_wrong_family_fails() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let err = measure_transfer(&address, 10, 1, &bound_to("::1".parse().unwrap()))
+        .await
+        .unwrap_err();
+    let TransferError::AllFailed { cause: Some(cause), .. } = &err else {
+        panic!("expected the iteration to fail: {:?}", err);
+    };
+    assert!(matches!(**cause, TransferError::Connect(_)), "{:?}", cause);
+    let message = err.to_string();
+    assert!(message.contains("the target has no IPv6 address to reach from ::1"), "{}", message);
+}
+
+#[test]
+fn the_local_address_survives_a_json_round_trip() {
+    let ip: IpAddr = "fd00::2".parse().unwrap();
+    let stats = NetworkStats { local_address: Some(ip), ..NetworkStats::default() };
+    assert_eq!(round_trip(&stats).local_address, Some(ip));
+}
+
+#[test]
+fn bind_takes_an_ip_or_a_socket_address() {
+    let bind = |raw: &str| match parse(&["--bind", raw]) {
+        Ok(Command::Run(config)) => config.bind_addr,
+        other => panic!("expected a run: {:?}", other.err()),
+    };
+    assert_eq!(bind("192.0.2.2"), Some("192.0.2.2:0".parse().unwrap()));
+    assert_eq!(bind("192.0.2.2:40000"), Some("192.0.2.2:40000".parse().unwrap()));
+    assert_eq!(bind("[fd00::2]"), Some("[fd00::2]:0".parse().unwrap()));
+    assert_eq!(bind("fd00::2"), Some("[fd00::2]:0".parse().unwrap()));
+    assert_eq!(
+        parse(&["--bind", "eth0"]).unwrap_err(),
+        "--bind expects a local IP address or IP:PORT, got 'eth0'"
+    );
+    assert_eq!(
+        parse(&["--bind", "192.0.2.2", "--url", "http://localhost/"]).unwrap_err(),
+        "--bind applies to raw TCP connections, not --url"
+    );
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::{
+    measure_transfer, run_mock_server, MockServerConfig, NetworkStats, TransferError,
+    TransferOptions,
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{watch, Barrier};
+
+use common::{parse, round_trip};
+
+// Relays to `upstream`, but only once `herd` connections are open at the same time, so
+// iterations that don't all connect together never finish
+async fn herd_relay(upstream: String, herd: usize) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let barrier = Arc::new(Barrier::new(herd));
+    tokio::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            let upstream = upstream.clone();
+            let barrier = barrier.clone();
+            tokio::spawn(async move {
+                barrier.wait().await;
+                let mut server = TcpStream::connect(upstream).await.unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+            });
+        }
+    });
+    address
+}
+
+fn burst() -> TransferOptions {
+    TransferOptions {
+        burst: true,
+        timeout: Duration::from_secs(5),
+        ..TransferOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn every_iteration_is_in_flight_at_once() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig::default();
+    let server_stats = config.stats.clone();
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+    let relay = herd_relay(bound.to_string(), 20).await;
+
+    let stats = measure_transfer(&relay, 1000, 20, &burst()).await.unwrap();
+    assert!(stats.burst);
+    assert_eq!(stats.successful_iterations, 20);
+    assert_eq!(stats.failed_connects, 0);
+    assert_eq!(server_stats.connections(), 20);
+    assert!(stats.min_latency <= stats.max_latency);
+    assert_eq!(stats.start_gaps.len(), 19);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn a_refused_burst_counts_as_connect_failures() {
+    // Bound and dropped, so nothing listens there
+    let closed = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+    let err = measure_transfer(&closed.to_string(), 1000, 10, &burst()).await.unwrap_err();
+    let TransferError::AllFailed { iterations, cause: Some(cause), .. } = &err else {
+        panic!("expected every iteration to fail: {:?}", err);
+    };
+    assert_eq!(*iterations, 10);
+    assert!(matches!(**cause, TransferError::Connect(_)), "{:?}", cause);
+}
+
+#[tokio::test]
+async fn a_burst_needs_its_count_up_front() {
+    let options = TransferOptions { reuse_connection: true, ..burst() };
+    let err = measure_transfer("127.0.0.1:9", 1000, 10, &options).await.unwrap_err();
+    assert!(matches!(err, TransferError::InvalidOptions(_)), "{:?}", err);
+
+    let options = TransferOptions { total_bytes: Some(10_000), ..burst() };
+    let err = measure_transfer("127.0.0.1:9", 1000, 10, &options).await.unwrap_err();
+    assert!(matches!(err, TransferError::InvalidOptions(_)), "{:?}", err);
+}
+
+#[test]
+fn burst_figures_survive_a_json_round_trip() {
+    let stats = NetworkStats { burst: true, failed_connects: 7, ..NetworkStats::default() };
+    let parsed = round_trip(&stats);
+    assert!(parsed.burst);
+    assert_eq!(parsed.failed_connects, 7);
+}
+
+#[test]
+fn burst_replaces_steady_concurrency() {
+    let Ok(Command::Run(config)) = parse(&["--burst", "--iterations", "500"]) else {
+        panic!("expected a run");
+    };
+    assert!(config.burst);
+
+    let error = parse(&["--burst", "--concurrency", "4"]).unwrap_err();
+    assert_eq!(
+        error,
+        "--burst starts every iteration at once on its own connection, so it can't be \
+         combined with --concurrency"
+    );
+    assert!(parse(&["--burst", "--reuse-connection"]).is_err());
+}
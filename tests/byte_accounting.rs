@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use network_traffic_monitoring::{
+    measure_transfer_with_progress, run_mock_server, MockServerConfig, NetworkStats,
+    TransferOptions,
+};
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+
+// Relays to `upstream`, except every third connection, which is hung up on once its
+// request has started to arrive
+async fn flaky_relay(upstream: String) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    tokio::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            let upstream = upstream.clone();
+            let nth = accepted.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async move {
+                if nth % 3 == 2 {
+                    let mut first = [0u8; 1];
+                    let _ = client.read_exact(&mut first).await;
+                    return;
+                }
+                let mut server = TcpStream::connect(upstream).await.unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+            });
+        }
+    });
+    address
+}
+
+// Runs with both the progress callback and the records, and checks that each adds up
+// to the totals
+async fn measure_summed(address: &str, size: usize, options: TransferOptions) -> NetworkStats {
+    let options = TransferOptions { keep_records: true, ..options };
+    let mut reported = (0, 0);
+    let stats = measure_transfer_with_progress(address, size, 9, &options, |result| {
+        reported.0 += result.bytes_sent;
+        reported.1 += result.bytes_received;
+    })
+    .await
+    .unwrap();
+    assert_eq!(reported, (stats.bytes_sent, stats.bytes_received));
+    let sent: usize = stats.records.iter().map(|record| record.bytes_sent).sum();
+    let received: usize = stats.records.iter().map(|record| record.bytes_received).sum();
+    assert_eq!((sent, received), (stats.bytes_sent, stats.bytes_received));
+    stats
+}
+
+#[tokio::test]
+async fn iterations_add_up_to_the_totals() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig { response_ratio: 0.5, ..MockServerConfig::default() };
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+    let relay = flaky_relay(bound.to_string()).await;
+    let base = TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() };
+
+    let stats = measure_summed(&relay, 10_000, base.clone()).await;
+    assert_eq!(stats.failed_iterations, 3);
+    assert_eq!(stats.records.len(), 9);
+    // Failures send nothing towards the totals, even when part of the request went out
+    assert_eq!(stats.bytes_sent, 6 * 10_000);
+    assert_eq!(stats.bytes_received, 6 * 5_000);
+
+    let concurrent = TransferOptions { concurrency: 3, ..base.clone() };
+    let stats = measure_summed(&relay, 10_000, concurrent).await;
+    assert_eq!(stats.successful_iterations + stats.failed_iterations, 9);
+
+    let reused = TransferOptions { reuse_connection: true, ..base.clone() };
+    measure_summed(&bound.to_string(), 10_000, reused).await;
+
+    let total = TransferOptions { total_bytes: Some(25_000), ..base };
+    let stats = measure_summed(&bound.to_string(), 10_000, total).await;
+    assert_eq!(stats.bytes_sent, 25_000);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
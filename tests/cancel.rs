@@ -0,0 +1,57 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::{measure_transfer, measure_transfer_with_progress, TransferOptions};
+use tokio::sync::watch;
+
+use common::start;
+
+#[tokio::test]
+async fn cancelling_keeps_the_finished_iterations() {
+    let server = start().await;
+
+    let (interrupt, cancel) = watch::channel(false);
+    let options = TransferOptions {
+        delay: Duration::ZERO,
+        cancel: Some(cancel),
+        ..TransferOptions::default()
+    };
+    let mut seen = 0;
+    let stats = measure_transfer_with_progress(&server.address, 1000, 100, &options, |_| {
+        seen += 1;
+        if seen == 3 {
+            interrupt.send(true).unwrap();
+        }
+    })
+    .await
+    .unwrap();
+
+    assert!(stats.interrupted);
+    assert_eq!(stats.successful_iterations, 3);
+    assert_eq!(stats.bytes_sent, 3000);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn a_cancelled_measurement_still_runs_what_is_in_flight() {
+    let server = start().await;
+    let address = server.address.clone();
+
+    // Already cancelled: one iteration sequentially, one window of them concurrently
+    let (_interrupt, cancel) = watch::channel(true);
+    let sequential = TransferOptions { cancel: Some(cancel.clone()), ..TransferOptions::default() };
+    let stats = measure_transfer(&address, 100, 10, &sequential).await.unwrap();
+    assert_eq!((stats.successful_iterations, stats.interrupted), (1, true));
+
+    let concurrent = TransferOptions { concurrency: 4, ..sequential };
+    let stats = measure_transfer(&address, 100, 20, &concurrent).await.unwrap();
+    assert_eq!((stats.successful_iterations, stats.interrupted), (4, true));
+
+    // A run that completes isn't marked, however late the flag is set
+    let stats = measure_transfer(&address, 100, 1, &concurrent).await.unwrap();
+    assert!(!stats.interrupted);
+
+    server.stop().await;
+}
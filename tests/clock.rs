@@ -0,0 +1,51 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use network_traffic_monitoring::{measure_transfer, Clock, ScriptedClock, TransferOptions};
+
+use common::start;
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+#[test]
+fn scripted_clock_hands_out_offsets_then_stands_still() {
+    let clock = ScriptedClock::new([ms(0), ms(40)]);
+    let start = clock.now();
+    assert_eq!(clock.elapsed_since(start), ms(40));
+    assert_eq!(clock.elapsed_since(start), ms(40));
+    assert_eq!(clock.remaining(), 0);
+}
+
+#[tokio::test]
+async fn rates_come_from_the_clock_readings() {
+    let server = start().await;
+
+    // Readings in order: first iteration's start, connect start and end, first
+    // iteration's end, then the second iteration's start and end on the same connection
+    let clock = Arc::new(ScriptedClock::new([ms(0), ms(0), ms(50), ms(300), ms(300), ms(550)]));
+    let options = TransferOptions {
+        delay: Duration::ZERO,
+        reuse_connection: true,
+        clock: clock.clone(),
+        ..TransferOptions::default()
+    };
+    let stats = measure_transfer(&server.address, 1000, 2, &options).await.unwrap();
+    assert_eq!(clock.remaining(), 0);
+
+    assert_eq!(stats.connect_time, ms(50));
+    assert_eq!(stats.latencies, vec![ms(300), ms(250)]);
+    // 550ms of round trips less the 50ms connect
+    assert_eq!(stats.elapsed_time, ms(500));
+    assert_eq!(stats.bytes_sent, 2000);
+    assert_eq!(stats.bytes_received, 1000);
+    assert_eq!(stats.upload_rate, 4000.0);
+    assert_eq!(stats.download_rate, 2000.0);
+    assert_eq!(stats.ratio, 0.5);
+    assert_eq!(stats.mean_latency, ms(275));
+
+    server.stop().await;
+}
@@ -0,0 +1,24 @@
+use network_traffic_monitoring::tool::cli::{parse_args, Command};
+use network_traffic_monitoring::tool::color::{paint, GREEN};
+use network_traffic_monitoring::Config;
+
+fn run_config(args: &[&str]) -> Config {
+    match parse_args(args.iter().map(|arg| arg.to_string())) {
+        Ok(Command::Run(config)) => *config,
+        other => panic!("expected a run, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn paint_wraps_only_when_on() {
+    assert_eq!(paint("1.00 MB/s", GREEN, true), "\x1b[32m1.00 MB/s\x1b[0m");
+    assert_eq!(paint("1.00 MB/s", GREEN, false), "1.00 MB/s");
+}
+
+#[test]
+fn color_is_off_for_pipes_machine_formats_and_no_color() {
+    assert!(!run_config(&[]).color_for(false));
+    assert!(!run_config(&["--format", "json"]).color_for(true));
+    assert!(!run_config(&["--format", "csv"]).color_for(true));
+    assert!(!run_config(&["--no-color"]).color_for(true));
+}
@@ -0,0 +1,68 @@
+// Helpers shared by the integration tests, each of which declares `mod common;`. Not
+// every test file uses every helper.
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::{parse_args, Command};
+use network_traffic_monitoring::tool::{framing, json};
+use network_traffic_monitoring::tool::mock_server::ServerResult;
+use network_traffic_monitoring::{run_mock_server, JsonObject, MockServerConfig, NetworkStats};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+// parse_args over string literals, the way they'd come from the command line
+pub fn parse(args: &[&str]) -> Result<Command, String> {
+    parse_args(args.iter().map(|arg| arg.to_string()))
+}
+
+// `stats` as a saved JSON result reads back
+pub fn round_trip(stats: &NetworkStats) -> NetworkStats {
+    let mut obj = JsonObject::new();
+    stats.write_json_fields(&mut obj);
+    NetworkStats::from_json(&json::parse(&obj.finish()).unwrap()).unwrap()
+}
+
+// The default mock server on a free port, for tests that only need something to measure
+// against
+pub struct MockServer {
+    pub address: String,
+    pub port: u16,
+    stop: watch::Sender<bool>,
+    handle: JoinHandle<ServerResult>,
+}
+
+pub async fn start() -> MockServer {
+    let (stop, stop_signal) = watch::channel(false);
+    let (bound, handle) = run_mock_server(MockServerConfig::default(), stop_signal).await.unwrap();
+    MockServer { address: bound.to_string(), port: bound.port(), stop, handle }
+}
+
+impl MockServer {
+    // Shuts the server down and checks that it stopped cleanly
+    pub async fn stop(self) {
+        self.stop.send(true).unwrap();
+        self.handle.await.unwrap().unwrap();
+    }
+}
+
+// Reads each request in full, then waits `think` before answering with 10 bytes
+pub async fn slow_server(think: Duration) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                while let Ok(Some(len)) = framing::read_frame_len(&mut socket).await {
+                    let mut body = vec![0u8; len];
+                    socket.read_exact(&mut body).await.unwrap();
+                    tokio::time::sleep(think).await;
+                    framing::write_frame(&mut socket, &[0u8; 10]).await.unwrap();
+                }
+            });
+        }
+    });
+    address
+}
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::confidence::{
+    confidence_interval_95, mean, sample_std_dev, standard_error,
+};
+use network_traffic_monitoring::NetworkStats;
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-6 * b.abs().max(1.0)
+}
+
+#[test]
+fn spread_of_known_samples() {
+    let samples = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+    assert_eq!(mean(&samples), Some(5.0));
+    // Sum of squares 32 over n - 1 = 7
+    assert!(close(sample_std_dev(&samples).unwrap(), (32.0f64 / 7.0).sqrt()));
+    let se = standard_error(&samples).unwrap();
+    assert!(close(se, (32.0f64 / 7.0).sqrt() / 8f64.sqrt()));
+    // t for 7 degrees of freedom
+    let (low, high) = confidence_interval_95(&samples).unwrap();
+    assert!(close(low, 5.0 - 2.365 * se) && close(high, 5.0 + 2.365 * se));
+}
+
+#[test]
+fn fewer_than_two_samples_have_no_spread() {
+    assert_eq!(mean(&[]), None);
+    assert_eq!(mean(&[3.0]), Some(3.0));
+    assert_eq!(sample_std_dev(&[3.0]), None);
+    assert_eq!(confidence_interval_95(&[3.0]), None);
+}
+
+#[test]
+fn download_rates_come_from_each_iteration() {
+    let stats = NetworkStats {
+        latencies: vec![Duration::from_secs(1), Duration::from_secs(2), Duration::from_secs(4)],
+        response_sizes: vec![1000, 1000, 4000],
+        ..NetworkStats::default()
+    };
+    assert_eq!(stats.download_rate_samples(), vec![1000.0, 500.0, 1000.0]);
+    let mean = stats.mean_download_rate().unwrap();
+    assert!(close(mean, 2500.0 / 3.0));
+    let se = stats.download_rate_standard_error().unwrap();
+    assert!(close(se, stats.download_rate_std_dev().unwrap() / 3f64.sqrt()));
+    let (low, high) = stats.download_rate_confidence_interval().unwrap();
+    assert!(close(high - mean, 4.303 * se) && close(mean - low, 4.303 * se));
+
+    let single = NetworkStats {
+        latencies: vec![Duration::from_secs(1)],
+        response_sizes: vec![10],
+        ..NetworkStats::default()
+    };
+    assert_eq!(single.mean_download_rate(), Some(10.0));
+    assert_eq!(single.download_rate_confidence_interval(), None);
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use network_traffic_monitoring::{measure_transfer, NetworkStats, TransferError, TransferOptions};
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream};
+
+use common::{round_trip, start};
+
+// Relays every other connection to `upstream` and resets the rest once their request
+// has started to arrive
+async fn resetting_relay(upstream: String) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    tokio::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            let upstream = upstream.clone();
+            let nth = accepted.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async move {
+                if nth % 2 == 1 {
+                    let mut first = [0u8; 1];
+                    let _ = client.read_exact(&mut first).await;
+                    // A zero linger makes the close an RST
+                    client.set_linger(Some(Duration::ZERO)).unwrap();
+                    return;
+                }
+                let mut server = TcpStream::connect(upstream).await.unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+            });
+        }
+    });
+    address
+}
+
+#[tokio::test]
+async fn resets_are_counted_apart_from_other_failures() {
+    let server = start().await;
+    let relay = resetting_relay(server.address.clone()).await;
+
+    let options = TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() };
+    let stats = measure_transfer(&relay, 100_000, 6, &options).await.unwrap();
+    assert_eq!(stats.successful_iterations, 3);
+    assert_eq!(stats.failed_iterations, 3);
+    assert_eq!(stats.connection_resets, 3);
+    assert_eq!(stats.premature_eofs, 0);
+    assert!(stats.errors.iter().all(|e| e.starts_with("connection reset by peer")), "{:?}", stats);
+
+    let options = TransferOptions { fail_fast: true, ..options };
+    let mut seen = Vec::new();
+    for _ in 0..2 {
+        match measure_transfer(&relay, 100_000, 1, &options).await {
+            Ok(_) => seen.push("ok"),
+            Err(err) => {
+                assert!(err.is_connection_reset(), "{:?}", err);
+                assert_eq!(err.category(), "reset");
+                seen.push("reset");
+            }
+        }
+    }
+    seen.sort();
+    assert_eq!(seen, ["ok", "reset"]);
+
+    server.stop().await;
+}
+
+#[test]
+fn resets_are_told_apart_by_their_error_kind() {
+    for kind in [ErrorKind::ConnectionReset, ErrorKind::ConnectionAborted, ErrorKind::BrokenPipe] {
+        for phase in ["write", "read"] {
+            let err = TransferError::during(phase, kind.into());
+            assert!(
+                matches!(err, TransferError::ConnectionReset { phase: p, .. } if p == phase),
+                "{:?}",
+                err
+            );
+        }
+        // A reset while connecting is a connect failure, like a refusal
+        assert!(matches!(TransferError::during("connect", kind.into()), TransferError::Connect(_)));
+    }
+    assert!(matches!(
+        TransferError::during("read", ErrorKind::UnexpectedEof.into()),
+        TransferError::Read(_)
+    ));
+
+    let cut_short = TransferError::PartialWrite {
+        written: 10,
+        total: 100,
+        cause: Box::new(TransferError::during("write", ErrorKind::BrokenPipe.into())),
+    };
+    assert!(cut_short.is_connection_reset());
+    assert_eq!(cut_short.category(), "reset");
+    assert!(!TransferError::during("read", ErrorKind::TimedOut.into()).is_connection_reset());
+}
+
+#[test]
+fn the_reset_count_survives_a_json_round_trip() {
+    let stats = NetworkStats { connection_resets: 4, ..NetworkStats::default() };
+    assert_eq!(round_trip(&stats).connection_resets, 4);
+}
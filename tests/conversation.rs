@@ -0,0 +1,35 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::{measure_transfer_with_progress, TransferOptions};
+
+use common::start;
+
+#[tokio::test]
+async fn each_iteration_is_one_conversation_without_think_time() {
+    let server = start().await;
+
+    let options = TransferOptions {
+        delay: Duration::ZERO,
+        requests_per_connection: 4,
+        think_time: Duration::from_millis(100),
+        ..TransferOptions::default()
+    };
+    let mut seen = Vec::new();
+    let stats = measure_transfer_with_progress(&server.address, 1000, 2, &options, |result| {
+        seen.push(result.clone())
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(stats.successful_iterations, 2);
+    assert_eq!(stats.requests_per_connection, 4);
+    assert_eq!(stats.bytes_sent, 2 * 4 * 1000);
+    assert_eq!(stats.bytes_received, 2 * 4 * 500);
+    assert!(seen.iter().all(|r| r.bytes_sent == 4000 && r.bytes_received == 2000));
+    // Three 100ms pauses per conversation happened but aren't in the latencies
+    assert!(stats.max_latency < Duration::from_millis(300), "{:?}", stats.max_latency);
+
+    server.stop().await;
+}
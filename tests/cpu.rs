@@ -0,0 +1,33 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cpu::{parse_proc_stat, process_cpu_time};
+use network_traffic_monitoring::NetworkStats;
+
+use common::round_trip;
+
+#[test]
+fn reads_user_and_system_ticks_past_the_command_name() {
+    let stat = "4242 (tokio (rt) worker) R 1 4242 4242 0 -1 4194560 1200 0 0 0 \
+                250 75 0 0 20 0 9 0 123456 1000000 500";
+    assert_eq!(parse_proc_stat(stat), Some(Duration::from_millis(3250)));
+    assert_eq!(parse_proc_stat("4242 (short) R 1"), None);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn this_process_has_cpu_time() {
+    let start = process_cpu_time().unwrap();
+    assert!(process_cpu_time().unwrap() >= start);
+}
+
+#[test]
+fn cpu_bound_from_a_core_busy_and_kept_in_json() {
+    let stats = NetworkStats { cpu_utilization: Some(0.95), ..NetworkStats::default() };
+    assert!(stats.cpu_bound());
+    assert!(!NetworkStats { cpu_utilization: Some(0.3), ..NetworkStats::default() }.cpu_bound());
+    assert!(!NetworkStats::default().cpu_bound());
+
+    assert_eq!(round_trip(&stats).cpu_utilization, Some(0.95));
+}
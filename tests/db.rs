@@ -0,0 +1,110 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+// These need the sqlite3 command-line shell on PATH
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use network_traffic_monitoring::tool::cli::{Command, Config};
+use network_traffic_monitoring::tool::db::RunDatabase;
+use network_traffic_monitoring::{run_benchmarks, NetworkStats, OutputFormat};
+
+use common::{parse, start};
+
+fn db_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("db-{}-{}.sqlite", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn labelled(label: Option<&str>, download_rate: f64) -> NetworkStats {
+    NetworkStats {
+        label: label.map(String::from),
+        download_rate,
+        timestamp: Some(UNIX_EPOCH + Duration::from_secs(1_714_564_800)),
+        config_summary: "tcp host:9000, 5 iterations".to_string(),
+        ..NetworkStats::default()
+    }
+}
+
+#[tokio::test]
+async fn the_last_runs_come_back_oldest_first() {
+    let path = db_path("last");
+    let db = RunDatabase::open(&path).await.unwrap();
+    for rate in [100.0, 200.0, 300.0, 400.0] {
+        db.insert(Some(1000), &labelled(Some("nightly"), rate)).await.unwrap();
+    }
+    db.insert(None, &labelled(Some("it's quoted"), 50.0)).await.unwrap();
+    db.insert(Some(1000), &labelled(None, 10.0)).await.unwrap();
+
+    let runs = db.last_runs(Some("nightly"), 3).await.unwrap();
+    let rates: Vec<f64> = runs.iter().map(|run| run.stats.download_rate).collect();
+    assert_eq!(rates, [200.0, 300.0, 400.0]);
+    assert!(runs.windows(2).all(|pair| pair[0].id < pair[1].id));
+    assert_eq!(runs[0].size, Some(1000));
+    assert_eq!(runs[0].stats.timestamp, Some(UNIX_EPOCH + Duration::from_secs(1_714_564_800)));
+    assert_eq!(runs[0].stats.config_summary, "tcp host:9000, 5 iterations");
+
+    let quoted = db.last_runs(Some("it's quoted"), 10).await.unwrap();
+    assert_eq!((quoted.len(), quoted[0].size), (1, None));
+    let unlabelled = db.last_runs(None, 10).await.unwrap();
+    assert_eq!(unlabelled.len(), 1);
+    assert_eq!(unlabelled[0].stats.download_rate, 10.0);
+    assert!(db.last_runs(Some("never used"), 10).await.unwrap().is_empty());
+
+    // Opening it again keeps what's there
+    let reopened = RunDatabase::open(&path).await.unwrap();
+    assert_eq!(reopened.last_runs(Some("nightly"), 10).await.unwrap().len(), 4);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn a_sweep_records_every_size() {
+    let server = start().await;
+    let path = db_path("sweep");
+    let output = std::env::temp_dir().join(format!("db-sweep-{}.json", std::process::id()));
+
+    let config = Config {
+        address: Some(server.address.clone()),
+        sizes: vec![1000, 5000],
+        iterations: 2,
+        delay: Duration::ZERO,
+        label: Some("sweep".to_string()),
+        format: OutputFormat::Json,
+        output: Some(output.display().to_string()),
+        db: Some(path.display().to_string()),
+        ..Config::default()
+    };
+    run_benchmarks(&config).await.unwrap();
+    let runs = RunDatabase::open(&path).await.unwrap().last_runs(Some("sweep"), 10).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&output).unwrap();
+    let sizes: Vec<Option<usize>> = runs.iter().map(|run| run.size).collect();
+    assert_eq!(sizes, [Some(1000), Some(5000)]);
+    assert!(runs.iter().all(|run| run.stats.successful_iterations == 2));
+    assert_eq!(runs[0].stats.config_summary, config.summary());
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn a_missing_shell_is_named() {
+    let err = RunDatabase::open_with(db_path("missing"), "/nonexistent/sqlite3").await.unwrap_err();
+    assert_eq!(
+        err,
+        "--db needs the sqlite3 command-line shell, and /nonexistent/sqlite3 wasn't found"
+    );
+}
+
+#[test]
+fn db_records_sweeps_only() {
+    let Ok(Command::Run(config)) = parse(&["--db", "runs.sqlite"]) else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.db.as_deref(), Some("runs.sqlite"));
+    assert_eq!(
+        parse(&["--db", "runs.sqlite", "--watch"]).unwrap_err(),
+        "--db records each size of a sweep, so it can't be combined with --watch"
+    );
+}
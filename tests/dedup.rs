@@ -0,0 +1,101 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::tool::dedup::dedup_json;
+use network_traffic_monitoring::{
+    measure_dedup, DedupClass, DedupOptions, DedupReport, TransferError, TransferOptions,
+};
+use tokio::sync::watch;
+
+use common::{parse, start};
+
+fn report(identical: &[f64], unique: &[f64]) -> DedupReport {
+    DedupReport {
+        size: 1000,
+        identical: DedupClass { rates: identical.to_vec(), failed_iterations: 0 },
+        unique: DedupClass { rates: unique.to_vec(), failed_iterations: 0 },
+        interrupted: false,
+    }
+}
+
+#[test]
+fn a_clear_gain_for_repeated_bytes_is_suspected() {
+    let cached = report(&[300.0, 310.0, 290.0, 305.0], &[100.0, 104.0, 98.0, 101.0]);
+    assert!((cached.gain_percent().unwrap() - 200.0).abs() < 1.0);
+    assert!(cached.suspected(25.0));
+    assert!(!cached.suspected(250.0));
+
+    // As big a gain, but too noisy to tell apart
+    let noisy = report(&[300.0, 20.0, 580.0, 300.0], &[100.0, 104.0, 98.0, 101.0]);
+    assert!(noisy.gain_percent().unwrap() > 25.0);
+    assert!(!noisy.suspected(25.0));
+
+    let clean = report(&[101.0, 99.0, 100.0], &[100.0, 98.0, 102.0]);
+    assert!(!clean.suspected(25.0));
+    // A single iteration each has no interval, so the gain decides
+    assert!(report(&[200.0], &[100.0]).suspected(25.0));
+    assert_eq!(report(&[], &[100.0]).gain_percent(), None);
+
+    let text = dedup_json(&cached, 25.0);
+    assert!(text.contains("\"dedup_suspected\":true"), "{}", text);
+    assert!(text.contains("\"identical\":{\"upload_rate\":301.25,"), "{}", text);
+}
+
+#[tokio::test]
+async fn both_classes_are_measured_in_turn() {
+    let server = start().await;
+
+    let options = DedupOptions {
+        iterations: 3,
+        transfer: TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() },
+        ..DedupOptions::default()
+    };
+    let report = measure_dedup(&server.address, 50_000, &options).await.unwrap();
+    assert_eq!(report.identical.rates.len(), 3);
+    assert_eq!(report.unique.rates.len(), 3);
+    assert!(report.identical.rate().unwrap() > 0.0 && report.unique.rate().unwrap() > 0.0);
+    assert!(report.gain_percent().is_some());
+    assert!(!report.interrupted);
+
+    let (_cancel, cancelled) = watch::channel(true);
+    let options = DedupOptions {
+        transfer: TransferOptions { cancel: Some(cancelled), ..options.transfer.clone() },
+        ..options
+    };
+    let report = measure_dedup(&server.address, 50_000, &options).await.unwrap();
+    assert!(report.interrupted);
+    assert!(report.identical.rates.is_empty());
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn an_unreachable_target_fails_the_check() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    drop(listener);
+    let options = DedupOptions { iterations: 1, ..DedupOptions::default() };
+    let err = measure_dedup(&address, 1000, &options).await.unwrap_err();
+    assert!(matches!(err, TransferError::AllFailed { .. }), "{:?}", err);
+}
+
+#[test]
+fn dedup_check_takes_a_threshold() {
+    let Ok(Command::Run(config)) = parse(&["--dedup-check", "--dedup-threshold", "10"]) else {
+        panic!("expected a run");
+    };
+    assert!(config.dedup_check);
+    assert_eq!(config.dedup_threshold, Some(10.0));
+
+    assert_eq!(
+        parse(&["--dedup-threshold", "10"]).unwrap_err(),
+        "--dedup-threshold needs --dedup-check"
+    );
+    assert_eq!(
+        parse(&["--dedup-check", "--verify"]).unwrap_err(),
+        "--dedup-check sends its own random payloads over raw TCP, one at a time, so it can't \
+         be combined with --verify"
+    );
+}
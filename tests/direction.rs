@@ -0,0 +1,82 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::framing;
+use network_traffic_monitoring::{
+    measure_transfer, run_mock_server, Direction, MockServerConfig, TransferError, TransferOptions,
+};
+use tokio::sync::watch;
+
+use common::start;
+
+fn options(direction: Direction) -> TransferOptions {
+    TransferOptions {
+        delay: Duration::ZERO,
+        timeout: Duration::from_secs(2),
+        direction,
+        ..TransferOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn upload_only_reads_nothing_back() {
+    let server = start().await;
+    let address = server.address.clone();
+
+    for reuse_connection in [false, true] {
+        let options = TransferOptions { reuse_connection, ..options(Direction::UploadOnly) };
+        let stats = measure_transfer(&address, 4096, 3, &options).await.unwrap();
+        assert_eq!(stats.successful_iterations, 3);
+        assert_eq!(stats.bytes_sent, 3 * 4096);
+        assert_eq!(stats.bytes_received, 0);
+        assert_eq!(stats.download_rate, 0.0);
+        assert!(stats.upload_rate > 0.0);
+    }
+
+    server.stop().await;
+}
+
+// The mock server answers with the requested size, not its usual half
+#[tokio::test]
+async fn download_only_sends_just_the_size() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig { echo: true, ..MockServerConfig::default() };
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+    let address = bound.to_string();
+
+    for reuse_connection in [false, true] {
+        let options = TransferOptions {
+            reuse_connection,
+            verify: true,
+            ..options(Direction::DownloadOnly)
+        };
+        let stats = measure_transfer(&address, 4096, 3, &options).await.unwrap();
+        assert_eq!(stats.successful_iterations, 3);
+        assert_eq!(stats.corrupted_iterations, 0);
+        assert_eq!(stats.bytes_received, 3 * 4096);
+        assert_eq!(stats.bytes_sent, 0);
+        assert_eq!(stats.upload_rate, 0.0);
+        assert!(stats.download_rate > 0.0);
+    }
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn upload_only_rejects_response_checks() {
+    let options = TransferOptions { verify: true, ..options(Direction::UploadOnly) };
+    let err = measure_transfer("127.0.0.1:9", 100, 1, &options).await.unwrap_err();
+    assert!(matches!(err, TransferError::InvalidOptions(_)), "{:?}", err);
+}
+
+#[test]
+fn flags_leave_thirty_bits_of_length() {
+    assert_eq!(framing::MAX_FRAME_LEN, (1 << 30) - 1);
+    assert!(framing::frame_header(framing::MAX_FRAME_LEN).is_ok());
+    assert!(framing::frame_header(framing::MAX_FRAME_LEN + 1).is_err());
+
+    let frame = framing::encode_sized_request(&[9], 512).unwrap();
+    assert_eq!(frame, [0x40, 0, 0, 5, 0, 0, 2, 0, 9]);
+}
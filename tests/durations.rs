@@ -0,0 +1,35 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::Command;
+
+use common::parse;
+
+#[test]
+fn a_duration_too_long_to_hold_is_rejected() {
+    assert_eq!(
+        parse(&["--watch", "--interval", "99999999999999999s"]).unwrap_err(),
+        "--interval expects a duration like 500ms, 5s or 1m, got '99999999999999999s'"
+    );
+    assert!(parse(&["--max-duration", "999999999999999m"]).is_err());
+}
+
+#[test]
+fn zero_means_no_wait_only_where_waiting_is_optional() {
+    let Ok(Command::Run(config)) = parse(&["--think-time", "0", "--connect-head-start", "0ms"])
+    else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.think_time, Duration::ZERO);
+    assert_eq!(config.connect_head_start, Duration::ZERO);
+
+    assert_eq!(
+        parse(&["--watch", "--interval", "0s"]).unwrap_err(),
+        "--interval must be greater than zero"
+    );
+    assert_eq!(
+        parse(&["--max-duration", "0"]).unwrap_err(),
+        "--max-duration must be greater than zero"
+    );
+}
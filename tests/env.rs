@@ -0,0 +1,76 @@
+use std::ffi::OsString;
+
+use network_traffic_monitoring::tool::cli::{parse_args, Command};
+use network_traffic_monitoring::{apply_env, Config};
+
+// apply_env over `vars` instead of the process environment
+fn apply(vars: &[(&str, &str)], config: &mut Config) -> Result<(), String> {
+    let lookup = |name: &str| {
+        let value = vars.iter().find(|(var, _)| *var == name)?.1;
+        Some(OsString::from(value))
+    };
+    apply_env(lookup, config)
+}
+
+#[test]
+fn variables_replace_the_defaults() {
+    let mut config = Config::default();
+    let vars = [
+        ("TRAFFIC_ADDRESS", "10.0.0.5:9000"),
+        ("TRAFFIC_SIZES", "1024, 65536"),
+        ("TRAFFIC_ITERATIONS", "25"),
+    ];
+    apply(&vars, &mut config).unwrap();
+    assert_eq!(config.address.as_deref(), Some("10.0.0.5:9000"));
+    assert_eq!(config.sizes, [1024, 65536]);
+    assert_eq!(config.iterations, 25);
+}
+
+#[test]
+fn unset_or_empty_variables_change_nothing() {
+    let mut config = Config::default();
+    apply(&[("TRAFFIC_SIZES", "  ")], &mut config).unwrap();
+    let defaults = Config::default();
+    assert_eq!(config.address, defaults.address);
+    assert_eq!(config.sizes, defaults.sizes);
+    assert_eq!(config.iterations, defaults.iterations);
+}
+
+#[test]
+fn invalid_values_name_the_variable() {
+    let mut config = Config::default();
+    let error = apply(&[("TRAFFIC_SIZES", "1024,lots")], &mut config).unwrap_err();
+    assert!(error.starts_with("TRAFFIC_SIZES expects sizes"), "{}", error);
+    let error = apply(&[("TRAFFIC_ITERATIONS", "-3")], &mut config).unwrap_err();
+    assert!(error.starts_with("TRAFFIC_ITERATIONS expects"), "{}", error);
+    let error = apply(&[("TRAFFIC_ITERATIONS", "0")], &mut config).unwrap_err();
+    assert_eq!(error, "TRAFFIC_ITERATIONS expects a positive integer, got '0'");
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_values_are_rejected() {
+    use std::os::unix::ffi::OsStringExt;
+
+    let lookup = |_: &str| Some(OsString::from_vec(vec![0xff, 0xfe]));
+    let error = apply_env(lookup, &mut Config::default()).unwrap_err();
+    assert_eq!(error, "TRAFFIC_ADDRESS is not valid UTF-8");
+}
+
+// The only test here that touches the process environment, so nothing else can see
+// the variables it sets
+#[test]
+fn flags_override_the_environment() {
+    std::env::set_var("TRAFFIC_SIZES", "2048");
+    std::env::set_var("TRAFFIC_ITERATIONS", "7");
+    let args = ["--iterations", "3"].iter().map(|arg| arg.to_string());
+    let parsed = parse_args(args);
+    std::env::remove_var("TRAFFIC_SIZES");
+    std::env::remove_var("TRAFFIC_ITERATIONS");
+
+    let Ok(Command::Run(config)) = parsed else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.sizes, [2048]);
+    assert_eq!(config.iterations, 3);
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::{render_estimate, NetworkStats};
+
+use common::parse;
+
+fn rates(upload_rate: f64, download_rate: f64) -> NetworkStats {
+    NetworkStats { upload_rate, download_rate, ..NetworkStats::default() }
+}
+
+#[test]
+fn times_follow_the_measured_rates() {
+    let stats = rates(50_000_000.0, 100_000_000.0);
+    assert_eq!(stats.estimate_transfer_time(5_000_000_000), Duration::from_secs(50));
+    assert_eq!(stats.estimate_upload_time(5_000_000_000), Duration::from_secs(100));
+    assert_eq!(stats.estimate_transfer_time(0), Duration::ZERO);
+}
+
+#[test]
+fn a_rate_of_zero_never_finishes() {
+    let upload_only = rates(1000.0, 0.0);
+    assert_eq!(upload_only.estimate_transfer_time(1), Duration::MAX);
+    assert_eq!(rates(f64::NAN, 0.0).estimate_upload_time(1), Duration::MAX);
+}
+
+#[test]
+fn the_report_covers_every_run() {
+    let mib = 1_048_576.0;
+    let runs = [(Some(1024), rates(2.0 * mib, mib)), (None, rates(1024.0, 0.0))];
+    let report = render_estimate(&runs, 42 << 20);
+    assert_eq!(
+        report,
+        "Moving 42.00 MiB (44040192 bytes)\n\
+         At the rates of the 1024-byte payloads\n  \
+         \u{2248} 42.0s at measured download rate (1.00 MiB/s)\n  \
+         \u{2248} 21.0s at measured upload rate (2.00 MiB/s)\n\
+         At the rates of run 2\n  \
+         no measured download rate to go by\n  \
+         \u{2248} 11h 56m at measured upload rate (1.00 KiB/s)\n"
+    );
+}
+
+#[test]
+fn estimate_takes_a_size_with_units() {
+    let expected = [("5GB", 5_000_000_000), ("512MiB", 512 << 20), ("1.5 KB", 1500), ("42", 42)];
+    for (raw, bytes) in expected {
+        let Ok(Command::Estimate { results, size }) =
+            parse(&["estimate", "--size", raw, "run.json"])
+        else {
+            panic!("expected an estimate for {}", raw);
+        };
+        assert_eq!((results.as_str(), size), ("run.json", bytes));
+    }
+
+    for args in [
+        &["estimate", "run.json"][..],
+        &["estimate", "--size", "5GB"],
+        &["estimate", "--size", "0", "run.json"],
+        &["estimate", "--size", "5XB", "run.json"],
+    ] {
+        assert!(parse(args).is_err(), "{:?}", args);
+    }
+}
@@ -0,0 +1,70 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use network_traffic_monitoring::{measure_transfer_with_progress, TransferError, TransferOptions};
+use tokio::net::TcpListener;
+
+// Accepts connections and hangs up on each without answering, counting them
+async fn hang_up_server() -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&accepted);
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            counter.fetch_add(1, Ordering::SeqCst);
+            drop(socket);
+        }
+    });
+    (address, accepted)
+}
+
+// Closing with the request unread resets the connection, unless the close wins the race
+// and the read finds EOF
+fn hung_up<T>(result: &Result<T, TransferError>) -> bool {
+    matches!(result, Err(TransferError::Read(_) | TransferError::ConnectionReset { .. }))
+}
+
+fn options(fail_fast: bool, concurrency: usize) -> TransferOptions {
+    TransferOptions { delay: Duration::ZERO, concurrency, fail_fast, ..TransferOptions::default() }
+}
+
+#[tokio::test]
+async fn fail_fast_returns_the_first_error() {
+    let (address, accepted) = hang_up_server().await;
+    let mut reported = 0;
+    let result =
+        measure_transfer_with_progress(&address, 100, 5, &options(true, 1), |_| reported += 1)
+            .await;
+
+    assert!(hung_up(&result), "{:?}", result);
+    assert_eq!(reported, 1);
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn fail_fast_stops_concurrent_iterations() {
+    let (address, _) = hang_up_server().await;
+    let mut reported = 0;
+    let result =
+        measure_transfer_with_progress(&address, 100, 50, &options(true, 4), |_| reported += 1)
+            .await;
+
+    assert!(hung_up(&result), "{:?}", result);
+    assert_eq!(reported, 1);
+}
+
+#[tokio::test]
+async fn without_fail_fast_every_iteration_runs() {
+    let (address, accepted) = hang_up_server().await;
+    let result =
+        measure_transfer_with_progress(&address, 100, 5, &options(false, 1), |_| {}).await;
+
+    assert!(
+        matches!(result, Err(TransferError::AllFailed { iterations: 5, .. })),
+        "{:?}",
+        result
+    );
+    assert_eq!(accepted.load(Ordering::SeqCst), 5);
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::confidence::jain_index;
+use network_traffic_monitoring::{
+    measure_transfer, IterationFairness, NetworkStats, TransferOptions,
+};
+
+use common::{round_trip, start};
+
+#[test]
+fn jain_index_of_known_shares() {
+    assert_eq!(jain_index(&[5.0, 5.0, 5.0, 5.0]), Some(1.0));
+    // One taking it all is 1/n
+    assert_eq!(jain_index(&[8.0, 0.0, 0.0, 0.0]), Some(0.25));
+    // (1 + 3)^2 / (2 * (1 + 9))
+    assert_eq!(jain_index(&[1.0, 3.0]), Some(0.8));
+    assert_eq!(jain_index(&[]), None);
+    assert_eq!(jain_index(&[0.0, 0.0]), None);
+}
+
+#[test]
+fn fairness_needs_two_iterations() {
+    let fairness = IterationFairness::of(&[100.0, 300.0, 200.0]).unwrap();
+    assert_eq!((fairness.min_rate, fairness.max_rate), (100.0, 300.0));
+    assert!((fairness.index - 36.0 / 42.0).abs() < 1e-9);
+    assert_eq!(IterationFairness::of(&[100.0]), None);
+}
+
+#[tokio::test]
+async fn only_concurrent_iterations_are_compared() {
+    let server = start().await;
+    let address = server.address.clone();
+
+    let concurrent = TransferOptions {
+        delay: Duration::ZERO,
+        concurrency: 4,
+        ..TransferOptions::default()
+    };
+    let stats = measure_transfer(&address, 10_000, 8, &concurrent).await.unwrap();
+    let fairness = stats.iteration_fairness.expect("concurrent iterations are compared");
+    assert!(fairness.index > 0.0 && fairness.index <= 1.0 + 1e-9, "{:?}", fairness);
+    assert!(0.0 < fairness.min_rate && fairness.min_rate <= fairness.max_rate);
+
+    let sequential = TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() };
+    let stats = measure_transfer(&address, 10_000, 4, &sequential).await.unwrap();
+    assert_eq!(stats.iteration_fairness, None);
+
+    server.stop().await;
+}
+
+#[test]
+fn fairness_survives_a_json_round_trip() {
+    let fairness = IterationFairness { index: 0.9, min_rate: 1000.0, max_rate: 2500.0 };
+    let stats = NetworkStats { iteration_fairness: Some(fairness), ..NetworkStats::default() };
+    assert_eq!(round_trip(&stats).iteration_fairness, Some(fairness));
+
+    assert_eq!(round_trip(&NetworkStats::default()).iteration_fairness, None);
+}
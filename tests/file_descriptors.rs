@@ -0,0 +1,81 @@
+mod common;
+
+use std::fs::File;
+use std::io;
+
+use network_traffic_monitoring::{measure_transfer, NetworkStats, TransferError, TransferOptions};
+
+use common::round_trip;
+
+#[cfg(unix)]
+const EMFILE: i32 = 24;
+#[cfg(unix)]
+const ENFILE: i32 = 23;
+
+// Opens files until the process has no descriptor left, or None if the limit is too
+// high to reach quickly
+#[cfg(target_os = "linux")]
+fn exhaust_descriptors() -> Option<Vec<File>> {
+    let mut held = Vec::new();
+    while held.len() < 100_000 {
+        match File::open("/dev/null") {
+            Ok(file) => held.push(file),
+            Err(e) if e.raw_os_error() == Some(EMFILE) => return Some(held),
+            Err(e) => panic!("unexpected error opening /dev/null: {}", e),
+        }
+    }
+    None
+}
+
+// Takes every descriptor the test process has, so nothing else here may open any
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn no_descriptor_left_says_so() {
+    let Some(held) = exhaust_descriptors() else {
+        return;
+    };
+    let options = TransferOptions { concurrency: 8, ..TransferOptions::default() };
+    let result = measure_transfer("127.0.0.1:9", 1000, 8, &options).await;
+    drop(held);
+
+    let err = result.unwrap_err();
+    let TransferError::AllFailed { iterations: 8, cause: Some(cause), .. } = &err else {
+        panic!("expected every iteration to fail: {:?}", err);
+    };
+    assert!(
+        matches!(**cause, TransferError::OutOfFileDescriptors { phase: "connect", .. }),
+        "{:?}",
+        cause
+    );
+    assert_eq!(err.category(), "file descriptors");
+    assert!(err.to_string().contains("raise the open file limit (ulimit -n)"), "{}", err);
+}
+
+#[cfg(unix)]
+#[test]
+fn descriptor_exhaustion_is_told_apart_in_every_phase() {
+    for code in [EMFILE, ENFILE] {
+        for phase in ["connect", "write", "read"] {
+            let err = TransferError::during(phase, io::Error::from_raw_os_error(code));
+            assert!(
+                matches!(err, TransferError::OutOfFileDescriptors { phase: p, .. } if p == phase),
+                "{:?}",
+                err
+            );
+            assert!(err.is_out_of_file_descriptors());
+            assert_eq!(err.category(), "file descriptors");
+        }
+    }
+    let err = TransferError::during("connect", io::Error::from_raw_os_error(EMFILE));
+    assert!(err.to_string().contains("lower the concurrency or raise the open file limit"));
+    assert!(!TransferError::during("connect", io::ErrorKind::ConnectionRefused.into())
+        .is_out_of_file_descriptors());
+}
+
+#[test]
+fn the_throttled_concurrency_survives_a_json_round_trip() {
+    let stats = NetworkStats { throttled_concurrency: Some(12), ..NetworkStats::default() };
+    assert_eq!(round_trip(&stats).throttled_concurrency, Some(12));
+
+    assert_eq!(round_trip(&NetworkStats::default()).throttled_concurrency, None);
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::{measure_transfer, Direction, NetworkStats, TransferOptions};
+
+use common::{round_trip, slow_server, start};
+
+#[tokio::test]
+async fn first_byte_waits_for_the_server_to_answer() {
+    let address = slow_server(Duration::from_millis(80)).await;
+    let options = TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() };
+
+    let stats = measure_transfer(&address, 1000, 3, &options).await.unwrap();
+    assert!(stats.time_to_first_byte >= Duration::from_millis(80), "{:?}", stats);
+    assert!(stats.time_to_first_byte <= stats.mean_latency);
+}
+
+#[tokio::test]
+async fn upload_only_requests_have_no_first_byte() {
+    let server = start().await;
+    let options = TransferOptions {
+        direction: Direction::UploadOnly,
+        delay: Duration::ZERO,
+        ..TransferOptions::default()
+    };
+
+    let stats = measure_transfer(&server.address, 1000, 2, &options).await.unwrap();
+    assert_eq!(stats.time_to_first_byte, Duration::ZERO);
+}
+
+#[test]
+fn first_byte_survives_a_json_round_trip() {
+    let stats = NetworkStats {
+        time_to_first_byte: Duration::from_millis(12),
+        ..NetworkStats::default()
+    };
+    assert_eq!(round_trip(&stats).time_to_first_byte, Duration::from_millis(12));
+}
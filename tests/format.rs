@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::RateUnits;
+use network_traffic_monitoring::{
+    format_bytes, format_bytes_with, format_rate, format_rate_bits, write_bytes, write_rate_as,
+    NetworkStats, UnitStyle,
+};
+
+#[test]
+fn binary_boundaries() {
+    assert_eq!(format_bytes_with(1000, UnitStyle::Binary), "1000 B");
+    assert_eq!(format_bytes_with(1024, UnitStyle::Binary), "1.00 KiB");
+    assert_eq!(format_bytes_with(1_000_000, UnitStyle::Binary), "976.56 KiB");
+    assert_eq!(format_bytes_with(1_048_576, UnitStyle::Binary), "1.00 MiB");
+}
+
+#[test]
+fn decimal_boundaries() {
+    assert_eq!(format_bytes_with(1000, UnitStyle::Decimal), "1.00 KB");
+    assert_eq!(format_bytes_with(1024, UnitStyle::Decimal), "1.02 KB");
+    assert_eq!(format_bytes_with(1_000_000, UnitStyle::Decimal), "1.00 MB");
+    assert_eq!(format_bytes_with(1_048_576, UnitStyle::Decimal), "1.05 MB");
+}
+
+#[test]
+fn format_bytes_defaults_to_binary() {
+    assert_eq!(format_bytes(1_048_576), format_bytes_with(1_048_576, UnitStyle::Binary));
+}
+
+#[test]
+fn rate_boundaries() {
+    assert_eq!(format_rate(1023.0), "1023.00 B/s");
+    assert_eq!(format_rate(1024.0), "1.00 KiB/s");
+    assert_eq!(format_rate(3.0 * 1024.0 * 1024.0 * 1024.0), "3.00 GiB/s");
+    assert_eq!(format_rate_bits(124.0), "992.00 bps");
+    assert_eq!(format_rate_bits(125.0), "1.00 Kbps");
+    assert_eq!(format_rate_bits(125_000_000.0), "1.00 Gbps");
+}
+
+#[test]
+fn write_forms_append_what_format_returns() {
+    let mut out = String::from("sent ");
+    write_bytes(&mut out, 5 * 1024 * 1024, UnitStyle::Binary).unwrap();
+    out.push_str(" at ");
+    write_rate_as(&mut out, 2048.0, RateUnits::Bytes).unwrap();
+    out.push_str(" / ");
+    write_rate_as(&mut out, 2048.0, RateUnits::Bits).unwrap();
+    assert_eq!(
+        out,
+        format!(
+            "sent {} at {} / {}",
+            format_bytes(5 * 1024 * 1024),
+            format_rate(2048.0),
+            format_rate_bits(2048.0)
+        )
+    );
+}
+
+#[test]
+fn percentiles_match_a_sorted_copy() {
+    // Out of order, with duplicates, so selection has work to do
+    let latencies: Vec<Duration> =
+        (0..101u64).map(|i| Duration::from_millis((i * 37) % 50)).collect();
+    let mut sorted = latencies.clone();
+    sorted.sort();
+    let stats = NetworkStats { latencies, ..NetworkStats::default() };
+
+    assert_eq!(stats.latency_percentile(0.0), sorted[0]);
+    assert_eq!(stats.p50_latency(), sorted[50]);
+    assert_eq!(stats.latency_percentile(100.0), sorted[100]);
+    // Rank 99.5 sits halfway between the two largest samples
+    let between = (sorted[99].as_secs_f64() + sorted[100].as_secs_f64()) / 2.0;
+    let p = stats.latency_percentile(99.5).as_secs_f64();
+    assert!((p - between).abs() < 1e-9, "{} vs {}", p, between);
+}
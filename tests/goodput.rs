@@ -0,0 +1,96 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::{
+    estimate_goodput, measure_transfer, parse_overhead_model, NetworkStats, ProtocolOverheadModel,
+    TransferOptions,
+};
+
+use common::{parse, start};
+
+// `iterations` single-request connections that moved `sent` and `received` in one second
+fn stats(iterations: usize, sent: usize, received: usize) -> NetworkStats {
+    NetworkStats {
+        bytes_sent: sent,
+        bytes_received: received,
+        elapsed_time: Duration::from_secs(1),
+        successful_iterations: iterations,
+        connections: iterations,
+        requests_per_connection: 1,
+        ..NetworkStats::default()
+    }
+}
+
+#[test]
+fn http_overhead_is_charged_per_request_and_response() {
+    let estimate = estimate_goodput(&stats(10, 10_000, 5_000), &ProtocolOverheadModel::http());
+    assert_eq!(estimate.overhead_sent, 2_000);
+    assert_eq!(estimate.overhead_received, 1_500);
+    assert_eq!(estimate.goodput_sent, 8_000);
+    assert_eq!(estimate.goodput_received, 3_500);
+    assert_eq!(estimate.upload_goodput, 8_000.0);
+    assert_eq!(estimate.download_goodput, 3_500.0);
+    assert_eq!(estimate.download_efficiency(), Some(0.7));
+}
+
+#[test]
+fn tls_overhead_counts_records_and_handshakes() {
+    // Two 40000-byte requests take three records each, and each connection a handshake
+    let estimate = estimate_goodput(&stats(2, 80_000, 0), &ProtocolOverheadModel::tls());
+    assert_eq!(estimate.overhead_sent, 2 * 600 + 6 * 22);
+    // Nothing received means an upload-only run: no responses to charge
+    assert_eq!(estimate.overhead_received, 0);
+    assert_eq!(estimate.download_efficiency(), None);
+
+    let https = ProtocolOverheadModel::https();
+    assert_eq!(https.request_bytes, ProtocolOverheadModel::http().request_bytes);
+    assert_eq!(https.record_size, ProtocolOverheadModel::tls().record_size);
+}
+
+#[test]
+fn overhead_never_exceeds_the_bytes_measured() {
+    let estimate = estimate_goodput(&stats(10, 640, 320), &ProtocolOverheadModel::https());
+    assert_eq!(estimate.overhead_sent, 640);
+    assert_eq!(estimate.goodput_sent, 0);
+    assert_eq!(estimate.goodput_received, 0);
+}
+
+#[test]
+fn custom_models_take_request_and_response_bytes() {
+    let model = parse_overhead_model("300:120").unwrap();
+    assert_eq!(model.name, "300:120");
+    assert_eq!((model.request_bytes, model.response_bytes), (300, 120));
+    assert_eq!((model.connection_sent, model.record_size), (0, 0));
+    assert_eq!(parse_overhead_model("https").unwrap(), ProtocolOverheadModel::https());
+
+    for bad in ["quic", "300", "300:x", ""] {
+        assert!(parse_overhead_model(bad).is_err(), "{}", bad);
+    }
+}
+
+#[tokio::test]
+async fn a_measured_run_loses_the_modelled_bytes() {
+    let server = start().await;
+    let options = TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() };
+
+    let measured = measure_transfer(&server.address, 4096, 5, &options).await.unwrap();
+    let estimate = estimate_goodput(&measured, &ProtocolOverheadModel::http());
+    assert_eq!(estimate.overhead_sent, 5 * 200);
+    assert_eq!(estimate.goodput_sent, measured.bytes_sent - 5 * 200);
+    assert_eq!(estimate.overhead_received, 5 * 150);
+    assert!(estimate.upload_goodput < measured.upload_rate);
+}
+
+#[test]
+fn goodput_flag_needs_a_plain_raw_sweep() {
+    let Ok(Command::Run(config)) = parse(&["--goodput", "tls"]) else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.goodput, Some(ProtocolOverheadModel::tls()));
+
+    let error = parse(&["--goodput", "http", "--url", "http://localhost/"]).unwrap_err();
+    assert!(error.starts_with("--goodput models the protocol"), "{}", error);
+    assert!(parse(&["--goodput", "quic"]).unwrap_err().starts_with("--goodput: "));
+}
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use network_traffic_monitoring::{measure_transfer, TransferError, TransferOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+// Reads each request to EOF, then answers with a 100-byte frame
+async fn spawn_read_to_eof_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut request = Vec::new();
+                socket.read_to_end(&mut request).await.unwrap();
+                let mut response = 100u32.to_be_bytes().to_vec();
+                response.extend_from_slice(&[7u8; 100]);
+                let _ = socket.write_all(&response).await;
+            });
+        }
+    });
+    address
+}
+
+#[tokio::test]
+async fn half_close_lets_read_to_eof_servers_answer() {
+    let address = spawn_read_to_eof_server().await;
+    let options = TransferOptions {
+        delay: Duration::ZERO,
+        timeout: Duration::from_millis(500),
+        half_close_after_send: true,
+        ..TransferOptions::default()
+    };
+    let stats = measure_transfer(&address, 1000, 3, &options).await.unwrap();
+    assert_eq!(stats.successful_iterations, 3);
+    assert_eq!(stats.bytes_received, 300);
+
+    // Without the half-close the server never sees the end of the request
+    let options = TransferOptions {
+        half_close_after_send: false,
+        ..options
+    };
+    let err = measure_transfer(&address, 1000, 1, &options).await.unwrap_err();
+    assert_eq!(err.category(), "timeout");
+}
+
+#[tokio::test]
+async fn half_close_rejects_connection_reuse() {
+    let options = TransferOptions {
+        half_close_after_send: true,
+        reuse_connection: true,
+        ..TransferOptions::default()
+    };
+    let err = measure_transfer("127.0.0.1:9", 1000, 1, &options).await.unwrap_err();
+    assert!(matches!(err, TransferError::InvalidOptions(_)));
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use network_traffic_monitoring::tool::cli::{parse_args, Command};
+use network_traffic_monitoring::{
+    connect_first, interleave_families, measure_transfer, run_mock_server, MockServerConfig,
+    NetworkStats, TransferOptions,
+};
+use tokio::sync::watch;
+
+use common::round_trip;
+
+fn addr(text: &str) -> SocketAddr {
+    text.parse().unwrap()
+}
+
+#[test]
+fn families_alternate_from_the_resolvers_first_choice() {
+    let (v6a, v6b) = (addr("[::1]:1"), addr("[::2]:1"));
+    let (v4a, v4b) = (addr("10.0.0.1:1"), addr("10.0.0.2:1"));
+    assert_eq!(interleave_families(&[v6a, v6b, v4a]), vec![v6a, v4a, v6b]);
+    assert_eq!(interleave_families(&[v4a, v4b, v6a, v6b]), vec![v4a, v6a, v4b, v6b]);
+    assert_eq!(interleave_families(&[v4b, v4a]), vec![v4b, v4a]);
+    assert!(interleave_families(&[]).is_empty());
+}
+
+#[tokio::test]
+async fn a_hanging_attempt_gets_only_its_head_start() {
+    let started = Arc::new(Mutex::new(Vec::new()));
+    let resolved = [addr("[::1]:1"), addr("127.0.0.1:1")];
+    // IPv6 never finishes, as over a broken IPv6 path, and IPv4 connects at once
+    let connect = |address: SocketAddr| {
+        started.lock().unwrap().push(address);
+        async move {
+            if address.is_ipv6() {
+                std::future::pending::<()>().await;
+            }
+            Ok(address)
+        }
+    };
+
+    let start = Instant::now();
+    let head_start = Duration::from_millis(50);
+    let winner = connect_first(&resolved, head_start, connect).await.unwrap();
+    assert_eq!(winner, resolved[1]);
+    assert!(start.elapsed() >= head_start);
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(*started.lock().unwrap(), resolved.to_vec());
+}
+
+#[tokio::test]
+async fn a_failed_attempt_starts_the_next_at_once() {
+    let resolved = [addr("[::1]:1"), addr("127.0.0.1:1")];
+    let start = Instant::now();
+    let connect = |address: SocketAddr| async move {
+        if address.is_ipv6() {
+            Err(io::Error::from(io::ErrorKind::ConnectionRefused))
+        } else {
+            Ok(address)
+        }
+    };
+    let winner = connect_first(&resolved, Duration::from_secs(30), connect).await.unwrap();
+    assert_eq!(winner, resolved[1]);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn the_last_error_is_returned_when_every_attempt_fails() {
+    let resolved = [addr("[::1]:1"), addr("127.0.0.1:1")];
+    let connect = |address: SocketAddr| async move {
+        let kind = if address.is_ipv6() {
+            io::ErrorKind::ConnectionRefused
+        } else {
+            io::ErrorKind::TimedOut
+        };
+        Err::<SocketAddr, _>(io::Error::from(kind))
+    };
+    let result = connect_first(&resolved, Duration::from_millis(10), connect).await;
+    assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+}
+
+#[tokio::test]
+async fn connections_are_counted_by_family() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config =
+        MockServerConfig { address: "[::1]:0".to_string(), ..MockServerConfig::default() };
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+
+    let options = TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() };
+    let stats = measure_transfer(&bound.to_string(), 100, 3, &options).await.unwrap();
+    assert_eq!(stats.ipv6_connections, 3);
+    assert_eq!(stats.ipv4_connections, 0);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[test]
+fn family_counts_survive_a_json_round_trip() {
+    let stats =
+        NetworkStats { ipv4_connections: 2, ipv6_connections: 5, ..NetworkStats::default() };
+    let parsed = round_trip(&stats);
+    assert_eq!((parsed.ipv4_connections, parsed.ipv6_connections), (2, 5));
+}
+
+#[test]
+fn head_start_flag_sets_the_option() {
+    let args = ["--connect-head-start", "100ms"].iter().map(|arg| arg.to_string());
+    let Ok(Command::Run(config)) = parse_args(args) else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.connect_head_start, Duration::from_millis(100));
+}
@@ -0,0 +1,191 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use network_traffic_monitoring::tool::cli::{parse_args, Command};
+use network_traffic_monitoring::{measure_http, measure_http2, HttpOptions, TransferError};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+
+use common::parse;
+
+const HEADERS: u8 = 0x1;
+const SETTINGS: u8 = 0x4;
+const DATA: u8 = 0x0;
+const END_STREAM: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+
+// HPACK blocks holding only a :status: the static table's 200, and literal 201 and 404
+// with Huffman-coded values
+const STATUS_200: &[u8] = &[0x88];
+const STATUS_201: &[u8] = &[0x08, 0x82, 0x10, 0x03];
+const STATUS_404: &[u8] = &[0x08, 0x83, 0x68, 0x0d, 0x7f];
+
+fn frame(kind: u8, flags: u8, stream: u32, payload: &[u8]) -> Vec<u8> {
+    let len = (payload.len() as u32).to_be_bytes();
+    let mut bytes = vec![len[1], len[2], len[3], kind, flags];
+    bytes.extend_from_slice(&stream.to_be_bytes());
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+// A bare-bones h2c server allowing `max_streams` at once. Once a request stream ends,
+// it waits `think` and answers with `status` and a body of `reply` bytes. Returns the
+// URL and the count of connections accepted.
+async fn server(
+    max_streams: u32,
+    status: &'static [u8],
+    reply: usize,
+    think: Duration,
+) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&accepted);
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async move {
+                let (mut reader, writer) = socket.into_split();
+                let writer = Arc::new(Mutex::new(writer));
+                let mut preface = [0u8; 24];
+                if reader.read_exact(&mut preface).await.is_err() {
+                    return;
+                }
+                let mut settings = Vec::new();
+                settings.extend_from_slice(&[0, 3]);
+                settings.extend_from_slice(&max_streams.to_be_bytes());
+                settings.extend_from_slice(&[0, 4]);
+                settings.extend_from_slice(&0x7fff_ffffu32.to_be_bytes());
+                let mut opening = frame(SETTINGS, 0, 0, &settings);
+                opening.extend(frame(0x8, 0, 0, &0x7fff_0000u32.to_be_bytes()));
+                writer.lock().await.write_all(&opening).await.unwrap();
+
+                loop {
+                    let mut head = [0u8; 9];
+                    if reader.read_exact(&mut head).await.is_err() {
+                        return;
+                    }
+                    let len = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+                    let mut payload = vec![0; len];
+                    reader.read_exact(&mut payload).await.unwrap();
+                    let (kind, flags) = (head[3], head[4]);
+                    let stream = u32::from_be_bytes([head[5], head[6], head[7], head[8]]);
+                    if kind == SETTINGS && flags == 0 {
+                        let ack = frame(SETTINGS, 1, 0, &[]);
+                        writer.lock().await.write_all(&ack).await.unwrap();
+                    }
+                    if (kind == HEADERS || kind == DATA) && flags & END_STREAM != 0 {
+                        let writer = Arc::clone(&writer);
+                        tokio::spawn(async move {
+                            tokio::time::sleep(think).await;
+                            let mut response = frame(HEADERS, END_HEADERS, stream, status);
+                            for (i, chunk) in vec![7u8; reply].chunks(16_384).enumerate() {
+                                let last = (i + 1) * 16_384 >= reply;
+                                let flags = if last { END_STREAM } else { 0 };
+                                response.extend(frame(DATA, flags, stream, chunk));
+                            }
+                            if reply == 0 {
+                                response[4] |= END_STREAM;
+                            }
+                            let _ = writer.lock().await.write_all(&response).await;
+                        });
+                    }
+                }
+            });
+        }
+    });
+    (format!("http://{}/upload", address), accepted)
+}
+
+#[tokio::test]
+async fn requests_share_one_connection() {
+    let think = Duration::from_millis(50);
+    let (url, accepted) = server(100, STATUS_200, 30_000, think).await;
+    let body = vec![1u8; 100_000];
+
+    let started = Instant::now();
+    let options = HttpOptions::default();
+    let stats = measure_http2(&url, "POST", &body, 16, 8, &options).await.unwrap();
+    assert_eq!(stats.successful_iterations, 16);
+    assert_eq!(stats.bytes_sent, 16 * 100_000);
+    assert_eq!(stats.bytes_received, 16 * 30_000);
+    assert_eq!(stats.connections, 1);
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    assert_eq!(stats.http2_streams, Some(8));
+    // Back to back, the server's think time alone would add up to 800ms
+    assert!(started.elapsed() < think * 12, "{:?}", started.elapsed());
+    assert!(stats.min_latency >= think);
+
+    // Through measure_http, as --http2 runs it
+    let options = HttpOptions { http2_streams: Some(4), ..HttpOptions::default() };
+    let stats = measure_http(&url, "GET", &[], 6, &options).await.unwrap();
+    assert_eq!((stats.successful_iterations, stats.bytes_sent), (6, 0));
+    assert_eq!(stats.http2_streams, Some(4));
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn the_servers_stream_limit_is_kept() {
+    let (url, _) = server(2, STATUS_201, 10, Duration::from_millis(20)).await;
+    let stats = measure_http2(&url, "POST", b"hello", 6, 32, &HttpOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(stats.successful_iterations, 6);
+    assert_eq!(stats.http2_streams, Some(2));
+    assert_eq!(stats.bytes_received, 60);
+}
+
+#[tokio::test]
+async fn error_statuses_fail_their_streams() {
+    let (url, _) = server(100, STATUS_404, 0, Duration::ZERO).await;
+    let err = measure_http2(&url, "GET", &[], 3, 3, &HttpOptions::default()).await.unwrap_err();
+    let err = err.downcast::<TransferError>().unwrap();
+    assert!(matches!(*err, TransferError::AllFailed { iterations: 3, .. }), "{:?}", err);
+    assert!(err.to_string().contains("HTTP 404"), "{}", err);
+
+    let fail_fast = HttpOptions { fail_fast: true, ..HttpOptions::default() };
+    let err = measure_http2(&url, "GET", &[], 3, 3, &fail_fast).await.unwrap_err();
+    assert_eq!(err.to_string(), "HTTP 404");
+}
+
+#[tokio::test]
+async fn a_silent_server_times_out() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (_socket, _) = listener.accept().await.unwrap();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    });
+    let options = HttpOptions { timeout: Duration::from_millis(100), ..HttpOptions::default() };
+    let err = measure_http2(&url, "GET", &[], 2, 2, &options).await.unwrap_err();
+    let err = err.downcast::<TransferError>().unwrap();
+    let TransferError::AllFailed { iterations: 2, cause: Some(cause), .. } = *err else {
+        panic!("expected both requests to fail: {:?}", err);
+    };
+    assert!(matches!(*cause, TransferError::Timeout { phase: "http2 settings", .. }));
+}
+
+#[test]
+fn http2_needs_a_url_and_no_pooling_flags() {
+    let args = ["--url", "http://localhost:8080/", "--http2", "16"];
+    let Ok(Command::Run(config)) = parse_args(args.iter().map(|arg| arg.to_string())) else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.http2, Some(16));
+
+    assert_eq!(
+        parse(&["--http2", "4"]).unwrap_err(),
+        "--http2 applies to --url measurements"
+    );
+    assert_eq!(
+        parse(&["--url", "http://localhost/", "--http2", "4", "--http-close"]).unwrap_err(),
+        "--http2 multiplexes every request over one connection without following redirects, \
+         so it can't be combined with --http-close"
+    );
+    assert!(parse(&["--url", "http://localhost/", "--http2", "0"]).is_err());
+}
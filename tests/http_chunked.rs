@@ -0,0 +1,65 @@
+use network_traffic_monitoring::tool::framing::MAX_FRAME_LEN;
+use network_traffic_monitoring::{measure_http, HttpOptions};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+// Answers each request, whose body must be empty, with `body` under chunked encoding
+async fn chunked_server(body: String) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            let body = body.clone();
+            tokio::spawn(async move {
+                let mut reader = BufReader::new(socket);
+                let mut line = String::new();
+                loop {
+                    line.clear();
+                    if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+                        return;
+                    }
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n{}",
+                    body
+                );
+                let _ = reader.get_mut().write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    format!("http://{}/", address)
+}
+
+fn options() -> HttpOptions {
+    HttpOptions { keep_alive: false, ..HttpOptions::default() }
+}
+
+#[tokio::test]
+async fn chunk_framing_is_left_out_of_the_body_length() {
+    let url = chunked_server("5\r\nhello\r\n3;name=value\r\nabc\r\n0\r\n\r\n".to_string()).await;
+    let stats = measure_http(&url, "GET", b"", 2, &options()).await.unwrap();
+    assert_eq!(stats.successful_iterations, 2);
+    assert_eq!(stats.bytes_received, 16);
+}
+
+#[tokio::test]
+async fn a_size_line_near_the_top_of_usize_fails_the_iteration() {
+    let url = chunked_server("ffffffffffffffff\r\nhello\r\n0\r\n\r\n".to_string()).await;
+    let err = measure_http(&url, "GET", b"", 1, &options()).await.unwrap_err().to_string();
+    let limit = format!("a {}-byte chunk is over the {}-byte limit", usize::MAX, MAX_FRAME_LEN);
+    assert!(err.contains(&limit), "{}", err);
+}
+
+#[tokio::test]
+async fn a_chunk_over_the_frame_limit_is_refused_before_reading() {
+    // Only five bytes of the chunk follow, so reading it would end in a premature close
+    let url = chunked_server(format!("{:x}\r\nhello", MAX_FRAME_LEN + 1)).await;
+    let err = measure_http(&url, "GET", b"", 1, &options()).await.unwrap_err().to_string();
+    let limit =
+        format!("a {}-byte chunk is over the {}-byte limit", MAX_FRAME_LEN + 1, MAX_FRAME_LEN);
+    assert!(err.contains(&limit), "{}", err);
+}
@@ -0,0 +1,95 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use network_traffic_monitoring::{measure_http, HttpOptions};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+// Answers every request with a 5-byte body, counting the connections it accepts.
+// `close_after` closes each connection after that many responses, without saying so
+// in the headers, like a server dropping idle keep-alive connections.
+async fn server(
+    close_after: Option<usize>,
+    reply_header: &'static str,
+) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&accepted);
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async move {
+                let mut reader = BufReader::new(socket);
+                let mut served = 0;
+                loop {
+                    let mut length = 0;
+                    let mut line = String::new();
+                    loop {
+                        line.clear();
+                        if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+                            return;
+                        }
+                        if line.trim().is_empty() {
+                            break;
+                        }
+                        let lower = line.to_ascii_lowercase();
+                        if let Some(value) = lower.strip_prefix("content-length:") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                    let mut body = vec![0u8; length];
+                    reader.read_exact(&mut body).await.unwrap();
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n{}\r\nhello",
+                        reply_header
+                    );
+                    reader.get_mut().write_all(response.as_bytes()).await.unwrap();
+                    served += 1;
+                    if close_after == Some(served) {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    (format!("http://{}/", address), accepted)
+}
+
+#[tokio::test]
+async fn keep_alive_reuses_one_connection() {
+    let (url, accepted) = server(None, "").await;
+    let stats = measure_http(&url, "POST", b"ping", 4, &HttpOptions::default()).await.unwrap();
+    assert_eq!(stats.successful_iterations, 4);
+    assert_eq!(stats.bytes_received, 20);
+    assert_eq!(stats.connections, 1);
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn http_close_dials_for_every_request() {
+    let (url, accepted) = server(None, "").await;
+    let options = HttpOptions { keep_alive: false, ..HttpOptions::default() };
+    let stats = measure_http(&url, "POST", b"ping", 4, &options).await.unwrap();
+    assert_eq!(stats.successful_iterations, 4);
+    assert_eq!(stats.connections, 4);
+    assert_eq!(accepted.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn a_connection_close_response_is_not_reused() {
+    let (url, accepted) = server(Some(1), "Connection: close\r\n").await;
+    let stats = measure_http(&url, "GET", b"", 3, &HttpOptions::default()).await.unwrap();
+    assert_eq!(stats.successful_iterations, 3);
+    assert_eq!(accepted.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn a_connection_the_server_dropped_is_redialled() {
+    // Every second request finds its pooled connection closed under it
+    let (url, accepted) = server(Some(2), "").await;
+    let stats = measure_http(&url, "GET", b"", 4, &HttpOptions::default()).await.unwrap();
+    assert_eq!(stats.successful_iterations, 4, "{:?}", stats.errors);
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+}
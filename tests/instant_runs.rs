@@ -0,0 +1,112 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::RateUnits;
+use network_traffic_monitoring::{
+    format_measured_rate, format_per_iteration_rate, format_rate, format_rate_bits,
+    measure_transfer, render_summary_table, Direction, NetworkStats, ScriptedClock, TransferOptions,
+    MIN_MEASURABLE_TIME,
+};
+
+use common::start;
+
+#[tokio::test]
+async fn no_time_passing_gives_zero_rates_not_inf() {
+    let server = start().await;
+
+    // A clock that never moves, as if the whole run took no time at all
+    let options = TransferOptions {
+        delay: Duration::ZERO,
+        clock: Arc::new(ScriptedClock::new([Duration::ZERO])),
+        ..TransferOptions::default()
+    };
+    let stats = measure_transfer(&server.address, 1000, 3, &options).await.unwrap();
+    assert_eq!(stats.elapsed_time, Duration::ZERO);
+    assert_eq!(stats.bytes_sent, 3000);
+    assert_eq!((stats.upload_rate, stats.download_rate), (0.0, 0.0));
+    assert_eq!(stats.ratio, 0.5);
+    assert!(stats.too_fast_to_measure());
+
+    // Nothing sent: the ratio has nothing to divide by either
+    let download = TransferOptions { direction: Direction::DownloadOnly, ..options };
+    let stats = measure_transfer(&server.address, 1000, 1, &download).await.unwrap();
+    assert_eq!(stats.bytes_sent, 0);
+    assert_eq!((stats.upload_rate, stats.ratio), (0.0, 0.0));
+
+    server.stop().await;
+}
+
+#[test]
+fn rates_that_arent_numbers_print_as_a_placeholder() {
+    assert_eq!(format_rate(f64::INFINITY), "N/A");
+    assert_eq!(format_rate(f64::NAN), "N/A");
+    assert_eq!(format_rate_bits(f64::NEG_INFINITY), "N/A");
+    assert_eq!(format_rate(0.0), "0.00 B/s");
+}
+
+#[test]
+fn sub_millisecond_runs_show_no_rate() {
+    let fast = Duration::from_micros(40);
+    assert_eq!(
+        format_measured_rate(25e6, fast, RateUnits::Bytes),
+        "N/A (too fast to measure)"
+    );
+    assert_eq!(format_measured_rate(2048.0, MIN_MEASURABLE_TIME, RateUnits::Bytes), "2.00 KiB/s");
+
+    let stats = NetworkStats {
+        bytes_sent: 1000,
+        elapsed_time: fast,
+        upload_rate: 25e6,
+        successful_iterations: 1,
+        ..NetworkStats::default()
+    };
+    assert!(stats.too_fast_to_measure());
+    let table = render_summary_table(&[(1000, stats)], RateUnits::Bytes, false);
+    assert!(table.contains("N/A (too fast to measure)"), "{}", table);
+    assert!(!table.contains("inf"), "{}", table);
+}
+
+#[test]
+fn per_iteration_rates_go_by_each_iterations_time() {
+    // A run long enough overall, but made of iterations too short to time
+    let fast = NetworkStats {
+        elapsed_time: Duration::from_millis(5),
+        response_sizes: vec![1000, 1000, 1000],
+        latencies: vec![
+            Duration::from_micros(300),
+            Duration::from_millis(3),
+            Duration::from_micros(500),
+        ],
+        successful_iterations: 3,
+        ..NetworkStats::default()
+    };
+    assert!(!fast.too_fast_to_measure());
+    assert!(fast.iterations_too_fast_to_measure());
+    assert_eq!(
+        format_per_iteration_rate(&fast, RateUnits::Bytes, false).unwrap(),
+        "N/A (too fast to measure)"
+    );
+
+    // And the other way round: every iteration timed, though the total is tiny
+    let slow = NetworkStats {
+        elapsed_time: Duration::from_micros(50),
+        latencies: vec![2, 4, 8].into_iter().map(Duration::from_millis).collect(),
+        ..fast
+    };
+    let line = format_per_iteration_rate(&slow, RateUnits::Bytes, false).unwrap();
+    assert!(line.ends_with("(95% CI)"), "{}", line);
+    assert!(!line.contains("N/A"), "{}", line);
+
+    let one = NetworkStats {
+        response_sizes: vec![1000],
+        latencies: vec![Duration::from_millis(2)],
+        ..slow
+    };
+    let line = format_per_iteration_rate(&one, RateUnits::Bytes, false).unwrap();
+    assert_eq!(line, "488.28 KiB/s (one iteration, so no confidence interval)");
+
+    let none = NetworkStats::default();
+    assert_eq!(format_per_iteration_rate(&none, RateUnits::Bytes, false), None);
+}
@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use network_traffic_monitoring::{
+    measure_transfer, run_mock_server, validate_address, MockServerConfig, TransferOptions,
+};
+use tokio::sync::watch;
+
+#[tokio::test]
+async fn transfer_over_ipv6_loopback() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig {
+        address: "[::1]:0".to_string(),
+        ..MockServerConfig::default()
+    };
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+    assert!(bound.is_ipv6());
+
+    let options = TransferOptions {
+        delay: Duration::ZERO,
+        ..TransferOptions::default()
+    };
+    let stats = measure_transfer(&bound.to_string(), 1024, 3, &options).await.unwrap();
+    assert_eq!(stats.successful_iterations, 3);
+    assert_eq!(stats.bytes_sent, 3 * 1024);
+    assert_eq!(stats.bytes_received, 3 * 512);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn validate_accepts_bracketed_ipv6() {
+    let addr = validate_address("[::1]:8080").await.unwrap();
+    assert!(addr.is_ipv6());
+    assert_eq!(addr.port(), 8080);
+    assert!(validate_address("[::1]").await.is_err());
+}
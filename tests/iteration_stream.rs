@@ -0,0 +1,80 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use network_traffic_monitoring::{
+    measure_transfer_stream, run_mock_server, MockServerConfig, MockServerStats, TransferError,
+    TransferOptions,
+};
+use tokio::sync::watch;
+
+async fn mock_server() -> (String, Arc<MockServerStats>, watch::Sender<bool>) {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig::default();
+    let stats = config.stats.clone();
+    let (bound, _server) = run_mock_server(config, stop_signal).await.unwrap();
+    (bound.to_string(), stats, stop)
+}
+
+#[tokio::test]
+async fn yields_every_iteration_as_it_finishes() {
+    let (address, _stats, _stop) = mock_server().await;
+    let options =
+        TransferOptions { delay: Duration::ZERO, warmup: 1, ..TransferOptions::default() };
+
+    let mut stream = measure_transfer_stream(&address, 1000, 5, &options);
+    let mut results = Vec::new();
+    while let Some(result) = stream.next().await {
+        results.push(result.unwrap());
+    }
+    // The warmup iteration isn't one of them
+    let indexes: Vec<usize> = results.iter().map(|result| result.index).collect();
+    assert_eq!(indexes, [0, 1, 2, 3]);
+    for result in &results {
+        assert_eq!(result.error, None);
+        assert_eq!((result.bytes_sent, result.bytes_received), (1000, 500));
+    }
+}
+
+#[tokio::test]
+async fn a_rejected_measurement_ends_the_stream_with_its_error() {
+    let (address, stats, _stop) = mock_server().await;
+    let options = TransferOptions { requests_per_connection: 0, ..TransferOptions::default() };
+
+    let mut stream = measure_transfer_stream(&address, 1000, 3, &options);
+    assert!(matches!(stream.next().await, Some(Err(TransferError::InvalidOptions(_)))));
+    assert!(stream.next().await.is_none());
+    assert_eq!(stats.requests(), 0);
+}
+
+#[tokio::test]
+async fn failed_iterations_are_items_not_the_end() {
+    // Bound and dropped, so nothing is listening there
+    let address = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+    let options = TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() };
+
+    let mut stream = measure_transfer_stream(&address.to_string(), 1000, 2, &options);
+    let mut failed = 0;
+    while let Some(result) = stream.next().await {
+        let result = result.expect("every iteration failing isn't a separate error");
+        assert!(result.error.is_some());
+        assert_eq!(result.bytes_sent, 0);
+        failed += 1;
+    }
+    assert_eq!(failed, 2);
+}
+
+#[tokio::test]
+async fn dropping_the_stream_stops_the_measurement() {
+    let (address, stats, _stop) = mock_server().await;
+    let options =
+        TransferOptions { delay: Duration::from_millis(10), ..TransferOptions::default() };
+
+    let mut stream = measure_transfer_stream(&address, 100, 1000, &options);
+    stream.next().await.unwrap().unwrap();
+    drop(stream);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let after_drop = stats.requests();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(stats.requests(), after_drop);
+    assert!(after_drop < 20, "{}", after_drop);
+}
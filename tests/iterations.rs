@@ -0,0 +1,21 @@
+mod common;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::{apply_config_text, Config};
+
+use common::parse;
+
+#[test]
+fn zero_iterations_is_a_usage_error() {
+    assert_eq!(parse(&["--iterations", "0"]).unwrap_err(), "--iterations must be at least 1");
+    let Ok(Command::Run(config)) = parse(&["--iterations", "1"]) else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.iterations, 1);
+
+    let mut config = Config::default();
+    let error = apply_config_text("iterations = 0\n", &mut config).unwrap_err();
+    assert!(error.ends_with("iterations must be at least 1"), "{}", error);
+    apply_config_text("iterations = 3\n", &mut config).unwrap();
+    assert_eq!(config.iterations, 3);
+}
@@ -0,0 +1,28 @@
+mod common;
+
+use network_traffic_monitoring::tool::cli::{Command, OutputFormat};
+
+use common::parse;
+
+#[test]
+fn jsonl_streams_watch_cycles() {
+    let Ok(Command::Run(config)) = parse(&["--watch", "--format", "jsonl"]) else {
+        panic!("expected a run");
+    };
+    assert!(config.watch);
+    assert_eq!(config.format, OutputFormat::JsonLines);
+    // Appending to a file suits a stream of lines
+    let args = ["--watch", "--format", "jsonl", "--output", "cycles.jsonl", "--append"];
+    assert!(parse(&args).is_ok());
+}
+
+#[test]
+fn jsonl_needs_watch() {
+    let error = parse(&["--format", "jsonl"]).unwrap_err();
+    assert_eq!(
+        error,
+        "--format jsonl streams --watch cycles; use --format json for a single sweep"
+    );
+    let error = parse(&["--format", "ndjson"]).unwrap_err();
+    assert_eq!(error, "--format expects text, json, jsonl or csv, got 'ndjson'");
+}
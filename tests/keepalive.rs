@@ -0,0 +1,90 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::{
+    measure_keepalive, run_mock_server, KeepaliveEnd, KeepaliveOptions, MockServerConfig,
+    TransferOptions,
+};
+use tokio::sync::watch;
+
+use common::{parse, start};
+
+fn options(interval_ms: u64, limit: Option<Duration>) -> KeepaliveOptions {
+    KeepaliveOptions {
+        interval: Duration::from_millis(interval_ms),
+        limit,
+        transfer: TransferOptions { timeout: Duration::from_secs(2), ..TransferOptions::default() },
+        ..KeepaliveOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn pings_keep_the_connection_up_until_the_limit() {
+    let (_stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig {
+        idle_timeout: Some(Duration::from_millis(500)),
+        ..MockServerConfig::default()
+    };
+    let (bound, _server) = run_mock_server(config, stop_signal).await.unwrap();
+
+    let limit = Some(Duration::from_millis(400));
+    let report = measure_keepalive(&bound.to_string(), &options(50, limit)).await.unwrap();
+    assert!(matches!(report.end, KeepaliveEnd::Limit), "{:?}", report.end);
+    let pings = report.pings.unwrap();
+    assert!(pings.successful_iterations >= 4, "{}", pings.successful_iterations);
+    assert_eq!(pings.bytes_sent, pings.successful_iterations * 64);
+    assert!(report.alive_for >= Duration::from_millis(300), "{:?}", report.alive_for);
+}
+
+#[tokio::test]
+async fn an_idle_timeout_longer_than_the_interval_is_found() {
+    let (_stop, stop_signal) = watch::channel(false);
+    let server = MockServerConfig::default();
+    let stats = server.stats.clone();
+    let config = MockServerConfig { idle_timeout: Some(Duration::from_millis(150)), ..server };
+    let (bound, _server) = run_mock_server(config, stop_signal).await.unwrap();
+
+    // The first gap is already longer than the server waits, so the first ping finds the
+    // connection closed
+    let report = measure_keepalive(&bound.to_string(), &options(300, None)).await.unwrap();
+    let KeepaliveEnd::Dropped { noticed_after, .. } = report.end else {
+        panic!("expected a drop, got {:?}", report.end);
+    };
+    assert!(report.pings.is_none());
+    assert!(noticed_after >= Duration::from_millis(300), "{:?}", noticed_after);
+    assert_eq!(stats.requests(), 0);
+}
+
+#[tokio::test]
+async fn cancelling_ends_with_the_connection_up() {
+    let server = start().await;
+    let (cancel, cancelled) = watch::channel(false);
+    let mut options = options(20, None);
+    options.transfer.cancel = Some(cancelled);
+
+    let address = server.address.clone();
+    let task = tokio::spawn(async move { measure_keepalive(&address, &options).await });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    cancel.send(true).unwrap();
+    let report = task.await.unwrap().unwrap();
+    assert!(matches!(report.end, KeepaliveEnd::Cancelled), "{:?}", report.end);
+    assert!(report.pings.unwrap().interrupted);
+}
+
+#[test]
+fn keepalive_flags_are_checked() {
+    let Ok(Command::Run(config)) = parse(&["--keepalive", "2s", "--keepalive-limit", "1m"]) else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.keepalive, Some(Duration::from_secs(2)));
+    assert_eq!(config.keepalive_limit, Some(Duration::from_secs(60)));
+
+    assert_eq!(
+        parse(&["--keepalive", "1s", "--watch"]).unwrap_err(),
+        "--watch and --keepalive are separate run modes, so they can't be combined"
+    );
+    assert!(parse(&["--keepalive-limit", "1s"]).is_err());
+    assert!(parse(&["--keepalive", "1s", "--quiet"]).is_err());
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use network_traffic_monitoring::tool::cli::{Command, Config};
+use network_traffic_monitoring::tool::compare::{describe_file, load_runs, run_compare};
+use network_traffic_monitoring::{
+    measure_transfer, run_benchmarks, JsonObject, NetworkStats, OutputFormat, TransferOptions,
+};
+
+use common::{parse, round_trip, start};
+
+#[test]
+fn labels_come_from_the_command_line() {
+    let Ok(Command::Run(config)) = parse(&["--label", "after tuning"]) else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.label.as_deref(), Some("after tuning"));
+    assert_eq!(parse(&["--label", " "]).unwrap_err(), "--label needs some text");
+}
+
+#[test]
+fn the_summary_names_the_settings_that_matter() {
+    let config = Config {
+        address: Some("10.0.0.5:9000".to_string()),
+        iterations: 8,
+        concurrency: 4,
+        delay: Duration::from_millis(50),
+        ..Config::default()
+    };
+    assert_eq!(config.summary(), "tcp 10.0.0.5:9000, 8 iterations, concurrency 4, delay 50ms");
+
+    let http = Config {
+        url: Some("http://example.com/upload".to_string()),
+        http2: Some(16),
+        ..Config::default()
+    };
+    assert!(
+        http.summary().starts_with("POST http://example.com/upload, 5 iterations, http2 with 16"),
+        "{}",
+        http.summary()
+    );
+}
+
+#[test]
+fn labels_survive_a_json_round_trip() {
+    let timestamp = UNIX_EPOCH + Duration::from_secs(1_714_564_800);
+    let stats = NetworkStats {
+        label: Some("nightly \"main\"".to_string()),
+        timestamp: Some(timestamp),
+        config_summary: "tcp 127.0.0.1:8080, 5 iterations".to_string(),
+        ..NetworkStats::default()
+    };
+    let parsed = round_trip(&stats);
+    assert_eq!(parsed.label, stats.label);
+    assert_eq!(parsed.timestamp, Some(timestamp));
+    assert_eq!(parsed.config_summary, stats.config_summary);
+
+    // Saved before the fields existed
+    let parsed = round_trip(&NetworkStats::default());
+    assert_eq!((parsed.label, parsed.timestamp), (None, None));
+    assert_eq!(parsed.config_summary, "");
+}
+
+#[test]
+fn a_timestamp_out_of_range_is_dropped_rather_than_overflowing() {
+    let stats = NetworkStats { timestamp: Some(UNIX_EPOCH), ..NetworkStats::default() };
+    let mut obj = JsonObject::new();
+    stats.write_json_fields(&mut obj);
+    let text = obj.finish();
+    assert!(text.contains("\"timestamp\":\"1970-01-01T00:00:00Z\""), "{}", text);
+    let path = std::env::temp_dir().join(format!("timestamp-{}.json", std::process::id()));
+    for year in ["300000000000", "99999999999999999", "99999999999999999999"] {
+        let far_off = text.replace("\"1970-", &format!("\"{}-", year));
+        assert_ne!(far_off, text);
+        std::fs::write(&path, far_off).unwrap();
+        let path = path.display().to_string();
+        assert_eq!(load_runs(&path).unwrap()[0].1.timestamp, None, "{}", year);
+        run_compare(&path, &path).unwrap();
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn saved_results_describe_themselves() {
+    let server = start().await;
+    let path = std::env::temp_dir().join(format!("labels-{}.json", std::process::id()));
+
+    // Library measurements are only stamped with the time
+    let before = SystemTime::now() - Duration::from_secs(1);
+    let options = TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() };
+    let stats = measure_transfer(&server.address, 1000, 2, &options).await.unwrap();
+    assert!(stats.timestamp.unwrap() >= before);
+    assert_eq!((stats.label, stats.config_summary), (None, String::new()));
+
+    let config = Config {
+        address: Some(server.address.clone()),
+        sizes: vec![1000],
+        iterations: 2,
+        delay: Duration::ZERO,
+        label: Some("baseline".to_string()),
+        format: OutputFormat::Json,
+        output: Some(path.display().to_string()),
+        ..Config::default()
+    };
+    run_benchmarks(&config).await.unwrap();
+    let runs = load_runs(&path.display().to_string()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let (_, saved) = &runs[0];
+    assert_eq!(saved.label.as_deref(), Some("baseline"));
+    assert_eq!(saved.config_summary, config.summary());
+    let line = describe_file("Baseline", "old.json", &runs).unwrap();
+    assert!(line.starts_with("Baseline: baseline ("), "{}", line);
+    assert!(line.ends_with(&format!("; {})", config.summary())), "{}", line);
+
+    server.stop().await;
+}
+
+#[test]
+fn older_files_get_no_description() {
+    let runs = vec![(Some(1000), NetworkStats::default())];
+    assert_eq!(describe_file("New", "new.json", &runs), None);
+    let unlabelled =
+        NetworkStats { config_summary: "tcp host:1".to_string(), ..NetworkStats::default() };
+    let runs = vec![(Some(1000), unlabelled)];
+    assert_eq!(describe_file("New", "new.json", &runs).unwrap(), "New: new.json (tcp host:1)");
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::{link_utilization, utilization_line};
+
+use common::parse;
+
+fn link_bandwidth(raw: &str) -> Result<Option<f64>, String> {
+    match parse(&["--link-bandwidth", raw])? {
+        Command::Run(config) => Ok(config.link_bandwidth),
+        other => panic!("expected a run, got {:?}", other),
+    }
+}
+
+#[test]
+fn bandwidth_takes_decimal_multiples_of_bits() {
+    assert_eq!(link_bandwidth("1G"), Ok(Some(1e9)));
+    assert_eq!(link_bandwidth("100Mbps"), Ok(Some(1e8)));
+    assert_eq!(link_bandwidth("2.5e9"), Ok(Some(2.5e9)));
+    assert_eq!(link_bandwidth("10k"), Ok(Some(1e4)));
+    assert_eq!(link_bandwidth("1000000"), Ok(Some(1e6)));
+    for bad in ["fast", "0", "-1G", "G", "1Q"] {
+        let error = link_bandwidth(bad).unwrap_err();
+        assert!(error.starts_with("--link-bandwidth expects a bandwidth"), "{}", error);
+    }
+}
+
+#[test]
+fn utilization_is_the_rate_in_bits_over_the_link() {
+    // 90 MB/s is 720 Mbps
+    assert_eq!(link_utilization(90_000_000.0, 1e9), 0.72);
+    assert_eq!(
+        utilization_line(45_000_000.0, 90_000_000.0, 1e9),
+        "72.0% of 1.00 Gbps down, 36.0% up"
+    );
+}
+
+#[test]
+fn link_bandwidth_needs_a_plain_sweep() {
+    let error = parse(&["--link-bandwidth", "1G", "--watch"]).unwrap_err();
+    assert!(error.starts_with("--link-bandwidth compares a plain sweep's rates"), "{}", error);
+}
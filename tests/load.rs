@@ -0,0 +1,47 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::{measure_latency_under_load, LoadOptions, TransferOptions};
+
+use common::{parse, start};
+
+fn options(idle_pings: usize) -> LoadOptions {
+    LoadOptions {
+        bulk_size: 1024 * 1024,
+        bulk_iterations: 4,
+        ping_interval: Duration::from_millis(1),
+        idle_pings,
+        transfer: TransferOptions { concurrency: 2, ..TransferOptions::default() },
+        ..LoadOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn pings_run_alongside_the_bulk_transfers() {
+    let server = start().await;
+    let address = server.address.clone();
+
+    let result = measure_latency_under_load(&address, &options(3)).await.unwrap();
+    assert_eq!(result.bulk.successful_iterations, 4);
+    assert_eq!(result.bulk.bytes_sent, 4 * 1024 * 1024);
+    let idle = result.idle.as_ref().unwrap();
+    assert_eq!((idle.successful_iterations, idle.bytes_sent), (3, 3 * 64));
+    assert!(result.loaded.successful_iterations >= 1);
+    assert_eq!(result.loaded.connections, 1); // the pings share one connection
+    assert!(!result.loaded.interrupted);
+    assert!(result.added_latency().is_some());
+
+    let result = measure_latency_under_load(&address, &options(0)).await.unwrap();
+    assert!(result.idle.is_none());
+    assert!(result.added_latency().is_none());
+
+    server.stop().await;
+}
+
+#[test]
+fn under_load_rejects_other_modes() {
+    assert!(parse(&["--under-load", "1000000", "--ping-interval", "10ms"]).is_ok());
+    let error = parse(&["--under-load", "1000000", "--watch"]).unwrap_err();
+    assert!(error.ends_with("are separate run modes, so they can't be combined"), "{}", error);
+}
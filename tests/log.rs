@@ -0,0 +1,35 @@
+use network_traffic_monitoring::tool::log::{parse_filter, Level};
+
+const CRATE: &str = "network_traffic_monitoring::tool::tool";
+
+#[test]
+fn rust_log_directives_for_this_crate_set_the_level() {
+    assert_eq!(parse_filter("debug").level_for(CRATE), Some(Level::Debug));
+    let filter = parse_filter("warn,network_traffic_monitoring=trace");
+    assert_eq!(filter.level_for(CRATE), Some(Level::Trace));
+    // Other crates' directives don't make this one chattier
+    assert_eq!(parse_filter("info,hyper=trace").level_for(CRATE), Some(Level::Info));
+    assert_eq!(parse_filter("off").level_for(CRATE), None);
+    assert_eq!(parse_filter("nonsense").level_for(CRATE), None);
+}
+
+#[test]
+fn the_longest_matching_target_decides() {
+    let filter = parse_filter("foo::bar=warn,foo=trace,info");
+    assert_eq!(filter.level_for("foo::bar"), Some(Level::Warn));
+    assert_eq!(filter.level_for("foo::bar::baz"), Some(Level::Warn));
+    assert_eq!(filter.level_for("foo::qux"), Some(Level::Trace));
+    assert_eq!(filter.level_for("other"), Some(Level::Info));
+
+    let filter = parse_filter("foo=trace,foo::bar=warn");
+    assert_eq!(filter.level_for("foo::bar"), Some(Level::Warn));
+    assert_eq!(filter.level_for("foo"), Some(Level::Trace));
+
+    // A target can be switched off under a chattier parent, and the later of two
+    // directives for the same target wins
+    let filter = parse_filter("foo=debug,foo::bar=off,foo=error");
+    assert_eq!(filter.level_for("foo::bar"), None);
+    assert_eq!(filter.level_for("foo::baz"), Some(Level::Error));
+    // Without a bare level, targets no directive names stay quiet
+    assert_eq!(filter.level_for("other"), None);
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::tool::json;
+use network_traffic_monitoring::{measure_transfer, run_benchmarks, NetworkStats, TransferOptions};
+
+use common::{parse, round_trip, start};
+
+fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+}
+
+#[tokio::test]
+async fn iterations_stop_once_the_time_is_up() {
+    let server = start().await;
+    let address = server.address.clone();
+
+    let options = TransferOptions {
+        delay: ms(50),
+        max_duration: Some(ms(200)),
+        ..TransferOptions::default()
+    };
+    let started = Instant::now();
+    let stats = measure_transfer(&address, 1000, 1000, &options).await.unwrap();
+    assert!(stats.time_limited);
+    assert!(!stats.interrupted);
+    let iterations = stats.successful_iterations;
+    assert!((2..=6).contains(&iterations), "{} iterations", iterations);
+    assert_eq!(stats.bytes_sent, 1000 * iterations);
+    // The last delay is cut short at the deadline
+    assert!(started.elapsed() < ms(400), "{:?}", started.elapsed());
+
+    let options = TransferOptions {
+        concurrency: 4,
+        max_duration: Some(ms(100)),
+        ..TransferOptions::default()
+    };
+    let stats = measure_transfer(&address, 1000, 1_000_000, &options).await.unwrap();
+    assert!(stats.time_limited);
+    assert!(stats.successful_iterations < 1_000_000);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn a_limit_that_isnt_reached_changes_nothing() {
+    let server = start().await;
+
+    let options = TransferOptions {
+        delay: Duration::ZERO,
+        max_duration: Some(Duration::from_secs(10)),
+        ..TransferOptions::default()
+    };
+    let stats = measure_transfer(&server.address, 1000, 3, &options).await.unwrap();
+    assert_eq!(stats.successful_iterations, 3);
+    assert!(!stats.time_limited);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn the_run_limit_covers_every_size() {
+    let server = start().await;
+    let path = std::env::temp_dir().join(format!("max-duration-{}.json", std::process::id()));
+
+    let address = server.address.clone();
+    let output = path.display().to_string();
+    let args = [
+        "--format", "json", "--output", &output, "--address", &address,
+        "--sizes", "1000,2000", "--iterations", "5", "--max-duration", "1ms",
+    ];
+    let Ok(Command::Run(config)) = parse(&args) else {
+        panic!("expected a run");
+    };
+    run_benchmarks(&config).await.unwrap();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    // One iteration always runs; the time was gone before the second size
+    assert_eq!(text.lines().count(), 1, "{}", text);
+    let stats = NetworkStats::from_json(&json::parse(text.trim()).unwrap()).unwrap();
+    assert!(stats.time_limited);
+    assert_eq!(stats.successful_iterations, 1);
+
+    server.stop().await;
+}
+
+#[test]
+fn time_limited_survives_a_json_round_trip() {
+    let stats = NetworkStats { time_limited: true, ..NetworkStats::default() };
+    assert!(round_trip(&stats).time_limited);
+}
+
+#[test]
+fn max_duration_only_limits_sweeps() {
+    assert!(parse(&["--max-duration", "30s"]).is_ok());
+    assert!(parse(&["--max-duration", "0"]).is_err());
+    let error = parse(&["--max-duration", "30s", "--watch"]).unwrap_err();
+    assert_eq!(
+        error,
+        "--max-duration limits a sweep's iterations, so it can't be combined with --watch"
+    );
+}
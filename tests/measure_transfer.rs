@@ -0,0 +1,69 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::{
+    measure_transfer, measure_transfer_udp, TransferError, TransferOptions,
+};
+
+use common::start;
+
+fn options() -> TransferOptions {
+    TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() }
+}
+
+// The default mock server answers every request with half its bytes
+async fn assert_contract(options: &TransferOptions) {
+    let server = start().await;
+
+    let stats = measure_transfer(&server.address, 2048, 6, options).await.unwrap();
+    assert_eq!(stats.successful_iterations, 6);
+    assert_eq!(stats.failed_iterations, 0);
+    assert_eq!(stats.bytes_sent, 2048 * 6);
+    assert_eq!(stats.bytes_received, 1024 * 6);
+    assert!((stats.ratio - 0.5).abs() < 1e-9, "ratio {}", stats.ratio);
+    assert!(stats.upload_rate > 0.0 && stats.download_rate > 0.0);
+    assert_eq!(stats.latencies.len(), 6);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn sequential_iterations_move_the_expected_bytes() {
+    assert_contract(&options()).await;
+}
+
+#[tokio::test]
+async fn concurrent_iterations_move_the_expected_bytes() {
+    assert_contract(&TransferOptions { concurrency: 3, ..options() }).await;
+}
+
+#[tokio::test]
+async fn one_reused_connection_moves_the_expected_bytes() {
+    assert_contract(&TransferOptions { reuse_connection: true, ..options() }).await;
+}
+
+#[tokio::test]
+async fn shutdown_stops_accepting_connections() {
+    let server = start().await;
+    assert_ne!(server.port, 0, "the bound port is reported, not the requested 0");
+    let address = server.address.clone();
+    server.stop().await;
+
+    let err = measure_transfer(&address, 100, 1, &options()).await.unwrap_err();
+    assert_eq!(err.category(), "connect", "{}", err);
+}
+
+#[tokio::test]
+async fn zero_iterations_is_rejected_rather_than_an_empty_success() {
+    let server = start().await;
+
+    for options in [options(), TransferOptions { concurrency: 4, ..options() }] {
+        let err = measure_transfer(&server.address, 1000, 0, &options).await.unwrap_err();
+        assert!(matches!(err, TransferError::InvalidOptions(_)), "{:?}", err);
+    }
+    let err = measure_transfer_udp(&server.address, 1000, 0).await.unwrap_err();
+    assert!(matches!(err, TransferError::InvalidOptions(_)), "{:?}", err);
+
+    server.stop().await;
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use std::net::IpAddr;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::DEFAULT_METRICS_BIND;
+
+use common::parse;
+
+#[test]
+fn metrics_stay_on_loopback_unless_bound_elsewhere() {
+    assert!(DEFAULT_METRICS_BIND.is_loopback());
+    let Ok(Command::Run(config)) = parse(&["--metrics-port", "9100"]) else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.metrics_bind, None);
+
+    let Ok(Command::Run(config)) = parse(&["--metrics-port", "9100", "--metrics-bind", "0.0.0.0"])
+    else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.metrics_bind, Some(IpAddr::from([0, 0, 0, 0])));
+
+    assert_eq!(
+        parse(&["--metrics-bind", "0.0.0.0"]).unwrap_err(),
+        "--metrics-bind needs --metrics-port"
+    );
+    assert_eq!(
+        parse(&["--metrics-port", "9100", "--metrics-bind", "everywhere"]).unwrap_err(),
+        "--metrics-bind expects an IP address like 0.0.0.0, got 'everywhere'"
+    );
+}
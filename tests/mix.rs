@@ -0,0 +1,88 @@
+mod common;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::{measure_mix, parse_mix, MixOptions, SizeMix, TransferOptions};
+use std::time::Duration;
+
+use common::{parse, start};
+
+#[test]
+fn weights_are_relative() {
+    let mix = parse_mix("1024:7, 65536:3").unwrap();
+    assert_eq!(mix, SizeMix::new(&[(1024, 0.7), (65536, 0.3)]).unwrap());
+    assert_eq!(mix.buckets()[0].0, 1024);
+    assert!((mix.buckets()[0].1 - 0.7).abs() < 1e-12);
+
+    assert!(parse_mix("").is_err());
+    assert!(parse_mix("1024").is_err());
+    assert!(parse_mix("1024:0").is_err());
+    assert!(parse_mix("1024:-1").is_err());
+    assert!(parse_mix("1024:NaN").is_err());
+    assert!(parse_mix("1024:0.5,1024:0.5").is_err());
+}
+
+#[test]
+fn huge_weights_still_share_the_mix() {
+    let mix = parse_mix("1024:1e308,65536:1e308").unwrap();
+    assert_eq!(mix.buckets(), &[(1024, 0.5), (65536, 0.5)]);
+    let sizes = mix.draw(1000, 7);
+    assert!(sizes.contains(&1024) && sizes.contains(&65536), "{:?}", sizes);
+
+    let mix = SizeMix::new(&[(1, f64::MAX), (2, f64::MAX / 4.0)]).unwrap();
+    assert_eq!(mix.buckets(), &[(1, 0.8), (2, 0.2)]);
+}
+
+#[test]
+fn draws_follow_the_weights_and_the_seed() {
+    let mix = SizeMix::new(&[(1, 0.7), (2, 0.25), (3, 0.05)]).unwrap();
+    let sizes = mix.draw(10_000, 42);
+    assert_eq!(sizes, mix.draw(10_000, 42));
+    assert_ne!(sizes, mix.draw(10_000, 43));
+    for (size, weight) in [(1, 0.7), (2, 0.25), (3, 0.05)] {
+        let share = sizes.iter().filter(|&&s| s == size).count() as f64 / 10_000.0;
+        assert!((share - weight).abs() < 0.02, "size {}: {}", size, share);
+    }
+}
+
+#[tokio::test]
+async fn each_size_and_the_whole_mix_are_reported() {
+    let server = start().await;
+    let mix = parse_mix("1000:0.5,4000:0.3,20000:0.2").unwrap();
+    let options = MixOptions {
+        seed: 7,
+        transfer: TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() },
+        ..MixOptions::default()
+    };
+
+    let result = measure_mix(&server.address, &mix, 30, &options).await.unwrap();
+    let drawn = mix.draw(30, 7);
+    assert_eq!(result.overall.successful_iterations, 30);
+    assert_eq!(result.overall.bytes_sent, drawn.iter().sum::<usize>());
+    assert_eq!(result.overall.bytes_received, result.overall.bytes_sent / 2);
+    for bucket in &result.buckets {
+        let count = drawn.iter().filter(|&&size| size == bucket.size).count();
+        assert_eq!(bucket.drawn, count, "size {}", bucket.size);
+        match &bucket.stats {
+            Some(stats) => {
+                assert_eq!(stats.successful_iterations, count);
+                assert_eq!(stats.bytes_sent, count * bucket.size);
+            }
+            None => assert_eq!(count, 0),
+        }
+    }
+}
+
+#[test]
+fn size_mix_is_its_own_mode() {
+    let Ok(Command::Run(config)) = parse(&["--size-mix", "1024:0.9,65536:0.1", "--seed", "3"])
+    else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.size_mix.unwrap().buckets().len(), 2);
+
+    let error = parse(&["--size-mix", "1024:x"]).unwrap_err();
+    assert!(error.starts_with("--size-mix: expected SIZE:WEIGHT"), "{}", error);
+    let error = parse(&["--size-mix", "1024:1", "--concurrency", "4"]).unwrap_err();
+    assert!(error.contains("can't be combined with --concurrency"), "{}", error);
+    assert!(parse(&["--size-mix", "1024:1", "--quiet"]).is_err());
+}
@@ -0,0 +1,203 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::{
+    measure_transfer, run_mock_server, Direction, MockServerConfig, MockServerStats,
+    TransferOptions,
+};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+
+use common::parse;
+
+#[tokio::test]
+async fn taken_port_fails_at_startup_naming_the_address() {
+    let taken = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = taken.local_addr().unwrap().to_string();
+    let (_stop, stop_signal) = watch::channel(false);
+
+    let config = MockServerConfig {
+        address: address.clone(),
+        ..MockServerConfig::default()
+    };
+    let err = run_mock_server(config, stop_signal).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+    assert!(err.to_string().contains(&address), "{}", err);
+}
+
+fn sized(response_size: usize) -> TransferOptions {
+    TransferOptions {
+        delay: Duration::ZERO,
+        timeout: Duration::from_secs(2),
+        response_size: Some(response_size),
+        ..TransferOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn replies_with_the_requested_size_whatever_the_request() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig { echo: true, ..MockServerConfig::default() };
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+
+    let options = TransferOptions { verify: true, reuse_connection: true, ..sized(10_000) };
+    let stats = measure_transfer(&bound.to_string(), 1024, 3, &options).await.unwrap();
+    assert_eq!(stats.corrupted_iterations, 0);
+    assert_eq!(stats.bytes_sent, 3 * 1024);
+    assert_eq!(stats.bytes_received, 3 * 10_000);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn requests_over_the_cap_fail() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig { max_response_len: 1000, ..MockServerConfig::default() };
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+    let address = bound.to_string();
+
+    assert_eq!(measure_transfer(&address, 10, 1, &sized(1000)).await.unwrap().bytes_received, 1000);
+    let err = measure_transfer(&address, 10, 1, &sized(1001)).await.unwrap_err();
+    assert_eq!(err.category(), "read", "{}", err);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn an_over_cap_request_is_refused_with_a_clean_close() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig { max_response_len: 1000, ..MockServerConfig::default() };
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+
+    // Big enough that the server closes with much of the body still in flight
+    for _ in 0..5 {
+        let err = measure_transfer(&bound.to_string(), 1 << 20, 1, &sized(1001)).await.unwrap_err();
+        assert_eq!(err.category(), "read", "{}", err);
+    }
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+// The server counts a request once its reply is out, which can be just after the client
+// has read it
+async fn wait_for_requests(stats: &MockServerStats, requests: u64) {
+    for _ in 0..200 {
+        if stats.requests() >= requests {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("server saw {} requests, waited for {}", stats.requests(), requests);
+}
+
+#[tokio::test]
+async fn counts_what_it_read_and_wrote() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig::default();
+    let stats = config.stats.clone();
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+    let address = bound.to_string();
+
+    let options = TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() };
+    let client = measure_transfer(&address, 1000, 3, &options).await.unwrap();
+    let upload = TransferOptions { direction: Direction::UploadOnly, ..options.clone() };
+    measure_transfer(&address, 500, 2, &upload).await.unwrap();
+    wait_for_requests(&stats, 5).await;
+
+    assert_eq!(stats.connections(), 5);
+    assert_eq!(stats.bytes_read(), client.bytes_sent as u64 + 2 * 500);
+    assert_eq!(stats.bytes_written(), client.bytes_received as u64);
+    assert_eq!(stats.errors(), 0);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn counts_failed_connections() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig { max_response_len: 1000, ..MockServerConfig::default() };
+    let stats = config.stats.clone();
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+
+    measure_transfer(&bound.to_string(), 10, 1, &sized(1001)).await.unwrap_err();
+    for _ in 0..200 {
+        if stats.errors() > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(stats.errors(), 1);
+    assert_eq!(stats.requests(), 0);
+    assert_eq!(stats.bytes_written(), 0);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+// Polls until `done` holds, for counters the server updates on its own tasks
+async fn wait_until(done: impl Fn() -> bool) {
+    for _ in 0..200 {
+        if done() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("gave up waiting");
+}
+
+#[tokio::test]
+async fn tracks_the_most_connections_open_at_once() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig::default();
+    let stats = config.stats.clone();
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+
+    let mut clients = Vec::new();
+    for _ in 0..5 {
+        clients.push(tokio::net::TcpStream::connect(bound).await.unwrap());
+    }
+    wait_until(|| stats.active_connections() == 5).await;
+    drop(clients);
+    wait_until(|| stats.active_connections() == 0).await;
+    assert_eq!(stats.peak_connections(), 5);
+    assert_eq!(stats.connections(), 5);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn a_larger_backlog_takes_a_burst_of_connections() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig { backlog: 4096, ..MockServerConfig::default() };
+    let stats = config.stats.clone();
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+
+    let options =
+        TransferOptions { delay: Duration::ZERO, concurrency: 32, ..TransferOptions::default() };
+    let client = measure_transfer(&bound.to_string(), 1000, 64, &options).await.unwrap();
+    assert_eq!(client.successful_iterations, 64);
+    assert_eq!(stats.connections(), 64);
+    assert!((1..=32).contains(&stats.peak_connections()), "{:?}", stats);
+    assert_eq!(stats.accept_errors(), 0);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[test]
+fn backlog_and_accept_pause_flags_set_the_mock_server_up() {
+    let args = ["--mock-backlog", "4096", "--mock-accept-pause", "5ms"];
+    let Ok(Command::Run(config)) = parse(&args) else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.mock_backlog, 4096);
+    assert_eq!(config.mock_accept_pause, Some(Duration::from_millis(5)));
+    assert!(parse(&["--mock-backlog", "0"]).is_err());
+}
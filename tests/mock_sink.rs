@@ -0,0 +1,77 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::{
+    measure_transfer, run_mock_server, Direction, MockServerConfig, TransferError,
+    TransferOptions,
+};
+use tokio::sync::watch;
+
+use common::parse;
+
+#[tokio::test]
+async fn a_sink_reads_everything_and_answers_nothing() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig { sink: true, ..MockServerConfig::default() };
+    let server_stats = config.stats.clone();
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+    let address = bound.to_string();
+
+    let upload = TransferOptions {
+        direction: Direction::UploadOnly,
+        delay: Duration::ZERO,
+        ..TransferOptions::default()
+    };
+    let stats = measure_transfer(&address, 50_000, 4, &upload).await.unwrap();
+    assert_eq!(stats.successful_iterations, 4);
+    assert_eq!(stats.bytes_sent, 200_000);
+    assert_eq!(stats.bytes_received, 0);
+    assert!(stats.upload_rate > 0.0);
+    // Nothing came back, which is a rate and a ratio of zero rather than NaN
+    assert_eq!((stats.download_rate, stats.ratio), (0.0, 0.0));
+
+    // A client that waits for the reply never gets one
+    let both = TransferOptions {
+        timeout: Duration::from_millis(200),
+        fail_fast: true,
+        ..TransferOptions::default()
+    };
+    let err = measure_transfer(&address, 1000, 1, &both).await.unwrap_err();
+    assert!(matches!(err, TransferError::Timeout { phase: "read", .. }), "{:?}", err);
+
+    // Asking for a response size doesn't get one either, and isn't counted as payload
+    let sized = TransferOptions { response_size: Some(10), ..both };
+    assert!(measure_transfer(&address, 1000, 1, &sized).await.is_err());
+
+    assert_eq!(server_stats.requests(), 6);
+    assert_eq!(server_stats.bytes_read(), 202_000);
+    assert_eq!(server_stats.bytes_written(), 0);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[test]
+fn mock_sink_switches_the_client_to_upload_only() {
+    let Ok(Command::Run(config)) = parse(&["--mock-sink"]) else {
+        panic!("expected a run");
+    };
+    assert!(config.mock_sink);
+    assert_eq!(config.direction, Direction::UploadOnly);
+
+    let error = parse(&["--mock-sink", "--verify"]).unwrap_err();
+    assert_eq!(
+        error,
+        "--mock-sink has the mock server discard every request unanswered, so it can't be \
+         combined with --verify"
+    );
+    for args in [
+        &["--mock-sink", "--direction", "download"][..],
+        &["--mock-sink", "--no-mock-server"],
+        &["--mock-sink", "--address", "127.0.0.1:9000"],
+    ] {
+        assert!(parse(args).is_err(), "{:?}", args);
+    }
+}
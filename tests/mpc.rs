@@ -0,0 +1,55 @@
+use network_traffic_monitoring::{
+    estimate_mpc, estimate_mpc_measured, monitor_mpc_measured, MpcOverheadModel, NetworkStats,
+};
+
+#[test]
+fn default_model_estimates() {
+    let estimates = estimate_mpc(&[1024], &MpcOverheadModel::default());
+    assert_eq!(estimates.len(), 1);
+    let estimate = &estimates[0];
+    assert_eq!(estimate.request_size, 1024);
+    assert_eq!(estimate.response_size, 10 * 1024);
+    assert_eq!(estimate.upload_overhead, 25 * 1024 * 1024 + 10 * 1024);
+    assert_eq!(estimate.download_overhead, 409);
+    assert!((estimate.upload_ratio - 25610.0).abs() < 1e-9);
+}
+
+#[test]
+fn one_estimate_per_size() {
+    let sizes = [1, 1024, 10 * 1024];
+    let estimates = estimate_mpc(&sizes, &MpcOverheadModel::default());
+    let got: Vec<usize> = estimates.iter().map(|e| e.size).collect();
+    assert_eq!(got, sizes);
+}
+
+#[test]
+fn measured_bytes_replace_the_nominal_sizes() {
+    let stats = NetworkStats {
+        bytes_sent: 4 * 1000,
+        bytes_received: 4 * 500,
+        successful_iterations: 4,
+        failed_iterations: 1,
+        upload_rate: 1024.0 * 1024.0,
+        ..NetworkStats::default()
+    };
+    let model = MpcOverheadModel::default();
+    let estimate = estimate_mpc_measured(1000, &stats, &model).unwrap();
+    assert_eq!((estimate.size, estimate.request_size, estimate.response_size), (1000, 1000, 500));
+    assert_eq!(estimate.upload_overhead, 25 * 1024 * 1024 + 10_000);
+    assert_eq!(estimate.download_overhead, 20);
+    assert!((estimate.download_ratio - 0.04).abs() < 1e-9);
+    // The printed figures are the same estimates
+    assert_eq!(monitor_mpc_measured(&[(1000, stats)], &model), vec![estimate]);
+}
+
+#[test]
+fn measurements_without_a_response_have_no_download_ratio() {
+    let model = MpcOverheadModel::default();
+    let upload_only =
+        NetworkStats { bytes_sent: 2048, successful_iterations: 2, ..NetworkStats::default() };
+    let estimate = estimate_mpc_measured(1024, &upload_only, &model).unwrap();
+    assert_eq!((estimate.response_size, estimate.download_ratio), (0, 0.0));
+
+    let all_failed = NetworkStats { failed_iterations: 3, ..NetworkStats::default() };
+    assert_eq!(estimate_mpc_measured(1024, &all_failed, &model), None);
+}
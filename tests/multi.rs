@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use network_traffic_monitoring::{
+    measure_many, render_host_table, run_mock_server, MockServerConfig, RateUnits,
+    TransferOptions,
+};
+use tokio::sync::watch;
+
+#[tokio::test]
+async fn measure_many_keeps_address_order_and_isolates_failures() {
+    let (stop, stop_signal) = watch::channel(false);
+    let light = MockServerConfig {
+        response_ratio: 0.5,
+        ..MockServerConfig::default()
+    };
+    let heavy = MockServerConfig {
+        response_ratio: 2.0,
+        ..MockServerConfig::default()
+    };
+    let (light_addr, light_server) = run_mock_server(light, stop_signal.clone()).await.unwrap();
+    let (heavy_addr, heavy_server) = run_mock_server(heavy, stop_signal).await.unwrap();
+    let (light_addr, heavy_addr) = (light_addr.to_string(), heavy_addr.to_string());
+
+    let options = TransferOptions {
+        delay: Duration::ZERO,
+        ..TransferOptions::default()
+    };
+    // Port 1 on loopback refuses connections
+    let addresses = [light_addr.as_str(), "127.0.0.1:1", heavy_addr.as_str()];
+    let results = measure_many(&addresses, 1000, 2, &options, true).await;
+
+    let names: Vec<&str> = results.iter().map(|(host, _)| host.as_str()).collect();
+    assert_eq!(names, addresses);
+    assert_eq!(results[0].1.as_ref().unwrap().bytes_received, 2 * 500);
+    assert!(results[1].1.is_err());
+    assert_eq!(results[2].1.as_ref().unwrap().bytes_received, 2 * 2000);
+
+    let table = render_host_table(&results, RateUnits::Bytes);
+    assert!(table.contains("127.0.0.1:1 failed"));
+
+    stop.send(true).unwrap();
+    light_server.await.unwrap().unwrap();
+    heavy_server.await.unwrap().unwrap();
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "otlp")]
+
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::json::{self, JsonValue};
+use network_traffic_monitoring::tool::otlp::{ExponentialHistogram, OtlpExporter, MAX_BUCKETS};
+use network_traffic_monitoring::measure_transfer;
+use network_traffic_monitoring::TransferOptions;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use common::start;
+
+#[test]
+fn histogram_buckets_cover_every_sample() {
+    let values = [0.0, 0.001, 0.002, 0.004, 0.004, 1.5];
+    let histogram = ExponentialHistogram::from_values(&values, MAX_BUCKETS);
+    assert_eq!(histogram.zero_count, 1);
+    assert_eq!(histogram.counts.iter().sum::<u64>(), 5);
+    assert!(histogram.counts.len() <= MAX_BUCKETS);
+
+    // Each positive sample lies inside the bucket it was counted in
+    let base = 2f64.powf(2f64.powi(-histogram.scale));
+    for value in &values[1..] {
+        let index = (value.log2() / base.log2()).ceil() as i32 - 1;
+        let bucket = (index - histogram.offset) as usize;
+        assert!(histogram.counts[bucket] > 0, "{} not in bucket {}", value, index);
+    }
+
+    // Only two buckets allowed: the scale drops until the whole range fits
+    let coarse = ExponentialHistogram::from_values(&values, 2);
+    assert!(coarse.scale < histogram.scale);
+    assert!(coarse.counts.len() <= 2);
+    assert_eq!(coarse.counts.iter().sum::<u64>(), 5);
+}
+
+#[tokio::test]
+async fn export_posts_otlp_json_to_the_collector() {
+    let server = start().await;
+    let options = TransferOptions {
+        delay: Duration::ZERO,
+        ..TransferOptions::default()
+    };
+    let stats = measure_transfer(&server.address, 4096, 3, &options).await.unwrap();
+
+    // A collector that takes one request and answers 200
+    let collector = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}", collector.local_addr().unwrap());
+    let request = tokio::spawn(async move {
+        let (mut socket, _) = collector.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        // The exporter sends Connection: close but waits for the reply before closing
+        loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head
+                    .lines()
+                    .find_map(|l| l.strip_prefix("Content-Length: "))
+                    .and_then(|l| l.parse::<usize>().ok())
+                    .unwrap();
+                if body.len() >= length {
+                    break;
+                }
+            }
+        }
+        socket.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await.unwrap();
+        String::from_utf8(request).unwrap()
+    });
+
+    let mut exporter = OtlpExporter::new(&endpoint);
+    assert_eq!(exporter.url(), format!("{}/v1/metrics", endpoint));
+    exporter.export(4096, &stats).await.unwrap();
+
+    let request = request.await.unwrap();
+    let (head, body) = request.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("POST /v1/metrics HTTP/1.1"));
+    assert!(head.contains("Content-Type: application/json"));
+
+    let body = json::parse(body).unwrap();
+    let metrics = body.get("resourceMetrics").and_then(JsonValue::as_array).unwrap()[0]
+        .get("scopeMetrics")
+        .and_then(JsonValue::as_array)
+        .unwrap()[0]
+        .get("metrics")
+        .and_then(JsonValue::as_array)
+        .unwrap();
+    let metric = |name: &str| {
+        metrics.iter().find(|m| m.get("name").and_then(JsonValue::as_str) == Some(name)).unwrap()
+    };
+    let first_point =
+        |data: &JsonValue| data.get("dataPoints").unwrap().as_array().unwrap()[0].clone();
+    let histogram = metric("traffic_monitor.latency").get("exponentialHistogram").unwrap();
+
+    let latency = first_point(histogram);
+    assert_eq!(latency.get("count").and_then(JsonValue::as_str), Some("3"));
+    let sent = first_point(metric("traffic_monitor.bytes_sent").get("sum").unwrap());
+    assert_eq!(sent.get("asInt").and_then(JsonValue::as_str), Some("12288"));
+    let download = first_point(metric("traffic_monitor.download_rate").get("gauge").unwrap());
+    assert_eq!(download.get("asDouble").and_then(JsonValue::as_f64), Some(stats.download_rate));
+
+    server.stop().await;
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::{parse_args, Command};
+use network_traffic_monitoring::{
+    measure_parallel, run_mock_server, MockServerConfig, ParallelOptions, ParallelStats, StreamRate,
+    TransferError,
+};
+use tokio::sync::watch;
+
+use common::{parse, start};
+
+fn options(streams: usize, duration: Duration) -> ParallelOptions {
+    ParallelOptions { streams, duration, frame_size: 64 * 1024, ..ParallelOptions::default() }
+}
+
+fn stream(upload_rate: f64) -> StreamRate {
+    StreamRate { bytes_sent: 0, elapsed: Duration::from_secs(1), upload_rate, error: None }
+}
+
+fn stats(rates: &[f64]) -> ParallelStats {
+    ParallelStats {
+        streams: rates.iter().copied().map(stream).collect(),
+        bytes_sent: 0,
+        elapsed: Duration::from_secs(1),
+        upload_rate: rates.iter().sum(),
+        connect_time: Duration::ZERO,
+        interrupted: false,
+    }
+}
+
+#[tokio::test]
+async fn every_stream_pushes_until_the_duration_is_up() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig::default();
+    let server_stats = config.stats.clone();
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+
+    let duration = Duration::from_millis(200);
+    let result = measure_parallel(&bound.to_string(), &options(3, duration)).await.unwrap();
+    assert_eq!(result.streams.len(), 3);
+    assert!(!result.interrupted);
+    assert!(result.elapsed >= duration, "{:?}", result);
+    for stream in &result.streams {
+        assert!(stream.error.is_none(), "{:?}", stream);
+        assert!(stream.bytes_sent > 0 && stream.upload_rate > 0.0, "{:?}", stream);
+    }
+    let total: usize = result.streams.iter().map(|stream| stream.bytes_sent).sum();
+    assert_eq!(result.bytes_sent, total);
+    // Each stream waits for the server to confirm its frames, so all of them were read
+    assert_eq!(server_stats.bytes_read(), total as u64);
+    assert_eq!(server_stats.connections(), 3);
+
+    assert!(result.upload_rate >= result.max_stream_rate());
+    assert!(result.fairness() > 0.0 && result.fairness() <= 1.0 + 1e-9);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn a_cancelled_run_is_marked_interrupted() {
+    let server = start().await;
+
+    let (interrupt, cancel) = watch::channel(false);
+    let mut options = options(2, Duration::from_secs(30));
+    options.transfer.cancel = Some(cancel);
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        interrupt.send(true).unwrap();
+    });
+    let result = measure_parallel(&server.address, &options).await.unwrap();
+    assert!(result.interrupted);
+    assert!(result.elapsed < Duration::from_secs(30));
+    assert_eq!(result.streams.len(), 2);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn zero_streams_is_rejected() {
+    let result = measure_parallel("127.0.0.1:1", &options(0, Duration::from_secs(1))).await;
+    assert!(matches!(result, Err(TransferError::InvalidOptions(_))), "{:?}", result);
+}
+
+#[test]
+fn fairness_is_one_for_an_even_share() {
+    let even = stats(&[100.0, 100.0, 100.0]);
+    assert!((even.fairness() - 1.0).abs() < 1e-9);
+    assert_eq!(even.stream_rate_std_dev(), 0.0);
+}
+
+#[test]
+fn fairness_drops_to_one_over_n_when_one_stream_takes_it_all() {
+    let skewed = stats(&[300.0, 0.0, 0.0]);
+    assert!((skewed.fairness() - 1.0 / 3.0).abs() < 1e-9);
+    assert_eq!(skewed.min_stream_rate(), 0.0);
+    assert_eq!(skewed.max_stream_rate(), 300.0);
+
+    let pair = stats(&[100.0, 300.0]);
+    assert!((pair.stream_rate_std_dev() - 100.0).abs() < 1e-9);
+    assert!((pair.fairness() - 0.8).abs() < 1e-9);
+}
+
+#[test]
+fn parallel_flags_set_the_streams_and_duration() {
+    let args = ["--parallel", "4", "--parallel-duration", "2s"].iter().map(|arg| arg.to_string());
+    let Ok(Command::Run(config)) = parse_args(args) else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.parallel, Some(4));
+    assert_eq!(config.parallel_duration, Duration::from_secs(2));
+}
+
+#[test]
+fn parallel_rejects_zero_streams_and_other_modes() {
+    assert!(parse(&["--parallel", "0"]).is_err());
+    let Err(message) = parse(&["--parallel", "2", "--watch"]) else {
+        panic!("expected --watch to be rejected");
+    };
+    assert_eq!(message, "--watch and --parallel are separate run modes, so they can't be combined");
+}
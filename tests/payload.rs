@@ -0,0 +1,68 @@
+use network_traffic_monitoring::tool::framing::{self, MAX_FRAME_LEN};
+use network_traffic_monitoring::{
+    measure_payload, measure_transfer, run_mock_server, MockServerConfig, PayloadSource,
+    TransferError, TransferOptions, GENERATED_PAYLOAD_MIN,
+};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+
+#[test]
+fn random_payload_is_reproducible_from_its_seed() {
+    let first = PayloadSource::Random { size: 1000, seed: 7 }.load().unwrap();
+    let again = PayloadSource::Random { size: 1000, seed: 7 }.load().unwrap();
+    let other = PayloadSource::Random { size: 1000, seed: 8 }.load().unwrap();
+
+    assert_eq!(first.len(), 1000);
+    assert_eq!(first, again);
+    assert_ne!(first, other);
+}
+
+#[tokio::test]
+async fn generated_payloads_match_the_loaded_bytes() {
+    // Odd-sized, so the random words don't line up with the chunks
+    let payload = PayloadSource::Random { size: GENERATED_PAYLOAD_MIN + 13, seed: 99 };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let server = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let len = framing::read_frame_len(&mut socket).await.unwrap().unwrap();
+        let mut body = vec![0u8; len];
+        socket.read_exact(&mut body).await.unwrap();
+        framing::write_frame(&mut socket, b"ok").await.unwrap();
+        body
+    });
+
+    let stats = measure_payload(&address, &payload, 1, &TransferOptions::default()).await.unwrap();
+    assert_eq!(stats.bytes_sent, GENERATED_PAYLOAD_MIN + 13);
+    assert_eq!(stats.bytes_received, 2);
+    assert!(server.await.unwrap() == payload.load().unwrap());
+}
+
+#[tokio::test]
+async fn large_payloads_are_counted_exactly_and_verify() {
+    let (_stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig { echo: true, ..MockServerConfig::default() };
+    let (bound, _server) = run_mock_server(config, stop_signal).await.unwrap();
+    let size = GENERATED_PAYLOAD_MIN + 1;
+    let options = TransferOptions {
+        verify: true,
+        reuse_connection: true,
+        delay: std::time::Duration::ZERO,
+        ..TransferOptions::default()
+    };
+
+    let stats = measure_transfer(&bound.to_string(), size, 2, &options).await.unwrap();
+    assert_eq!(stats.successful_iterations, 2);
+    assert_eq!(stats.corrupted_iterations, 0);
+    assert_eq!(stats.bytes_sent, 2 * size);
+    assert_eq!(stats.bytes_received, 2 * (size / 2));
+}
+
+#[tokio::test]
+async fn a_payload_over_the_frame_limit_is_refused_up_front() {
+    // Nothing listens there: the size is checked before connecting or allocating
+    let options = TransferOptions::default();
+    let error = measure_transfer("127.0.0.1:9", MAX_FRAME_LEN + 1, 1, &options).await.unwrap_err();
+    assert!(matches!(error, TransferError::InvalidOptions(_)), "{}", error);
+}
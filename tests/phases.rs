@@ -0,0 +1,81 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::{
+    measure_transfer, phase_line, Direction, NetworkStats, PhaseBreakdown, RunSummary,
+    TransferOptions,
+};
+
+use common::{round_trip, slow_server, start};
+
+fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+}
+
+#[tokio::test]
+async fn a_slow_server_shows_up_as_waiting() {
+    let address = slow_server(ms(60)).await;
+    let options = TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() };
+
+    let stats = measure_transfer(&address, 1000, 3, &options).await.unwrap();
+    let phases = stats.phases;
+    assert!(phases.wait >= ms(60), "{:?}", phases);
+    assert_eq!(phases.wait, stats.time_to_first_byte);
+    assert!(phases.connect > Duration::ZERO && phases.send > Duration::ZERO, "{:?}", phases);
+    // Every phase lies within the iteration's round trip
+    assert!(phases.total() <= stats.mean_latency, "{:?} {:?}", phases, stats.mean_latency);
+    assert!(phases.shares()[2] > 0.5, "{:?}", phases.shares());
+}
+
+#[tokio::test]
+async fn upload_only_requests_only_connect_and_send() {
+    let server = start().await;
+
+    let options = TransferOptions {
+        delay: Duration::ZERO,
+        direction: Direction::UploadOnly,
+        ..TransferOptions::default()
+    };
+    let stats = measure_transfer(&server.address, 10_000, 3, &options).await.unwrap();
+    assert!(stats.phases.send > Duration::ZERO);
+    assert_eq!(stats.phases.wait, Duration::ZERO);
+    assert_eq!(stats.phases.receive, Duration::ZERO);
+
+    server.stop().await;
+}
+
+#[test]
+fn shares_add_up_to_the_whole() {
+    let phases = PhaseBreakdown { connect: ms(1), send: ms(2), wait: ms(5), receive: ms(2) };
+    assert_eq!(phases.total(), ms(10));
+    let shares = phases.shares();
+    assert_eq!(shares, [0.1, 0.2, 0.5, 0.2]);
+    assert_eq!(PhaseBreakdown::default().shares(), [0.0; 4]);
+
+    assert_eq!(
+        phase_line(&phases),
+        "connect 1.00ms (10.0%), send 2.00ms (20.0%), wait 5.00ms (50.0%), receive 2.00ms (20.0%)"
+    );
+}
+
+#[test]
+fn the_summary_weighs_sizes_by_their_iterations() {
+    let stats = |iterations: usize, wait: Duration| NetworkStats {
+        successful_iterations: iterations,
+        phases: PhaseBreakdown { wait, ..PhaseBreakdown::default() },
+        ..NetworkStats::default()
+    };
+    let results = [stats(3, ms(10)), stats(1, ms(50))];
+
+    let summary = RunSummary::from_stats(&results).unwrap();
+    assert_eq!(summary.phases.wait, ms(20));
+    assert_eq!(summary.phases.connect, Duration::ZERO);
+}
+
+#[test]
+fn phases_survive_a_json_round_trip() {
+    let phases = PhaseBreakdown { connect: ms(1), send: ms(2), wait: ms(3), receive: ms(4) };
+    let stats = NetworkStats { phases, ..NetworkStats::default() };
+    assert_eq!(round_trip(&stats).phases, phases);
+}
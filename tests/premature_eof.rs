@@ -0,0 +1,86 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::{parse_args, Command};
+use network_traffic_monitoring::tool::framing;
+use network_traffic_monitoring::{measure_transfer, NetworkStats, TransferError, TransferOptions};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+
+use common::round_trip;
+
+// Reads each connection's request in full, then hangs up without a response on every
+// other connection (the first, the third, ...) and answers with 10 bytes on the rest
+async fn flaky_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        let mut accepted = 0;
+        while let Ok((mut socket, _)) = listener.accept().await {
+            accepted += 1;
+            let hang_up = accepted % 2 == 1;
+            tokio::spawn(async move {
+                let len = framing::read_frame_len(&mut socket).await.unwrap().unwrap();
+                let mut body = vec![0u8; len];
+                socket.read_exact(&mut body).await.unwrap();
+                if !hang_up {
+                    framing::write_frame(&mut socket, &[0u8; 10]).await.unwrap();
+                }
+            });
+        }
+    });
+    address
+}
+
+fn options(eof_retries: usize) -> TransferOptions {
+    TransferOptions { delay: Duration::ZERO, eof_retries, ..TransferOptions::default() }
+}
+
+#[tokio::test]
+async fn a_hang_up_fails_the_iteration_and_is_counted() {
+    let address = flaky_server().await;
+
+    let stats = measure_transfer(&address, 100, 4, &options(0)).await.unwrap();
+    assert_eq!(stats.successful_iterations, 2);
+    assert_eq!(stats.failed_iterations, 2);
+    assert_eq!(stats.premature_eofs, 2);
+    assert_eq!(stats.bytes_received, 20);
+    assert!(stats.errors.iter().all(|error| error.starts_with("read failed")), "{:?}", stats);
+}
+
+#[tokio::test]
+async fn a_hang_up_is_retried_on_a_new_connection() {
+    let address = flaky_server().await;
+
+    let stats = measure_transfer(&address, 100, 4, &options(1)).await.unwrap();
+    assert_eq!(stats.successful_iterations, 4);
+    assert_eq!(stats.failed_iterations, 0);
+    // The retried EOFs still show
+    assert_eq!(stats.premature_eofs, 4);
+    assert_eq!(stats.connections, 8);
+    assert_eq!(stats.bytes_received, 40);
+}
+
+#[test]
+fn only_a_closed_read_is_a_premature_eof() {
+    let closed = TransferError::Read(std::io::ErrorKind::UnexpectedEof.into());
+    assert!(closed.is_premature_eof());
+    assert!(!TransferError::Read(std::io::ErrorKind::ConnectionReset.into()).is_premature_eof());
+    assert!(!TransferError::Connect(std::io::ErrorKind::UnexpectedEof.into()).is_premature_eof());
+}
+
+#[test]
+fn premature_eofs_survive_a_json_round_trip() {
+    let stats = NetworkStats { premature_eofs: 3, ..NetworkStats::default() };
+    assert_eq!(round_trip(&stats).premature_eofs, 3);
+}
+
+#[test]
+fn eof_retries_flag_sets_the_option() {
+    let args = ["--eof-retries", "2"].iter().map(|arg| arg.to_string());
+    let Ok(Command::Run(config)) = parse_args(args) else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.eof_retries, 2);
+}
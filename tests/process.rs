@@ -0,0 +1,41 @@
+#![cfg(target_os = "linux")]
+
+use std::time::Duration;
+
+use network_traffic_monitoring::{sample_delta, sample_process};
+use tokio::net::{TcpListener, TcpStream};
+
+#[tokio::test]
+async fn sample_lists_own_tcp_connections() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let client = TcpStream::connect(address).await.unwrap();
+    let (_server, _) = listener.accept().await.unwrap();
+
+    let sample = sample_process(std::process::id()).unwrap();
+    assert!(sample.connections.iter().any(|c| c.local == address && c.state == "LISTEN"));
+    assert!(sample.connections.iter().any(|c| {
+        c.local == client.local_addr().unwrap() && c.remote == address && c.state == "ESTABLISHED"
+    }));
+    assert!(sample.shares_namespace);
+}
+
+#[test]
+fn delta_counts_bytes_between_samples() {
+    let before = sample_process(std::process::id()).unwrap();
+    let mut after = before.clone();
+    after.sent += 1000;
+    after.received += 4000;
+
+    let stats = sample_delta(&before, &after, Duration::from_secs(2));
+    assert_eq!(stats.bytes_sent, 1000);
+    assert_eq!(stats.bytes_received, 4000);
+    assert_eq!(stats.upload_rate, 500.0);
+    assert_eq!(stats.ratio, 4.0);
+}
+
+#[test]
+fn missing_process_is_not_found() {
+    let err = sample_process(u32::MAX).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::{measure_transfer_with_progress, TransferOptions};
+
+use common::start;
+
+#[tokio::test]
+async fn callback_sees_every_measured_iteration() {
+    let server = start().await;
+
+    let options = TransferOptions {
+        delay: Duration::ZERO,
+        warmup: 1,
+        ..TransferOptions::default()
+    };
+    let mut seen = Vec::new();
+    let stats = measure_transfer_with_progress(&server.address, 2048, 4, &options, |result| {
+        seen.push(result.clone())
+    })
+    .await
+    .unwrap();
+
+    // The warmup iteration isn't reported
+    assert_eq!(seen.len(), 3);
+    assert_eq!(seen.iter().map(|r| r.index).collect::<Vec<_>>(), vec![0, 1, 2]);
+    assert!(seen.iter().all(|r| r.bytes_sent == 2048 && r.bytes_received == 1024));
+    assert!(seen.iter().all(|r| r.error.is_none()));
+    let sent: usize = seen.iter().map(|r| r.bytes_sent).sum();
+    assert_eq!(sent, stats.bytes_sent);
+
+    server.stop().await;
+}
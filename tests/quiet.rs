@@ -0,0 +1,46 @@
+mod common;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::tool::json;
+use network_traffic_monitoring::run_benchmarks;
+
+use common::{parse, start};
+
+#[test]
+fn quiet_rejects_modes_with_their_own_output() {
+    assert!(parse(&["--quiet"]).is_ok());
+    assert!(parse(&["-q", "--format", "json"]).is_ok());
+    for args in [&["-q", "--watch"][..], &["-q", "--format", "csv"], &["-q", "--progress"]] {
+        let error = parse(args).unwrap_err();
+        assert!(error.starts_with("--quiet reports a plain sweep's totals"), "{}", error);
+    }
+}
+
+#[tokio::test]
+async fn quiet_json_is_one_object_of_totals() {
+    let server = start().await;
+    let path = std::env::temp_dir().join(format!("quiet-{}.json", std::process::id()));
+
+    let address = server.address.clone();
+    let output = path.display().to_string();
+    let args = [
+        "--quiet", "--format", "json", "--output", &output, "--address", &address,
+        "--sizes", "1000,3000", "--iterations", "2", "--delay-ms", "0",
+    ];
+    let Ok(Command::Run(config)) = parse(&args) else {
+        panic!("expected a run");
+    };
+    run_benchmarks(&config).await.unwrap();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(text.lines().count(), 1, "{}", text);
+    let totals = json::parse(text.trim()).unwrap();
+    let number = |key| totals.get(key).and_then(|v| v.as_f64()).unwrap();
+    assert_eq!(number("sizes"), 2.0);
+    assert_eq!(number("bytes_sent"), 8000.0);
+    assert_eq!(number("bytes_received"), 4000.0);
+    assert_eq!(number("failed_sizes"), 0.0);
+
+    server.stop().await;
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::tool::ramp::ramp_json;
+use network_traffic_monitoring::{
+    measure_ramp, NetworkStats, RampLevel, RampOptions, RampReport, TransferError, TransferOptions,
+};
+use tokio::sync::watch;
+
+use common::{parse, start};
+
+fn report(throughputs: &[Option<f64>]) -> RampReport {
+    let levels = throughputs
+        .iter()
+        .enumerate()
+        .map(|(i, throughput)| RampLevel {
+            concurrency: 1 << i,
+            iterations: 5 << i,
+            result: throughput
+                .map(|rate| NetworkStats { download_rate: rate, ..NetworkStats::default() })
+                .ok_or_else(|| "all 5 iterations failed".to_string()),
+        })
+        .collect();
+    RampReport { size: 1000, levels, interrupted: false }
+}
+
+#[test]
+fn the_knee_is_the_last_level_that_scaled() {
+    let ramp = report(&[Some(100.0), Some(190.0), Some(300.0), Some(310.0), Some(290.0)]);
+    assert_eq!(ramp.knee().unwrap().concurrency, 4);
+    assert_eq!(ramp.peak().unwrap().concurrency, 8);
+
+    let scaling = report(&[Some(100.0), Some(200.0), Some(400.0)]);
+    assert!(scaling.knee().is_none());
+    assert_eq!(scaling.peak().unwrap().concurrency, 4);
+
+    // A failed level is stepped over
+    let overloaded = report(&[Some(100.0), Some(200.0), None, Some(205.0)]);
+    assert_eq!(overloaded.knee().unwrap().concurrency, 2);
+    assert!(report(&[None, None]).peak().is_none());
+
+    let text = ramp_json(&overloaded);
+    assert!(text.contains("\"knee_concurrency\":2,"), "{}", text);
+    assert!(text.contains("\"error\":\"all 5 iterations failed\",\"stats\":null"), "{}", text);
+}
+
+#[tokio::test]
+async fn each_level_runs_its_own_batch() {
+    let server = start().await;
+
+    let options = RampOptions {
+        levels: vec![1, 2, 4],
+        iterations: 2,
+        transfer: TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() },
+    };
+    let ramp = measure_ramp(&server.address, 10_000, &options).await.unwrap();
+    assert_eq!(ramp.levels.len(), 3);
+    for level in &ramp.levels {
+        let stats = level.result.as_ref().unwrap();
+        assert_eq!(level.iterations, 2 * level.concurrency);
+        assert_eq!(stats.successful_iterations, level.iterations);
+        assert!(level.throughput().unwrap() > 0.0);
+        // Only the concurrent levels compare their iterations
+        assert_eq!(stats.iteration_fairness.is_some(), level.concurrency > 1);
+    }
+    assert!(ramp.peak().is_some());
+
+    let (_cancel, cancelled) = watch::channel(true);
+    let options = RampOptions {
+        transfer: TransferOptions { cancel: Some(cancelled), ..options.transfer.clone() },
+        ..options
+    };
+    let ramp = measure_ramp(&server.address, 10_000, &options).await.unwrap();
+    assert!(ramp.interrupted);
+    assert!(ramp.levels.is_empty());
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn an_unreachable_target_fails_the_ramp() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    drop(listener);
+    let options = RampOptions { levels: vec![1, 2], iterations: 1, ..RampOptions::default() };
+    let err = measure_ramp(&address, 1000, &options).await.unwrap_err();
+    assert!(matches!(err, TransferError::AllFailed { .. }), "{:?}", err);
+}
+
+#[test]
+fn ramp_takes_a_list_or_a_top_level() {
+    let levels = |raw: &str| match parse(&["--ramp", raw]) {
+        Ok(Command::Run(config)) => config.ramp.unwrap(),
+        other => panic!("expected a run: {:?}", other.err()),
+    };
+    assert_eq!(levels("1, 3, 9"), [1, 3, 9]);
+    assert_eq!(levels("16"), [1, 2, 4, 8, 16]);
+    assert_eq!(levels("12"), [1, 2, 4, 8, 12]);
+
+    assert_eq!(parse(&["--ramp", "4,2"]).unwrap_err(), "--ramp levels have to rise, got '4,2'");
+    assert_eq!(
+        parse(&["--ramp", "1"]).unwrap_err(),
+        "--ramp needs at least two levels to compare, got '1'"
+    );
+    assert_eq!(
+        parse(&["--ramp", "0,4"]).unwrap_err(),
+        "--ramp expects concurrencies like 1,2,4,8 or a top level like 16, got '0,4'"
+    );
+    assert_eq!(
+        parse(&["--ramp", "8", "--concurrency", "4"]).unwrap_err(),
+        "--ramp measures raw TCP transfers at each of its concurrency levels, so it can't be \
+         combined with --concurrency"
+    );
+}
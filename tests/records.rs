@@ -0,0 +1,56 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::{measure_transfer, IterationRecord, TransferOptions};
+
+use common::start;
+
+#[tokio::test]
+async fn records_are_kept_only_when_asked_for() {
+    let server = start().await;
+    let address = server.address.clone();
+
+    let options = TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() };
+    let stats = measure_transfer(&address, 1000, 3, &options).await.unwrap();
+    assert!(stats.records.is_empty());
+
+    let keep = TransferOptions { keep_records: true, warmup: 1, ..options };
+    let stats = measure_transfer(&address, 1000, 4, &keep).await.unwrap();
+    let indexes: Vec<usize> = stats.records.iter().map(|r| r.index).collect();
+    assert_eq!(indexes, vec![0, 1, 2]); // the warmup iteration isn't one of them
+    for (record, latency) in stats.records.iter().zip(&stats.latencies) {
+        assert_eq!((record.bytes_sent, record.bytes_received), (1000, 500));
+        assert_eq!(record.round_trip, *latency);
+        assert!(record.connect_time > Duration::ZERO && record.connect_time <= record.round_trip);
+        assert_eq!(record.error, None);
+    }
+
+    // A reused connection is only paid for once
+    let reuse = TransferOptions { reuse_connection: true, warmup: 0, ..keep };
+    let stats = measure_transfer(&address, 10, 3, &reuse).await.unwrap();
+    let connects: Vec<bool> = stats.records.iter().map(|r| !r.connect_time.is_zero()).collect();
+    assert_eq!(connects, vec![true, false, false]);
+
+    server.stop().await;
+}
+
+#[test]
+fn csv_rows_quote_the_error() {
+    let record = IterationRecord {
+        index: 4,
+        bytes_sent: 0,
+        bytes_received: 0,
+        connect_time: Duration::from_millis(2),
+        round_trip: Duration::from_millis(5),
+        error: Some("read failed: \"early eof\", giving up".to_string()),
+    };
+    assert_eq!(
+        IterationRecord::CSV_HEADER,
+        "size,iteration,bytes_sent,bytes_received,connect_secs,round_trip_secs,error"
+    );
+    assert_eq!(
+        record.csv_row(1024),
+        "1024,4,0,0,0.002,0.005,\"read failed: \"\"early eof\"\", giving up\""
+    );
+}
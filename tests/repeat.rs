@@ -0,0 +1,106 @@
+mod common;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::tool::json;
+use network_traffic_monitoring::{run_repeat, NetworkStats, RepeatedSize};
+
+use common::{parse, start};
+
+fn rates(download_rate: f64, upload_rate: f64) -> Result<NetworkStats, String> {
+    Ok(NetworkStats { download_rate, upload_rate, ..NetworkStats::default() })
+}
+
+#[test]
+fn runs_are_summed_up_per_size() {
+    let mut entry = RepeatedSize::new(1000);
+    entry.runs = vec![
+        rates(200.0, 100.0),
+        Err("connection refused".to_string()),
+        rates(400.0, 300.0),
+        rates(300.0, 500.0),
+    ];
+    assert_eq!(entry.failed_runs(), 1);
+    assert_eq!(entry.mean_download_rate(), 300.0);
+    assert_eq!(entry.mean_upload_rate(), 300.0);
+    assert_eq!(entry.download_rate_std_dev(), Some(100.0));
+    assert_eq!(entry.upload_rate_std_dev(), Some(200.0));
+    // Ranked by download rate, counting the failed run in the numbering
+    assert_eq!(entry.best_run().map(|(run, _)| run), Some(2));
+    assert_eq!(entry.worst_run().map(|(run, _)| run), Some(0));
+
+    // Nothing downloaded, as with --direction upload: ranked by upload rate instead
+    let mut upload = RepeatedSize::new(1000);
+    upload.runs = vec![rates(0.0, 100.0), rates(0.0, 300.0)];
+    assert_eq!(upload.best_run().map(|(run, _)| run), Some(1));
+    assert_eq!(upload.worst_run().map(|(run, _)| run), Some(0));
+
+    let mut failed = RepeatedSize::new(1000);
+    failed.runs = vec![Err("timed out".to_string())];
+    assert!(failed.best_run().is_none());
+    assert_eq!(failed.mean_download_rate(), 0.0);
+    assert_eq!(failed.download_rate_std_dev(), None);
+}
+
+#[tokio::test]
+async fn repeat_json_has_every_run_of_every_size() {
+    let server = start().await;
+    let path = std::env::temp_dir().join(format!("repeat-{}.json", std::process::id()));
+
+    let address = server.address.clone();
+    let output = path.display().to_string();
+    let args = [
+        "--repeat", "3", "--format", "json", "--output", &output, "--address", &address,
+        "--sizes", "1000,4000", "--iterations", "2", "--delay-ms", "0",
+    ];
+    let Ok(Command::Run(config)) = parse(&args) else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.repeat, Some(3));
+    run_repeat(&config, 3, None, None).await.unwrap();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let sizes: Vec<_> = text.lines().map(|line| json::parse(line).unwrap()).collect();
+    assert_eq!(sizes.len(), 2, "{}", text);
+    for (entry, size) in sizes.iter().zip([1000.0, 4000.0]) {
+        let number = |key| entry.get(key).and_then(|v| v.as_f64());
+        assert_eq!(number("size"), Some(size));
+        assert_eq!(number("repeats"), Some(3.0));
+        assert_eq!(number("failed_runs"), Some(0.0));
+        assert!(number("mean_download_rate").unwrap() > 0.0);
+        assert!(number("download_rate_std_dev").is_some());
+        assert!((1.0..=3.0).contains(&number("best_run").unwrap()));
+        assert!((1.0..=3.0).contains(&number("worst_run").unwrap()));
+
+        let runs = entry.get("runs").and_then(|v| v.as_array()).unwrap();
+        assert_eq!(runs.len(), 3);
+        for run in runs {
+            let stats = NetworkStats::from_json(run).unwrap();
+            assert_eq!(stats.bytes_sent, 2 * size as usize);
+        }
+    }
+
+    server.stop().await;
+}
+
+#[test]
+fn repeat_needs_a_plain_sweep() {
+    assert_eq!(parse(&["--repeat", "0"]).unwrap_err(), "--repeat needs at least one run");
+    assert_eq!(
+        parse(&["--repeat", "3", "--watch"]).unwrap_err(),
+        "--watch and --repeat are separate run modes, so they can't be combined"
+    );
+    assert_eq!(
+        parse(&["--repeat", "3", "--sample-ms", "10"]).unwrap_err(),
+        "--repeat runs the whole sweep N times and reports each size across the runs, so it \
+         can't be combined with --sample-ms"
+    );
+    for args in [
+        &["--repeat", "2", "--parallel", "4"][..],
+        &["--repeat", "2", "--quiet"],
+        &["--repeat", "2", "--format", "csv"],
+        &["--repeat", "2", "--min-download-rate", "1000"],
+    ] {
+        assert!(parse(args).is_err(), "{:?}", args);
+    }
+}
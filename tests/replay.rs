@@ -0,0 +1,106 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use network_traffic_monitoring::tool::cli::{parse_args, Command};
+use network_traffic_monitoring::{
+    parse_schedule, replay, replay_with_options, run_mock_server, MockServerConfig, TransferError,
+    TransferOptions,
+};
+use tokio::sync::watch;
+
+use common::start;
+
+#[test]
+fn schedule_skips_header_comments_and_blank_lines() {
+    let text = "delay_ms,size_bytes\n0,1024\n\n# a burst\n2.5, 10 # inline\n100,0\n";
+    assert_eq!(
+        parse_schedule(text).unwrap(),
+        vec![
+            (Duration::ZERO, 1024),
+            (Duration::from_micros(2500), 10),
+            (Duration::from_millis(100), 0),
+        ]
+    );
+}
+
+#[test]
+fn schedule_errors_name_the_line() {
+    assert_eq!(parse_schedule("0,1\n5\n").unwrap_err(), "line 2: expected delay_ms,size_bytes");
+    assert_eq!(parse_schedule("0,1\n-5,1\n").unwrap_err(), "line 2: invalid delay '-5'");
+    assert_eq!(parse_schedule("0,big\n").unwrap_err(), "line 1: invalid size 'big'");
+    assert!(parse_schedule("delay_ms,size_bytes\n").is_err());
+}
+
+#[tokio::test]
+async fn replay_sends_each_size_after_its_gap() {
+    let server = start().await;
+
+    let schedule = [
+        (Duration::ZERO, 1000),
+        (Duration::from_millis(150), 4000),
+        (Duration::from_millis(50), 10),
+    ];
+    let start = Instant::now();
+    let stats = replay(&server.address, &schedule).await.unwrap();
+
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert_eq!(stats.successful_iterations, 3);
+    assert_eq!(stats.bytes_sent, 5010);
+    assert_eq!(stats.bytes_received, 500 + 2000 + 5);
+    assert_eq!(stats.response_sizes, vec![500, 2000, 5]);
+    // The gaps are excluded from the rates
+    assert!(stats.elapsed_time < Duration::from_millis(150), "{:?}", stats.elapsed_time);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn replay_verifies_against_an_echo_server() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config =
+        MockServerConfig { echo: true, response_ratio: 1.0, ..MockServerConfig::default() };
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+
+    let options = TransferOptions { verify: true, ..TransferOptions::default() };
+    let schedule = [(Duration::ZERO, 3000), (Duration::ZERO, 100)];
+    let stats = replay_with_options(&bound.to_string(), &schedule, &options).await.unwrap();
+    assert_eq!(stats.corrupted_iterations, 0);
+    assert_eq!(stats.bytes_received, 3100);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn an_oversized_row_is_rejected_before_anything_is_allocated() {
+    let schedule = parse_schedule("1,99999999999\n").unwrap();
+    let err = replay("127.0.0.1:9", &schedule).await.unwrap_err();
+    assert!(matches!(err, TransferError::InvalidOptions(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn a_big_row_is_generated_as_it_is_sent() {
+    let server = start().await;
+
+    // Over GENERATED_PAYLOAD_MIN, so it's never held in memory
+    let schedule = [(Duration::ZERO, 20 * 1024 * 1024), (Duration::ZERO, 1000)];
+    let stats = replay(&server.address, &schedule).await.unwrap();
+    assert_eq!(stats.successful_iterations, 2);
+    assert_eq!(stats.bytes_sent, 20 * 1024 * 1024 + 1000);
+
+    server.stop().await;
+}
+
+#[test]
+fn replay_rejects_sweep_only_flags() {
+    let args = |extra: &[&str]| {
+        let mut args = vec!["--replay".to_string(), "schedule.csv".to_string()];
+        args.extend(extra.iter().map(|arg| arg.to_string()));
+        parse_args(args)
+    };
+    assert!(matches!(args(&[]), Ok(Command::Run(_))));
+    let error = args(&["--concurrency", "4"]).unwrap_err();
+    assert!(error.ends_with("can't be combined with --concurrency"), "{}", error);
+    assert!(args(&["--format", "csv"]).is_err());
+}
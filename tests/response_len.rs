@@ -0,0 +1,61 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::{parse_args, Command};
+use network_traffic_monitoring::{measure_transfer, TransferError, TransferOptions};
+
+use common::start;
+
+fn options(expected: usize) -> TransferOptions {
+    TransferOptions {
+        delay: Duration::ZERO,
+        timeout: Duration::from_secs(2),
+        expected_response_len: Some(expected),
+        ..TransferOptions::default()
+    }
+}
+
+// The mock server keeps each connection open after replying with half the bytes read
+#[tokio::test]
+async fn expected_length_matches_the_half_size_reply() {
+    let server = start().await;
+    let address = server.address.clone();
+
+    let stats = measure_transfer(&address, 1024, 3, &options(512)).await.unwrap();
+    assert_eq!(stats.successful_iterations, 3);
+    assert_eq!(stats.bytes_received, 3 * 512);
+
+    let reused = TransferOptions { reuse_connection: true, ..options(512) };
+    let stats = measure_transfer(&address, 1024, 3, &reused).await.unwrap();
+    assert_eq!(stats.bytes_received, 3 * 512);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn another_announced_length_fails_the_iteration() {
+    let server = start().await;
+
+    let fail_fast = TransferOptions { fail_fast: true, ..options(1000) };
+    let err = measure_transfer(&server.address, 1024, 1, &fail_fast).await.unwrap_err();
+    assert!(matches!(err, TransferError::Read(_)), "{:?}", err);
+    assert_eq!(err.to_string(), "read failed: response announces 512 bytes, expected 1000");
+
+    server.stop().await;
+}
+
+#[test]
+fn expect_response_needs_a_single_size() {
+    let args = |extra: &[&str]| {
+        let mut args = vec!["--expect-response".to_string(), "512".to_string()];
+        args.extend(extra.iter().map(|arg| arg.to_string()));
+        parse_args(args)
+    };
+    match args(&["--sizes", "1024"]) {
+        Ok(Command::Run(config)) => assert_eq!(config.expected_response_len, Some(512)),
+        other => panic!("{:?}", other.err()),
+    }
+    assert!(args(&[]).unwrap_err().contains("single --sizes value"));
+    assert!(args(&["--sizes", "1024", "--url", "http://localhost/"]).is_err());
+}
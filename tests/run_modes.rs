@@ -0,0 +1,51 @@
+mod common;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::RunMode;
+
+use common::parse;
+
+// Each mode flag with a value it accepts, in run_main's order
+const MODES: [&[&str]; 11] = [
+    &["--hosts", "127.0.0.1:9000,127.0.0.1:9001"],
+    &["--watch"],
+    &["--replay", "schedule.csv"],
+    &["--under-load", "1000000"],
+    &["--keepalive", "1s"],
+    &["--size-mix", "1024:1"],
+    &["--repeat", "2"],
+    &["--parallel", "2"],
+    &["--soak", "1m"],
+    &["--ramp", "4"],
+    &["--dedup-check"],
+];
+
+#[test]
+fn every_mode_runs_alone() {
+    let Ok(Command::Run(config)) = parse(&[]) else {
+        panic!("expected a run");
+    };
+    assert!(matches!(config.mode(), RunMode::Sweep));
+    for args in MODES {
+        let Ok(Command::Run(config)) = parse(args) else {
+            panic!("expected {:?} to run", args);
+        };
+        assert_eq!(config.mode().flag(), args[0]);
+    }
+}
+
+#[test]
+fn two_modes_are_rejected_either_way_round() {
+    for (i, first) in MODES.iter().enumerate() {
+        for second in &MODES[i + 1..] {
+            let expected = format!(
+                "{} and {} are separate run modes, so they can't be combined",
+                first[0], second[0]
+            );
+            let forwards = [*first, *second].concat();
+            let backwards = [*second, *first].concat();
+            assert_eq!(parse(&forwards).unwrap_err(), expected);
+            assert_eq!(parse(&backwards).unwrap_err(), expected);
+        }
+    }
+}
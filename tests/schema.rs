@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::compare::load_runs;
+use network_traffic_monitoring::tool::json;
+use network_traffic_monitoring::{stats_schema_version, NetworkStats, STATS_SCHEMA_VERSION};
+
+// The six fields every version has written
+const RATES: &str = concat!(
+    r#""bytes_sent":5000,"bytes_received":5000,"elapsed_secs":0.5,"#,
+    r#""upload_rate":10000,"download_rate":10000,"ratio":1"#
+);
+
+fn saved(name: &str, lines: &[&str]) -> String {
+    let path = std::env::temp_dir().join(format!("schema-{}-{}.json", name, std::process::id()));
+    std::fs::write(&path, lines.join("\n")).unwrap();
+    path.display().to_string()
+}
+
+#[test]
+fn results_carry_the_current_version() {
+    let text = NetworkStats::default().to_json();
+    let version = format!("{{\"schema_version\":{},", STATS_SCHEMA_VERSION);
+    assert!(text.starts_with(&version), "{}", text);
+    assert_eq!(stats_schema_version(&json::parse(&text).unwrap()), Ok(STATS_SCHEMA_VERSION));
+}
+
+#[test]
+fn unversioned_files_load_with_defaults() {
+    // As the first JSON output wrote it
+    let path = saved("v1", &[&format!("{{\"size\":1000,{}}}", RATES)]);
+    let runs = load_runs(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let (size, stats) = &runs[0];
+    assert_eq!(*size, Some(1000));
+    assert_eq!(stats.elapsed_time, Duration::from_millis(500));
+    assert_eq!((stats.successful_iterations, stats.requests_per_connection), (0, 1));
+    assert_eq!((stats.local_address, stats.timestamp), (None, None));
+    assert_eq!(stats_schema_version(&json::parse("{}").unwrap()), Ok(1));
+}
+
+#[test]
+fn version_1_files_are_upgraded_field_by_field() {
+    let v1 = format!("{{{},\"successful_iterations\":4,\"retries\":2}}", RATES);
+    let stats = NetworkStats::from_json(&json::parse(&v1).unwrap()).unwrap();
+    let expected = NetworkStats {
+        bytes_sent: 5000,
+        bytes_received: 5000,
+        elapsed_time: Duration::from_millis(500),
+        upload_rate: 10_000.0,
+        download_rate: 10_000.0,
+        ratio: 1.0,
+        successful_iterations: 4,
+        retries: 2,
+        requests_per_connection: 1,
+        ..NetworkStats::default()
+    };
+    assert_eq!(stats.to_json(), expected.to_json());
+
+    // The upgrade only fills in what version 2 added, not what every version wrote
+    let missing = json::parse(r#"{"bytes_sent":5000}"#).unwrap();
+    let err = NetworkStats::from_json(&missing).unwrap_err();
+    assert_eq!(err, "missing numeric field 'bytes_received'");
+}
+
+#[test]
+fn newer_files_are_refused() {
+    let newer = format!("{{\"schema_version\":{},{}}}", STATS_SCHEMA_VERSION + 1, RATES);
+    let current = NetworkStats::default().to_json();
+    let path = saved("newer", &[&current, &newer]);
+    let err = load_runs(&path).unwrap_err().to_string();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        err,
+        format!(
+            "{}:2: schema_version {} is newer than this build reads (up to {}); load it with a \
+             newer traffic-monitor",
+            path,
+            STATS_SCHEMA_VERSION + 1,
+            STATS_SCHEMA_VERSION
+        )
+    );
+
+    for bad in ["\"2\"", "1.5", "0"] {
+        let value = json::parse(&format!("{{\"schema_version\":{}}}", bad)).unwrap();
+        let err = NetworkStats::from_json(&value).unwrap_err();
+        assert_eq!(err, "schema_version must be a whole number of at least 1", "{}", bad);
+    }
+}
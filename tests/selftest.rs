@@ -0,0 +1,40 @@
+mod common;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::{self_test, SelfTestCheck, SelfTestReport};
+
+use common::parse;
+
+#[tokio::test]
+async fn the_mock_server_passes_every_check() {
+    let report = self_test().await.unwrap();
+    assert!(report.address.ip().is_loopback());
+    assert_eq!(report.checks.len(), 6);
+    assert!(report.passed(), "{:?}", report.checks);
+    assert_eq!(report.failures(), 0);
+    let received = report.checks.iter().find(|check| check.detail.starts_with("received"));
+    assert_eq!(received.unwrap().detail, "received 15000 bytes, half of those sent");
+}
+
+#[test]
+fn one_failed_check_fails_the_report() {
+    let report = SelfTestReport {
+        address: "127.0.0.1:1".parse().unwrap(),
+        checks: vec![
+            SelfTestCheck { passed: true, detail: "sent 30000 bytes".to_string() },
+            SelfTestCheck { passed: false, detail: "received 0 bytes".to_string() },
+        ],
+    };
+    assert!(!report.passed());
+    assert_eq!(report.failures(), 1);
+}
+
+#[test]
+fn selftest_takes_no_arguments() {
+    assert!(matches!(parse(&["selftest"]), Ok(Command::SelfTest)));
+    assert!(matches!(parse(&["selftest", "--help"]), Ok(Command::Help)));
+    let Err(message) = parse(&["selftest", "--sizes", "10"]) else {
+        panic!("expected an error");
+    };
+    assert!(message.contains("no arguments"), "{}", message);
+}
@@ -0,0 +1,98 @@
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::framing::{self, SequenceCheck};
+use network_traffic_monitoring::{measure_transfer, TransferError, TransferOptions};
+use tokio::net::TcpListener;
+
+fn sequenced_frame(sequence: u64, body_len: usize) -> Vec<u8> {
+    let mut frame = framing::encode_frame(&vec![0u8; body_len]).unwrap();
+    framing::stamp_sequence(&mut frame, sequence).unwrap();
+    frame
+}
+
+fn stream_of(sequences: &[u64]) -> Vec<u8> {
+    sequences.iter().flat_map(|&sequence| sequenced_frame(sequence, 20)).collect()
+}
+
+#[test]
+fn in_order_frames_split_anywhere_count_clean() {
+    let stream = stream_of(&[0, 1, 2, 3]);
+    let mut check = SequenceCheck::new();
+    for byte in &stream {
+        check.feed(std::slice::from_ref(byte));
+    }
+    assert_eq!(check.frames, 4);
+    assert_eq!((check.reordered, check.duplicated, check.missing()), (0, 0, 0));
+}
+
+#[test]
+fn counts_reordered_duplicated_and_missing_numbers() {
+    let mut check = SequenceCheck::new();
+    check.feed(&stream_of(&[0, 2, 1, 2, 5]));
+    check.feed(&framing::encode_frame(&[1, 2, 3]).unwrap()); // too short for a number
+    assert_eq!(check.frames, 6);
+    assert_eq!(check.reordered, 1);
+    assert_eq!(check.duplicated, 1);
+    assert_eq!(check.missing(), 2); // 3 and 4
+    assert_eq!(check.unsequenced, 1);
+}
+
+#[test]
+fn sized_requests_carry_the_number_after_the_requested_length() {
+    let mut frame = framing::encode_sized_request(&[0u8; 8], 100).unwrap();
+    framing::stamp_sequence(&mut frame, 7).unwrap();
+    assert_eq!(&frame[8..], &7u64.to_be_bytes());
+
+    let mut check = SequenceCheck::new();
+    check.feed(&frame);
+    check.feed(&stream_of(&[6]));
+    assert_eq!((check.frames, check.reordered), (2, 1));
+}
+
+// Answers every request with an empty frame, keeping the number each one carried
+async fn recording_server() -> (String, Arc<Mutex<Vec<u64>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let numbers = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&numbers);
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let recorded = Arc::clone(&recorded);
+            tokio::spawn(async move {
+                while let Ok(Some((_, len))) = framing::read_request_header(&mut socket).await {
+                    let mut body = Vec::new();
+                    let mut buffer = [0u8; 1024];
+                    framing::read_payload_chunks(&mut socket, len, &mut buffer, |chunk, _| {
+                        body.extend_from_slice(chunk)
+                    })
+                    .await
+                    .unwrap();
+                    let number = u64::from_be_bytes(body[..8].try_into().unwrap());
+                    recorded.lock().unwrap().push(number);
+                    framing::write_frame(&mut socket, &[]).await.unwrap();
+                }
+            });
+        }
+    });
+    (address, numbers)
+}
+
+#[tokio::test]
+async fn requests_are_numbered_in_order() {
+    let (address, numbers) = recording_server().await;
+    let options = TransferOptions {
+        delay: Duration::ZERO,
+        reuse_connection: true,
+        requests_per_connection: 2,
+        sequence: Some(Arc::new(AtomicU64::new(0))),
+        ..TransferOptions::default()
+    };
+    let stats = measure_transfer(&address, 64, 3, &options).await.unwrap();
+    assert_eq!(stats.bytes_sent, 6 * 64); // the numbers are part of the payload
+    assert_eq!(*numbers.lock().unwrap(), [0, 1, 2, 3, 4, 5]);
+
+    let err = measure_transfer(&address, 4, 1, &options).await.unwrap_err();
+    assert!(matches!(err, TransferError::InvalidOptions(_)), "{:?}", err);
+}
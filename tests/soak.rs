@@ -0,0 +1,126 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::tool::confidence::linear_trend;
+use network_traffic_monitoring::{
+    measure_soak, SoakOptions, SoakReport, TransferError, TransferOptions,
+};
+use tokio::sync::watch;
+
+use common::{parse, start};
+
+fn report(rates: &[f64]) -> SoakReport {
+    let window = Duration::from_secs(60);
+    SoakReport {
+        window,
+        windows: rates.iter().enumerate().map(|(i, &rate)| (window * i as u32, rate)).collect(),
+        ..SoakReport::default()
+    }
+}
+
+#[test]
+fn a_straight_line_is_fitted_exactly() {
+    let trend = linear_trend(&[(0.0, 10.0), (1.0, 8.0), (2.0, 6.0), (3.0, 4.0)]).unwrap();
+    assert_eq!((trend.slope, trend.intercept), (-2.0, 10.0));
+    assert_eq!(trend.slope_margin_95, Some(0.0));
+    assert_eq!(trend.at(5.0), 0.0);
+    // Two points fit any line, so there's no interval
+    assert_eq!(linear_trend(&[(0.0, 1.0), (1.0, 3.0)]).unwrap().slope_margin_95, None);
+    assert_eq!(linear_trend(&[(1.0, 1.0), (1.0, 3.0)]), None);
+}
+
+#[test]
+fn a_steady_decline_is_flagged() {
+    let falling = report(&[100.0, 95.0, 91.0, 84.0, 80.0]);
+    assert!(falling.declining());
+    let drop = falling.drop_percent().unwrap();
+    assert!((19.0..21.0).contains(&drop), "{}", drop);
+    assert!(falling.degraded(10.0));
+    assert!(!falling.degraded(25.0));
+
+    // Noise around a flat line isn't significant, however the ends compare
+    let noisy = report(&[100.0, 80.0, 110.0, 90.0, 105.0, 85.0]);
+    assert!(!noisy.declining());
+    assert!(!noisy.degraded(1.0));
+
+    let rising = report(&[80.0, 85.0, 90.0, 95.0]);
+    assert!(!rising.declining());
+    assert!(rising.drop_percent().unwrap() < 0.0);
+
+    assert!(report(&[100.0]).trend().is_none());
+}
+
+#[tokio::test]
+async fn soaks_for_the_whole_duration_in_full_windows() {
+    let server = start().await;
+
+    let options = SoakOptions {
+        duration: Duration::from_millis(900),
+        window: Duration::from_millis(250),
+        iterations: 3,
+        transfer: TransferOptions { delay: Duration::from_millis(5), ..TransferOptions::default() },
+        ..SoakOptions::default()
+    };
+    let report = measure_soak(&server.address, 10_000, &options).await.unwrap();
+    assert!(report.elapsed >= options.duration);
+    // The fourth window is cut short, so only three count
+    assert_eq!(report.windows.len(), 3, "{:?}", report.windows);
+    assert!(report.windows.iter().all(|&(_, rate)| rate > 0.0), "{:?}", report.windows);
+    assert_eq!(report.windows[2].0, Duration::from_millis(500));
+    assert!(report.iterations > 3);
+    assert_eq!(report.failed_iterations, 0);
+    assert!(report.trend().unwrap().slope_margin_95.is_some());
+
+    // Cancelled before it starts, it stops straight away
+    let (_cancel, cancelled) = watch::channel(true);
+    let options = SoakOptions {
+        transfer: TransferOptions { cancel: Some(cancelled), ..options.transfer.clone() },
+        ..options
+    };
+    let report = measure_soak(&server.address, 10_000, &options).await.unwrap();
+    assert!(report.interrupted);
+    assert_eq!(report.iterations, 0);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn an_unreachable_target_fails_the_soak() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    drop(listener);
+    let options = SoakOptions {
+        duration: Duration::from_millis(300),
+        window: Duration::from_millis(100),
+        iterations: 2,
+        ..SoakOptions::default()
+    };
+    let err = measure_soak(&address, 1000, &options).await.unwrap_err();
+    assert!(matches!(err, TransferError::AllFailed { .. }), "{:?}", err);
+}
+
+#[test]
+fn soak_needs_room_for_three_windows() {
+    let Ok(Command::Run(config)) = parse(&["--soak", "10m", "--soak-window", "30s"]) else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.soak, Some(Duration::from_secs(600)));
+    assert_eq!(config.soak_window, Some(Duration::from_secs(30)));
+
+    assert_eq!(
+        parse(&["--soak", "1m", "--soak-window", "30s"]).unwrap_err(),
+        "--soak needs to last at least three --soak-window widths"
+    );
+    assert_eq!(
+        parse(&["--soak-max-drop", "5"]).unwrap_err(),
+        "--soak-window and --soak-max-drop need --soak"
+    );
+    assert_eq!(
+        parse(&["--soak", "1m", "--url", "http://localhost/"]).unwrap_err(),
+        "--soak repeats raw TCP transfers for a fixed time and reports their trend, so it \
+         can't be combined with --url"
+    );
+    assert!(parse(&["--soak", "1m", "--format", "csv"]).is_err());
+}
@@ -0,0 +1,232 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::{
+    measure_transfer, run_mock_server, MockServerConfig, SocksAuth, SocksConfig, TransferError,
+    TransferOptions,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+
+use common::{parse, start};
+
+// A SOCKS5 server that only speaks CONNECT: it demands `login` when set, answers every
+// request with `reply` and, on success, relays to the target. The targets asked for
+// are recorded as "host:port", with "domain " in front of names.
+async fn socks_proxy(login: Option<(&'static str, &'static str)>, reply: u8) -> Proxy {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let targets = Arc::new(Mutex::new(Vec::new()));
+    let seen = targets.clone();
+    tokio::spawn(async move {
+        while let Ok((client, _)) = listener.accept().await {
+            tokio::spawn(serve(client, login, reply, seen.clone()));
+        }
+    });
+    Proxy { address, targets }
+}
+
+struct Proxy {
+    address: String,
+    targets: Arc<Mutex<Vec<String>>>,
+}
+
+async fn serve(
+    mut client: TcpStream,
+    login: Option<(&str, &str)>,
+    reply: u8,
+    targets: Arc<Mutex<Vec<String>>>,
+) -> std::io::Result<()> {
+    let mut header = [0u8; 2];
+    client.read_exact(&mut header).await?;
+    let mut methods = vec![0u8; header[1] as usize];
+    client.read_exact(&mut methods).await?;
+    match login {
+        Some(login) if methods.contains(&2) => {
+            client.write_all(&[5, 2]).await?;
+            let version = client.read_u8().await?;
+            assert_eq!(version, 1);
+            let username = read_field(&mut client).await?;
+            let password = read_field(&mut client).await?;
+            let ok = (username.as_str(), password.as_str()) == login;
+            client.write_all(&[1, if ok { 0 } else { 1 }]).await?;
+            if !ok {
+                return Ok(());
+            }
+        }
+        Some(_) => return client.write_all(&[5, 0xff]).await,
+        None => client.write_all(&[5, 0]).await?,
+    }
+
+    let mut request = [0u8; 4];
+    client.read_exact(&mut request).await?;
+    assert_eq!(request[..3], [5, 1, 0]);
+    let host = match request[3] {
+        1 => {
+            let mut ip = [0u8; 4];
+            client.read_exact(&mut ip).await?;
+            std::net::Ipv4Addr::from(ip).to_string()
+        }
+        3 => format!("domain {}", read_field(&mut client).await?),
+        other => panic!("unexpected address type {}", other),
+    };
+    let port = client.read_u16().await?;
+    targets.lock().unwrap().push(format!("{}:{}", host, port));
+
+    let bound = [0, 1, 127, 0, 0, 1, 0, 0];
+    if reply != 0 {
+        client.write_all(&[5, reply]).await?;
+        return client.write_all(&bound).await;
+    }
+    let target = format!("{}:{}", host.trim_start_matches("domain "), port);
+    let mut upstream = TcpStream::connect(target).await?;
+    client.write_all(&[5, 0]).await?;
+    client.write_all(&bound).await?;
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+async fn read_field(stream: &mut TcpStream) -> std::io::Result<String> {
+    let len = stream.read_u8().await?;
+    let mut field = vec![0u8; len as usize];
+    stream.read_exact(&mut field).await?;
+    Ok(String::from_utf8(field).unwrap())
+}
+
+fn through(proxy: &str, auth: Option<SocksAuth>) -> TransferOptions {
+    TransferOptions {
+        delay: Duration::ZERO,
+        fail_fast: true,
+        proxy: Some(SocksConfig { proxy: proxy.to_string(), auth }),
+        ..TransferOptions::default()
+    }
+}
+
+fn login(username: &str, password: &str) -> Option<SocksAuth> {
+    Some(SocksAuth { username: username.to_string(), password: password.to_string() })
+}
+
+#[tokio::test]
+async fn bytes_are_counted_over_the_proxied_connection() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig::default();
+    let server_stats = config.stats.clone();
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+    let proxy = socks_proxy(None, 0).await;
+
+    let options = through(&proxy.address, None);
+    let stats = measure_transfer(&bound.to_string(), 10_000, 3, &options).await.unwrap();
+    assert_eq!(stats.successful_iterations, 3);
+    assert_eq!(stats.bytes_sent, 30_000);
+    assert_eq!(stats.bytes_received, 15_000);
+    assert_eq!(server_stats.connections(), 3);
+    assert_eq!(*proxy.targets.lock().unwrap(), vec![bound.to_string(); 3]);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn host_names_are_left_to_the_proxy() {
+    let server = start().await;
+    let proxy = socks_proxy(None, 0).await;
+
+    let target = format!("localhost:{}", server.port);
+    let stats = measure_transfer(&target, 1000, 1, &through(&proxy.address, None)).await.unwrap();
+    assert_eq!(stats.successful_iterations, 1);
+    assert_eq!(proxy.targets.lock().unwrap()[0], format!("domain {}", target));
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn credentials_are_checked_by_the_proxy() {
+    let server = start().await;
+    let target = server.address.clone();
+    let proxy = socks_proxy(Some(("alice", "secret")), 0).await;
+
+    let options = through(&proxy.address, login("alice", "secret"));
+    let stats = measure_transfer(&target, 1000, 2, &options).await.unwrap();
+    assert_eq!(stats.successful_iterations, 2);
+
+    let options = through(&proxy.address, login("alice", "wrong"));
+    let err = measure_transfer(&target, 1000, 2, &options).await.unwrap_err();
+    assert!(matches!(err, TransferError::ProxyAuth(_)), "{:?}", err);
+    assert_eq!(err.category(), "proxy auth");
+    assert!(!err.to_string().contains("wrong"), "{}", err);
+
+    let err = measure_transfer(&target, 1000, 2, &through(&proxy.address, None)).await.unwrap_err();
+    assert!(err.to_string().contains("requires authentication"), "{}", err);
+    // Only the successful login got as far as a CONNECT
+    assert_eq!(proxy.targets.lock().unwrap().len(), 2);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn a_target_the_proxy_cant_reach_is_refused_not_a_connect_failure() {
+    let proxy = socks_proxy(None, 5).await;
+    let options = through(&proxy.address, None);
+
+    let err = measure_transfer("127.0.0.1:9", 1000, 1, &options).await.unwrap_err();
+    assert!(matches!(err, TransferError::ProxyRefused { code: 5 }), "{:?}", err);
+    assert_eq!(err.category(), "proxy refused");
+    assert_eq!(
+        err.to_string(),
+        "proxy couldn't reach the target: connection refused (SOCKS5 reply 5)"
+    );
+}
+
+#[tokio::test]
+async fn an_unreachable_proxy_is_a_proxy_connect_failure() {
+    // Bound and dropped, so nothing listens there
+    let closed = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+    let options = through(&closed.to_string(), None);
+
+    let err = measure_transfer("127.0.0.1:9", 1000, 1, &options).await.unwrap_err();
+    assert!(matches!(err, TransferError::ProxyConnect(_)), "{:?}", err);
+    assert_eq!(err.category(), "proxy connect");
+    assert!(err.to_string().starts_with("proxy connect failed: "), "{}", err);
+}
+
+#[test]
+fn the_password_stays_out_of_debug_output() {
+    let auth = login("alice", "secret").unwrap();
+    let shown = format!("{:?}", auth);
+    assert!(shown.contains("alice") && !shown.contains("secret"), "{}", shown);
+}
+
+#[test]
+fn socks5_flags_make_one_proxy_config() {
+    let Ok(Command::Run(config)) = parse(&[
+        "--socks5",
+        "proxy.example:1080",
+        "--socks5-user",
+        "alice",
+        "--socks5-password",
+        "secret",
+    ]) else {
+        panic!("expected a run");
+    };
+    let socks5 = config.socks5.unwrap();
+    assert_eq!(socks5.proxy, "proxy.example:1080");
+    assert_eq!(socks5.auth, login("alice", "secret"));
+
+    let errors = [
+        (&["--socks5", "proxy.example"][..], "expects HOST:PORT"),
+        (&["--socks5-user", "alice", "--socks5-password", "x"][..], "need --socks5"),
+        (&["--socks5", "p:1080", "--socks5-user", "alice"][..], "go together"),
+        (&["--socks5", "p:1080", "--url", "http://example.com/"][..], "not --url"),
+        (&["--socks5", "p:1080", "--socks5-user", ""][..], "1 to 255 bytes"),
+    ];
+    for (args, expected) in errors {
+        let Err(message) = parse(args) else {
+            panic!("expected {:?} to fail", args);
+        };
+        assert!(message.contains(expected), "{:?}: {}", args, message);
+    }
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::json;
+use network_traffic_monitoring::{measure_transfer, JsonObject, NetworkStats, TransferOptions};
+
+use common::{round_trip, start};
+
+fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+}
+
+fn gaps(gaps: &[u64]) -> NetworkStats {
+    let start_gaps = gaps.iter().map(|&gap| ms(gap)).collect();
+    NetworkStats { start_gaps, ..NetworkStats::default() }
+}
+
+#[tokio::test]
+async fn sequential_starts_are_at_least_the_delay_apart() {
+    let server = start().await;
+    let address = server.address.clone();
+
+    let options = TransferOptions { delay: ms(20), ..TransferOptions::default() };
+    let stats = measure_transfer(&address, 1000, 5, &options).await.unwrap();
+    assert_eq!(stats.start_gaps.len(), 4);
+    assert!(stats.start_gaps.iter().all(|&gap| gap >= ms(20)), "{:?}", stats.start_gaps);
+    assert!(stats.mean_start_gap() >= ms(20));
+
+    // Concurrent iterations skip the delay, but their starts are still recorded
+    let options = TransferOptions { delay: ms(20), concurrency: 2, ..TransferOptions::default() };
+    let stats = measure_transfer(&address, 1000, 5, &options).await.unwrap();
+    assert_eq!(stats.start_gaps.len(), 4);
+
+    server.stop().await;
+}
+
+#[test]
+fn mean_and_p95_cover_every_gap() {
+    let stats = gaps(&[100, 100, 100, 100, 120]);
+    assert_eq!(stats.mean_start_gap(), ms(104));
+    assert_eq!(stats.p95_start_gap(), ms(116));
+    assert_eq!(gaps(&[]).mean_start_gap(), Duration::ZERO);
+    assert_eq!(gaps(&[]).p95_start_gap(), Duration::ZERO);
+}
+
+#[test]
+fn the_histogram_spans_the_shortest_to_the_longest_gap() {
+    let histogram = gaps(&[100, 101, 101, 110]).start_gap_histogram();
+    assert_eq!(histogram.len(), 10);
+    assert_eq!(histogram[0], (ms(100), 1));
+    assert_eq!(histogram[1], (ms(101), 2));
+    // The longest gap lands in the last bucket
+    assert_eq!(histogram[9], (ms(109), 1));
+    assert_eq!(histogram.iter().map(|&(_, count)| count).sum::<usize>(), 4);
+
+    assert_eq!(gaps(&[50, 50]).start_gap_histogram(), vec![(ms(50), 2)]);
+    assert!(gaps(&[]).start_gap_histogram().is_empty());
+}
+
+#[test]
+fn the_histogram_renders_one_line_per_bucket() {
+    let rendered = gaps(&[100, 101, 101, 110]).render_start_gap_histogram();
+    assert_eq!(rendered.lines().count(), 10);
+    assert!(rendered.starts_with("    100.00ms .. 101.00ms  ###############"), "{}", rendered);
+    let single = gaps(&[50, 50]).render_start_gap_histogram();
+    assert_eq!(single.trim_start(), format!("50.00ms  {}  2\n", "#".repeat(30)));
+}
+
+#[test]
+fn gap_figures_are_in_the_json() {
+    let mut obj = JsonObject::new();
+    gaps(&[100, 120]).write_json_fields(&mut obj);
+    let parsed = json::parse(&obj.finish()).unwrap();
+
+    assert_eq!(parsed.get("mean_start_gap_secs").and_then(|v| v.as_f64()), Some(0.11));
+    let histogram = parsed.get("start_gap_histogram").and_then(|v| v.as_array()).unwrap();
+    assert_eq!(histogram.len(), 10);
+    // The gaps themselves aren't written, so they don't come back
+    assert!(round_trip(&gaps(&[100, 120])).start_gaps.is_empty());
+}
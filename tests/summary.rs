@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::json;
+use network_traffic_monitoring::{render_summary_table, NetworkStats, RateUnits, RunSummary};
+
+fn stats(sent: usize, received: usize, secs: f64) -> NetworkStats {
+    let text = format!(
+        concat!(
+            r#"{{"bytes_sent":{},"bytes_received":{},"elapsed_secs":{},"#,
+            r#""upload_rate":{},"download_rate":{},"ratio":0.5}}"#
+        ),
+        sent,
+        received,
+        secs,
+        sent as f64 / secs,
+        received as f64 / secs
+    );
+    NetworkStats::from_json(&json::parse(&text).unwrap()).unwrap()
+}
+
+#[test]
+fn summary_rates_are_total_bytes_over_total_time() {
+    // A small fast size and a big slow one: 11000 bytes in 2s overall
+    let results = [stats(1000, 500, 0.1), stats(10_000, 5000, 1.9)];
+    let summary = RunSummary::from_stats(&results).unwrap();
+    assert_eq!(summary.sizes, 2);
+    assert_eq!(summary.bytes_sent, 11_000);
+    assert_eq!(summary.bytes_received, 5500);
+    assert_eq!(summary.elapsed_time, Duration::from_secs(2));
+    assert!((summary.upload_rate - 5500.0).abs() < 1e-6);
+    assert!((summary.download_rate - 2750.0).abs() < 1e-6);
+
+    assert!(RunSummary::from_stats(&[]).is_none());
+}
+
+#[test]
+fn table_has_a_row_per_size_and_a_total() {
+    let results = vec![(1000, stats(1000, 500, 0.1)), (10_000, stats(10_000, 5000, 1.9))];
+    let table = render_summary_table(&results, RateUnits::Bytes, false);
+    let lines: Vec<&str> = table.lines().collect();
+    // Top rule, header, rule, two sizes, rule, total, bottom rule
+    assert_eq!(lines.len(), 8);
+    assert!(lines.iter().all(|line| line.chars().count() == lines[0].chars().count()));
+    assert!(lines[1].contains("Size") && lines[1].contains("Download"));
+    assert!(lines[6].contains("Total") && lines[6].contains("10.74 KiB"));
+
+    let fancy = render_summary_table(&results, RateUnits::Bytes, true);
+    assert!(fancy.starts_with("  ┌") && fancy.trim_end().ends_with('┘'));
+}
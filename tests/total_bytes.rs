@@ -0,0 +1,110 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::{
+    measure_payload, measure_transfer, run_mock_server, Direction, MockServerConfig, NetworkStats,
+    PayloadSource, TransferError, TransferOptions,
+};
+use tokio::sync::watch;
+
+use common::{parse, round_trip, start};
+
+fn total(bytes: usize) -> TransferOptions {
+    TransferOptions {
+        delay: Duration::ZERO,
+        total_bytes: Some(bytes),
+        ..TransferOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn the_last_iteration_sends_what_remains() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig::default();
+    let server_stats = config.stats.clone();
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+    let address = bound.to_string();
+
+    // `iterations` doesn't matter once there is a total
+    let stats = measure_transfer(&address, 10_000, 1, &total(25_000)).await.unwrap();
+    assert_eq!(stats.bytes_sent, 25_000);
+    assert_eq!(stats.successful_iterations, 3);
+    assert_eq!(stats.total_bytes_iterations, Some(3));
+    assert_eq!(server_stats.bytes_read(), 25_000);
+
+    // Concurrent iterations don't overshoot either
+    let options = TransferOptions { concurrency: 4, ..total(95_000) };
+    let stats = measure_transfer(&address, 10_000, 1, &options).await.unwrap();
+    assert_eq!(stats.bytes_sent, 95_000);
+    assert_eq!(stats.total_bytes_iterations, Some(10));
+
+    // A generated payload is cut short the same way
+    let payload = PayloadSource::Random { size: 100_000, seed: 7 };
+    let stats = measure_payload(&address, &payload, 1, &total(150_000)).await.unwrap();
+    assert_eq!(stats.bytes_sent, 150_000);
+    assert_eq!(stats.total_bytes_iterations, Some(2));
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn without_a_total_there_is_no_count_to_report() {
+    let server = start().await;
+
+    let options = TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() };
+    let stats = measure_transfer(&server.address, 1000, 2, &options).await.unwrap();
+    assert_eq!(stats.total_bytes_iterations, None);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn a_server_that_keeps_failing_isnt_retried_forever() {
+    let err = measure_transfer("127.0.0.1:9", 1000, 1, &total(3000)).await.unwrap_err();
+    // One failure allowed for each of the three iterations the total takes
+    assert!(
+        matches!(err, TransferError::AllFailed { iterations: 3, .. }),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn totals_the_count_cant_come_from_are_rejected() {
+    let options = TransferOptions { direction: Direction::DownloadOnly, ..total(3000) };
+    let err = measure_transfer("127.0.0.1:9", 1000, 1, &options).await.unwrap_err();
+    assert!(matches!(err, TransferError::InvalidOptions(_)), "{:?}", err);
+
+    let err = measure_transfer("127.0.0.1:9", 0, 1, &total(3000)).await.unwrap_err();
+    assert!(matches!(err, TransferError::InvalidOptions(_)), "{:?}", err);
+}
+
+#[test]
+fn the_iteration_count_survives_a_json_round_trip() {
+    let stats = NetworkStats { total_bytes_iterations: Some(42), ..NetworkStats::default() };
+    assert_eq!(round_trip(&stats).total_bytes_iterations, Some(42));
+}
+
+#[test]
+fn total_bytes_replaces_a_fixed_count() {
+    let Ok(Command::Run(config)) = parse(&["--total-bytes", "1000000000"]) else {
+        panic!("expected a run");
+    };
+    assert_eq!(config.total_bytes, Some(1_000_000_000));
+
+    let errors = [
+        (&["--total-bytes", "0"][..], "at least one byte"),
+        (&["--total-bytes", "1000", "--iterations", "auto"], "with --iterations auto"),
+        (&["--total-bytes", "1000", "--direction", "download"], "with --direction download"),
+        (&["--total-bytes", "1000", "--sizes", "0,10"], "0-byte payloads"),
+    ];
+    for (args, expected) in errors {
+        let Err(message) = parse(args) else {
+            panic!("expected {:?} to fail", args);
+        };
+        assert!(message.contains(expected), "{:?}: {}", args, message);
+    }
+}
@@ -0,0 +1,70 @@
+use std::time::{Duration, Instant};
+
+use network_traffic_monitoring::{
+    measure_transfer_udp, measure_transfer_udp_with_options, TransferError, TransferOptions,
+};
+use tokio::net::UdpSocket;
+
+// Answers datagram N with its first 8 bytes padded to 100 * (N + 1) bytes, so each echo
+// shows which iteration it was counted for. `duplicate` is echoed twice and `drop` not
+// at all.
+async fn echo_server(duplicate: Option<u64>, drop: Option<u64>) -> String {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let address = socket.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        let mut buffer = vec![0u8; 65536];
+        while let Ok((n, peer)) = socket.recv_from(&mut buffer).await {
+            let sequence = u64::from_be_bytes(buffer[..8].try_into().unwrap());
+            if Some(sequence) == drop || n < 8 {
+                continue;
+            }
+            let mut reply = buffer[..8].to_vec();
+            reply.resize(100 * (sequence as usize + 1), 0);
+            let copies = if Some(sequence) == duplicate { 2 } else { 1 };
+            for _ in 0..copies {
+                socket.send_to(&reply, peer).await.unwrap();
+            }
+        }
+    });
+    address
+}
+
+fn options(timeout: Duration) -> TransferOptions {
+    TransferOptions { timeout, delay: Duration::ZERO, ..TransferOptions::default() }
+}
+
+#[tokio::test]
+async fn each_echo_counts_for_its_own_iteration() {
+    let address = echo_server(Some(0), None).await;
+    let options = options(Duration::from_secs(1));
+    let stats = measure_transfer_udp_with_options(&address, 64, 3, &options).await.unwrap();
+    assert_eq!(stats.successful_iterations, 3);
+    assert_eq!(stats.failed_iterations, 0);
+    // The second copy of the first echo is skipped, not taken as the second's
+    assert_eq!(stats.bytes_received, 100 + 200 + 300);
+    assert_eq!(stats.bytes_sent, 3 * 64);
+}
+
+#[tokio::test]
+async fn a_lost_echo_fails_after_the_timeout() {
+    let address = echo_server(None, Some(1)).await;
+    let options = options(Duration::from_millis(50));
+    let start = Instant::now();
+    let stats = measure_transfer_udp_with_options(&address, 64, 3, &options).await.unwrap();
+    assert!(start.elapsed() < Duration::from_secs(1), "{:?}", start.elapsed());
+    assert_eq!((stats.successful_iterations, stats.failed_iterations), (2, 1));
+    assert_eq!(stats.bytes_received, 100 + 300);
+
+    let address = echo_server(None, Some(0)).await;
+    let err = measure_transfer_udp(&address, 64, 1).await.unwrap_err();
+    let TransferError::AllFailed { cause: Some(cause), .. } = &err else {
+        panic!("expected every iteration to fail: {:?}", err);
+    };
+    assert!(matches!(**cause, TransferError::Timeout { phase: "read", .. }), "{:?}", cause);
+}
+
+#[tokio::test]
+async fn datagrams_need_room_for_a_sequence_number() {
+    let err = measure_transfer_udp("127.0.0.1:9", 4, 1).await.unwrap_err();
+    assert!(matches!(err, TransferError::InvalidOptions(_)), "{:?}", err);
+}
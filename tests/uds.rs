@@ -0,0 +1,80 @@
+#![cfg(unix)]
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use network_traffic_monitoring::{
+    measure_transfer_uds, measure_transfer_uds_with_options, run_mock_server_uds,
+    MockServerConfig, TransferOptions,
+};
+use tokio::sync::watch;
+
+fn socket_path(name: &str) -> PathBuf {
+    let name = format!("traffic-monitor-{}-{}.sock", std::process::id(), name);
+    let path = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[tokio::test]
+async fn measures_over_a_unix_socket() {
+    let path = socket_path("measure");
+    let (stop, stop_signal) = watch::channel(false);
+    let server =
+        run_mock_server_uds(&path, MockServerConfig::default(), stop_signal).await.unwrap();
+
+    let stats = measure_transfer_uds(&path, 1000, 2).await.unwrap();
+    assert_eq!(stats.successful_iterations, 2);
+    assert_eq!(stats.bytes_sent, 2000);
+    assert_eq!(stats.bytes_received, 1000); // the default 0.5 response ratio
+    assert_eq!(stats.connections, 2);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+    assert!(!path.exists(), "socket file left behind");
+}
+
+#[tokio::test]
+async fn verifies_echoes_on_a_reused_connection() {
+    let path = socket_path("verify");
+    let (stop, stop_signal) = watch::channel(false);
+    let config =
+        MockServerConfig { echo: true, response_ratio: 1.0, ..MockServerConfig::default() };
+    let server = run_mock_server_uds(&path, config, stop_signal).await.unwrap();
+
+    let options = TransferOptions {
+        delay: Duration::ZERO,
+        verify: true,
+        reuse_connection: true,
+        ..TransferOptions::default()
+    };
+    let stats = measure_transfer_uds_with_options(&path, 50_000, 5, &options).await.unwrap();
+    assert_eq!(stats.successful_iterations, 5);
+    assert_eq!(stats.corrupted_iterations, 0);
+    assert_eq!(stats.bytes_received, 5 * 50_000);
+    assert_eq!(stats.connections, 1);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn missing_socket_fails_every_iteration_at_connect() {
+    let path = socket_path("missing");
+    let options = TransferOptions { delay: Duration::ZERO, ..TransferOptions::default() };
+    let err = measure_transfer_uds_with_options(&path, 10, 2, &options).await.unwrap_err();
+    assert_eq!(err.category(), "connect", "{}", err);
+}
+
+#[tokio::test]
+async fn taken_path_fails_at_startup_naming_it() {
+    let path = socket_path("taken");
+    std::fs::write(&path, b"").unwrap();
+    let (_stop, stop_signal) = watch::channel(false);
+
+    let err =
+        run_mock_server_uds(&path, MockServerConfig::default(), stop_signal).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+    assert!(err.to_string().contains(&path.display().to_string()), "{}", err);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,51 @@
+use network_traffic_monitoring::{RateUnits, WatchDisplay, WatchRow};
+
+fn row(size: usize, latest: Result<(f64, f64), String>) -> WatchRow {
+    WatchRow {
+        size,
+        latest,
+        rolling_upload: 2048.0,
+        rolling_download: 1024.0,
+        bytes_sent: 4096,
+        bytes_received: 2048,
+    }
+}
+
+#[test]
+fn the_first_frame_prints_where_the_cursor_is() {
+    let mut display = WatchDisplay::new();
+    let rows = [row(1024, Ok((4096.0, 2048.0)))];
+    let frame = display.frame("cycle 1", &rows, RateUnits::Bytes, false);
+    assert_eq!(
+        frame,
+        concat!(
+            "cycle 1\x1b[K\n",
+            "  1.00 KiB: down 2.00 KiB/s (avg 1.00 KiB/s), up 4.00 KiB/s (avg 2.00 KiB/s)\x1b[K\n",
+            "  Total: 4.00 KiB sent, 2.00 KiB received\x1b[K\n"
+        )
+    );
+}
+
+#[test]
+fn later_frames_move_back_over_the_block() {
+    let mut display = WatchDisplay::new();
+    let rows = [row(1024, Ok((4096.0, 2048.0))), row(65536, Ok((1.0, 1.0)))];
+    display.frame("cycle 1", &rows, RateUnits::Bytes, false);
+
+    let frame = display.frame("cycle 2", &rows, RateUnits::Bytes, false);
+    // A header, two rows and the total
+    assert!(frame.starts_with("\x1b[4Fcycle 2\x1b[K\n"), "{:?}", frame);
+    assert_eq!(frame.matches('\n').count(), 4);
+    assert!(frame.contains("Total: 8.00 KiB sent, 4.00 KiB received"), "{:?}", frame);
+}
+
+#[test]
+fn a_failed_size_shows_its_error_in_its_row() {
+    let mut display = WatchDisplay::new();
+    let rows = [row(1024, Err("connect failed: connection refused".to_string()))];
+    let frame = display.frame("cycle 1", &rows, RateUnits::Bytes, false);
+    assert!(frame.contains("  1.00 KiB: failed: connect failed: connection refused\x1b[K\n"));
+
+    let painted = WatchDisplay::new().frame("cycle 1", &rows, RateUnits::Bytes, true);
+    assert!(painted.contains("\x1b[31mfailed"), "{:?}", painted);
+}
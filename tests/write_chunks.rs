@@ -0,0 +1,122 @@
+mod common;
+
+use std::time::Duration;
+
+use network_traffic_monitoring::tool::cli::Command;
+use network_traffic_monitoring::tool::json;
+use network_traffic_monitoring::{
+    measure_payload, measure_transfer, run_benchmarks, run_mock_server, MockServerConfig,
+    NetworkStats, PayloadSource, TransferOptions, GENERATED_PAYLOAD_MIN,
+};
+use tokio::sync::watch;
+
+use common::{parse, round_trip};
+
+fn chunked(chunk: usize) -> TransferOptions {
+    TransferOptions {
+        delay: Duration::ZERO,
+        write_chunk_size: Some(chunk),
+        flush_chunks: true,
+        verify: true,
+        ..TransferOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn chunked_requests_arrive_whole() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig { echo: true, ..MockServerConfig::default() };
+    let server_stats = config.stats.clone();
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+    let address = bound.to_string();
+
+    // An odd chunk size, so the last piece is a short one; verify checks the echo
+    let stats = measure_transfer(&address, 10_000, 3, &chunked(333)).await.unwrap();
+    assert_eq!(stats.successful_iterations, 3);
+    assert_eq!(stats.corrupted_iterations, 0);
+    assert_eq!(stats.bytes_sent, 30_000);
+    assert_eq!(stats.write_chunk_size, Some(333));
+    assert_eq!(server_stats.bytes_read(), 30_000);
+
+    // A generated payload is cut into the same pieces as it is made
+    let payload = PayloadSource::Zeros(GENERATED_PAYLOAD_MIN + 1000);
+    let stats = measure_payload(&address, &payload, 2, &chunked(10_000)).await.unwrap();
+    assert_eq!(stats.successful_iterations, 2);
+    assert_eq!(stats.corrupted_iterations, 0);
+    assert_eq!(stats.bytes_sent, 2 * (GENERATED_PAYLOAD_MIN + 1000));
+
+    // A paced chunk size smaller than the chunks still wins
+    let options = TransferOptions { target_rate: Some(1e9), ..chunked(100_000) };
+    let stats = measure_transfer(&address, 10_000, 1, &options).await.unwrap();
+    assert_eq!(stats.corrupted_iterations, 0);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn the_sweep_compares_against_single_writes() {
+    let (stop, stop_signal) = watch::channel(false);
+    let config = MockServerConfig::default();
+    let server_stats = config.stats.clone();
+    let (bound, server) = run_mock_server(config, stop_signal).await.unwrap();
+    let path = std::env::temp_dir().join(format!("write-chunks-{}.json", std::process::id()));
+
+    let address = bound.to_string();
+    let output = path.display().to_string();
+    let args = [
+        "--format", "json", "--output", &output, "--address", &address,
+        "--sizes", "5000", "--iterations", "2", "--delay-ms", "0", "--write-chunk-size", "500",
+    ];
+    let Ok(Command::Run(config)) = parse(&args) else {
+        panic!("expected a run");
+    };
+    run_benchmarks(&config).await.unwrap();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let stats = NetworkStats::from_json(&json::parse(text.trim()).unwrap()).unwrap();
+    assert_eq!(stats.write_chunk_size, Some(500));
+    assert!(stats.single_write_upload_rate.is_some_and(|rate| rate > 0.0), "{}", text);
+    // Both measurements ran; only the chunked one is reported
+    assert_eq!(server_stats.requests(), 4);
+    assert_eq!(stats.successful_iterations, 2);
+
+    stop.send(true).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[test]
+fn chunk_figures_survive_a_json_round_trip() {
+    let stats = NetworkStats {
+        write_chunk_size: Some(1400),
+        single_write_upload_rate: Some(2.5e6),
+        ..NetworkStats::default()
+    };
+    let parsed = round_trip(&stats);
+    assert_eq!(parsed.write_chunk_size, Some(1400));
+    assert_eq!(parsed.single_write_upload_rate, Some(2.5e6));
+
+    let parsed = round_trip(&NetworkStats::default());
+    assert_eq!((parsed.write_chunk_size, parsed.single_write_upload_rate), (None, None));
+}
+
+#[test]
+fn chunk_flags_apply_to_raw_tcp_requests() {
+    let Ok(Command::Run(config)) = parse(&["--write-chunk-size", "1400", "--flush-chunks"]) else {
+        panic!("expected a run");
+    };
+    assert_eq!((config.write_chunk_size, config.flush_chunks), (Some(1400), true));
+
+    let errors = [
+        (&["--write-chunk-size", "0"][..], "at least 1 byte"),
+        (&["--flush-chunks"], "needs --write-chunk-size"),
+        (&["--write-chunk-size", "10", "--url", "http://example.com/"], "not --url"),
+    ];
+    for (args, expected) in errors {
+        let Err(message) = parse(args) else {
+            panic!("expected {:?} to fail", args);
+        };
+        assert!(message.contains(expected), "{:?}: {}", args, message);
+    }
+}